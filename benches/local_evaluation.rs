@@ -0,0 +1,108 @@
+//! Local evaluation throughput against a realistic set of definitions.
+//!
+//! Run with `cargo bench --bench local_evaluation`.
+
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use posthog_rs::{FlagCache, LocalEvaluationResponse, LocalEvaluator};
+use serde_json::{json, Value};
+
+const FLAG_COUNT: usize = 100;
+const COHORT_COUNT: usize = 10;
+
+/// Release conditions cycling through the operators seen in real projects.
+fn condition(i: usize) -> Value {
+    let property = match i % 6 {
+        0 => {
+            json!({ "key": "email", "value": "@example\\.com$", "operator": "regex", "type": "person" })
+        }
+        1 => {
+            json!({ "key": "plan", "value": ["pro", "enterprise"], "operator": "exact", "type": "person" })
+        }
+        2 => {
+            json!({ "key": "app_version", "value": "2.1.0", "operator": "semver_gte", "type": "person" })
+        }
+        3 => {
+            json!({ "key": "signed_up_at", "value": "-30d", "operator": "is_date_after", "type": "person" })
+        }
+        4 => json!({ "key": "seats", "value": "10", "operator": "gt", "type": "person" }),
+        _ => {
+            json!({ "key": "id", "value": (i % COHORT_COUNT).to_string(), "operator": "in", "type": "cohort" })
+        }
+    };
+    json!({ "properties": [property], "rollout_percentage": 50.0 })
+}
+
+fn definitions() -> LocalEvaluationResponse {
+    let flags: Vec<Value> = (0..FLAG_COUNT)
+        .map(|i| {
+            let mut filters = json!({ "groups": [condition(i), condition(i + 1)] });
+            if i % 4 == 0 {
+                filters["multivariate"] = json!({ "variants": [
+                    { "key": "control", "rollout_percentage": 50.0 },
+                    { "key": "test", "rollout_percentage": 50.0 }
+                ] });
+            }
+            json!({ "key": format!("flag-{i}"), "active": true, "filters": filters })
+        })
+        .collect();
+    let cohorts: serde_json::Map<String, Value> = (0..COHORT_COUNT)
+        .map(|i| {
+            let cohort = json!({
+                "id": i.to_string(),
+                "name": format!("Cohort {i}"),
+                "properties": { "type": "AND", "values": [
+                    { "type": "property", "key": "country", "value": "ch|de|fr", "operator": "regex" },
+                    { "type": "property", "key": "email", "value": "example", "operator": "icontains" }
+                ] }
+            });
+            (i.to_string(), cohort)
+        })
+        .collect();
+    serde_json::from_value(json!({ "flags": flags, "cohorts": cohorts })).unwrap()
+}
+
+fn person_properties() -> HashMap<String, Value> {
+    HashMap::from([
+        ("email".to_string(), json!("jane@example.com")),
+        ("plan".to_string(), json!("pro")),
+        ("app_version".to_string(), json!("2.4.1")),
+        ("signed_up_at".to_string(), json!("2026-09-30T12:00:00Z")),
+        ("seats".to_string(), json!(25)),
+        ("country".to_string(), json!("ch")),
+    ])
+}
+
+fn local_evaluation(c: &mut Criterion) {
+    let cache = FlagCache::new();
+    cache.update(definitions());
+    let evaluator = LocalEvaluator::new(cache);
+    let properties = person_properties();
+    let (groups, group_properties) = (HashMap::new(), HashMap::new());
+
+    c.bench_function("evaluate_flag", |b| {
+        b.iter(|| {
+            evaluator.evaluate_flag(
+                black_box("flag-5"),
+                black_box("user-42"),
+                &properties,
+                &groups,
+                &group_properties,
+            )
+        })
+    });
+    c.bench_function("evaluate_all_flags", |b| {
+        b.iter(|| {
+            evaluator.evaluate_all_flags(
+                black_box("user-42"),
+                &properties,
+                &groups,
+                &group_properties,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, local_evaluation);
+criterion_main!(benches);
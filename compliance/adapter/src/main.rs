@@ -88,6 +88,8 @@ struct FeatureFlagRequest {
     group_properties: Option<HashMap<String, HashMap<String, serde_json::Value>>>,
     #[serde(default)]
     disable_geoip: Option<bool>,
+    #[serde(default)]
+    device_id: Option<String>,
 }
 
 #[derive(Deserialize, Default)]
@@ -323,6 +325,7 @@ async fn get_feature_flag(
         only_evaluate_locally: false,
         disable_geoip: req.disable_geoip,
         flag_keys: Some(vec![key.clone()]),
+        device_id: req.device_id,
     };
 
     match client.evaluate_flags(req.distinct_id, options).await {
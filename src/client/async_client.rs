@@ -2,66 +2,57 @@ use std::collections::{HashMap, HashSet};
 #[cfg(feature = "error-tracking")]
 use std::error::Error as StdError;
 use std::sync::{Arc, OnceLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use reqwest::{header::CONTENT_TYPE, header::USER_AGENT, Client as HttpClient};
 use serde_json::json;
-use tracing::{debug, instrument, trace, warn};
+use tracing::{debug, error, instrument, trace, warn};
 
-use super::get_default_user_agent;
+use crate::definitions_diff::FlagDefinitionsDiff;
 use crate::endpoints::Endpoint;
 #[cfg(feature = "error-tracking")]
 use crate::error_tracking::{build_exception_event, CaptureExceptionOptions};
+use crate::experiments::ExperimentAssignment;
 use crate::feature_flag_evaluations::{
     EvaluateFlagsOptions, EvaluatedFlagRecord, FeatureFlagEvaluations, FeatureFlagEvaluationsHost,
     FlagCalledEventParams,
 };
 use crate::feature_flags::{match_feature_flag, FeatureFlag, FeatureFlagsResponse, FlagValue};
-use crate::local_evaluation::{AsyncFlagPoller, FlagCache, LocalEvaluationConfig, LocalEvaluator};
+use crate::flags_cache::{FlagsCache, FlagsCacheKey, FlagsCacheStatus};
+use crate::group::Group;
+use crate::http::{HttpResponse, TransportError};
+use crate::local_evaluation::{
+    load_definitions_file, AsyncFlagPoller, DefinitionsFileWatcher, FlagCache,
+    LocalEvaluationConfig, LocalEvaluator,
+};
+use crate::person::{
+    alias_event, identify_event, set_event, set_once_event, unset_event, IdentifyOptions,
+};
 use crate::{Error, Event};
 
-fn is_retryable_feature_flags_error(err: &reqwest::Error) -> bool {
-    if err.is_timeout() {
-        return true;
-    }
-
-    let mut source = std::error::Error::source(err);
-    while let Some(error) = source {
-        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
-            return matches!(
-                io_error.kind(),
-                std::io::ErrorKind::ConnectionReset
-                    | std::io::ErrorKind::TimedOut
-                    | std::io::ErrorKind::UnexpectedEof
-            );
-        }
-        source = std::error::Error::source(error);
-    }
-
-    !err.to_string()
-        .to_lowercase()
-        .contains("connection refused")
-}
-
 use super::common::{
-    already_reported, build_dedup_key, extract_flag_details, flag_called_event,
-    flag_event_dedup_cache, local_record, remote_record_from_detail, report_flags_error,
-    DetailedFlagsResponse, FlagEventDedupCache,
+    already_reported, apply_flag_overrides, build_dedup_key, build_flags_request,
+    extract_flag_details, flag_called_event, flag_event_dedup_cache, local_record,
+    overridden_payload, override_payloads, override_record, remote_record_from_detail,
+    report_flags_error, DetailedFlagsResponse, FlagEventDedupCache,
 };
+use super::recording::{self, OfflineTransport, Recorder, RECORDING_API_KEY};
 use super::transport::{Completion, Control, TransportHandle};
-use super::{CaptureSummary, ClientOptions};
-#[cfg(not(feature = "capture-v1"))]
-use reqwest::header::CONTENT_ENCODING;
+use super::{CaptureSummary, ClientOptions, ClientOptionsBuilder, ClientStats};
 
 /// A [`Client`] facilitates interactions with the PostHog API over HTTP.
 pub struct Client {
     options: ClientOptions,
-    client: HttpClient,
     local_evaluator: Option<LocalEvaluator>,
     _flag_poller: Option<AsyncFlagPoller>,
+    /// Reloads a watched static definitions file.
+    _definitions_watcher: Option<DefinitionsFileWatcher>,
     flag_event_host: OnceLock<Arc<dyn FeatureFlagEvaluationsHost>>,
     /// Background event transport. `None` for disabled clients.
     transport: Option<Arc<TransportHandle>>,
+    /// Captured wire events. `Some` only for recording clients.
+    recorder: Option<Recorder>,
+    /// Cached remote `/flags` responses. `None` unless configured.
+    flags_cache: Option<FlagsCache>,
 }
 
 /// Implementation of [`FeatureFlagEvaluationsHost`] that emits dedup-aware
@@ -126,39 +117,56 @@ impl FeatureFlagEvaluationsHost for AsyncFlagEventHost {
 /// This constructor is available with the default `async-client` feature and
 /// must be awaited. Passing a blank API key creates a disabled client.
 pub async fn client<C: Into<ClientOptions>>(options: C) -> Client {
-    let options = options.into().sanitize();
-    let client = HttpClient::builder()
-        .timeout(Duration::from_secs(options.request_timeout_seconds))
-        .build()
-        .unwrap(); // Unwrap here is as safe as `HttpClient::new`
-
-    let (local_evaluator, flag_poller) =
-        if options.enable_local_evaluation && !options.is_disabled() {
-            if let Some(ref secret_key) = options.secret_key {
-                let cache = FlagCache::new();
-
-                let config = LocalEvaluationConfig {
-                    personal_api_key: secret_key.clone(),
-                    project_api_key: options.api_key.clone(),
-                    api_host: options.endpoints().api_host(),
-                    poll_interval: Duration::from_secs(options.poll_interval_seconds),
-                    request_timeout: Duration::from_secs(options.request_timeout_seconds),
-                };
-
-                let mut poller = AsyncFlagPoller::new(config, cache.clone());
-                poller.set_on_error(options.on_error.clone());
-                poller.start().await;
-
-                (Some(LocalEvaluator::new(cache)), Some(poller))
-            } else {
-                warn!(
-                "Local evaluation enabled but secret_key not set, falling back to API evaluation"
-            );
-                (None, None)
+    let mut options = options.into().sanitize();
+    let recorder = options.recording.then(|| recording::install(&mut options));
+
+    let (local_evaluator, flag_poller, definitions_watcher) = if options.is_disabled() {
+        (None, None, None)
+    } else if let Some(path) = &options.flag_definitions_path {
+        let cache = FlagCache::new();
+        let loaded = load_definitions_file(path, &cache);
+        let watcher = options.watch_flag_definitions.then(|| {
+            DefinitionsFileWatcher::spawn(
+                path.clone(),
+                cache.clone(),
+                loaded,
+                Duration::from_secs(options.poll_interval_seconds),
+            )
+        });
+        (Some(LocalEvaluator::new(cache)), None, watcher)
+    } else if options.enable_local_evaluation {
+        if let Some(ref secret_key) = options.secret_key {
+            let cache = FlagCache::new();
+
+            let config = LocalEvaluationConfig {
+                personal_api_key: secret_key.clone(),
+                project_api_key: options.api_key.clone(),
+                api_host: options.endpoints().api_host(),
+                poll_interval: Duration::from_secs(options.poll_interval_seconds),
+                request_timeout: Duration::from_secs(options.request_timeout_seconds),
+            };
+
+            let mut poller = AsyncFlagPoller::new(config, cache.clone());
+            poller.set_on_error(options.on_error.clone());
+            poller.set_http_transport(options.http_transport.clone());
+            poller.set_stats(options.stats.clone());
+            if let Some(definitions_cache) = &options.flag_definitions_cache {
+                poller = poller.with_definitions_cache(definitions_cache.clone());
             }
+            poller.start().await;
+
+            (Some(LocalEvaluator::new(cache)), Some(poller), None)
         } else {
-            (None, None)
-        };
+            warn!(
+                "Local evaluation enabled but secret_key not set, falling back to API evaluation"
+            );
+            (None, None, None)
+        }
+    } else {
+        (None, None, None)
+    };
+    let local_evaluator = local_evaluator
+        .map(|evaluator| evaluator.with_providers(options.local_evaluation_providers.clone()));
 
     let transport = if options.is_disabled() {
         None
@@ -166,13 +174,17 @@ pub async fn client<C: Into<ClientOptions>>(options: C) -> Client {
         Some(Arc::new(TransportHandle::spawn(options.clone())))
     };
 
+    let flags_cache = options.flags_cache.clone().map(FlagsCache::new);
+
     Client {
         options,
-        client,
         local_evaluator,
         _flag_poller: flag_poller,
+        _definitions_watcher: definitions_watcher,
         flag_event_host: OnceLock::new(),
+        flags_cache,
         transport,
+        recorder,
     }
 }
 
@@ -189,8 +201,8 @@ impl Client {
     /// Fire-and-forget: the event is handed to the background worker, which
     /// batches, sends, and retries it. Returns once the event is queued — not
     /// once it is delivered, and delivery failures are not surfaced to the
-    /// caller. Disabled clients and a full queue drop the event (the latter
-    /// with a single warning).
+    /// caller. Disabled clients drop the event; a full queue is handled by
+    /// the configured [`OverflowPolicy`](crate::OverflowPolicy).
     #[instrument(skip(self, event), level = "debug")]
     pub fn capture(&self, event: Event) {
         if let Some(transport) = &self.transport {
@@ -198,6 +210,20 @@ impl Client {
         }
     }
 
+    /// Capture the provided event, waiting for room in the queue instead of
+    /// applying the [`OverflowPolicy`](crate::OverflowPolicy).
+    ///
+    /// Like [`Client::capture`], returns once the event is queued, not once it
+    /// is delivered. While the queue is full this waits for the worker to
+    /// settle earlier events, however long that takes; the event is only
+    /// dropped if the client is disabled or shut down first.
+    #[instrument(skip(self, event), level = "debug")]
+    pub async fn capture_async(&self, event: Event) {
+        if let Some(transport) = &self.transport {
+            transport.enqueue_async(event).await;
+        }
+    }
+
     /// Flush queued events, returning once the worker has attempted delivery of
     /// everything queued before this call. Transient failures are kept for retry
     /// (the call still returns without error). A no-op for disabled clients.
@@ -397,6 +423,259 @@ impl Client {
         }
     }
 
+    // ----- Person updates ---------------------------------------------------
+    //
+    // Typed wrappers over the reserved `$identify`/`$create_alias`/`$set`
+    // events. Each builds the event and hands it to `capture`, so it shares the
+    // fire-and-forget transport, `before_send` hooks, and client defaults.
+
+    /// Identify a person, updating their person properties.
+    ///
+    /// Sends an `$identify` event carrying the `$set`/`$set_once` properties
+    /// from `options`. When [`IdentifyOptions::anon_distinct_id`] is set, the
+    /// anonymous ID is merged into `distinct_id`'s person.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the event cannot be built. Delivery
+    /// is fire-and-forget, like [`Client::capture`].
+    pub fn identify<S: Into<String>>(
+        &self,
+        distinct_id: S,
+        options: IdentifyOptions,
+    ) -> Result<(), Error> {
+        self.capture(identify_event(distinct_id.into(), options)?);
+        Ok(())
+    }
+
+    /// Link `alias` to `distinct_id` so both IDs resolve to the same person.
+    ///
+    /// Sends a `$create_alias` event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the event cannot be built.
+    pub fn alias<D: Into<String>, A: Into<String>>(
+        &self,
+        distinct_id: D,
+        alias: A,
+    ) -> Result<(), Error> {
+        self.capture(alias_event(distinct_id.into(), alias.into())?);
+        Ok(())
+    }
+
+    /// Set person properties, overwriting existing values. Sends a `$set` event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the event cannot be built.
+    pub fn set_person_properties<S: Into<String>>(
+        &self,
+        distinct_id: S,
+        properties: HashMap<String, serde_json::Value>,
+    ) -> Result<(), Error> {
+        self.capture(set_event(distinct_id.into(), properties)?);
+        Ok(())
+    }
+
+    /// Set person properties only where the person doesn't already have them.
+    /// Sends a `$set_once` event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the event cannot be built.
+    pub fn set_person_properties_once<S: Into<String>>(
+        &self,
+        distinct_id: S,
+        properties: HashMap<String, serde_json::Value>,
+    ) -> Result<(), Error> {
+        self.capture(set_once_event(distinct_id.into(), properties)?);
+        Ok(())
+    }
+
+    /// Remove person properties. Sends a `$set` event carrying `$unset`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the event cannot be built.
+    pub fn unset_person_properties<S: Into<String>>(
+        &self,
+        distinct_id: S,
+        keys: Vec<String>,
+    ) -> Result<(), Error> {
+        self.capture(unset_event(distinct_id.into(), keys)?);
+        Ok(())
+    }
+
+    /// Create or update a group's properties.
+    ///
+    /// Sends a `$groupidentify` event recording `properties` as the group's
+    /// `$group_set`. Build a [`Group`] and convert it into an [`Event`] instead
+    /// to override the distinct ID or to send it with
+    /// [`Client::capture_immediate`].
+    ///
+    /// # Parameters
+    ///
+    /// - `group_type`: Group type, such as `"company"`.
+    /// - `group_key`: Stable identifier for the group.
+    /// - `properties`: Group properties to set.
+    pub fn group_identify<T: Into<String>, K: Into<String>>(
+        &self,
+        group_type: T,
+        group_key: K,
+        properties: HashMap<String, serde_json::Value>,
+    ) {
+        self.capture(
+            Group::new(group_type, group_key)
+                .properties(properties)
+                .into(),
+        );
+    }
+
+    // ----- Super properties ----------------------------------------------
+    //
+    // Shared with the transport handle and flag-event host through
+    // `ClientOptions`, so changes apply to every event captured afterwards.
+    // Events are stamped when captured: events already queued or spooled keep
+    // the super properties registered at the time.
+
+    /// Register properties to attach to every event this client sends,
+    /// overwriting any already registered under the same keys.
+    ///
+    /// Properties set on the event itself win. See
+    /// [`ClientOptionsBuilder::super_properties`](crate::ClientOptionsBuilder::super_properties)
+    /// for which events receive them.
+    pub fn register(&self, properties: HashMap<String, serde_json::Value>) {
+        self.options.super_properties.register(properties);
+    }
+
+    /// Stop attaching the super property `key` to events. A no-op when `key`
+    /// isn't registered.
+    pub fn unregister(&self, key: &str) {
+        self.options.super_properties.unregister(key);
+    }
+
+    // ----- Flag overrides -------------------------------------------------
+    //
+    // Shared through `ClientOptions` like super properties, so a change
+    // applies to every flag read made afterwards. Snapshots already returned
+    // by `evaluate_flags` keep the values they were built with.
+
+    /// Force `key` to `value` for every `distinct_id`, in front of local
+    /// evaluation and remote `/flags`. See [`FlagOverrides`].
+    ///
+    /// [`FlagOverrides`]: crate::FlagOverrides
+    pub fn override_flag<K: Into<String>, V: Into<FlagValue>>(&self, key: K, value: V) {
+        self.options
+            .flag_overrides
+            .set(None, key.into(), value.into());
+    }
+
+    /// Force `key` to `value` for `distinct_id` only. Wins over a global
+    /// override of the same flag.
+    pub fn override_flag_for<D: Into<String>, K: Into<String>, V: Into<FlagValue>>(
+        &self,
+        distinct_id: D,
+        key: K,
+        value: V,
+    ) {
+        self.options
+            .flag_overrides
+            .set(Some(distinct_id.into()), key.into(), value.into());
+    }
+
+    /// Stop forcing `key`, and its payload, for every `distinct_id`.
+    /// Per-`distinct_id` overrides of `key` are kept. A no-op when `key`
+    /// isn't overridden.
+    pub fn remove_flag_override(&self, key: &str) {
+        self.options.flag_overrides.remove(None, key);
+    }
+
+    /// Stop forcing `key`, and its payload, for `distinct_id`. A no-op when it
+    /// isn't overridden.
+    pub fn remove_flag_override_for(&self, distinct_id: &str, key: &str) {
+        self.options.flag_overrides.remove(Some(distinct_id), key);
+    }
+
+    /// Drop every flag override, including those set through
+    /// [`ClientOptionsBuilder::flag_overrides`](crate::ClientOptionsBuilder::flag_overrides).
+    pub fn clear_flag_overrides(&self) {
+        self.options.flag_overrides.clear();
+    }
+
+    // ----- Recording ------------------------------------------------------
+    //
+    // A recording client runs the full capture pipeline but stops at the HTTP
+    // layer, keeping the final wire events for tests to inspect.
+
+    /// Construct a recording client for unit tests.
+    ///
+    /// Captured events run through the whole pipeline — super properties,
+    /// client defaults, runtime context, `before_send` hooks, and
+    /// `$feature_flag_called` dedup — and are stored as the JSON events that
+    /// would have been sent instead of going over HTTP. Read them with
+    /// [`Client::captured_events`] or [`Client::assert_captured`]. The client
+    /// makes no network calls: remote flag requests return no flags, so every
+    /// flag is disabled unless set with [`Client::override_flag`]. To combine
+    /// recording with other options, set
+    /// [`ClientOptionsBuilder::recording`] and build with [`client`].
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// use posthog_rs::{Client, Event};
+    ///
+    /// let posthog = Client::recording().await;
+    /// posthog.capture(Event::new("signed_up", "user-1"));
+    /// let event = posthog.assert_captured("signed_up").await;
+    /// assert_eq!(event["distinct_id"], "user-1");
+    /// # }
+    /// ```
+    pub async fn recording() -> Client {
+        let options = ClientOptionsBuilder::default()
+            .api_key(RECORDING_API_KEY.to_string())
+            .recording(true)
+            .http_transport(OfflineTransport)
+            .build()
+            .expect("We always set the API key, so this is infallible");
+        client(options).await
+    }
+
+    /// Wire events recorded so far, oldest first. Flushes queued events
+    /// first, so everything captured before the call is included.
+    ///
+    /// Events are the JSON objects of the capture request's `batch`, in the
+    /// shape of the active pipeline (v0 `/batch/` or v1). Always empty for a
+    /// client that is not recording.
+    pub async fn captured_events(&self) -> Vec<serde_json::Value> {
+        let Some(recorder) = &self.recorder else {
+            return Vec::new();
+        };
+        self.flush().await;
+        recorder.events()
+    }
+
+    /// Return the first recorded event named `event`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, listing the recorded event names, when no such event was
+    /// captured or the client is not recording.
+    pub async fn assert_captured(&self, event: &str) -> serde_json::Value {
+        let Some(recorder) = &self.recorder else {
+            panic!("assert_captured requires a recording client (see Client::recording)");
+        };
+        self.flush().await;
+        recorder.assert_captured(event)
+    }
+
+    /// Discard the events recorded so far.
+    pub async fn clear_captured(&self) {
+        self.flush().await;
+        if let Some(recorder) = &self.recorder {
+            recorder.clear();
+        }
+    }
+
     // ----- Immediate (inline) capture -------------------------------------
     //
     // `capture`/`capture_batch` above are fire-and-forget: they enqueue onto the
@@ -469,6 +748,7 @@ impl Client {
         historical_migration: bool,
     ) -> Result<CaptureSummary, Error> {
         use super::v1_capture::{self, Step};
+        use crate::http::HttpRequest;
 
         let Some(mut prep) =
             v1_capture::prepare_immediate(&self.options, events, historical_migration)
@@ -488,14 +768,10 @@ impl Client {
                 &prep.pending,
             )?;
 
-            let step = match self
-                .client
-                .post(&prep.url)
-                .headers(headers)
-                .body(body)
-                .send()
-                .await
-            {
+            let request = HttpRequest::post(&prep.url, body)
+                .with_header_map(&headers)
+                .with_timeout(Duration::from_secs(self.options.request_timeout_seconds));
+            let step = match self.options.http_transport.send_async(request).await {
                 Err(e) => v1_capture::after_transport_error(
                     &self.options,
                     &prep.request_id,
@@ -503,12 +779,9 @@ impl Client {
                     e.to_string(),
                 ),
                 Ok(response) => {
-                    let status = response.status().as_u16();
-                    let retry_after = v1_capture::parse_retry_after(response.headers());
-                    let text = response
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "Unknown error".to_string());
+                    let status = response.status;
+                    let retry_after = v1_capture::parse_retry_after(&response);
+                    let text = response.text();
                     v1_capture::after_response(
                         &self.options,
                         &prep.request_id,
@@ -554,32 +827,26 @@ impl Client {
 
         let mut attempt: u32 = 1;
         loop {
-            let mut request = self
-                .client
-                .post(&prep.url)
-                .header(CONTENT_TYPE, "application/json")
-                .header(USER_AGENT, get_default_user_agent())
-                .body(prep.body.clone());
-            if let Some(token) = prep.encoding {
-                request = request.header(CONTENT_ENCODING, token);
-            }
-            #[cfg(feature = "test-harness")]
-            if let Some(ref extra) = self.options.extra_capture_headers {
-                for (k, v) in extra {
-                    request = request.header(k.as_str(), v.as_str());
-                }
-            }
+            let request = v0_capture::build_request(
+                &self.options,
+                &prep.url,
+                prep.body.clone(),
+                prep.encoding,
+            )
+            .with_timeout(Duration::from_secs(self.options.request_timeout_seconds));
 
-            let step = match request.send().await {
+            let step = match self.options.http_transport.send_async(request).await {
                 Err(e) => v0_after_transport_error(&self.options, attempt, e.to_string()),
                 Ok(response) => {
-                    let status = response.status().as_u16();
-                    let retry_after = retry::parse_retry_after(response.headers());
-                    let text = response
-                        .text()
-                        .await
-                        .unwrap_or_else(|_| "Unknown error".to_string());
-                    v0_after_response(&self.options, attempt, status, retry_after, &text)
+                    let status = response.status;
+                    let retry_after = retry::parse_retry_after(&response);
+                    v0_after_response(
+                        &self.options,
+                        attempt,
+                        status,
+                        retry_after,
+                        &response.text(),
+                    )
                 }
             };
 
@@ -605,6 +872,16 @@ impl Client {
         self.transport.as_ref().map_or(0, |t| t.pending())
     }
 
+    /// A snapshot of this client's delivery counters: events enqueued, dropped,
+    /// sent, retried and rejected by the background worker, capture bytes, remote
+    /// `/flags` latencies and definitions poller outcomes. See [`ClientStats`].
+    ///
+    /// With the `metrics` feature the same counters are also reported through
+    /// the `metrics` crate facade as they change.
+    pub fn stats(&self) -> ClientStats {
+        self.options.stats.snapshot()
+    }
+
     /// Get all remote feature flags and payloads for a user.
     ///
     /// For new code, prefer [`Client::evaluate_flags`] so flag reads are
@@ -644,16 +921,18 @@ impl Client {
         ),
         Error,
     > {
+        let distinct_id: String = distinct_id.into();
+        let overrides = self.options.flag_overrides.snapshot();
         if self.options.is_disabled() {
             trace!("Client is disabled, skipping feature flags request");
-            return Ok((HashMap::new(), HashMap::new()));
+            let (mut flags, mut payloads) = (HashMap::new(), HashMap::new());
+            apply_flag_overrides(&overrides, &distinct_id, &mut flags, &mut payloads);
+            return Ok((flags, payloads));
         }
 
-        let flags_endpoint = self.options.endpoints().build_url(Endpoint::Flags);
-
         let mut payload = json!({
             "api_key": self.options.api_key,
-            "distinct_id": distinct_id.into(),
+            "distinct_id": &distinct_id,
         });
 
         if let Some(groups) = groups {
@@ -673,48 +952,11 @@ impl Client {
             payload["disable_geoip"] = json!(true);
         }
 
-        let response = self
-            .send_feature_flags_request(&flags_endpoint, &payload)
-            .await?;
-
-        let distinct_id = payload.get("distinct_id").and_then(|v| v.as_str());
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let err = Error::Connection(format!("API request failed with status {status}: {text}"));
-            report_flags_error(
-                &self.options.on_error,
-                &flags_endpoint,
-                distinct_id,
-                Some(status.as_u16()),
-                Some(&text),
-                &err,
-            );
-            return Err(err);
-        }
-
-        let status = response.status().as_u16();
-        let flags_response = match response.json::<FeatureFlagsResponse>().await {
-            Ok(r) => r,
-            Err(e) => {
-                let err =
-                    Error::Serialization(format!("Failed to parse feature flags response: {e}"));
-                report_flags_error(
-                    &self.options.on_error,
-                    &flags_endpoint,
-                    distinct_id,
-                    Some(status),
-                    None,
-                    &err,
-                );
-                return Err(err);
-            }
-        };
+        let (flags_response, _) = self.request_flags(&distinct_id, &payload).await?;
 
-        Ok(flags_response.normalize())
+        let (mut flags, mut payloads) = flags_response.normalize();
+        apply_flag_overrides(&overrides, &distinct_id, &mut flags, &mut payloads);
+        Ok((flags, payloads))
     }
 
     /// Get a specific feature flag value for a user.
@@ -756,6 +998,16 @@ impl Client {
         let key_str = key.into();
         let distinct_id_str = distinct_id.into();
 
+        if let Some(value) = self
+            .options
+            .flag_overrides
+            .snapshot()
+            .get(&distinct_id_str, &key_str)
+        {
+            debug!(flag = %key_str, ?value, "Flag overridden locally");
+            return Ok(Some(value.clone()));
+        }
+
         // Try local evaluation first if available
         if let Some(ref evaluator) = self.local_evaluator {
             let empty_props = HashMap::new();
@@ -776,13 +1028,22 @@ impl Client {
             };
             let groups_ref = groups.as_ref().unwrap_or(&empty_groups);
             let group_props_ref = group_properties.as_ref().unwrap_or(&empty_group_props);
-            match evaluator.evaluate_flag(
-                &key_str,
-                &distinct_id_str,
-                props,
-                groups_ref,
-                group_props_ref,
-            ) {
+            let offload = !evaluator.providers().is_empty();
+            let (evaluator, key, distinct_id) =
+                (evaluator.clone(), key_str.clone(), distinct_id_str.clone());
+            let (props, groups_owned, group_props_owned) =
+                (props.clone(), groups_ref.clone(), group_props_ref.clone());
+            let local = evaluate_locally(offload, move || {
+                evaluator.evaluate_flag(
+                    &key,
+                    &distinct_id,
+                    &props,
+                    &groups_owned,
+                    &group_props_owned,
+                )
+            })
+            .await;
+            match local.unwrap_or(Ok(None)) {
                 Ok(Some(value)) => {
                     debug!(flag = %key_str, ?value, "Flag evaluated locally");
                     return Ok(Some(value));
@@ -883,17 +1144,25 @@ impl Client {
         key: K,
         distinct_id: D,
     ) -> Result<Option<serde_json::Value>, Error> {
+        let key_str = key.into();
+        let distinct_id: String = distinct_id.into();
+
+        let overrides = self.options.flag_overrides.snapshot();
+        if let Some(payload) = overridden_payload(&overrides, &distinct_id, &key_str) {
+            debug!(flag = %key_str, "Flag payload overridden locally");
+            return Ok(payload);
+        }
+
         if self.options.is_disabled() {
             trace!("Client is disabled, skipping feature flag payload request");
             return Ok(None);
         }
 
-        let key_str = key.into();
         let flags_endpoint = self.options.endpoints().build_url(Endpoint::Flags);
 
         let mut payload = json!({
             "api_key": self.options.api_key,
-            "distinct_id": distinct_id.into(),
+            "distinct_id": distinct_id,
         });
 
         // Add geoip disable parameter if configured
@@ -902,18 +1171,7 @@ impl Client {
         }
 
         let distinct_id = payload.get("distinct_id").and_then(|v| v.as_str());
-        let response = match self
-            .client
-            .post(&flags_endpoint)
-            .header(CONTENT_TYPE, "application/json")
-            .header(USER_AGENT, get_default_user_agent())
-            .json(&payload)
-            .timeout(Duration::from_secs(
-                self.options.feature_flags_request_timeout_seconds,
-            ))
-            .send()
-            .await
-        {
+        let response = match self.send_flags_attempt(&flags_endpoint, &payload).await {
             Ok(r) => r,
             Err(e) => {
                 let err = Error::Connection(e.to_string());
@@ -929,12 +1187,12 @@ impl Client {
             }
         };
 
-        if !response.status().is_success() {
+        if !response.is_success() {
             return Ok(None);
         }
 
-        let status = response.status().as_u16();
-        let flags_response: FeatureFlagsResponse = match response.json().await {
+        let status = response.status;
+        let flags_response: FeatureFlagsResponse = match serde_json::from_slice(&response.body) {
             Ok(r) => r,
             Err(e) => {
                 let err = Error::Serialization(format!("Failed to parse response: {e}"));
@@ -998,6 +1256,28 @@ impl Client {
         .map_err(|e| Error::InconclusiveMatch(e.message))
     }
 
+    /// Call `listener` with a [`FlagDefinitionsDiff`] each time the
+    /// local-evaluation flag definitions change, for example to invalidate
+    /// caches or log a flipped kill switch. See [`FlagCache::on_change`].
+    ///
+    /// Definitions loaded while the client was being built are already in
+    /// place, so the first diff describes the first change after this call. A
+    /// no-op, with a warning, when the client doesn't evaluate flags locally.
+    ///
+    /// [`FlagCache::on_change`]: crate::FlagCache::on_change
+    pub fn on_flag_definitions_change<F>(&self, listener: F)
+    where
+        F: Fn(&FlagDefinitionsDiff) + Send + Sync + 'static,
+    {
+        match &self.local_evaluator {
+            Some(evaluator) => evaluator.cache().on_change(listener),
+            None => warn!(
+                "on_flag_definitions_change called on a client without local evaluation; \
+                 the listener will never be called"
+            ),
+        }
+    }
+
     /// Evaluate feature flags for `distinct_id`, returning a
     /// [`FeatureFlagEvaluations`] snapshot.
     ///
@@ -1028,7 +1308,7 @@ impl Client {
         let distinct_id: String = distinct_id.into();
         let host = self.flag_event_host();
 
-        if distinct_id.is_empty() || self.options.is_disabled() {
+        if distinct_id.is_empty() {
             return Ok(FeatureFlagEvaluations::empty(host));
         }
 
@@ -1036,8 +1316,36 @@ impl Client {
         options.groups.get_or_insert_with(HashMap::new);
         options.group_properties.get_or_insert_with(HashMap::new);
 
+        // Overrides sit in front of every flag source: local evaluation and
+        // `/flags` only fill in the flags they leave unresolved.
+        let overrides = self.options.flag_overrides.snapshot();
+        let forced_payloads = overrides.resolve_payloads(&distinct_id);
         let mut records: HashMap<String, EvaluatedFlagRecord> = HashMap::new();
-        let mut locally_evaluated_keys: HashSet<String> = HashSet::new();
+        for (key, value) in overrides.resolve(&distinct_id) {
+            if let Some(filter) = &options.flag_keys {
+                if !filter.contains(&key) {
+                    continue;
+                }
+            }
+            records.insert(key, override_record(value));
+        }
+
+        if self.options.is_disabled() {
+            override_payloads(&mut records, forced_payloads);
+            return Ok(FeatureFlagEvaluations::new(
+                host,
+                distinct_id,
+                records,
+                options.groups.unwrap_or_default(),
+                options.disable_geoip,
+                None,
+                None,
+                false,
+                false,
+            ));
+        }
+
+        let mut resolved_keys: HashSet<String> = records.keys().cloned().collect();
 
         if let Some(evaluator) = &self.local_evaluator {
             let mut person_props_owned = options.person_properties.clone().unwrap_or_default();
@@ -1046,29 +1354,46 @@ impl Client {
                 .or_insert_with(|| json!(distinct_id.clone()));
             let groups_owned = options.groups.clone().unwrap_or_default();
             let group_props_owned = options.group_properties.clone().unwrap_or_default();
-            let local_results = evaluator.evaluate_all_flags(
-                &distinct_id,
-                &person_props_owned,
-                &groups_owned,
-                &group_props_owned,
-            );
-            // Pin the gate from the poller's current definitions snapshot at the
-            // point local evaluation succeeded, so it travels with these records
-            // rather than being re-read from shared state at event time.
-            let local_minimal_gate = evaluator.cache().minimal_flag_called_events();
+            let snapshot = evaluator.cache().snapshot();
+            let providers = evaluator.providers().clone();
+            let local_results = {
+                let (snapshot, distinct_id, device_id) = (
+                    Arc::clone(&snapshot),
+                    distinct_id.clone(),
+                    options.device_id.clone(),
+                );
+                evaluate_locally(!providers.is_empty(), move || {
+                    snapshot.evaluate_all_detailed(
+                        &distinct_id,
+                        device_id.as_deref(),
+                        &person_props_owned,
+                        &groups_owned,
+                        &group_props_owned,
+                        &providers,
+                    )
+                })
+                .await
+                .unwrap_or_default()
+            };
+            // Pin the gate from the definitions snapshot that produced these
+            // values, so it travels with these records rather than being
+            // re-read from shared state at event time.
+            let local_minimal_gate = snapshot.minimal_flag_called_events();
             for (key, result) in local_results {
+                if resolved_keys.contains(&key) {
+                    continue;
+                }
                 if let Some(filter) = &options.flag_keys {
                     if !filter.iter().any(|k| k == &key) {
                         continue;
                     }
                 }
-                if let Ok(value) = result {
-                    let has_experiment = evaluator.cache().has_experiment(&key);
+                if let Ok(detail) = result {
                     records.insert(
                         key.clone(),
-                        local_record(value, has_experiment, local_minimal_gate),
+                        local_record(detail, snapshot.flag(&key), local_minimal_gate),
                     );
-                    locally_evaluated_keys.insert(key);
+                    resolved_keys.insert(key);
                 }
             }
         }
@@ -1076,23 +1401,27 @@ impl Client {
         let mut request_id: Option<String> = None;
         let mut errors_while_computing = false;
         let mut quota_limited = false;
+        let mut flags_cache_status = None;
 
-        // Skip the remote round-trip when local evaluation has already covered
-        // every requested flag. Without `flag_keys` we have to assume the caller
-        // wants every flag the project has and still hit `/flags` to discover
-        // any not loaded by the poller.
+        // Skip the remote round-trip when overrides and local evaluation have
+        // already covered every requested flag. Without `flag_keys` we have to
+        // assume the caller wants every flag the project has and still hit
+        // `/flags` to discover any not loaded by the poller.
         let local_covers_request = options
             .flag_keys
             .as_ref()
-            .is_some_and(|keys| keys.iter().all(|k| locally_evaluated_keys.contains(k)));
+            .is_some_and(|keys| keys.iter().all(|k| resolved_keys.contains(k)));
 
-        if !options.only_evaluate_locally && !local_covers_request {
+        let only_local =
+            options.only_evaluate_locally || self.options.flag_definitions_path.is_some();
+        if !only_local && !local_covers_request {
             // Don't lose successful local evaluations if `/flags` fails — degrade
             // to a snapshot built from the local results we already have. The
             // alternative (returning Err) wastes useful data and surprises
             // callers who would otherwise get partial coverage.
             match self.fetch_flag_details(&distinct_id, &options).await {
-                Ok(response) => {
+                Ok((response, cache_status)) => {
+                    flags_cache_status = cache_status;
                     request_id = response.request_id;
                     errors_while_computing = response.errors_while_computing_flags;
                     quota_limited = response.quota_limited;
@@ -1100,7 +1429,7 @@ impl Client {
                     // so it is also the source of their minimization gate.
                     let remote_minimal_gate = response.minimal_flag_called_events;
                     for (key, detail) in response.flags {
-                        if locally_evaluated_keys.contains(&key) {
+                        if resolved_keys.contains(&key) {
                             continue;
                         }
                         records.insert(key, remote_record_from_detail(detail, remote_minimal_gate));
@@ -1120,6 +1449,7 @@ impl Client {
             }
         }
 
+        override_payloads(&mut records, forced_payloads);
         Ok(FeatureFlagEvaluations::new(
             host,
             distinct_id,
@@ -1130,7 +1460,39 @@ impl Client {
             None,
             errors_while_computing,
             quota_limited,
-        ))
+        )
+        .with_flags_cache_status(flags_cache_status)
+        .with_device_id(options.device_id))
+    }
+
+    /// Evaluate the experiment flag `flag_key` for `distinct_id` and record
+    /// the user's exposure to it.
+    ///
+    /// Shorthand for [`Client::evaluate_flags`] restricted to `flag_key`,
+    /// followed by [`FeatureFlagEvaluations::get_experiment`]. The exposure
+    /// is a `$feature_flag_called` event, deduplicated per user and variant.
+    ///
+    /// # Returns
+    ///
+    /// The user's assignment, with the variant's payload and whether the user
+    /// is in a holdout group. `None` when the flag does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Client::evaluate_flags`].
+    pub async fn get_experiment_variant<K: Into<String>, D: Into<String>>(
+        &self,
+        flag_key: K,
+        distinct_id: D,
+        options: EvaluateFlagsOptions,
+    ) -> Result<Option<ExperimentAssignment>, Error> {
+        let flag_key = flag_key.into();
+        let options = EvaluateFlagsOptions {
+            flag_keys: Some(vec![flag_key.clone()]),
+            ..options
+        };
+        let flags = self.evaluate_flags(distinct_id, options).await?;
+        Ok(flags.get_experiment(&flag_key))
     }
 
     fn flag_event_host(&self) -> Arc<dyn FeatureFlagEvaluationsHost> {
@@ -1144,39 +1506,43 @@ impl Client {
             .clone()
     }
 
+    /// One `/flags` request through the configured transport, without retries.
+    async fn send_flags_attempt(
+        &self,
+        flags_endpoint: &str,
+        payload: &serde_json::Value,
+    ) -> Result<HttpResponse, TransportError> {
+        let request = build_flags_request(&self.options, flags_endpoint, payload);
+        self.options.http_transport.send_async(request).await
+    }
+
+    /// Send a `/flags` request with retries, timing it for [`Client::stats`].
     async fn send_feature_flags_request(
         &self,
         flags_endpoint: &str,
         payload: &serde_json::Value,
-    ) -> Result<reqwest::Response, Error> {
+    ) -> Result<HttpResponse, Error> {
+        let started = Instant::now();
+        let result = self
+            .retry_feature_flags_request(flags_endpoint, payload)
+            .await;
+        let success = result.as_ref().is_ok_and(HttpResponse::is_success);
+        self.options.stats.flag_request(started.elapsed(), success);
+        result
+    }
+
+    async fn retry_feature_flags_request(
+        &self,
+        flags_endpoint: &str,
+        payload: &serde_json::Value,
+    ) -> Result<HttpResponse, Error> {
         let mut attempt = 1;
         loop {
-            let request = self
-                .client
-                .post(flags_endpoint)
-                .header(CONTENT_TYPE, "application/json")
-                .header(USER_AGENT, get_default_user_agent())
-                .json(payload)
-                .timeout(Duration::from_secs(
-                    self.options.feature_flags_request_timeout_seconds,
-                ));
-            #[cfg(feature = "test-harness")]
-            let request = {
-                let mut request = request;
-                if let Some(ref extra) = self.options.extra_capture_headers {
-                    for (k, v) in extra {
-                        request = request.header(k.as_str(), v.as_str());
-                    }
-                }
-                request
-            };
-            let result = request.send().await;
-
-            match result {
+            match self.send_flags_attempt(flags_endpoint, payload).await {
                 Ok(response) => match super::retry::feature_flags_after_response(
                     &self.options,
                     attempt,
-                    response.status().as_u16(),
+                    response.status,
                 ) {
                     super::retry::FeatureFlagsResponseStep::Backoff(delay) => {
                         tokio::time::sleep(delay).await;
@@ -1185,12 +1551,11 @@ impl Client {
                     super::retry::FeatureFlagsResponseStep::Done => return Ok(response),
                 },
                 Err(e) => {
-                    let err_msg = e.to_string();
                     match super::retry::feature_flags_after_transport_error(
                         &self.options,
                         attempt,
-                        is_retryable_feature_flags_error(&e),
-                        err_msg,
+                        e.is_retryable(),
+                        e.to_string(),
                     ) {
                         super::retry::FeatureFlagsTransportStep::Backoff(delay) => {
                             tokio::time::sleep(delay).await;
@@ -1217,9 +1582,7 @@ impl Client {
         &self,
         distinct_id: &str,
         options: &EvaluateFlagsOptions,
-    ) -> Result<DetailedFlagsResponse, Error> {
-        let flags_endpoint = self.options.endpoints().build_url(Endpoint::Flags);
-
+    ) -> Result<(DetailedFlagsResponse, Option<FlagsCacheStatus>), Error> {
         let person_properties = options.person_properties.clone().unwrap_or_default();
         let groups = options.groups.clone().unwrap_or_default();
         let group_properties = options.group_properties.clone().unwrap_or_default();
@@ -1236,31 +1599,66 @@ impl Client {
         if let Some(flag_keys) = &options.flag_keys {
             payload["flag_keys_to_evaluate"] = json!(flag_keys);
         }
+        if let Some(device_id) = &options.device_id {
+            payload["device_id"] = json!(device_id);
+        }
+
+        let (response, cache_status) = self.request_flags(distinct_id, &payload).await?;
+        Ok((extract_flag_details(response), cache_status))
+    }
+
+    /// Request `/flags` for `payload`, serving from and filling the flags
+    /// cache when one is configured. The cache status is `None` without one.
+    async fn request_flags(
+        &self,
+        distinct_id: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(FeatureFlagsResponse, Option<FlagsCacheStatus>), Error> {
+        let Some(cache) = &self.flags_cache else {
+            return self
+                .fetch_flags_response(distinct_id, payload)
+                .await
+                .map(|response| (response, None));
+        };
+        let key = FlagsCacheKey::new(distinct_id, payload);
+        if let Some(response) = cache.fresh(&key) {
+            return Ok((response, Some(FlagsCacheStatus::Hit)));
+        }
+        let result = self.fetch_flags_response(distinct_id, payload).await;
+        cache
+            .settle(key, result)
+            .map(|(response, status)| (response, Some(status)))
+    }
+
+    /// One `/flags` request for `payload`, with retries, parsed.
+    async fn fetch_flags_response(
+        &self,
+        distinct_id: &str,
+        payload: &serde_json::Value,
+    ) -> Result<FeatureFlagsResponse, Error> {
+        let flags_endpoint = self.options.endpoints().build_url(Endpoint::Flags);
 
         let response = self
-            .send_feature_flags_request(&flags_endpoint, &payload)
+            .send_feature_flags_request(&flags_endpoint, payload)
             .await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+        if !response.is_success() {
+            let status = response.status_line();
+            let text = response.text();
             let err = Error::Connection(format!("API request failed with status {status}: {text}"));
             report_flags_error(
                 &self.options.on_error,
                 &flags_endpoint,
                 Some(distinct_id),
-                Some(status.as_u16()),
+                Some(response.status),
                 Some(&text),
                 &err,
             );
             return Err(err);
         }
 
-        let status = response.status().as_u16();
-        let parsed = match response.json::<FeatureFlagsResponse>().await {
+        let status = response.status;
+        let parsed = match serde_json::from_slice::<FeatureFlagsResponse>(&response.body) {
             Ok(p) => p,
             Err(e) => {
                 let err =
@@ -1276,7 +1674,7 @@ impl Client {
                 return Err(err);
             }
         };
-        Ok(extract_flag_details(parsed))
+        Ok(parsed)
     }
 }
 
@@ -1304,6 +1702,23 @@ impl Drop for Client {
     }
 }
 
+/// Run the local evaluation `evaluate`. With `offload` set, because it may
+/// call the application's property or cohort providers, it runs on tokio's
+/// blocking thread pool so a provider reading a database or a file never
+/// stalls the runtime. `None` when the blocking task failed.
+async fn evaluate_locally<T: Send + 'static>(
+    offload: bool,
+    evaluate: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    if !offload {
+        return Some(evaluate());
+    }
+    tokio::task::spawn_blocking(evaluate)
+        .await
+        .inspect_err(|e| error!(error = %e, "Local flag evaluation task failed"))
+        .ok()
+}
+
 #[cfg(test)]
 mod minimal_gate_tests {
     use super::*;
@@ -1313,11 +1728,13 @@ mod minimal_gate_tests {
         let options = ClientOptions::from(("phc_test", "http://localhost:0"));
         let client = Client {
             options,
-            client: HttpClient::builder().build().unwrap(),
             local_evaluator: Some(LocalEvaluator::new(cache)),
             _flag_poller: None,
+            _definitions_watcher: None,
             flag_event_host: OnceLock::new(),
             transport: None,
+            recorder: None,
+            flags_cache: None,
         };
         client
             .flag_event_host
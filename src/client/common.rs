@@ -1,13 +1,19 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
 
 use crate::client::BeforeSendHook;
 use crate::client::CaptureDefaults;
+use crate::client::ClientOptions;
+use crate::client::FlagOverrides;
 use crate::client::FlagsFailure;
 use crate::client::OnErrorHook;
 use crate::client::PostHogError;
 use crate::feature_flag_evaluations::{EvaluatedFlagRecord, FlagCalledEventParams};
-use crate::feature_flags::{FeatureFlagsResponse, FlagDetail, FlagMetadata, FlagValue};
+use crate::feature_flags::{
+    FeatureFlag, FeatureFlagsResponse, FlagDetail, FlagMetadata, FlagReason, FlagValue,
+};
+use crate::http::HttpRequest;
 use crate::Error;
 use crate::Event;
 use tracing::error;
@@ -49,6 +55,9 @@ pub(super) fn flag_event_dedup_cache() -> FlagEventDedupCache {
 }
 
 pub(super) fn apply_capture_defaults(event: &mut Event, defaults: &CaptureDefaults) {
+    for (key, value) in defaults.super_properties.iter() {
+        event.insert_prop_default(key.clone(), value.clone());
+    }
     if defaults.disable_geoip {
         event.insert_prop_default("$geoip_disable", serde_json::Value::Bool(true));
     }
@@ -90,6 +99,32 @@ pub(crate) fn apply_on_error_hooks(hooks: &[OnErrorHook], failure: &PostHogError
     }
 }
 
+/// Build a remote `/flags` request carrying `payload`, bounded by the
+/// configured flags timeout. Shared by both clients' request loops.
+pub(super) fn build_flags_request(
+    options: &ClientOptions,
+    flags_endpoint: &str,
+    payload: &serde_json::Value,
+) -> HttpRequest {
+    let request = HttpRequest::post(flags_endpoint, payload.to_string().into_bytes())
+        .with_header("content-type", "application/json")
+        .with_header("user-agent", super::get_default_user_agent())
+        .with_timeout(Duration::from_secs(
+            options.feature_flags_request_timeout_seconds,
+        ));
+    #[cfg(feature = "test-harness")]
+    let request = {
+        let mut request = request;
+        if let Some(ref extra) = options.extra_capture_headers {
+            for (k, v) in extra {
+                request = request.with_header(k, v.as_str());
+            }
+        }
+        request
+    };
+    request
+}
+
 /// Fire the `on_error` hooks for a failed `/flags` request. Each failed request
 /// reports exactly once, from the leaf that finalizes the [`Error`], so a caller
 /// that degrades gracefully (e.g. [`Client::evaluate_flags`](crate::Client::evaluate_flags)
@@ -272,11 +307,41 @@ pub(super) fn extract_flag_details(response: FeatureFlagsResponse) -> DetailedFl
     }
 }
 
+/// Record for a flag evaluated locally from `flag`, its definition. The
+/// payload is the definition's payload for the served variant, or for `"true"`
+/// when a boolean flag is enabled.
 pub(super) fn local_record(
-    value: FlagValue,
-    has_experiment: Option<bool>,
+    detail: FlagDetail,
+    flag: Option<&FeatureFlag>,
     minimal_flag_called_events: bool,
 ) -> EvaluatedFlagRecord {
+    let payload_key = match detail.value() {
+        FlagValue::Boolean(enabled) => enabled.then(|| "true".to_string()),
+        FlagValue::String(variant) => Some(variant),
+    };
+    let payload = flag
+        .zip(payload_key)
+        .and_then(|(flag, key)| flag.filters.payloads.get(&key).cloned())
+        .map(normalize_payload);
+    EvaluatedFlagRecord {
+        enabled: detail.enabled,
+        variant: detail.variant,
+        payload,
+        id: None,
+        version: None,
+        reason_code: reason_code(detail.reason.as_ref()),
+        reason: reason_description(detail.reason),
+        locally_evaluated: true,
+        overridden: false,
+        has_experiment: flag.and_then(|f| f.has_experiment),
+        minimal_flag_called_events,
+    }
+}
+
+/// Record for a flag forced by the client's [`FlagOverrides`](crate::FlagOverrides).
+/// Its `$feature_flag_called` events are never minimized, so the
+/// `$feature_flag_overridden` marker always reaches PostHog.
+pub(super) fn override_record(value: FlagValue) -> EvaluatedFlagRecord {
     let (enabled, variant) = match value {
         FlagValue::Boolean(b) => (b, None),
         FlagValue::String(s) => (true, Some(s)),
@@ -284,14 +349,59 @@ pub(super) fn local_record(
     EvaluatedFlagRecord {
         enabled,
         variant,
-        // Local definitions do not surface a payload through the poller today.
         payload: None,
         id: None,
         version: None,
-        reason: Some("Evaluated locally".to_string()),
+        reason: Some("Overridden locally".to_string()),
+        reason_code: None,
         locally_evaluated: true,
-        has_experiment,
-        minimal_flag_called_events,
+        overridden: true,
+        has_experiment: None,
+        minimal_flag_called_events: false,
+    }
+}
+
+/// Force the payload overrides in `payloads` onto the flags in `records`.
+pub(super) fn override_payloads(
+    records: &mut HashMap<String, EvaluatedFlagRecord>,
+    payloads: HashMap<String, serde_json::Value>,
+) {
+    for (key, payload) in payloads {
+        if let Some(record) = records.get_mut(&key) {
+            record.payload = Some(payload);
+        }
+    }
+}
+
+/// Apply the overrides for `distinct_id` to a `get_feature_flags` result. An
+/// overridden flag only keeps a forced payload, since PostHog's payload
+/// belongs to the value it evaluated.
+pub(super) fn apply_flag_overrides(
+    overrides: &FlagOverrides,
+    distinct_id: &str,
+    flags: &mut HashMap<String, FlagValue>,
+    payloads: &mut HashMap<String, serde_json::Value>,
+) {
+    let forced = overrides.resolve(distinct_id);
+    payloads.retain(|key, _| !forced.contains_key(key));
+    flags.extend(forced);
+    for (key, payload) in overrides.resolve_payloads(distinct_id) {
+        if flags.contains_key(&key) {
+            payloads.insert(key, payload);
+        }
+    }
+}
+
+/// The payload of `key` when the overrides for `distinct_id` decide it: the
+/// forced payload, or none for a flag whose value is overridden without one.
+pub(super) fn overridden_payload(
+    overrides: &FlagOverrides,
+    distinct_id: &str,
+    key: &str,
+) -> Option<Option<serde_json::Value>> {
+    match overrides.get_payload(distinct_id, key) {
+        Some(payload) => Some(Some(payload.clone())),
+        None => overrides.get(distinct_id, key).map(|_| None),
     }
 }
 
@@ -300,10 +410,8 @@ pub(super) fn remote_record_from_detail(
     minimal_flag_called_events: bool,
 ) -> EvaluatedFlagRecord {
     let metadata = detail.metadata;
-    let reason = detail
-        .reason
-        .and_then(|r| r.description.or(Some(r.code)))
-        .filter(|s| !s.is_empty());
+    let reason_code = reason_code(detail.reason.as_ref());
+    let reason = reason_description(detail.reason);
     let id = metadata.as_ref().map(|m| m.id);
     let version = metadata.as_ref().map(|m| m.version);
     let has_experiment = metadata.as_ref().and_then(|m| m.has_experiment);
@@ -315,12 +423,28 @@ pub(super) fn remote_record_from_detail(
         id,
         version,
         reason,
+        reason_code,
         locally_evaluated: false,
+        overridden: false,
         has_experiment,
         minimal_flag_called_events,
     }
 }
 
+/// The machine-readable reason code, if non-empty.
+fn reason_code(reason: Option<&FlagReason>) -> Option<String> {
+    reason
+        .map(|r| r.code.clone())
+        .filter(|code| !code.is_empty())
+}
+
+/// The human-readable form of a flag reason, falling back to its code.
+fn reason_description(reason: Option<FlagReason>) -> Option<String> {
+    reason
+        .and_then(|r| r.description.or(Some(r.code)))
+        .filter(|s| !s.is_empty())
+}
+
 /// `metadata.payload` from `/flags?v=2` is sometimes a JSON-encoded string
 /// (e.g. `"{\"color\":\"blue\"}"`) rather than already-parsed JSON. Try to
 /// parse a `String` payload as JSON and fall back to the raw string on
@@ -0,0 +1,246 @@
+//! Dead-letter sinks: where the transport worker hands events it gave up on.
+//!
+//! With a [`DeadLetterSink`] set on [`ClientOptions`](super::ClientOptions),
+//! events that can no longer be delivered — rejected by PostHog, out of
+//! retries, unserializable, or abandoned at shutdown — are passed to the sink
+//! instead of being discarded, so they can be inspected or re-driven later.
+//! [`NdjsonDeadLetterSink`] is the built-in sink, appending them to a file.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::event::SpooledEventRef;
+use crate::Event;
+
+/// Why the transport worker gave up on events handed to a [`DeadLetterSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeadLetterReason {
+    /// PostHog refused the events: a non-retryable status such as `400` or
+    /// `413`, or a V1 `drop` verdict.
+    Rejected {
+        /// The HTTP status of the final attempt.
+        status: Option<u16>,
+    },
+    /// Delivery still failed after the last retry: a transport error, a
+    /// retryable status, or a V1 `retry` verdict.
+    RetriesExhausted {
+        /// The HTTP status of the final attempt, or `None` for a transport
+        /// error.
+        status: Option<u16>,
+    },
+    /// The batch could not be serialized.
+    Serialization,
+    /// The client shut down before the events were delivered: still queued or
+    /// awaiting a retry when `shutdown_timeout_ms` ran out.
+    Shutdown,
+}
+
+impl DeadLetterReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            DeadLetterReason::Rejected { .. } => "rejected",
+            DeadLetterReason::RetriesExhausted { .. } => "retries_exhausted",
+            DeadLetterReason::Serialization => "serialization",
+            DeadLetterReason::Shutdown => "shutdown",
+        }
+    }
+
+    fn status(self) -> Option<u16> {
+        match self {
+            DeadLetterReason::Rejected { status }
+            | DeadLetterReason::RetriesExhausted { status } => status,
+            _ => None,
+        }
+    }
+}
+
+/// Receives events the client could not deliver.
+///
+/// Set one with
+/// [`ClientOptionsBuilder::dead_letter_sink`](crate::ClientOptionsBuilder::dead_letter_sink).
+/// The sink is called on the background transport thread, once per failed
+/// batch (or per verdict within a V1 batch), so keep it quick — a slow sink
+/// delays delivery of everything behind it. Panics are caught and the events
+/// dropped.
+///
+/// Events that reached a send attempt arrive as the `before_send` hooks left
+/// them, with client defaults such as super properties already applied.
+/// Events abandoned at shutdown before any attempt arrive as captured.
+/// Either way they keep their UUIDs, so re-capturing one that did reach
+/// PostHog is deduplicated. Events sent with the `*_immediate` methods report
+/// through their return value instead, and queue-full and `before_send` drops
+/// are not dead-lettered. With a [spool](crate::SpoolOptions), events it keeps
+/// on disk for a later re-drive or replay are not dead-lettered either; the
+/// sink only gets those the spool could not hold, so replaying the sink never
+/// duplicates what the spool delivers.
+pub trait DeadLetterSink: Send + Sync + 'static {
+    /// Take `events` the client gave up on for `reason`.
+    /// `historical_migration` is set when they were captured as a
+    /// historical-migration batch.
+    fn dead_letter(&self, events: Vec<Event>, reason: DeadLetterReason, historical_migration: bool);
+}
+
+/// Shared handle to the configured sink, stored on
+/// [`ClientOptions`](super::ClientOptions).
+#[derive(Clone)]
+pub(crate) struct SharedDeadLetterSink(pub(crate) Arc<dyn DeadLetterSink>);
+
+impl SharedDeadLetterSink {
+    /// Hand `events` to the sink, catching a panicking sink so the worker
+    /// survives it.
+    pub(crate) fn send(
+        &self,
+        events: Vec<Event>,
+        reason: DeadLetterReason,
+        historical_migration: bool,
+    ) {
+        if events.is_empty() {
+            return;
+        }
+        let sink = &self.0;
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sink.dead_letter(events, reason, historical_migration)
+        }))
+        .is_err()
+        {
+            error!("panic in PostHog dead-letter sink; dropping events");
+        }
+    }
+}
+
+/// A dead-lettered event line.
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    historical_migration: bool,
+    event: SpooledEventRef<'a>,
+}
+
+/// A [`DeadLetterSink`] appending each event to an NDJSON file.
+///
+/// Every line is one event:
+/// `{"reason":"rejected","status":400,"event":{"event":…,"distinct_id":…,"properties":…,"timestamp":…,"uuid":…}}`,
+/// with `"historical_migration":true` on events from historical-migration
+/// batches. `reason` is one of `rejected`, `retries_exhausted`,
+/// `serialization` or `shutdown`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use posthog_rs::{ClientOptionsBuilder, NdjsonDeadLetterSink};
+///
+/// let options = ClientOptionsBuilder::default()
+///     .api_key("phc_project_api_key".to_string())
+///     .dead_letter_sink(NdjsonDeadLetterSink::open("/var/lib/my-service/posthog-dead.ndjson")?)
+///     .build()
+///     .unwrap();
+/// # let _ = options;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct NdjsonDeadLetterSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl NdjsonDeadLetterSink {
+    /// Append to the file at `path`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if the file cannot be opened for appending.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// The file this sink appends to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl DeadLetterSink for NdjsonDeadLetterSink {
+    fn dead_letter(
+        &self,
+        events: Vec<Event>,
+        reason: DeadLetterReason,
+        historical_migration: bool,
+    ) {
+        let mut lines = Vec::new();
+        for event in &events {
+            let record = RecordRef {
+                reason: reason.as_str(),
+                status: reason.status(),
+                historical_migration,
+                event: SpooledEventRef::from(event),
+            };
+            match serde_json::to_vec(&record) {
+                Ok(line) => {
+                    lines.extend(line);
+                    lines.push(b'\n');
+                }
+                Err(e) => warn!("posthog-rs: not dead-lettering event, serialization failed: {e}"),
+            }
+        }
+        let mut file = self.file.lock().unwrap_or_else(|p| p.into_inner());
+        if let Err(e) = file.write_all(&lines) {
+            warn!(
+                "posthog-rs: failed to write {} dead-lettered event(s) to {:?}: {e}",
+                events.len(),
+                self.path
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn ndjson_sink_appends_one_line_per_event() {
+        let path = std::env::temp_dir().join(format!("posthog-dead-{}.ndjson", Uuid::now_v7()));
+        let sink = NdjsonDeadLetterSink::open(&path).unwrap();
+        let mut event = Event::new("signed_up", "user-1");
+        event.insert_prop("plan", "pro").unwrap();
+
+        sink.dead_letter(
+            vec![event.clone()],
+            DeadLetterReason::Rejected { status: Some(400) },
+            false,
+        );
+        sink.dead_letter(vec![event], DeadLetterReason::Shutdown, true);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["reason"], "rejected");
+        assert_eq!(lines[0]["status"], 400);
+        assert!(lines[0].get("historical_migration").is_none());
+        assert_eq!(lines[0]["event"]["event"], "signed_up");
+        assert_eq!(lines[0]["event"]["properties"]["plan"], "pro");
+        assert_eq!(lines[1]["reason"], "shutdown");
+        assert_eq!(lines[1]["historical_migration"], true);
+        assert_eq!(lines[0]["event"]["uuid"], lines[1]["event"]["uuid"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+use crate::feature_flags::FlagValue;
+
+/// Flag values forced on the client side, in front of both local evaluation
+/// and remote `/flags`.
+///
+/// An override applies either to every `distinct_id` or to a single one; a
+/// per-`distinct_id` override wins over a global override of the same flag.
+/// Overridden flags are reported with the reason `"Overridden locally"`, and
+/// the `$feature_flag_called` events they produce carry
+/// `$feature_flag_overridden: true` so they can be filtered out of analysis.
+/// An overridden flag has no payload unless one is forced with
+/// [`payload`](Self::payload) too, since PostHog's payload belongs to the
+/// value it evaluated.
+///
+/// Set them up front with
+/// [`ClientOptionsBuilder::flag_overrides`](crate::ClientOptionsBuilder::flag_overrides)
+/// and change them at runtime with
+/// [`Client::override_flag`](crate::Client::override_flag).
+///
+/// # Examples
+///
+/// ```
+/// use posthog_rs::{ClientOptionsBuilder, FlagOverrides};
+///
+/// let options = ClientOptionsBuilder::default()
+///     .api_key("phc_project_api_key".to_string())
+///     .flag_overrides(
+///         FlagOverrides::new()
+///             .flag("new-checkout", "variant-b")
+///             .payload("new-checkout", serde_json::json!({ "steps": 2 }))
+///             .flag_for("developer@example.com", "beta-dashboard", true),
+///     )
+///     .build()
+///     .unwrap();
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlagOverrides {
+    global: HashMap<String, FlagValue>,
+    by_distinct_id: HashMap<String, HashMap<String, FlagValue>>,
+    global_payloads: HashMap<String, Value>,
+    payloads_by_distinct_id: HashMap<String, HashMap<String, Value>>,
+}
+
+impl FlagOverrides {
+    /// No overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Force `key` to `value` for every `distinct_id`.
+    pub fn flag<K: Into<String>, V: Into<FlagValue>>(mut self, key: K, value: V) -> Self {
+        self.set(None, key.into(), value.into());
+        self
+    }
+
+    /// Force `key` to `value` for `distinct_id` only.
+    pub fn flag_for<D: Into<String>, K: Into<String>, V: Into<FlagValue>>(
+        mut self,
+        distinct_id: D,
+        key: K,
+        value: V,
+    ) -> Self {
+        self.set(Some(distinct_id.into()), key.into(), value.into());
+        self
+    }
+
+    /// Force the payload of `key` to `payload` for every `distinct_id`.
+    pub fn payload<K: Into<String>, P: Into<Value>>(mut self, key: K, payload: P) -> Self {
+        insert(
+            &mut self.global_payloads,
+            &mut self.payloads_by_distinct_id,
+            None,
+            key.into(),
+            payload.into(),
+        );
+        self
+    }
+
+    /// Force the payload of `key` to `payload` for `distinct_id` only.
+    pub fn payload_for<D: Into<String>, K: Into<String>, P: Into<Value>>(
+        mut self,
+        distinct_id: D,
+        key: K,
+        payload: P,
+    ) -> Self {
+        insert(
+            &mut self.global_payloads,
+            &mut self.payloads_by_distinct_id,
+            Some(distinct_id.into()),
+            key.into(),
+            payload.into(),
+        );
+        self
+    }
+
+    /// Whether no override is set.
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty()
+            && self.by_distinct_id.is_empty()
+            && self.global_payloads.is_empty()
+            && self.payloads_by_distinct_id.is_empty()
+    }
+
+    /// The override of `key` in effect for `distinct_id`, if any.
+    pub fn get(&self, distinct_id: &str, key: &str) -> Option<&FlagValue> {
+        self.by_distinct_id
+            .get(distinct_id)
+            .and_then(|flags| flags.get(key))
+            .or_else(|| self.global.get(key))
+    }
+
+    /// The payload override of `key` in effect for `distinct_id`, if any.
+    pub fn get_payload(&self, distinct_id: &str, key: &str) -> Option<&Value> {
+        self.payloads_by_distinct_id
+            .get(distinct_id)
+            .and_then(|payloads| payloads.get(key))
+            .or_else(|| self.global_payloads.get(key))
+    }
+
+    /// Every override in effect for `distinct_id`, keyed by flag.
+    pub(crate) fn resolve(&self, distinct_id: &str) -> HashMap<String, FlagValue> {
+        resolve(&self.global, &self.by_distinct_id, distinct_id)
+    }
+
+    /// Every payload override in effect for `distinct_id`, keyed by flag.
+    pub(crate) fn resolve_payloads(&self, distinct_id: &str) -> HashMap<String, Value> {
+        resolve(
+            &self.global_payloads,
+            &self.payloads_by_distinct_id,
+            distinct_id,
+        )
+    }
+
+    fn set(&mut self, distinct_id: Option<String>, key: String, value: FlagValue) {
+        insert(
+            &mut self.global,
+            &mut self.by_distinct_id,
+            distinct_id,
+            key,
+            value,
+        );
+    }
+
+    /// Drop the value and payload overrides of `key`.
+    fn remove(&mut self, distinct_id: Option<&str>, key: &str) {
+        remove(&mut self.global, &mut self.by_distinct_id, distinct_id, key);
+        remove(
+            &mut self.global_payloads,
+            &mut self.payloads_by_distinct_id,
+            distinct_id,
+            key,
+        );
+    }
+}
+
+fn resolve<V: Clone>(
+    global: &HashMap<String, V>,
+    by_distinct_id: &HashMap<String, HashMap<String, V>>,
+    distinct_id: &str,
+) -> HashMap<String, V> {
+    let mut resolved = global.clone();
+    if let Some(flags) = by_distinct_id.get(distinct_id) {
+        resolved.extend(flags.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    resolved
+}
+
+fn insert<V>(
+    global: &mut HashMap<String, V>,
+    by_distinct_id: &mut HashMap<String, HashMap<String, V>>,
+    distinct_id: Option<String>,
+    key: String,
+    value: V,
+) {
+    match distinct_id {
+        Some(distinct_id) => {
+            by_distinct_id
+                .entry(distinct_id)
+                .or_default()
+                .insert(key, value);
+        }
+        None => {
+            global.insert(key, value);
+        }
+    }
+}
+
+fn remove<V>(
+    global: &mut HashMap<String, V>,
+    by_distinct_id: &mut HashMap<String, HashMap<String, V>>,
+    distinct_id: Option<&str>,
+    key: &str,
+) {
+    match distinct_id {
+        Some(distinct_id) => {
+            if let Some(flags) = by_distinct_id.get_mut(distinct_id) {
+                flags.remove(key);
+                if flags.is_empty() {
+                    by_distinct_id.remove(distinct_id);
+                }
+            }
+        }
+        None => {
+            global.remove(key);
+        }
+    }
+}
+
+/// The client's [`FlagOverrides`], shared by every clone of its
+/// [`ClientOptions`](super::ClientOptions) so runtime changes reach all flag
+/// paths. Writers copy-on-write, like [`SuperProperties`](super::SuperProperties).
+#[derive(Clone, Default)]
+pub(crate) struct SharedFlagOverrides(Arc<RwLock<Arc<FlagOverrides>>>);
+
+impl SharedFlagOverrides {
+    pub(crate) fn new(overrides: FlagOverrides) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(overrides))))
+    }
+
+    /// Force `key` to `value`, for `distinct_id` only when given.
+    pub(crate) fn set(&self, distinct_id: Option<String>, key: String, value: FlagValue) {
+        let mut current = self.0.write().unwrap_or_else(|p| p.into_inner());
+        Arc::make_mut(&mut current).set(distinct_id, key, value);
+    }
+
+    /// Drop the value and payload overrides of `key`, for `distinct_id` only
+    /// when given. A no-op when there is none.
+    pub(crate) fn remove(&self, distinct_id: Option<&str>, key: &str) {
+        let mut current = self.0.write().unwrap_or_else(|p| p.into_inner());
+        Arc::make_mut(&mut current).remove(distinct_id, key);
+    }
+
+    /// Drop every override.
+    pub(crate) fn clear(&self) {
+        *self.0.write().unwrap_or_else(|p| p.into_inner()) = Arc::default();
+    }
+
+    /// The overrides set right now.
+    pub(crate) fn snapshot(&self) -> Arc<FlagOverrides> {
+        self.0.read().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// A copy with the same overrides that no longer shares updates with
+    /// `self`.
+    pub(crate) fn detached(&self) -> Self {
+        Self::new(self.snapshot().as_ref().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn per_distinct_id_overrides_win_over_global_ones() {
+        let overrides = FlagOverrides::new()
+            .flag("new-checkout", "control")
+            .flag("beta", true)
+            .flag_for("dev-1", "new-checkout", "variant-b");
+
+        assert_eq!(
+            overrides.get("dev-1", "new-checkout"),
+            Some(&FlagValue::String("variant-b".into()))
+        );
+        assert_eq!(
+            overrides.get("user-2", "new-checkout"),
+            Some(&FlagValue::String("control".into()))
+        );
+        assert_eq!(
+            overrides.resolve("dev-1"),
+            HashMap::from([
+                ("new-checkout".to_string(), "variant-b".into()),
+                ("beta".to_string(), true.into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn payload_overrides_resolve_like_values_and_are_removed_with_them() {
+        let mut overrides = FlagOverrides::new()
+            .flag("new-checkout", "control")
+            .payload("new-checkout", json!({ "steps": 3 }))
+            .payload_for("dev-1", "new-checkout", json!({ "steps": 2 }));
+
+        assert_eq!(
+            overrides.get_payload("dev-1", "new-checkout"),
+            Some(&json!({ "steps": 2 }))
+        );
+        assert_eq!(
+            overrides.resolve_payloads("user-2"),
+            HashMap::from([("new-checkout".to_string(), json!({ "steps": 3 }))])
+        );
+
+        overrides.remove(None, "new-checkout");
+        assert_eq!(overrides.get("user-2", "new-checkout"), None);
+        assert_eq!(overrides.get_payload("user-2", "new-checkout"), None);
+        assert!(!overrides.is_empty());
+    }
+
+    #[test]
+    fn changes_are_shared_by_clones_but_not_detached_copies() {
+        let shared = SharedFlagOverrides::new(FlagOverrides::new().flag("beta", true));
+        let clone = shared.clone();
+        let detached = shared.detached();
+
+        clone.set(Some("dev-1".into()), "beta".into(), false.into());
+        clone.remove(None, "beta");
+
+        assert_eq!(
+            *shared.snapshot(),
+            FlagOverrides::new().flag_for("dev-1", "beta", false)
+        );
+        assert_eq!(
+            *detached.snapshot(),
+            FlagOverrides::new().flag("beta", true)
+        );
+
+        shared.remove(Some("dev-1"), "beta");
+        assert!(shared.snapshot().is_empty());
+    }
+}
@@ -30,6 +30,7 @@ fn gated_flag(has_experiment: Option<bool>) -> FeatureFlag {
         key: "gated".into(),
         active: true,
         has_experiment,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![],
@@ -41,6 +42,8 @@ fn gated_flag(has_experiment: Option<bool>) -> FeatureFlag {
             payloads: HashMap::new(),
             aggregation_group_type_index: None,
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     }
 }
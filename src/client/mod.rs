@@ -1,22 +1,46 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+use crate::cohort_membership::CohortMembershipProvider;
+use crate::definitions_cache::DefinitionsCacheOptions;
 use crate::endpoints::{EndpointManager, DEFAULT_HOST};
 #[cfg(feature = "error-tracking")]
 use crate::error_tracking::ErrorTrackingOptions;
 use crate::event::Event;
+use crate::flags_cache::FlagsCacheOptions;
+use crate::http::{HttpTransport, SharedTransport};
+use crate::local_evaluation::Providers;
+use crate::property_providers::{GroupPropertyProvider, PersonPropertyProvider};
 use derive_builder::Builder;
 use tracing::warn;
 
 mod common;
+mod dead_letter;
+mod flag_overrides;
 #[cfg(test)]
 mod minimal_gate_test_support;
 mod on_error;
+mod recording;
+mod spool;
+mod stats;
 mod summary;
+mod super_properties;
 
 pub(crate) use common::apply_on_error_hooks;
+pub(crate) use dead_letter::SharedDeadLetterSink;
+pub use dead_letter::{DeadLetterReason, DeadLetterSink, NdjsonDeadLetterSink};
+pub use flag_overrides::FlagOverrides;
+pub(crate) use flag_overrides::SharedFlagOverrides;
 pub(crate) use on_error::OnErrorHook;
-pub use on_error::{CaptureFailure, FlagsFailure, LocalEvaluationFailure, PostHogError};
+pub use on_error::{
+    CaptureFailure, FlagsFailure, LocalEvaluationFailure, PostHogError, QueueOverflowFailure,
+};
+pub use spool::SpoolOptions;
+pub(crate) use stats::Stats;
+pub use stats::{ClientStats, FlagRequestStats};
 pub use summary::CaptureSummary;
+pub(crate) use super_properties::SuperProperties;
 
 /// Request-body compression algorithm for the capture pipelines.
 ///
@@ -48,6 +72,7 @@ impl CaptureCompression {
 mod blocking;
 mod retry;
 mod transport;
+pub use transport::OverflowPolicy;
 #[cfg(not(feature = "capture-v1"))]
 mod v0_capture;
 #[cfg(feature = "capture-v1")]
@@ -155,11 +180,48 @@ pub struct ClientOptions {
     #[builder(default = "30")]
     poll_interval_seconds: u64,
 
+    /// Evaluate flags locally from a static file in the `/flags/definitions`
+    /// response format instead of polling PostHog; no secret key is needed.
+    /// Flags are then evaluated only from the file: `evaluate_flags` and the
+    /// local-first flag methods never fall back to remote `/flags`, and the
+    /// definitions poller options (`enable_local_evaluation`,
+    /// `flag_definitions_cache`) are ignored. Combine with `recording` for a
+    /// client that makes no network requests at all.
+    #[builder(setter(into, strip_option), default)]
+    pub(crate) flag_definitions_path: Option<PathBuf>,
+
+    /// Reload `flag_definitions_path` whenever the file changes, checking
+    /// every `poll_interval_seconds`. Defaults to `false`.
+    #[builder(default = "false")]
+    pub(crate) watch_flag_definitions: bool,
+
+    /// Persist local-evaluation flag definitions to disk and load them on
+    /// startup, before the first poll. `None` (default) keeps them in memory
+    /// only. See [`DefinitionsCacheOptions`].
+    #[builder(default, setter(strip_option))]
+    pub(crate) flag_definitions_cache: Option<DefinitionsCacheOptions>,
+
+    /// Cache remote `/flags` responses per distinct ID, groups and
+    /// properties, and serve a stale response when `/flags` fails or is
+    /// quota limited. `None` (default) requests `/flags` on every call. See
+    /// [`FlagsCacheOptions`].
+    #[builder(default, setter(strip_option))]
+    pub(crate) flags_cache: Option<FlagsCacheOptions>,
+
     /// Disable tracking and remote flag requests. Useful for development and
     /// tests.
     #[builder(default = "false")]
     disabled: bool,
 
+    /// Keep captured events in memory instead of sending them, for unit tests.
+    /// Events still run the full capture pipeline; read the final wire events
+    /// with [`Client::captured_events`]. Remote `/flags` and definitions
+    /// requests go through the configured transport as usual (unlike
+    /// [`Client::recording`], which answers them locally), and capture
+    /// compression is ignored.
+    #[builder(default = "false")]
+    pub(crate) recording: bool,
+
     /// Disable automatic GeoIP enrichment for capture and flag requests.
     #[builder(default = "false")]
     disable_geoip: bool,
@@ -222,11 +284,17 @@ pub struct ClientOptions {
     #[builder(default = "5000")]
     pub(crate) flush_interval_ms: u64,
 
-    /// Maximum number of events buffered before new events are dropped
-    /// (default: 10000). A single warning is logged while the queue is full.
+    /// Maximum number of events buffered before the [`OverflowPolicy`] kicks
+    /// in (default: 10000). A single warning is logged while the queue is
+    /// full.
     #[builder(default = "10000")]
     pub(crate) max_queue_size: usize,
 
+    /// What to do with an event captured while the queue is full (default:
+    /// [`OverflowPolicy::DropNewest`]).
+    #[builder(default)]
+    pub(crate) overflow_policy: OverflowPolicy,
+
     /// Maximum time `shutdown()` and `Drop` spend draining buffered and
     /// retrying events before abandoning the rest, in milliseconds (default:
     /// 30000). This bounds the drain itself, including any delivery the drain
@@ -239,6 +307,12 @@ pub struct ClientOptions {
     #[builder(default = "30000")]
     pub(crate) shutdown_timeout_ms: u64,
 
+    /// Persist accepted events to disk until they are delivered, and replay
+    /// those left over from a previous run on startup. `None` (default) keeps
+    /// events in memory only. See [`SpoolOptions`].
+    #[builder(default, setter(strip_option))]
+    pub(crate) spool: Option<SpoolOptions>,
+
     /// Optional request-body compression. When `None` (default), bodies are
     /// sent uncompressed. The V0 pipeline supports `Gzip` only; V1 supports all
     /// variants.
@@ -257,6 +331,36 @@ pub struct ClientOptions {
     #[builder(default, setter(custom))]
     pub(crate) on_error: Vec<OnErrorHook>,
 
+    /// Receives the events the transport worker gives up on. `None` (default)
+    /// drops them. See [`DeadLetterSink`].
+    #[builder(default, setter(custom))]
+    pub(crate) dead_letter_sink: Option<SharedDeadLetterSink>,
+
+    /// Callbacks local evaluation consults before falling back to remote
+    /// `/flags`: cohort membership, person properties and group properties
+    /// it was not given. None by default. See [`CohortMembershipProvider`],
+    /// [`PersonPropertyProvider`] and [`GroupPropertyProvider`].
+    #[builder(default, setter(custom))]
+    pub(crate) local_evaluation_providers: Providers,
+
+    /// Properties attached to every captured event. Properties set on the event
+    /// itself win. Change them at runtime with
+    /// [`Client::register`]/[`Client::unregister`].
+    #[builder(default, setter(custom))]
+    pub(crate) super_properties: SuperProperties,
+
+    /// Flag values forced in front of local evaluation and remote `/flags`.
+    /// Change them at runtime with [`Client::override_flag`]. See
+    /// [`FlagOverrides`].
+    #[builder(default, setter(custom))]
+    pub(crate) flag_overrides: SharedFlagOverrides,
+
+    /// HTTP stack used for every request the client makes: capture, remote
+    /// `/flags`, and the local-evaluation definitions poller. Defaults to
+    /// [`ReqwestTransport`](crate::ReqwestTransport).
+    #[builder(default, setter(custom))]
+    pub(crate) http_transport: SharedTransport,
+
     /// Extra HTTP headers injected into every outbound capture request.
     /// Used by the SDK test harness adapter to attach `X-Test-Id` for
     /// parallel test isolation.
@@ -265,6 +369,11 @@ pub struct ClientOptions {
     #[allow(dead_code)]
     pub(crate) extra_capture_headers: Option<std::collections::HashMap<String, String>>,
 
+    /// Delivery counters read by [`Client::stats`]. Shared with the transport
+    /// worker and the definitions poller.
+    #[builder(setter(skip))]
+    pub(crate) stats: Arc<Stats>,
+
     #[builder(setter(skip))]
     #[builder(default = "EndpointManager::new(DEFAULT_HOST.to_string())")]
     endpoint_manager: EndpointManager,
@@ -276,10 +385,12 @@ pub struct ClientOptions {
 /// paths (V0 capture, V0 flag-called host, V1 capture) so each default is
 /// applied in exactly one place with caller-wins (`entry().or_insert`)
 /// semantics.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) struct CaptureDefaults {
     pub(crate) disable_geoip: bool,
     pub(crate) is_server: bool,
+    /// Super properties as registered when the defaults were resolved.
+    pub(crate) super_properties: Arc<HashMap<String, serde_json::Value>>,
 }
 
 impl ClientOptions {
@@ -288,6 +399,7 @@ impl ClientOptions {
         CaptureDefaults {
             disable_geoip: self.disable_geoip,
             is_server: self.is_server,
+            super_properties: self.super_properties.snapshot(),
         }
     }
 
@@ -340,6 +452,17 @@ impl ClientOptions {
                 .clone()
                 .expect("host is always normalized in sanitize"),
         );
+        // Each client owns its super properties: `register` on one client must
+        // not leak into another built from a clone of the same options.
+        self.super_properties = self.super_properties.detached();
+        self.flag_overrides = self.flag_overrides.detached();
+        // Likewise for delivery counters.
+        self.stats = Arc::default();
+        // Static definitions are the only flag source: never fall back to
+        // remote `/flags` when they can't resolve a flag.
+        if self.flag_definitions_path.is_some() {
+            self.local_evaluation_only = true;
+        }
         self
     }
 }
@@ -378,8 +501,10 @@ impl ClientOptionsBuilder {
     ///
     /// Registering a hook silences the default WARN for terminal capture
     /// reject/exhaustion and serialization failures (the caller now owns that
-    /// signal). Shutdown-timeout, queue-full, and `before_send` drops keep their
-    /// WARN logs and do **not** fire the hook — they are not delivery failures.
+    /// signal). Shutdown-timeout and `before_send` drops keep their WARN logs
+    /// and do **not** fire the hook — they are not delivery failures. Queue-full
+    /// drops keep their WARN too, and are also reported as
+    /// [`PostHogError::QueueOverflow`].
     /// The existing `/flags` and poller WARN logs are unaffected.
     pub fn on_error<F>(&mut self, hook: F) -> &mut Self
     where
@@ -391,6 +516,97 @@ impl ClientOptionsBuilder {
         self
     }
 
+    /// Set properties attached to every captured event, replacing any set
+    /// earlier on this builder.
+    ///
+    /// Super properties reach every capture path — `capture`, `capture_batch`,
+    /// the `*_immediate` variants, and `$feature_flag_called` events — before
+    /// `before_send` runs. Properties set on the event itself win. Minimized
+    /// `$feature_flag_called` events are still trimmed to their allowlist, so
+    /// super properties are dropped from those.
+    pub fn super_properties(
+        &mut self,
+        properties: HashMap<String, serde_json::Value>,
+    ) -> &mut Self {
+        self.super_properties = Some(SuperProperties::new(properties));
+        self
+    }
+
+    /// Force flag values for every `distinct_id` or for single ones, replacing
+    /// any overrides set earlier on this builder.
+    ///
+    /// Overrides are applied in front of local evaluation and remote
+    /// `/flags`: an overridden flag is not evaluated locally, and whatever
+    /// PostHog returns for it is ignored. Reads of a single flag, or of
+    /// [`evaluate_flags`](crate::Client::evaluate_flags) keys that are all
+    /// overridden, skip the request; reads of every flag, such as
+    /// `get_feature_flags`, still ask PostHog for the rest. They also apply
+    /// to disabled clients.
+    pub fn flag_overrides(&mut self, overrides: FlagOverrides) -> &mut Self {
+        self.flag_overrides = Some(SharedFlagOverrides::new(overrides));
+        self
+    }
+
+    /// Hand events the client can't deliver to `sink` instead of dropping
+    /// them: batches rejected by PostHog, out of retries or unserializable, and
+    /// events abandoned at shutdown. Replaces any sink set earlier on this
+    /// builder.
+    pub fn dead_letter_sink<S: DeadLetterSink>(&mut self, sink: S) -> &mut Self {
+        self.dead_letter_sink = Some(Some(SharedDeadLetterSink(Arc::new(sink))));
+        self
+    }
+
+    /// Ask `provider` whether a person is in a cohort that local evaluation
+    /// cannot evaluate — a static or behavioral cohort, or one missing from
+    /// the flag definitions — so flags targeting it stay local instead of
+    /// falling back to remote `/flags`. Replaces any provider set earlier on
+    /// this builder.
+    pub fn cohort_membership_provider<P: CohortMembershipProvider>(
+        &mut self,
+        provider: P,
+    ) -> &mut Self {
+        self.local_evaluation_providers
+            .get_or_insert_with(Providers::default)
+            .cohort_membership = Some(Arc::new(provider));
+        self
+    }
+
+    /// Load person properties a locally evaluated flag filters on but that
+    /// were not passed in from `provider`, so the flag can still be decided
+    /// locally instead of falling back to remote `/flags`. The async client
+    /// calls it on tokio's blocking thread pool. Replaces any provider set
+    /// earlier on this builder.
+    pub fn person_property_provider<P: PersonPropertyProvider>(
+        &mut self,
+        provider: P,
+    ) -> &mut Self {
+        self.local_evaluation_providers
+            .get_or_insert_with(Providers::default)
+            .person_properties = Some(Arc::new(provider));
+        self
+    }
+
+    /// Load group properties like
+    /// [`person_property_provider`](Self::person_property_provider) does for
+    /// person properties. Only groups passed to the evaluation are looked up.
+    /// Replaces any provider set earlier on this builder.
+    pub fn group_property_provider<P: GroupPropertyProvider>(&mut self, provider: P) -> &mut Self {
+        self.local_evaluation_providers
+            .get_or_insert_with(Providers::default)
+            .group_properties = Some(Arc::new(provider));
+        self
+    }
+
+    /// Send the client's HTTP requests through `transport` instead of the
+    /// default [`ReqwestTransport`](crate::ReqwestTransport).
+    ///
+    /// Every clone of the built options, and every client built from them,
+    /// shares the one transport.
+    pub fn http_transport<T: HttpTransport>(&mut self, transport: T) -> &mut Self {
+        self.http_transport = Some(SharedTransport(Arc::new(transport)));
+        self
+    }
+
     /// Build sanitized [`ClientOptions`].
     ///
     /// Missing or whitespace-only API keys are allowed and disable the client so
@@ -3,7 +3,8 @@
 //! Registering a hook via [`ClientOptionsBuilder::on_error`] lets a caller
 //! observe terminal failures across the SDK's network surfaces — capture batch
 //! delivery, remote `/flags` requests, and the local-evaluation definitions
-//! poller — without reverting to a blocking API. The hook receives a
+//! poller — plus events dropped because the capture queue was full, without
+//! reverting to a blocking API. The hook receives a
 //! [`PostHogError`], a `#[non_exhaustive]` enum with one variant per surface so
 //! more can be added without breaking callers.
 //!
@@ -23,6 +24,7 @@
 
 use std::sync::Arc;
 
+use super::OverflowPolicy;
 use crate::error::Error;
 
 #[cfg(feature = "capture-v1")]
@@ -60,8 +62,8 @@ impl OnErrorHook {
     }
 }
 
-/// A terminal failure on one of the SDK's network surfaces, passed by reference
-/// to each registered `on_error` hook.
+/// A terminal failure on one of the SDK's network surfaces, or a queue-full
+/// drop, passed by reference to each registered `on_error` hook.
 ///
 /// `#[non_exhaustive]`: new variants may be added as more surfaces gain hook
 /// coverage, so a `match` must include a wildcard arm.
@@ -79,6 +81,9 @@ pub enum PostHogError<'a> {
     /// error, non-success status, or an unparseable response body). The SDK
     /// keeps serving the previously cached definitions.
     LocalEvaluation(LocalEvaluationFailure<'a>),
+    /// Events dropped because the capture queue was at `max_queue_size`, as
+    /// decided by the client's [`OverflowPolicy`].
+    QueueOverflow(QueueOverflowFailure),
 }
 
 /// Details of a terminal capture batch failure.
@@ -86,7 +91,8 @@ pub enum PostHogError<'a> {
 /// Fields are read through accessors; the struct is `#[non_exhaustive]`.
 ///
 /// Does not fire for shutdown-timeout, queue-full, or `before_send` drops —
-/// those are not delivery failures.
+/// those are not delivery failures. Queue-full drops are reported as
+/// [`PostHogError::QueueOverflow`] instead.
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct CaptureFailure<'a> {
@@ -243,3 +249,29 @@ impl<'a> LocalEvaluationFailure<'a> {
         self.status
     }
 }
+
+/// Details of events dropped because the capture queue was full.
+///
+/// Fields are read through accessors; the struct is `#[non_exhaustive]`.
+/// Fires on the capturing thread, or on the background transport thread for
+/// [`OverflowPolicy::DropOldest`] evictions.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct QueueOverflowFailure {
+    pub(crate) dropped: usize,
+    pub(crate) policy: OverflowPolicy,
+}
+
+impl QueueOverflowFailure {
+    /// Number of events dropped: the new event(s) under
+    /// [`OverflowPolicy::DropNewest`] and [`OverflowPolicy::Block`], the
+    /// evicted ones under [`OverflowPolicy::DropOldest`].
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// The policy that dropped them.
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use super::ClientOptions;
+#[cfg(feature = "async-client")]
+use crate::http::HttpFuture;
+use crate::http::{
+    HttpMethod, HttpRequest, HttpResponse, HttpTransport, SharedTransport, TransportError,
+};
+
+/// Project API key for clients built with `Client::recording()`. Never sent
+/// anywhere: capture requests stop at the recorder, and every other request at
+/// [`OfflineTransport`].
+pub(crate) const RECORDING_API_KEY: &str = "phc_recording";
+
+/// Remote `/flags` answer for `Client::recording()`: no flags enabled.
+const OFFLINE_FLAGS_RESPONSE: &str = r#"{"flags":{}}"#;
+
+/// Flag definitions answer for `Client::recording()`: nothing defined.
+const OFFLINE_DEFINITIONS_RESPONSE: &str = r#"{"flags":[],"group_type_mapping":{},"cohorts":{}}"#;
+
+/// Wire events captured by a recording client, in send order.
+#[derive(Clone, Default)]
+pub(crate) struct Recorder(Arc<Mutex<Vec<Value>>>);
+
+impl Recorder {
+    pub(crate) fn events(&self) -> Vec<Value> {
+        self.0.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.lock().unwrap_or_else(|p| p.into_inner()).clear();
+    }
+
+    /// The first recorded event named `event`, panicking with the names that
+    /// were recorded when there is none.
+    #[track_caller]
+    pub(crate) fn assert_captured(&self, event: &str) -> Value {
+        let events = self.events();
+        if let Some(found) = events.iter().find(|e| e["event"] == event) {
+            return found.clone();
+        }
+        let names: Vec<&str> = events.iter().filter_map(|e| e["event"].as_str()).collect();
+        panic!(
+            "expected a captured `{}` event; captured: {:?}",
+            event, names
+        );
+    }
+
+    /// Store the events of a capture request and acknowledge it, or return
+    /// `None` when `request` is not a capture request.
+    fn record(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.method != HttpMethod::Post || request.url.contains("/flags") {
+            return None;
+        }
+        let Ok(Value::Object(mut body)) = serde_json::from_slice::<Value>(&request.body) else {
+            return None;
+        };
+        let Some(Value::Array(batch)) = body.remove("batch") else {
+            return None;
+        };
+        self.0
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .extend(batch);
+        Some(HttpResponse::new(200, r#"{"results":{}}"#))
+    }
+}
+
+/// Answers capture requests from the [`Recorder`] and passes everything else
+/// (remote `/flags`, definitions polls) to the configured transport.
+struct RecordingTransport {
+    inner: SharedTransport,
+    recorder: Recorder,
+}
+
+impl HttpTransport for RecordingTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        match self.recorder.record(&request) {
+            Some(response) => Ok(response),
+            None => self.inner.send(request),
+        }
+    }
+
+    #[cfg(feature = "async-client")]
+    fn send_async(self: Arc<Self>, request: HttpRequest) -> HttpFuture {
+        match self.recorder.record(&request) {
+            Some(response) => Box::pin(std::future::ready(Ok(response))),
+            None => self.inner.send_async(request),
+        }
+    }
+}
+
+/// The transport behind `Client::recording()`, so a unit test never reaches
+/// PostHog: remote `/flags` requests get an empty response (every flag
+/// disabled) and definitions requests an empty set of definitions.
+pub(crate) struct OfflineTransport;
+
+impl HttpTransport for OfflineTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let body = if request.url.contains("/flags/definitions") {
+            OFFLINE_DEFINITIONS_RESPONSE
+        } else {
+            OFFLINE_FLAGS_RESPONSE
+        };
+        Ok(HttpResponse::new(200, body))
+    }
+
+    #[cfg(feature = "async-client")]
+    fn send_async(self: Arc<Self>, request: HttpRequest) -> HttpFuture {
+        Box::pin(std::future::ready(self.send(request)))
+    }
+}
+
+/// Route `options`' capture requests into a fresh [`Recorder`]. Bodies are left
+/// uncompressed so the recorder can read them.
+pub(crate) fn install(options: &mut ClientOptions) -> Recorder {
+    let recorder = Recorder::default();
+    options.capture_compression = None;
+    options.http_transport = SharedTransport(Arc::new(RecordingTransport {
+        inner: options.http_transport.clone(),
+        recorder: recorder.clone(),
+    }));
+    recorder
+}
@@ -3,10 +3,10 @@
 use std::time::{Duration, SystemTime};
 
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
-use reqwest::header::HeaderMap;
 
 use super::ClientOptions;
 use crate::error::Error;
+use crate::http::HttpResponse;
 
 /// Outcome of one capture attempt, computed without any I/O so both the async
 /// and blocking clients (and both V0 and V1) can share the decision logic and
@@ -50,12 +50,12 @@ pub(crate) fn should_retry_v0(status: u16, has_retry_after: bool) -> bool {
 ///
 /// Non-positive delays and dates in the past are ignored, matching the HTTP
 /// semantics that `Retry-After` is a minimum delay before the next attempt.
-pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
-    parse_retry_after_at(headers, SystemTime::now())
+pub(crate) fn parse_retry_after(response: &HttpResponse) -> Option<Duration> {
+    parse_retry_after_at(response.header("retry-after"), SystemTime::now())
 }
 
-fn parse_retry_after_at(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
-    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+fn parse_retry_after_at(value: Option<&str>, now: SystemTime) -> Option<Duration> {
+    let value = value?.trim();
     if value.is_empty() {
         return None;
     }
@@ -185,8 +185,6 @@ pub(crate) fn feature_flags_after_transport_error(
 
 #[cfg(test)]
 mod tests {
-    use reqwest::header::{HeaderMap, HeaderValue};
-
     use super::*;
     use crate::client::ClientOptionsBuilder;
 
@@ -265,12 +263,8 @@ mod tests {
             (Some("Thu, 01 Jan 1970 00:00:00 GMT"), None),
         ];
         for (header_val, expected) in cases {
-            let mut headers = HeaderMap::new();
-            if let Some(v) = header_val {
-                headers.insert("retry-after", HeaderValue::from_str(v).unwrap());
-            }
             assert_eq!(
-                parse_retry_after_at(&headers, now),
+                parse_retry_after_at(header_val, now),
                 expected,
                 "header={:?}",
                 header_val
@@ -0,0 +1,836 @@
+//! Opt-in on-disk spool for the transport worker.
+//!
+//! With [`SpoolOptions`] set on [`ClientOptions`](super::ClientOptions), the
+//! worker appends every event it accepts to a segment file before buffering
+//! it, and releases the event once it reaches a terminal outcome: delivered,
+//! rejected by PostHog, or dropped by `before_send`. Events that exhaust their
+//! retries on a transient failure are parked on disk and sent again after the
+//! next successful delivery, backing off while the same events keep failing.
+//! Events still undelivered when the process goes
+//! away — abandoned at the shutdown deadline, still parked, or cut off by a
+//! crash or OOM-kill — stay on disk and are replayed by the next client that
+//! opens the same directory. Every event is synced to disk before the worker
+//! buffers it, and the directory is locked for as long as the client runs.
+//!
+//! Delivery is at-least-once: an event delivered just before a crash is sent
+//! again on replay, with its original UUID so PostHog can deduplicate it.
+//!
+//! Segments are NDJSON: one line per event, plus a release marker for each
+//! event released while others in its segment are still outstanding, so replay
+//! sends only what is left. A segment is deleted once every event in it is
+//! released. Replay skips lines that don't parse (a torn write from a crash, or
+//! other corruption) with a warning. Total size is capped by
+//! [`SpoolOptions::max_bytes`]; past the cap new events are still sent from
+//! memory but are no longer persisted.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::event::{SpooledEvent, SpooledEventRef};
+use crate::Event;
+
+/// Default cap on the spool's size on disk: 64 MiB.
+const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Size at which the worker starts a new segment file, so fully released
+/// segments can be deleted while newer ones are still filling.
+const SEGMENT_BYTES: u64 = 1024 * 1024;
+
+/// Wait before re-driving parked events that failed again after their last
+/// re-drive, doubled on each repeat failure up to [`MAX_REDRIVE_BACKOFF`]. The
+/// first re-drive after a delivery is immediate.
+const REDRIVE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_REDRIVE_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Lock file held for as long as a client has the directory open.
+const LOCK_FILE: &str = "spool.lock";
+
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_SUFFIX: &str = ".ndjson";
+
+/// Configuration for the on-disk event spool.
+///
+/// The spool keeps undelivered events across restarts: events the client
+/// could not deliver before exiting (or crashing) are replayed by the next
+/// client opened on the same directory. Only one client may use a directory at
+/// a time: the client holds an exclusive lock on it, and a second client
+/// opened on the same directory runs without a spool (with a warning).
+///
+/// # Examples
+///
+/// ```
+/// use posthog_rs::{ClientOptionsBuilder, SpoolOptions};
+///
+/// let options = ClientOptionsBuilder::default()
+///     .api_key("phc_project_api_key".to_string())
+///     .spool(SpoolOptions::new("/var/lib/my-service/posthog").max_bytes(16 * 1024 * 1024))
+///     .build()
+///     .unwrap();
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpoolOptions {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl SpoolOptions {
+    /// Spool events under `dir`, creating it if needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+
+    /// Cap the spool's size on disk, in bytes (default: 64 MiB). Once reached,
+    /// new events are still sent but not persisted until space frees up.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+/// A spooled event line.
+#[derive(Serialize)]
+struct RecordRef<'a> {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    historical_migration: bool,
+    event: SpooledEventRef<'a>,
+}
+
+/// A release marker line: the event with this UUID earlier in the same segment
+/// reached a terminal outcome.
+#[derive(Serialize, Deserialize)]
+struct Released {
+    released: Uuid,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Released(Released),
+    Record {
+        #[serde(default)]
+        historical_migration: bool,
+        event: SpooledEvent,
+    },
+}
+
+/// An event left over from a previous run, handed back to the worker to send.
+pub(crate) struct Replayed {
+    pub(crate) event: Event,
+    pub(crate) historical_migration: bool,
+}
+
+struct Segment {
+    path: PathBuf,
+    bytes: u64,
+    /// Events in this segment not yet released.
+    outstanding: usize,
+}
+
+/// The worker-owned spool. Not shared across threads.
+pub(crate) struct Spool {
+    /// Exclusive lock on the directory, released when the spool is dropped.
+    _lock: File,
+    dir: PathBuf,
+    max_bytes: u64,
+    segment_bytes: u64,
+    segments: BTreeMap<u64, Segment>,
+    /// Append handle for the newest segment.
+    active: Option<(u64, File)>,
+    next_id: u64,
+    total_bytes: u64,
+    /// The segment holding each unreleased event (several if a UUID repeats).
+    owners: HashMap<Uuid, Vec<u64>>,
+    /// Latches the single "spool full" warning per episode.
+    full_warned: bool,
+    /// Events kept on disk after exhausting their retries on a transient
+    /// failure, to be sent again once a delivery succeeds.
+    parked: HashSet<Uuid>,
+    /// Whether a delivery succeeded since events were last parked.
+    redrive: bool,
+    /// Parked events handed back to the worker and not yet settled again.
+    redriven: HashSet<Uuid>,
+    /// Current wait before a re-drive; zero until re-driven events fail again.
+    redrive_backoff: Duration,
+    /// Earliest time parked events may be re-driven.
+    redrive_at: Option<Instant>,
+}
+
+impl Spool {
+    /// Open the spool directory, returning the spool plus every event left
+    /// over from a previous run, oldest first.
+    pub(crate) fn open(options: &SpoolOptions) -> io::Result<(Self, Vec<Replayed>)> {
+        Self::open_with_segment_bytes(options, SEGMENT_BYTES)
+    }
+
+    fn open_with_segment_bytes(
+        options: &SpoolOptions,
+        segment_bytes: u64,
+    ) -> io::Result<(Self, Vec<Replayed>)> {
+        fs::create_dir_all(&options.dir)?;
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(options.dir.join(LOCK_FILE))?;
+        if lock.try_lock_exclusive().is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the directory is in use by another client",
+            ));
+        }
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&options.dir)? {
+            if let Some(id) = segment_id(&entry?.file_name()) {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let mut spool = Self {
+            _lock: lock,
+            dir: options.dir.clone(),
+            max_bytes: options.max_bytes,
+            segment_bytes: segment_bytes.max(1),
+            segments: BTreeMap::new(),
+            active: None,
+            next_id: ids.last().map_or(0, |id| id + 1),
+            total_bytes: 0,
+            owners: HashMap::new(),
+            full_warned: false,
+            parked: HashSet::new(),
+            redrive: false,
+            redriven: HashSet::new(),
+            redrive_backoff: Duration::ZERO,
+            redrive_at: None,
+        };
+
+        let mut replayed = Vec::new();
+        let mut corrupt = 0usize;
+        for id in ids {
+            let path = spool.segment_path(id);
+            let contents = match read_segment(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("posthog-rs: skipping unreadable spool segment {path:?}: {e}");
+                    continue;
+                }
+            };
+            corrupt += contents.corrupt;
+            let outstanding = contents.outstanding.len();
+            if outstanding == 0 {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            for record in contents.outstanding {
+                spool
+                    .owners
+                    .entry(record.event.uuid())
+                    .or_default()
+                    .push(id);
+                replayed.push(record);
+            }
+            spool.total_bytes += contents.bytes;
+            spool.segments.insert(
+                id,
+                Segment {
+                    path,
+                    bytes: contents.bytes,
+                    outstanding,
+                },
+            );
+        }
+        if corrupt > 0 {
+            warn!("posthog-rs: skipped {corrupt} corrupt spooled event(s) on replay");
+        }
+        Ok((spool, replayed))
+    }
+
+    /// Persist `event` before the worker buffers it, returning whether it was
+    /// persisted. A write failure or a full spool only costs durability: the
+    /// event is still sent from memory.
+    pub(crate) fn append(&mut self, event: &Event, historical_migration: bool) -> bool {
+        let record = RecordRef {
+            historical_migration,
+            event: SpooledEventRef::from(event),
+        };
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("posthog-rs: not spooling event, serialization failed: {e}");
+                return false;
+            }
+        };
+        line.push(b'\n');
+        let len = line.len() as u64;
+
+        if self.total_bytes + len > self.max_bytes {
+            if !self.full_warned {
+                self.full_warned = true;
+                warn!(
+                    "posthog-rs: spool full ({} bytes); new events are not persisted",
+                    self.max_bytes
+                );
+            }
+            return false;
+        }
+
+        let (id, file) = match self.writable_segment(len) {
+            Ok(active) => active,
+            Err(e) => {
+                warn!("posthog-rs: not spooling event, failed to open segment: {e}");
+                return false;
+            }
+        };
+        if let Err(e) = file.write_all(&line) {
+            warn!("posthog-rs: not spooling event, write failed: {e}");
+            // A partial line would corrupt the next one; start a fresh segment.
+            self.close_active();
+            return false;
+        }
+        if let Err(e) = file.sync_data() {
+            // The line is written; only its durability across a crash is lost.
+            warn!("posthog-rs: failed to sync spooled event to disk: {e}");
+        }
+
+        self.full_warned = false;
+        self.total_bytes += len;
+        if let Some(segment) = self.segments.get_mut(&id) {
+            segment.bytes += len;
+            segment.outstanding += 1;
+        }
+        self.owners.entry(event.uuid()).or_default().push(id);
+        true
+    }
+
+    /// Release events that reached a terminal outcome, deleting segments that
+    /// no longer hold any unreleased event. Unknown UUIDs are ignored.
+    pub(crate) fn release<'a>(&mut self, uuids: impl IntoIterator<Item = &'a Uuid>) {
+        let mut by_segment: BTreeMap<u64, Vec<Uuid>> = BTreeMap::new();
+        for uuid in uuids {
+            self.redriven.remove(uuid);
+            let Some(ids) = self.owners.get_mut(uuid) else {
+                continue;
+            };
+            let id = ids.remove(0);
+            if ids.is_empty() {
+                self.owners.remove(uuid);
+            }
+            by_segment.entry(id).or_default().push(*uuid);
+        }
+
+        for (id, uuids) in by_segment {
+            let Some(segment) = self.segments.get_mut(&id) else {
+                continue;
+            };
+            segment.outstanding = segment.outstanding.saturating_sub(uuids.len());
+            if segment.outstanding == 0 {
+                self.discard(id);
+            } else {
+                self.mark_released(id, &uuids);
+            }
+        }
+        if self.parked.is_empty() && self.redriven.is_empty() {
+            self.redrive_backoff = Duration::ZERO;
+        }
+    }
+
+    /// Whether the event with this UUID is still on disk, waiting to be
+    /// delivered by this client or replayed by the next one.
+    pub(crate) fn holds(&self, uuid: &Uuid) -> bool {
+        self.owners.contains_key(uuid)
+    }
+
+    /// Keep events that exhausted their retries on a transient failure on
+    /// disk, to be sent again once a later delivery succeeds (see
+    /// [`Spool::take_parked`]). Re-driven events that fail again back off
+    /// before their next re-drive. Unknown UUIDs are ignored.
+    pub(crate) fn park<'a>(&mut self, uuids: impl IntoIterator<Item = &'a Uuid>, now: Instant) {
+        let mut parked = false;
+        let mut repeat = false;
+        for uuid in uuids {
+            if self.owners.contains_key(uuid) {
+                self.parked.insert(*uuid);
+                parked = true;
+                repeat |= self.redriven.remove(uuid);
+            }
+        }
+        if !parked {
+            return;
+        }
+        if repeat {
+            self.redrive_backoff = if self.redrive_backoff.is_zero() {
+                REDRIVE_BACKOFF
+            } else {
+                (self.redrive_backoff * 2).min(MAX_REDRIVE_BACKOFF)
+            };
+        }
+        self.redrive_at = Some(now + self.redrive_backoff);
+    }
+
+    /// Record a successful delivery: PostHog is reachable again, so parked
+    /// events are worth another attempt.
+    pub(crate) fn delivered(&mut self) {
+        self.redrive = !self.parked.is_empty();
+    }
+
+    /// When parked events are next due for a re-drive, once a delivery has
+    /// succeeded since they were parked.
+    pub(crate) fn next_redrive(&self) -> Option<Instant> {
+        if self.redrive {
+            self.redrive_at
+        } else {
+            None
+        }
+    }
+
+    /// Read parked events back from disk once a delivery has succeeded since
+    /// they were parked and their backoff has elapsed, oldest first. They stay
+    /// spooled (and owned by their segments) until the worker settles them
+    /// again.
+    pub(crate) fn take_parked(&mut self, now: Instant) -> Vec<Replayed> {
+        if !self.redrive || self.redrive_at.is_some_and(|at| now < at) {
+            return Vec::new();
+        }
+        self.redrive = false;
+        let parked = std::mem::take(&mut self.parked);
+        self.redriven.extend(parked.iter().copied());
+        let ids: BTreeSet<u64> = parked
+            .iter()
+            .filter_map(|uuid| self.owners.get(uuid))
+            .flatten()
+            .copied()
+            .collect();
+        let mut unparked = Vec::new();
+        for id in ids {
+            let Some(segment) = self.segments.get(&id) else {
+                continue;
+            };
+            match read_segment(&segment.path) {
+                Ok(contents) => unparked.extend(
+                    contents
+                        .outstanding
+                        .into_iter()
+                        .filter(|record| parked.contains(&record.event.uuid())),
+                ),
+                Err(e) => {
+                    warn!("posthog-rs: failed to read parked events from spool; they stay on disk: {e}")
+                }
+            }
+        }
+        unparked
+    }
+
+    /// Append release markers to a segment that still holds outstanding events,
+    /// so a replay skips the released ones. Failing to write them only means
+    /// those events may be sent again.
+    fn mark_released(&mut self, id: u64, uuids: &[Uuid]) {
+        let mut lines = Vec::new();
+        for uuid in uuids {
+            if serde_json::to_writer(&mut lines, &Released { released: *uuid }).is_err() {
+                return;
+            }
+            lines.push(b'\n');
+        }
+        let written = match &mut self.active {
+            Some((active_id, file)) if *active_id == id => file.write_all(&lines),
+            _ => match self.segments.get(&id) {
+                Some(segment) => File::options()
+                    .append(true)
+                    .open(&segment.path)
+                    .and_then(|mut file| file.write_all(&lines)),
+                None => return,
+            },
+        };
+        match written {
+            Ok(()) => {
+                let len = lines.len() as u64;
+                self.total_bytes += len;
+                if let Some(segment) = self.segments.get_mut(&id) {
+                    segment.bytes += len;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "posthog-rs: failed to mark delivered events in spool; they may be resent: {e}"
+                );
+                if self
+                    .active
+                    .as_ref()
+                    .is_some_and(|(active_id, _)| *active_id == id)
+                {
+                    self.close_active();
+                }
+            }
+        }
+    }
+
+    /// Drop a fully released segment: truncate it if it is still the append
+    /// target, delete it otherwise.
+    fn discard(&mut self, id: u64) {
+        match &self.active {
+            Some((active_id, file)) if *active_id == id => {
+                if let Err(e) = file.set_len(0) {
+                    warn!("posthog-rs: failed to truncate spool segment: {e}");
+                    return;
+                }
+                if let Some(segment) = self.segments.get_mut(&id) {
+                    self.total_bytes -= segment.bytes;
+                    segment.bytes = 0;
+                }
+            }
+            _ => {
+                if let Some(segment) = self.segments.remove(&id) {
+                    self.total_bytes -= segment.bytes;
+                    if let Err(e) = fs::remove_file(&segment.path) {
+                        warn!("posthog-rs: failed to delete spool segment: {e}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// The segment to append a `len`-byte line to, rotating once the active
+    /// segment is full.
+    fn writable_segment(&mut self, len: u64) -> io::Result<(u64, &mut File)> {
+        let full = self.active.as_ref().is_some_and(|(id, _)| {
+            self.segments
+                .get(id)
+                .is_some_and(|s| s.bytes > 0 && s.bytes + len > self.segment_bytes)
+        });
+        if full {
+            self.close_active();
+        }
+        if self.active.is_none() {
+            let id = self.next_id;
+            let path = self.segment_path(id);
+            let file = File::options().create(true).append(true).open(&path)?;
+            self.next_id += 1;
+            self.segments.insert(
+                id,
+                Segment {
+                    path,
+                    bytes: 0,
+                    outstanding: 0,
+                },
+            );
+            self.active = Some((id, file));
+        }
+        let (id, file) = self
+            .active
+            .as_mut()
+            .expect("active segment was just opened");
+        Ok((*id, file))
+    }
+
+    /// Stop appending to the active segment, deleting it if nothing in it is
+    /// still outstanding.
+    fn close_active(&mut self) {
+        if let Some((id, _)) = self.active.take() {
+            if self.segments.get(&id).is_some_and(|s| s.outstanding == 0) {
+                self.discard(id);
+            }
+        }
+    }
+
+    fn segment_path(&self, id: u64) -> PathBuf {
+        segment_path(&self.dir, id)
+    }
+}
+
+/// A segment read back from disk.
+struct SegmentContents {
+    /// Events in the segment without a matching release marker, in order.
+    outstanding: Vec<Replayed>,
+    bytes: u64,
+    /// Lines that didn't parse.
+    corrupt: usize,
+}
+
+fn read_segment(path: &Path) -> io::Result<SegmentContents> {
+    let contents = fs::read(path)?;
+    let mut records = Vec::new();
+    let mut released: HashMap<Uuid, usize> = HashMap::new();
+    let mut corrupt = 0;
+    for line in contents.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+        match serde_json::from_slice::<Line>(line) {
+            Ok(Line::Record {
+                historical_migration,
+                event,
+            }) => records.push(Replayed {
+                event: Event::from(event),
+                historical_migration,
+            }),
+            Ok(Line::Released(marker)) => *released.entry(marker.released).or_default() += 1,
+            Err(_) => corrupt += 1,
+        }
+    }
+    let outstanding = records
+        .into_iter()
+        .filter(|record| {
+            match released
+                .get_mut(&record.event.uuid())
+                .filter(|count| **count > 0)
+            {
+                Some(count) => {
+                    *count -= 1;
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect();
+    Ok(SegmentContents {
+        outstanding,
+        bytes: contents.len() as u64,
+        corrupt,
+    })
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{SEGMENT_PREFIX}{id:020}{SEGMENT_SUFFIX}"))
+}
+
+fn segment_id(file_name: &OsStr) -> Option<u64> {
+    file_name
+        .to_str()?
+        .strip_prefix(SEGMENT_PREFIX)?
+        .strip_suffix(SEGMENT_SUFFIX)?
+        .parse()
+        .ok()
+}
+
+/// Whether events that failed terminally with `error` should stay spooled for
+/// replay. Transport errors, rate limiting, and server errors may succeed on a
+/// later run; anything else (a rejected payload, a bad API key, an exhausted
+/// quota) never will.
+pub(crate) fn keeps_spooled(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Connection(_) | Error::RateLimit | Error::ServerError { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("posthog-spool-{}", Uuid::now_v7()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn segment_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| segment_id(p.file_name().unwrap()).is_some())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn event(name: &str) -> Event {
+        let mut event = Event::new(name, "user-1");
+        event.insert_prop("plan", "pro").unwrap();
+        event
+    }
+
+    #[test]
+    fn unreleased_events_are_replayed_on_next_open() {
+        let dir = temp_dir();
+        let options = SpoolOptions::new(&dir);
+        let delivered = event("delivered");
+        let mut pending = event("pending");
+        pending.add_group("company", "acme");
+        pending.mark_minimal_flag_called();
+
+        let (mut spool, replayed) = Spool::open(&options).unwrap();
+        assert!(replayed.is_empty());
+        spool.append(&delivered, false);
+        spool.append(&pending, true);
+        spool.release([&delivered.uuid()]);
+        drop(spool);
+
+        let (_, replayed) = Spool::open(&options).unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].event, pending);
+        assert!(replayed[0].historical_migration);
+        assert!(replayed[0].event.is_minimal_flag_called());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fully_released_segments_are_removed() {
+        let dir = temp_dir();
+        let events: Vec<Event> = (0..6).map(|i| event(&format!("e{i}"))).collect();
+
+        // Tiny segments so every event rotates into a new file.
+        let (mut spool, _) = Spool::open_with_segment_bytes(&SpoolOptions::new(&dir), 1).unwrap();
+        for event in &events {
+            spool.append(event, false);
+        }
+        assert_eq!(segment_files(&dir).len(), 6);
+
+        let uuids: Vec<Uuid> = events.iter().map(Event::uuid).collect();
+        spool.release(&uuids);
+        // Only the (now empty) active segment remains.
+        let files = segment_files(&dir);
+        assert_eq!(files.len(), 1);
+        assert_eq!(fs::metadata(&files[0]).unwrap().len(), 0);
+        assert_eq!(spool.total_bytes, 0);
+        drop(spool);
+
+        let (_, replayed) = Spool::open(&SpoolOptions::new(&dir)).unwrap();
+        assert!(replayed.is_empty());
+        assert!(segment_files(&dir).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_and_torn_lines_are_skipped_on_replay() {
+        let dir = temp_dir();
+        let (mut spool, _) = Spool::open(&SpoolOptions::new(&dir)).unwrap();
+        spool.append(&event("first"), false);
+        drop(spool);
+
+        let path = segment_files(&dir).remove(0);
+        let mut file = File::options().append(true).open(&path).unwrap();
+        file.write_all(b"not json at all\n").unwrap();
+        let torn = serde_json::to_vec(&json!({"event": {"event": "torn"}})).unwrap();
+        file.write_all(&torn[..torn.len() / 2]).unwrap();
+        drop(file);
+
+        let (_, replayed) = Spool::open(&SpoolOptions::new(&dir)).unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].event.event_name(), "first");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appends_past_max_bytes_are_not_persisted() {
+        let dir = temp_dir();
+        let first = event("first");
+        let line_len = serde_json::to_vec(&RecordRef {
+            historical_migration: false,
+            event: SpooledEventRef::from(&first),
+        })
+        .unwrap()
+        .len() as u64
+            + 1;
+
+        let options = SpoolOptions::new(&dir).max_bytes(line_len + line_len / 2);
+        let (mut spool, _) = Spool::open(&options).unwrap();
+        spool.append(&first, false);
+        spool.append(&event("second"), false);
+        assert_eq!(spool.total_bytes, line_len);
+
+        // Releasing frees space for later events.
+        spool.release([&first.uuid()]);
+        let third = event("third");
+        spool.append(&third, false);
+        drop(spool);
+
+        let (_, replayed) = Spool::open(&options).unwrap();
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0].event, third);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parked_events_are_read_back_after_a_delivery() {
+        let dir = temp_dir();
+        let (mut spool, _) = Spool::open(&SpoolOptions::new(&dir)).unwrap();
+        let parked = event("parked");
+        let delivered = event("delivered");
+        spool.append(&parked, true);
+        spool.append(&delivered, false);
+        let now = Instant::now();
+        spool.park([&parked.uuid()], now);
+        assert!(spool.take_parked(now).is_empty());
+
+        spool.release([&delivered.uuid()]);
+        spool.delivered();
+        let unparked = spool.take_parked(now);
+        assert_eq!(unparked.len(), 1);
+        assert_eq!(unparked[0].event, parked);
+        assert!(unparked[0].historical_migration);
+        // Each delivery re-drives them once.
+        assert!(spool.take_parked(now).is_empty());
+
+        spool.release([&parked.uuid()]);
+        assert_eq!(spool.total_bytes, 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn re_driven_events_that_fail_again_back_off() {
+        let dir = temp_dir();
+        let (mut spool, _) = Spool::open(&SpoolOptions::new(&dir)).unwrap();
+        let parked = event("parked");
+        spool.append(&parked, false);
+        let now = Instant::now();
+        spool.park([&parked.uuid()], now);
+        spool.delivered();
+        assert_eq!(spool.next_redrive(), Some(now));
+        assert_eq!(spool.take_parked(now).len(), 1);
+
+        // The re-drive failed again: the next one waits, doubling each time.
+        spool.park([&parked.uuid()], now);
+        spool.delivered();
+        assert_eq!(spool.next_redrive(), Some(now + REDRIVE_BACKOFF));
+        assert!(spool.take_parked(now).is_empty());
+        assert_eq!(spool.take_parked(now + REDRIVE_BACKOFF).len(), 1);
+        spool.park([&parked.uuid()], now);
+        spool.delivered();
+        assert_eq!(spool.next_redrive(), Some(now + REDRIVE_BACKOFF * 2));
+
+        // Settling everything resets the backoff.
+        assert_eq!(spool.take_parked(now + REDRIVE_BACKOFF * 2).len(), 1);
+        spool.release([&parked.uuid()]);
+        assert_eq!(spool.redrive_backoff, Duration::ZERO);
+        assert!(!spool.holds(&parked.uuid()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_directory_is_locked_by_one_spool_at_a_time() {
+        let dir = temp_dir();
+        let options = SpoolOptions::new(&dir);
+        let (spool, _) = Spool::open(&options).unwrap();
+        let err = Spool::open(&options).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        drop(spool);
+        assert!(Spool::open(&options).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_transient_failures_keep_events_spooled() {
+        assert!(keeps_spooled(&Error::Connection("reset".to_string())));
+        assert!(keeps_spooled(&Error::RateLimit));
+        assert!(keeps_spooled(&Error::ServerError {
+            status: 503,
+            message: String::new(),
+        }));
+        assert!(!keeps_spooled(&Error::BadRequest(String::new())));
+        assert!(!keeps_spooled(&Error::Unauthorized));
+        assert!(!keeps_spooled(&Error::Serialization(String::new())));
+    }
+}
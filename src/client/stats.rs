@@ -0,0 +1,242 @@
+//! Delivery counters behind [`Client::stats`](crate::Client::stats).
+//!
+//! One [`Stats`] is shared (through [`ClientOptions`](super::ClientOptions))
+//! by the client, its transport worker and its definitions poller; each bumps
+//! the counters it owns. With the `metrics` feature every update is also
+//! forwarded to the [`metrics`](https://docs.rs/metrics) facade, so whichever
+//! recorder the application installed exports them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// A point-in-time copy of a client's delivery counters, from
+/// [`Client::stats`](crate::Client::stats).
+///
+/// Event counters cover the background worker — `capture`, `capture_batch`
+/// and `$feature_flag_called` events. The `*_immediate` variants report
+/// through their return value instead and are not counted. Events still
+/// queued or dropped by the shutdown timeout are neither sent nor rejected.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ClientStats {
+    /// Events accepted onto the capture queue.
+    pub events_enqueued: u64,
+    /// Events dropped because the queue was at `max_queue_size`.
+    pub events_dropped_queue_full: u64,
+    /// Events discarded by a `before_send` hook.
+    pub events_dropped_before_send: u64,
+    /// Events the server accepted.
+    pub events_sent: u64,
+    /// Events scheduled for another attempt, counted once per retry.
+    pub events_retried: u64,
+    /// Events given up on: rejected by the server, out of attempts, or
+    /// unserializable.
+    pub events_rejected: u64,
+    /// Capture request bytes before compression, summed over every attempt.
+    pub bytes_sent_uncompressed: u64,
+    /// Capture request bytes as sent, after compression, summed over every
+    /// attempt.
+    pub bytes_sent: u64,
+    /// Remote `/flags` request outcomes and latencies.
+    pub flag_requests: FlagRequestStats,
+    /// When the definitions poller last refreshed (or confirmed) the flag
+    /// definitions. `None` without local evaluation or before a success.
+    pub last_poll_success: Option<DateTime<Utc>>,
+    /// When the definitions poller last failed. `None` if it never has.
+    pub last_poll_failure: Option<DateTime<Utc>>,
+}
+
+/// Remote `/flags` request counters within [`ClientStats`]. Latencies span
+/// the whole request, retries included.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct FlagRequestStats {
+    /// Completed `/flags` requests, successful or not.
+    pub requests: u64,
+    /// Requests that ended in a transport error or a non-`2xx` status.
+    pub failures: u64,
+    /// Latency of the most recent request.
+    pub last_latency: Option<Duration>,
+    /// Slowest request so far.
+    pub max_latency: Option<Duration>,
+    /// Sum of all request latencies; divide by `requests` for the mean.
+    pub total_latency: Duration,
+}
+
+/// The live counters behind [`ClientStats`].
+#[derive(Default)]
+pub(crate) struct Stats {
+    events_enqueued: AtomicU64,
+    events_dropped_queue_full: AtomicU64,
+    events_dropped_before_send: AtomicU64,
+    events_sent: AtomicU64,
+    events_retried: AtomicU64,
+    events_rejected: AtomicU64,
+    bytes_sent_uncompressed: AtomicU64,
+    bytes_sent: AtomicU64,
+    flag_requests: Mutex<FlagRequestStats>,
+    polls: Mutex<PollTimes>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct PollTimes {
+    success: Option<DateTime<Utc>>,
+    failure: Option<DateTime<Utc>>,
+}
+
+impl Stats {
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        let polls = *self.polls.lock().unwrap_or_else(|p| p.into_inner());
+        ClientStats {
+            events_enqueued: self.events_enqueued.load(Ordering::Relaxed),
+            events_dropped_queue_full: self.events_dropped_queue_full.load(Ordering::Relaxed),
+            events_dropped_before_send: self.events_dropped_before_send.load(Ordering::Relaxed),
+            events_sent: self.events_sent.load(Ordering::Relaxed),
+            events_retried: self.events_retried.load(Ordering::Relaxed),
+            events_rejected: self.events_rejected.load(Ordering::Relaxed),
+            bytes_sent_uncompressed: self.bytes_sent_uncompressed.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            flag_requests: self
+                .flag_requests
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .clone(),
+            last_poll_success: polls.success,
+            last_poll_failure: polls.failure,
+        }
+    }
+
+    pub(crate) fn events_enqueued(&self, n: usize) {
+        add(&self.events_enqueued, "posthog.events.enqueued", n);
+    }
+
+    pub(crate) fn events_dropped_queue_full(&self, n: usize) {
+        add(
+            &self.events_dropped_queue_full,
+            "posthog.events.dropped_queue_full",
+            n,
+        );
+    }
+
+    /// Count a panic-hook enqueue. Atomics only: the hook runs on the panicking
+    /// thread, where the `metrics` recorder (arbitrary application code) must
+    /// not run.
+    #[cfg(feature = "error-tracking")]
+    pub(crate) fn panic_enqueue(&self, accepted: bool) {
+        let counter = if accepted {
+            &self.events_enqueued
+        } else {
+            &self.events_dropped_queue_full
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn events_dropped_before_send(&self, n: usize) {
+        add(
+            &self.events_dropped_before_send,
+            "posthog.events.dropped_before_send",
+            n,
+        );
+    }
+
+    pub(crate) fn events_sent(&self, n: usize) {
+        add(&self.events_sent, "posthog.events.sent", n);
+    }
+
+    pub(crate) fn events_retried(&self, n: usize) {
+        add(&self.events_retried, "posthog.events.retried", n);
+    }
+
+    pub(crate) fn events_rejected(&self, n: usize) {
+        add(&self.events_rejected, "posthog.events.rejected", n);
+    }
+
+    /// One capture attempt's body size, before and after compression.
+    pub(crate) fn bytes_sent(&self, uncompressed: usize, sent: usize) {
+        add(
+            &self.bytes_sent_uncompressed,
+            "posthog.capture.bytes_uncompressed",
+            uncompressed,
+        );
+        add(&self.bytes_sent, "posthog.capture.bytes_sent", sent);
+    }
+
+    pub(crate) fn flag_request(&self, latency: Duration, success: bool) {
+        let mut flags = self.flag_requests.lock().unwrap_or_else(|p| p.into_inner());
+        flags.requests += 1;
+        if !success {
+            flags.failures += 1;
+        }
+        flags.last_latency = Some(latency);
+        flags.max_latency = flags.max_latency.max(Some(latency));
+        flags.total_latency += latency;
+        drop(flags);
+
+        #[cfg(feature = "metrics")]
+        {
+            let outcome = if success { "success" } else { "failure" };
+            metrics::counter!("posthog.flags.requests", "outcome" => outcome).increment(1);
+            metrics::histogram!("posthog.flags.request_duration_seconds")
+                .record(latency.as_secs_f64());
+        }
+    }
+
+    pub(crate) fn definitions_poll(&self, success: bool) {
+        let now = Utc::now();
+        let mut polls = self.polls.lock().unwrap_or_else(|p| p.into_inner());
+        if success {
+            polls.success = Some(now);
+        } else {
+            polls.failure = Some(now);
+        }
+        drop(polls);
+
+        #[cfg(feature = "metrics")]
+        {
+            let outcome = if success { "success" } else { "failure" };
+            metrics::counter!("posthog.flags.definitions_polls", "outcome" => outcome).increment(1);
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+fn add(counter: &AtomicU64, metric: &'static str, n: usize) {
+    if n == 0 {
+        return;
+    }
+    counter.fetch_add(n as u64, Ordering::Relaxed);
+    #[cfg(feature = "metrics")]
+    metrics::counter!(metric).increment(n as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_request_latencies_accumulate() {
+        let stats = Stats::default();
+        stats.flag_request(Duration::from_millis(30), true);
+        stats.flag_request(Duration::from_millis(10), false);
+
+        let flags = stats.snapshot().flag_requests;
+        assert_eq!(flags.requests, 2);
+        assert_eq!(flags.failures, 1);
+        assert_eq!(flags.last_latency, Some(Duration::from_millis(10)));
+        assert_eq!(flags.max_latency, Some(Duration::from_millis(30)));
+        assert_eq!(flags.total_latency, Duration::from_millis(40));
+    }
+
+    #[test]
+    fn definitions_polls_stamp_their_outcome() {
+        let stats = Stats::default();
+        stats.definitions_poll(false);
+
+        let snapshot = stats.snapshot();
+        assert!(snapshot.last_poll_success.is_none());
+        assert!(snapshot.last_poll_failure.is_some());
+    }
+}
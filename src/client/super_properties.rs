@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+use crate::Event;
+
+/// Client-level properties stamped on every captured event.
+///
+/// Clones share one registry, so a `register`/`unregister` on the client is
+/// seen by every capture path holding a copy of its [`ClientOptions`]: the
+/// transport handle, the flag-event host, and immediate capture. Events are
+/// stamped when they are captured, not when they are sent, so a change never
+/// rewrites events already queued or spooled. Readers take a cheap
+/// [`snapshot`](Self::snapshot); writers copy-on-write, so a snapshot in use
+/// by an in-flight batch is never mutated under it.
+///
+/// [`ClientOptions`]: super::ClientOptions
+#[derive(Clone, Default)]
+pub(crate) struct SuperProperties(Arc<RwLock<Arc<HashMap<String, Value>>>>);
+
+impl SuperProperties {
+    pub(crate) fn new(properties: HashMap<String, Value>) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(properties))))
+    }
+
+    /// Merge `properties` into the registry, overwriting existing keys.
+    pub(crate) fn register(&self, properties: HashMap<String, Value>) {
+        let mut current = self.0.write().unwrap_or_else(|p| p.into_inner());
+        Arc::make_mut(&mut current).extend(properties);
+    }
+
+    /// Remove `key` from the registry. A no-op when it isn't registered.
+    pub(crate) fn unregister(&self, key: &str) {
+        let mut current = self.0.write().unwrap_or_else(|p| p.into_inner());
+        if current.contains_key(key) {
+            Arc::make_mut(&mut current).remove(key);
+        }
+    }
+
+    /// The properties registered right now.
+    pub(crate) fn snapshot(&self) -> Arc<HashMap<String, Value>> {
+        self.0.read().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    /// Stamp the properties registered right now on `event`. Properties set on
+    /// the event itself win.
+    pub(crate) fn stamp(&self, event: &mut Event) {
+        for (key, value) in self.snapshot().iter() {
+            event.insert_prop_default(key.clone(), value.clone());
+        }
+    }
+
+    /// A registry with the same properties that no longer shares updates with
+    /// `self`.
+    pub(crate) fn detached(&self) -> Self {
+        Self::new(self.snapshot().as_ref().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn register_and_unregister_are_shared_by_clones_but_not_detached_copies() {
+        let props = SuperProperties::new(HashMap::from([("region".to_string(), json!("eu"))]));
+        let shared = props.clone();
+        let detached = props.detached();
+        let before = props.snapshot();
+
+        shared.register(HashMap::from([("tier".to_string(), json!("pro"))]));
+        shared.unregister("region");
+
+        assert_eq!(
+            *props.snapshot(),
+            HashMap::from([("tier".to_string(), json!("pro"))])
+        );
+        assert_eq!(
+            *detached.snapshot(),
+            HashMap::from([("region".to_string(), json!("eu"))])
+        );
+        // An earlier snapshot is unaffected by later writes.
+        assert_eq!(before.get("region"), Some(&json!("eu")));
+    }
+}
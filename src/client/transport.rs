@@ -1,8 +1,9 @@
 //! Runtime-independent event transport.
 //!
 //! A single background `std::thread` drains a channel, batches events, sends
-//! them with **blocking** reqwest, and retries transient failures on a schedule.
-//! Being a plain thread with a blocking client (never a tokio task) it works for
+//! them with the client's [`HttpTransport`](crate::HttpTransport) in blocking
+//! mode, and retries transient failures on a schedule. Being a plain thread
+//! making blocking sends (never a tokio task) it works for
 //! the async client, the blocking client, and — in a later change — a
 //! `std::panic` hook with no runtime present.
 //!
@@ -18,16 +19,23 @@
 
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc};
 use tracing::warn;
 
+use uuid::Uuid;
+
 use super::common::apply_on_error_hooks;
-use super::{CaptureFailure, ClientOptions, PostHogError};
+use super::spool::{keeps_spooled, Replayed, Spool};
+use super::{
+    CaptureFailure, ClientOptions, DeadLetterReason, OnErrorHook, PostHogError,
+    QueueOverflowFailure, SharedDeadLetterSink, Stats, SuperProperties,
+};
 use crate::error::Error;
+use crate::http::SharedTransport;
 use crate::Event;
 
 /// Messages sent from producers (`capture`/`flush`/`shutdown`) to the worker.
@@ -78,6 +86,40 @@ impl Completion {
     }
 }
 
+/// What a client does with an event captured while its queue already holds
+/// `max_queue_size` events. Set with
+/// [`ClientOptionsBuilder::overflow_policy`](crate::ClientOptionsBuilder::overflow_policy).
+///
+/// Every dropped event is counted in
+/// [`ClientStats::events_dropped_queue_full`](crate::ClientStats::events_dropped_queue_full)
+/// and reported to the `on_error` hooks as a
+/// [`PostHogError::QueueOverflow`]. Events captured by the panic hook always
+/// use [`DropNewest`](Self::DropNewest), and
+/// [`Client::capture_async`](crate::Client::capture_async) waits for space
+/// whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Drop the new event (default).
+    #[default]
+    DropNewest,
+    /// Block the capturing thread until the queue has room, dropping the new
+    /// event if none frees up within `timeout`. This blocks inside async code
+    /// too — prefer [`Client::capture_async`](crate::Client::capture_async)
+    /// there.
+    Block {
+        /// How long to wait for room before dropping the event; one deadline
+        /// covers all of a `capture_batch`'s events.
+        timeout: Duration,
+    },
+    /// Make room by dropping the oldest event that has not been sent yet: the
+    /// oldest buffered live event, then the oldest queued historical-migration
+    /// event. Events already sent or held for retry are never evicted, so
+    /// while the worker is mid-send the queue can briefly run past
+    /// `max_queue_size` until it evicts.
+    DropOldest,
+}
+
 /// Source of time for the worker. Injected so tests can drive the interval
 /// timer, retry backoff, and v1 wire timestamps deterministically.
 pub(crate) trait Clock: Send + Sync + 'static {
@@ -99,20 +141,58 @@ impl Clock for SystemClock {
     }
 }
 
+/// The in-flight event count gating the bounded queue, shared by the handle
+/// and the worker, plus the wakeups producers waiting for space park on.
+#[derive(Default)]
+struct QueueLen {
+    count: AtomicUsize,
+    /// Events producers enqueued past capacity under
+    /// [`OverflowPolicy::DropOldest`], which the worker still has to evict.
+    evict: AtomicUsize,
+    /// Paired with `space` so an [`OverflowPolicy::Block`] producer can't miss
+    /// a wakeup between checking the count and waiting.
+    lock: Mutex<()>,
+    space: Condvar,
+    /// Wakes `capture_async` callers waiting for space.
+    #[cfg(feature = "async-client")]
+    notify: tokio::sync::Notify,
+}
+
+impl QueueLen {
+    /// Wake every producer waiting for space (a slot freed or the client
+    /// closed).
+    fn wake(&self) {
+        drop(self.lock.lock().unwrap_or_else(|p| p.into_inner()));
+        self.space.notify_all();
+        #[cfg(feature = "async-client")]
+        self.notify.notify_waiters();
+    }
+}
+
 /// Handle stored on the client. `&self` methods use the atomics/mutex so the
 /// client can stay a plain field while `capture`/`flush`/`shutdown` take `&self`.
 pub(crate) struct TransportHandle {
     tx: mpsc::Sender<Control>,
     /// Pending `Capture` events not yet pulled by the worker. Gates the bounded queue.
-    len: Arc<AtomicUsize>,
+    len: Arc<QueueLen>,
     /// Set once `shutdown`/`Drop` begins; blocks further enqueue and control sends.
     closed: AtomicBool,
     worker: Mutex<Option<JoinHandle<()>>>,
     /// Latches the single "queue full" warning so a full queue doesn't spam logs.
     full_warned: AtomicBool,
     max_queue_size: usize,
+    overflow_policy: OverflowPolicy,
+    /// Told about queue-full drops.
+    on_error: Vec<OnErrorHook>,
     /// Shares the worker's clock; used to stamp capture (enqueue) time.
     clock: Arc<dyn Clock>,
+    /// The client's super properties, stamped at enqueue time. The worker's
+    /// options carry an empty registry, so queued, retried and spooled events
+    /// keep the super properties they were captured with.
+    super_properties: SuperProperties,
+    /// The client's delivery counters; the handle counts enqueues and
+    /// queue-full drops.
+    stats: Arc<Stats>,
     /// The worker thread's id, so the panic hook can tell when it is running on
     /// this client's own worker — where capturing would deadlock or recurse.
     #[cfg_attr(not(feature = "error-tracking"), allow(dead_code))]
@@ -134,10 +214,14 @@ impl TransportHandle {
         Self::spawn_with_clock(options, Arc::new(SystemClock))
     }
 
-    fn spawn_with_clock(options: ClientOptions, clock: Arc<dyn Clock>) -> Self {
+    fn spawn_with_clock(mut options: ClientOptions, clock: Arc<dyn Clock>) -> Self {
         let (tx, rx) = mpsc::channel::<Control>();
-        let len = Arc::new(AtomicUsize::new(0));
+        let super_properties = std::mem::take(&mut options.super_properties);
+        let len = Arc::new(QueueLen::default());
         let max_queue_size = options.max_queue_size;
+        let overflow_policy = options.overflow_policy;
+        let on_error = options.on_error.clone();
+        let stats = Arc::clone(&options.stats);
         let worker_len = len.clone();
         let worker_clock = Arc::clone(&clock);
         let worker = thread::Builder::new()
@@ -152,20 +236,105 @@ impl TransportHandle {
             worker: Mutex::new(worker),
             full_warned: AtomicBool::new(false),
             max_queue_size,
+            overflow_policy,
+            on_error,
             clock,
+            super_properties,
+            stats,
             worker_id,
         }
     }
 
-    /// Non-blocking enqueue. Drops (with a single warning) when the queue is full
-    /// or the client is closed.
+    /// Enqueue under the [`OverflowPolicy`]. Only
+    /// [`OverflowPolicy::Block`] ever waits. Drops silently when the client is
+    /// closed.
     pub(crate) fn enqueue(&self, event: Event) {
         if self.closed.load(Ordering::Acquire) {
             return;
         }
-        if try_reserve(&self.len, self.max_queue_size, &self.full_warned) {
+        if self.reserve(None) {
+            self.stats.events_enqueued(1);
             self.send_reserved(event);
+        } else {
+            self.overflowed(1);
+        }
+    }
+
+    /// Enqueue, waiting for as long as it takes for the queue to have room.
+    /// Gives up only when the client closes.
+    #[cfg(feature = "async-client")]
+    pub(crate) async fn enqueue_async(&self, event: Event) {
+        loop {
+            // Registered before the check, so a slot freed in between still
+            // wakes this wait.
+            let space = self.len.notify.notified();
+            if self.closed.load(Ordering::Acquire) {
+                return;
+            }
+            if reserve_rearming(&self.len.count, self.max_queue_size, &self.full_warned) {
+                self.stats.events_enqueued(1);
+                self.send_reserved(event);
+                return;
+            }
+            space.await;
+        }
+    }
+
+    /// Reserve a queue slot for one event under the [`OverflowPolicy`],
+    /// returning `false` when the event has to be dropped. `deadline` carries
+    /// one [`OverflowPolicy::Block`] deadline across a batch's events.
+    fn reserve(&self, deadline: Option<Instant>) -> bool {
+        match self.overflow_policy {
+            OverflowPolicy::Block { timeout } => {
+                let deadline =
+                    deadline.unwrap_or_else(|| Instant::now() + timeout.min(MAX_SHUTDOWN_TIMEOUT));
+                self.wait_for_slot(deadline)
+            }
+            OverflowPolicy::DropOldest => {
+                if !try_reserve(&self.len.count, self.max_queue_size, &self.full_warned) {
+                    // Take the slot anyway; the worker evicts its oldest
+                    // unsent event to give it back.
+                    self.len.count.fetch_add(1, Ordering::AcqRel);
+                    self.len.evict.fetch_add(1, Ordering::AcqRel);
+                }
+                true
+            }
+            OverflowPolicy::DropNewest => {
+                try_reserve(&self.len.count, self.max_queue_size, &self.full_warned)
+            }
+        }
+    }
+
+    /// Block until a slot frees up, the client closes, or `deadline` passes.
+    /// Real time, not the worker's clock: the wait is on the producer thread.
+    fn wait_for_slot(&self, deadline: Instant) -> bool {
+        let mut guard = self.len.lock.lock().unwrap_or_else(|p| p.into_inner());
+        loop {
+            if reserve_rearming(&self.len.count, self.max_queue_size, &self.full_warned) {
+                return true;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || self.closed.load(Ordering::Acquire) {
+                warn_full(self.max_queue_size, &self.full_warned);
+                return false;
+            }
+            guard = self
+                .len
+                .space
+                .wait_timeout(guard, remaining)
+                .unwrap_or_else(|p| p.into_inner())
+                .0;
+        }
+    }
+
+    /// Count `dropped` queue-full drops and report them to the `on_error`
+    /// hooks.
+    fn overflowed(&self, dropped: usize) {
+        if dropped == 0 {
+            return;
         }
+        self.stats.events_dropped_queue_full(dropped);
+        fire_overflow(&self.on_error, self.overflow_policy, dropped);
     }
 
     /// Like `enqueue`, but never logs — no full-queue `warn!`. The panic hook
@@ -177,17 +346,21 @@ impl TransportHandle {
         if self.closed.load(Ordering::Acquire) {
             return;
         }
-        if reserve_slot(&self.len, self.max_queue_size).is_some() {
+        let reserved = reserve_slot(&self.len.count, self.max_queue_size).is_some();
+        self.stats.panic_enqueue(reserved);
+        if reserved {
             self.send_reserved(event);
         }
     }
 
-    /// Stamp the capture (enqueue) time on the producer side — so a batched or
-    /// retried event records when it occurred, not when it was finally sent —
-    /// then hand it to the worker, releasing the reserved slot if the worker is
-    /// gone. The slot must already be reserved by the caller.
+    /// Stamp the capture (enqueue) time and super properties on the producer
+    /// side — so a batched or retried event records when it occurred and what
+    /// was registered then, not when it was finally sent — then hand it to the
+    /// worker, releasing the reserved slot if the worker is gone. The slot must
+    /// already be reserved by the caller.
     fn send_reserved(&self, mut event: Event) {
         event.ensure_timestamp(self.clock.now_utc());
+        self.super_properties.stamp(&mut event);
         if self
             .tx
             .send(Control::Capture {
@@ -202,19 +375,27 @@ impl TransportHandle {
 
     /// Enqueue a caller-formed historical-migration batch on its own path, kept
     /// off the live buffer (which is always non-historical). Reserves a queue
-    /// slot per event up to the bound, dropping any overflow with the usual
-    /// once-per-episode full warning.
+    /// slot per event under the [`OverflowPolicy`] — one
+    /// [`OverflowPolicy::Block`] timeout covers the whole batch — and drops
+    /// whatever doesn't fit.
     pub(crate) fn enqueue_historical(&self, mut events: Vec<Event>) {
         if self.closed.load(Ordering::Acquire) {
             return;
         }
+        let deadline = match self.overflow_policy {
+            OverflowPolicy::Block { timeout } => {
+                Some(Instant::now() + timeout.min(MAX_SHUTDOWN_TIMEOUT))
+            }
+            _ => None,
+        };
         let mut fitted = 0;
-        while fitted < events.len()
-            && try_reserve(&self.len, self.max_queue_size, &self.full_warned)
-        {
+        while fitted < events.len() && self.reserve(deadline) {
             events[fitted].ensure_timestamp(self.clock.now_utc());
+            self.super_properties.stamp(&mut events[fitted]);
             fitted += 1;
         }
+        self.stats.events_enqueued(fitted);
+        self.overflowed(events.len() - fitted);
         events.truncate(fitted);
         if events.is_empty() {
             return;
@@ -243,13 +424,16 @@ impl TransportHandle {
     /// `test-harness`-gated `Client::pending_events` and the unit tests read this.
     #[cfg(any(test, feature = "test-harness"))]
     pub(crate) fn pending(&self) -> usize {
-        self.len.load(Ordering::Acquire)
+        self.len.count.load(Ordering::Acquire)
     }
 
     /// Mark closed. Returns `true` for the caller that won the transition (so
     /// shutdown is idempotent and only one caller drives teardown).
     pub(crate) fn begin_close(&self) -> bool {
-        !self.closed.swap(true, Ordering::AcqRel)
+        let won = !self.closed.swap(true, Ordering::AcqRel);
+        // Producers waiting for space give up once closed.
+        self.len.wake();
+        won
     }
 
     /// Join the worker thread. Safe to call repeatedly.
@@ -341,6 +525,16 @@ fn reserve_slot(len: &AtomicUsize, max: usize) -> Option<usize> {
 /// queue has fully drained, so a service that repeatedly fills then drains warns
 /// once per episode instead of only on the very first overflow.
 fn try_reserve(len: &AtomicUsize, max: usize, warned: &AtomicBool) -> bool {
+    if reserve_rearming(len, max, warned) {
+        return true;
+    }
+    warn_full(max, warned);
+    false
+}
+
+/// `reserve_slot` that re-arms the full-queue warning when it finds the queue
+/// empty. Never warns itself, for producers that wait before giving up.
+fn reserve_rearming(len: &AtomicUsize, max: usize, warned: &AtomicBool) -> bool {
     match reserve_slot(len, max) {
         Some(current) => {
             if current == 0 {
@@ -348,15 +542,23 @@ fn try_reserve(len: &AtomicUsize, max: usize, warned: &AtomicBool) -> bool {
             }
             true
         }
-        None => {
-            if !warned.swap(true, Ordering::AcqRel) {
-                warn!("posthog-rs: event queue full (capacity {max}); dropping events");
-            }
-            false
-        }
+        None => false,
+    }
+}
+
+/// Log the full-queue warning, once per episode.
+fn warn_full(max: usize, warned: &AtomicBool) {
+    if !warned.swap(true, Ordering::AcqRel) {
+        warn!("posthog-rs: event queue full (capacity {max}); dropping events");
     }
 }
 
+/// Report `dropped` queue-full drops to the `on_error` hooks.
+fn fire_overflow(hooks: &[OnErrorHook], policy: OverflowPolicy, dropped: usize) {
+    let failure = PostHogError::QueueOverflow(QueueOverflowFailure { dropped, policy });
+    apply_on_error_hooks(hooks, &failure);
+}
+
 /// Decrement the in-flight counter by `n` (no-op for 0). Called when events reach
 /// a terminal outcome (delivered or dropped) so `pending()` reflects everything
 /// still in flight: channel + worker buffer + retry queue.
@@ -367,19 +569,22 @@ fn try_reserve(len: &AtomicUsize, max: usize, warned: &AtomicBool) -> bool {
 /// `AtomicUsize` and wrap to a huge value, making the bounded queue look
 /// permanently full and silently dropping every later event. A `debug_assert`
 /// still surfaces such a bug in tests; release builds clamp rather than wrap.
-fn dec_len(len: &AtomicUsize, n: usize) {
+fn dec_len(len: &QueueLen, n: usize) {
     if n == 0 {
         return;
     }
-    let _ = len.fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
-        debug_assert!(
-            current >= n,
-            "posthog-rs: in-flight counter underflow ({} - {})",
-            current,
-            n
-        );
-        Some(current.saturating_sub(n))
-    });
+    let _ = len
+        .count
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+            debug_assert!(
+                current >= n,
+                "posthog-rs: in-flight counter underflow ({} - {})",
+                current,
+                n
+            );
+            Some(current.saturating_sub(n))
+        });
+    len.wake();
 }
 
 /// Per-request timeout for a send. On the shutdown/disconnect path (`deadline`
@@ -387,18 +592,15 @@ fn dec_len(len: &AtomicUsize, n: usize) {
 /// more than the configured request timeout — so a stalled endpoint that accepts
 /// but never responds can't push teardown past `shutdown_timeout_ms`. Off that
 /// path it keeps the full configured timeout.
-fn bound_request(
-    request: reqwest::blocking::RequestBuilder,
+fn request_timeout(
     deadline: Option<Instant>,
     now: Instant,
     request_timeout_seconds: u64,
-) -> reqwest::blocking::RequestBuilder {
+) -> Duration {
+    let configured = Duration::from_secs(request_timeout_seconds);
     match deadline {
-        Some(d) => request.timeout(
-            d.saturating_duration_since(now)
-                .min(Duration::from_secs(request_timeout_seconds)),
-        ),
-        None => request,
+        Some(d) => d.saturating_duration_since(now).min(configured),
+        None => configured,
     }
 }
 
@@ -429,7 +631,7 @@ enum Wake {
 fn run_worker(
     options: ClientOptions,
     rx: mpsc::Receiver<Control>,
-    len: Arc<AtomicUsize>,
+    len: Arc<QueueLen>,
     clock: Arc<dyn Clock>,
 ) {
     let flush_at = options.flush_at.max(1);
@@ -440,7 +642,15 @@ fn run_worker(
     // any sane teardown budget.
     let shutdown_timeout =
         Duration::from_millis(options.shutdown_timeout_ms).min(MAX_SHUTDOWN_TIMEOUT);
-    let mut pipeline = Pipeline::new(&options, Arc::clone(&clock), len);
+    let (spool, replayed) = match options.spool.as_ref().map(Spool::open) {
+        Some(Ok((spool, replayed))) => (Some(spool), replayed),
+        Some(Err(e)) => {
+            warn!("posthog-rs: failed to open spool directory; events won't be persisted: {e}");
+            (None, Vec::new())
+        }
+        None => (None, Vec::new()),
+    };
+    let mut pipeline = Pipeline::new(&options, Arc::clone(&clock), len, spool);
 
     let mut buffer: Vec<Event> = Vec::new();
     let mut buffer_since: Option<Instant> = None;
@@ -451,6 +661,15 @@ fn run_worker(
     let mut historical: VecDeque<Vec<Event>> = VecDeque::new();
     let mut historical_since: Option<Instant> = None;
 
+    // Events a previous run left in the spool are sent before anything new.
+    resend_spooled(
+        &mut pipeline,
+        replayed,
+        &mut buffer,
+        &mut historical,
+        max_batch_size,
+    );
+
     loop {
         #[cfg(test)]
         let mut tick_completion: Option<Completion> = None;
@@ -459,7 +678,7 @@ fn run_worker(
                 clock.now(),
                 buffer_since,
                 flush_interval,
-                pipeline.earliest_retry(),
+                pipeline.next_timer(),
             );
             match historical_since {
                 Some(since) => {
@@ -486,16 +705,27 @@ fn run_worker(
                 // `len` is not decremented here: the in-flight counter spans the
                 // whole worker lifecycle (channel + buffer + retries) and is
                 // decremented by the pipeline once a batch is delivered or dropped.
+                pipeline.spool_append(&event, false);
                 if buffer.is_empty() {
                     buffer_since = Some(clock.now());
                 }
                 buffer.push(*event);
+                evict_overflow(&mut pipeline, &mut buffer, &mut historical);
+                if buffer.is_empty() {
+                    buffer_since = None;
+                }
+                if historical.is_empty() {
+                    historical_since = None;
+                }
                 if buffer.len() >= flush_at {
                     send_buffer(&mut pipeline, &mut buffer, max_batch_size, None);
                     buffer_since = None;
                 }
             }
             Wake::Msg(Control::HistoricalBatch { mut events }) => {
+                for event in &events {
+                    pipeline.spool_append(event, true);
+                }
                 // Queue the chunks off the live buffer (which stays non-historical
                 // — no per-event flag, no homogeneity flush). Below `flush_at` they
                 // wait rather than being sent inline, so a Shutdown queued behind
@@ -507,7 +737,14 @@ fn run_worker(
                     let take = events.len().min(max_batch_size);
                     historical.push_back(events.drain(..take).collect());
                 }
-                if historical_since.is_none() {
+                evict_overflow(&mut pipeline, &mut buffer, &mut historical);
+                if buffer.is_empty() {
+                    buffer_since = None;
+                }
+                if historical.is_empty() {
+                    historical_since = None;
+                }
+                if historical_since.is_none() && !historical.is_empty() {
                     historical_since = Some(clock.now());
                 }
                 if historical.iter().map(Vec::len).sum::<usize>() >= flush_at {
@@ -551,10 +788,18 @@ fn run_worker(
                 pipeline.flush_retries(Some(deadline));
                 drain_historical(&mut pipeline, &mut historical, Some(deadline));
                 send_buffer(&mut pipeline, &mut buffer, max_batch_size, Some(deadline));
-                completion.signal();
                 // A flush/shutdown that raced in behind this Shutdown is still queued;
                 // signal those completions so their callers don't block forever.
-                drain_pending_completions(&rx, &pipeline.len);
+                drain_pending_completions(
+                    &rx,
+                    &pipeline.len,
+                    pipeline.spool.as_mut(),
+                    pipeline.options.dead_letter_sink.as_ref(),
+                );
+                // Release the spool's directory lock before the caller resumes,
+                // so a client opened right after shutdown can take it over.
+                drop(pipeline.spool.take());
+                completion.signal();
                 return;
             }
             #[cfg(test)]
@@ -592,6 +837,22 @@ fn run_worker(
             historical_since = None;
         }
         pipeline.attempt_due();
+        // Parked events get another try once a delivery shows PostHog is
+        // reachable again. Deliveries they trigger don't re-arm this: each
+        // failure parks them again until the next success, and the spool backs
+        // off while the same events keep failing.
+        let parked = pipeline.take_parked();
+        if !parked.is_empty() {
+            resend_spooled(
+                &mut pipeline,
+                parked,
+                &mut buffer,
+                &mut historical,
+                max_batch_size,
+            );
+            buffer_since = None;
+            historical_since = None;
+        }
         #[cfg(test)]
         if let Some(completion) = tick_completion {
             completion.signal();
@@ -601,20 +862,87 @@ fn run_worker(
 
 /// Signal any flush/shutdown completions still queued when the worker exits, so a
 /// caller whose control message raced in behind the `Shutdown` doesn't block forever
-/// on a completion that will never be processed. Queued captures are dropped, but
-/// their reserved in-flight slots are released so `pending_events()` settles to 0.
-fn drain_pending_completions(rx: &mpsc::Receiver<Control>, len: &AtomicUsize) {
+/// on a completion that will never be processed. Queued captures are persisted
+/// for the next run when a spool is configured, and otherwise handed to the
+/// dead-letter sink; either way their reserved in-flight slots are released so
+/// `pending_events()` settles to 0.
+fn drain_pending_completions(
+    rx: &mpsc::Receiver<Control>,
+    len: &QueueLen,
+    mut spool: Option<&mut Spool>,
+    dead_letter_sink: Option<&SharedDeadLetterSink>,
+) {
     while let Ok(control) = rx.try_recv() {
         match control {
             Control::Flush(c) | Control::FlushCaptures(c) | Control::Shutdown(c) => c.signal(),
             #[cfg(test)]
             Control::Tick(c) => c.signal(),
-            Control::Capture { .. } => dec_len(len, 1),
-            Control::HistoricalBatch { events } => dec_len(len, events.len()),
+            Control::Capture { event } => {
+                dec_len(len, 1);
+                let spooled = spool
+                    .as_deref_mut()
+                    .is_some_and(|spool| spool.append(&event, false));
+                if let (false, Some(sink)) = (spooled, dead_letter_sink) {
+                    sink.send(vec![*event], DeadLetterReason::Shutdown, false);
+                }
+            }
+            Control::HistoricalBatch { mut events } => {
+                dec_len(len, events.len());
+                if let Some(spool) = spool.as_deref_mut() {
+                    events.retain(|event| !spool.append(event, true));
+                }
+                if let (false, Some(sink)) = (events.is_empty(), dead_letter_sink) {
+                    sink.send(events, DeadLetterReason::Shutdown, true);
+                }
+            }
         }
     }
 }
 
+/// Under [`OverflowPolicy::DropOldest`], drop as many not-yet-sent events as
+/// producers enqueued past capacity: the oldest buffered live events first,
+/// then the oldest queued historical ones.
+fn evict_overflow(
+    pipeline: &mut Pipeline,
+    buffer: &mut Vec<Event>,
+    historical: &mut VecDeque<Vec<Event>>,
+) {
+    let owed = pipeline.len.evict.load(Ordering::Acquire);
+    if owed == 0 {
+        return;
+    }
+    let mut evicted: Vec<Event> = buffer.drain(..owed.min(buffer.len())).collect();
+    while evicted.len() < owed {
+        let Some(chunk) = historical.front_mut() else {
+            break;
+        };
+        let take = (owed - evicted.len()).min(chunk.len());
+        evicted.extend(chunk.drain(..take));
+        if chunk.is_empty() {
+            historical.pop_front();
+        }
+    }
+    if evicted.is_empty() {
+        return;
+    }
+    pipeline
+        .len
+        .evict
+        .fetch_sub(evicted.len(), Ordering::AcqRel);
+    let spooled = pipeline.spooled_uuids(&evicted);
+    pipeline.settle_spooled(&spooled, None);
+    dec_len(&pipeline.len, evicted.len());
+    pipeline
+        .options
+        .stats
+        .events_dropped_queue_full(evicted.len());
+    fire_overflow(
+        &pipeline.options.on_error,
+        OverflowPolicy::DropOldest,
+        evicted.len(),
+    );
+}
+
 /// Drain `buffer` into batches of at most `max_batch_size`, FIFO from the front,
 /// attempting each once. `deadline` is `Some` only on the shutdown/disconnect
 /// path: those attempts are final (warn-and-drop on transient failure instead of
@@ -633,7 +961,7 @@ fn send_buffer(
                 buffer.len()
             );
             dec_len(&pipeline.len, buffer.len());
-            buffer.clear();
+            pipeline.dead_letter(std::mem::take(buffer), DeadLetterReason::Shutdown, false);
             return;
         }
         let take = buffer.len().min(max_batch_size);
@@ -657,8 +985,10 @@ fn drain_historical(
             let dropped = chunk.len() + historical.iter().map(Vec::len).sum::<usize>();
             warn!("posthog-rs: shutdown timeout reached; dropping {dropped} historical event(s)");
             dec_len(&pipeline.len, chunk.len());
+            pipeline.dead_letter(chunk, DeadLetterReason::Shutdown, true);
             for rest in historical.drain(..) {
                 dec_len(&pipeline.len, rest.len());
+                pipeline.dead_letter(rest, DeadLetterReason::Shutdown, true);
             }
             return;
         }
@@ -666,14 +996,166 @@ fn drain_historical(
     }
 }
 
+// ===========================================================================
+// Spool bookkeeping (shared by both pipelines)
+// ===========================================================================
+
+impl Pipeline {
+    /// Persist an accepted event, when a spool is configured.
+    fn spool_append(&mut self, event: &Event, historical_migration: bool) {
+        if let Some(spool) = &mut self.spool {
+            spool.append(event, historical_migration);
+        }
+    }
+
+    /// UUIDs of the events entering a batch, so the batch can release them
+    /// from the spool once it settles. Empty without a spool.
+    fn spooled_uuids(&self, events: &[Event]) -> Vec<Uuid> {
+        if self.spool.is_some() {
+            events.iter().map(Event::uuid).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Settle a batch's spooled events once it reaches a terminal outcome.
+    /// They are released unless `error` is transient, in which case they are
+    /// parked on disk until a later delivery succeeds (or the next run replays
+    /// them). Batches abandoned on shutdown never get here and stay spooled too.
+    fn settle_spooled(&mut self, spooled: &[Uuid], error: Option<&Error>) {
+        if error.is_some_and(keeps_spooled) {
+            self.park_spooled(spooled);
+        } else if let Some(spool) = &mut self.spool {
+            spool.release(spooled);
+        }
+    }
+
+    /// Park spooled events on disk for another try after a later delivery.
+    fn park_spooled(&mut self, spooled: &[Uuid]) {
+        let now = self.clock.now();
+        if let Some(spool) = &mut self.spool {
+            spool.park(spooled, now);
+        }
+    }
+
+    /// Record that a batch delivered events, so parked events get another try.
+    fn spool_delivered(&mut self) {
+        if let Some(spool) = &mut self.spool {
+            spool.delivered();
+        }
+    }
+
+    /// Parked events due for another try after a successful delivery.
+    fn take_parked(&mut self) -> Vec<Replayed> {
+        let now = self.clock.now();
+        self.spool
+            .as_mut()
+            .map_or_else(Vec::new, |spool| spool.take_parked(now))
+    }
+
+    /// The next scheduled wakeup: the earliest retry or parked re-drive.
+    fn next_timer(&self) -> Option<Instant> {
+        let redrive = self.spool.as_ref().and_then(Spool::next_redrive);
+        match (self.earliest_retry(), redrive) {
+            (Some(retry), Some(redrive)) => Some(retry.min(redrive)),
+            (retry, redrive) => retry.or(redrive),
+        }
+    }
+}
+
+/// Send events read back from the spool — left over from a previous run, or
+/// parked after a transient failure — ahead of anything buffered since. They
+/// are already on disk, so they count as in flight but aren't appended again.
+fn resend_spooled(
+    pipeline: &mut Pipeline,
+    spooled: Vec<Replayed>,
+    buffer: &mut Vec<Event>,
+    historical: &mut VecDeque<Vec<Event>>,
+    max_batch_size: usize,
+) {
+    if spooled.is_empty() {
+        return;
+    }
+    pipeline
+        .len
+        .count
+        .fetch_add(spooled.len(), Ordering::AcqRel);
+    let mut live = Vec::new();
+    let mut spooled_historical = Vec::new();
+    for replayed in spooled {
+        if replayed.historical_migration {
+            spooled_historical.push(replayed.event);
+        } else {
+            live.push(replayed.event);
+        }
+    }
+    while !spooled_historical.is_empty() {
+        let take = spooled_historical.len().min(max_batch_size);
+        historical.push_front(
+            spooled_historical
+                .drain(spooled_historical.len() - take..)
+                .collect(),
+        );
+    }
+    live.append(buffer);
+    *buffer = live;
+    drain_historical(pipeline, historical, None);
+    send_buffer(pipeline, buffer, max_batch_size, None);
+}
+
+// ===========================================================================
+// Dead-letter bookkeeping (shared by both pipelines)
+// ===========================================================================
+
+impl Pipeline {
+    /// Copies of the events entering a batch, so the batch can hand whichever
+    /// it fails to deliver to the dead-letter sink. Empty without a sink.
+    fn dead_letter_copies(&self, events: &[Event]) -> Vec<Event> {
+        if self.options.dead_letter_sink.is_some() {
+            events.to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Hand `events` to the dead-letter sink, when one is configured. Events
+    /// still held by the spool are left out: they are delivered later by this
+    /// client or the next one, so an event never ends up in both.
+    fn dead_letter(
+        &self,
+        mut events: Vec<Event>,
+        reason: DeadLetterReason,
+        historical_migration: bool,
+    ) {
+        let Some(sink) = &self.options.dead_letter_sink else {
+            return;
+        };
+        if let Some(spool) = &self.spool {
+            events.retain(|event| !spool.holds(&event.uuid()));
+        }
+        if !events.is_empty() {
+            sink.send(events, reason, historical_migration);
+        }
+    }
+}
+
+/// The dead-letter reason for a batch that failed terminally with `error`:
+/// failures a later attempt might have overcome (the same ones the spool keeps)
+/// ran out of retries, anything else was rejected.
+fn dead_letter_reason(error: &Error, status: Option<u16>) -> DeadLetterReason {
+    if keeps_spooled(error) {
+        DeadLetterReason::RetriesExhausted { status }
+    } else {
+        DeadLetterReason::Rejected { status }
+    }
+}
+
 // ===========================================================================
 // V1 pipeline
 // ===========================================================================
 
 #[cfg(feature = "capture-v1")]
 use std::collections::HashMap;
-#[cfg(feature = "capture-v1")]
-use uuid::Uuid;
 
 #[cfg(feature = "capture-v1")]
 struct RetryBatch {
@@ -684,25 +1166,32 @@ struct RetryBatch {
     historical_migration: bool,
     attempt: u32,
     next_at: Instant,
+    /// UUIDs of the spooled events this batch settles (empty without a spool).
+    spooled: Vec<Uuid>,
+    /// The batch's events, for the dead-letter sink (empty without a sink).
+    dead_letters: Vec<Event>,
 }
 
 #[cfg(feature = "capture-v1")]
 struct Pipeline {
-    http: reqwest::blocking::Client,
+    http: SharedTransport,
     options: ClientOptions,
     url: String,
     clock: Arc<dyn Clock>,
-    len: Arc<AtomicUsize>,
+    len: Arc<QueueLen>,
     retries: VecDeque<RetryBatch>,
+    spool: Option<Spool>,
 }
 
 #[cfg(feature = "capture-v1")]
 impl Pipeline {
-    fn new(options: &ClientOptions, clock: Arc<dyn Clock>, len: Arc<AtomicUsize>) -> Self {
-        let http = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_secs(options.request_timeout_seconds))
-            .build()
-            .unwrap_or_default();
+    fn new(
+        options: &ClientOptions,
+        clock: Arc<dyn Clock>,
+        len: Arc<QueueLen>,
+        spool: Option<Spool>,
+    ) -> Self {
+        let http = options.http_transport.clone();
         let url = options
             .endpoints()
             .build_custom_url(super::v1_capture::V1_CAPTURE_PATH);
@@ -713,6 +1202,7 @@ impl Pipeline {
             clock,
             len,
             retries: VecDeque::new(),
+            spool,
         }
     }
 
@@ -726,15 +1216,29 @@ impl Pipeline {
 
         let defaults = self.options.capture_defaults();
         let original = events.len();
+        let mut spooled = Vec::new();
+        let mut dropped = Vec::new();
+        let keep_uuids = self.spool.is_some();
         let processed: Vec<Event> = events
             .into_iter()
             .filter_map(|mut event| {
+                let uuid = event.uuid();
                 apply_capture_defaults(&mut event, &defaults);
-                apply_before_send_hooks(&self.options.before_send, event)
+                let kept = apply_before_send_hooks(&self.options.before_send, event);
+                match (keep_uuids, kept.is_some()) {
+                    (true, true) => spooled.push(uuid),
+                    (true, false) => dropped.push(uuid),
+                    (false, _) => {}
+                }
+                kept
             })
             .collect();
         // Events dropped by before_send are terminal.
         dec_len(&self.len, original - processed.len());
+        self.options
+            .stats
+            .events_dropped_before_send(original - processed.len());
+        self.settle_spooled(&dropped, None);
         if processed.is_empty() {
             return;
         }
@@ -749,6 +1253,8 @@ impl Pipeline {
             historical_migration,
             attempt: 1,
             next_at: now,
+            spooled,
+            dead_letters: self.dead_letter_copies(&processed),
         };
         self.attempt(batch, deadline);
     }
@@ -756,6 +1262,7 @@ impl Pipeline {
     fn attempt(&mut self, mut batch: RetryBatch, deadline: Option<Instant>) {
         use super::v1_capture::{self, Step};
         use crate::event_v1::{V1BatchRequestRef, V1ErrorResponse};
+        use crate::http::HttpRequest;
 
         let req = V1BatchRequestRef {
             created_at: &batch.created_at,
@@ -773,7 +1280,10 @@ impl Pipeline {
                     let lost = count + undelivered_results(&batch.final_results);
                     self.fire_capture(&batch, None, Some(&err), None, None, lost);
                 }
+                self.options.stats.events_rejected(count);
                 dec_len(&self.len, count);
+                self.settle_spooled(&batch.spooled, None);
+                self.dead_letter_settled(&mut batch, DeadLetterReason::Serialization, None);
                 return;
             }
         };
@@ -783,22 +1293,25 @@ impl Pipeline {
             batch.attempt,
             self.clock.now_utc(),
         );
+        let uncompressed = payload.len();
         let body =
             v1_capture::maybe_compress(self.options.capture_compression, &mut headers, payload);
+        self.options.stats.bytes_sent(uncompressed, body.len());
 
         let count = batch.pending.len();
-        let request = bound_request(
-            self.http.post(&self.url).headers(headers).body(body),
-            deadline,
-            self.clock.now(),
-            self.options.request_timeout_seconds,
-        );
+        let request = HttpRequest::post(&self.url, body)
+            .with_header_map(&headers)
+            .with_timeout(request_timeout(
+                deadline,
+                self.clock.now(),
+                self.options.request_timeout_seconds,
+            ));
         // The final attempt's status and (on a non-2xx) raw body, kept so the
         // `on_error` hook can surface them. The body is only retained when a hook
         // is registered, so the common path stays allocation-neutral.
         let mut http_status: Option<u16> = None;
         let mut response_body: Option<String> = None;
-        let step = match request.send() {
+        let step = match self.http.send(request) {
             Err(e) => v1_capture::after_transport_error(
                 &self.options,
                 &batch.request_id,
@@ -806,10 +1319,10 @@ impl Pipeline {
                 e.to_string(),
             ),
             Ok(resp) => {
-                let status = resp.status().as_u16();
+                let status = resp.status;
                 http_status = Some(status);
-                let retry_after = v1_capture::parse_retry_after(resp.headers());
-                let text = resp.text().unwrap_or_else(|_| "Unknown error".to_string());
+                let retry_after = v1_capture::parse_retry_after(&resp);
+                let text = resp.text();
                 let step = v1_capture::after_response(
                     &self.options,
                     &batch.request_id,
@@ -848,6 +1361,36 @@ impl Pipeline {
                         );
                     }
                 }
+                self.count_settled(&batch, 0);
+                // Events the backend still asked to retry after the final
+                // attempt stay parked in the spool.
+                let (parked, settled): (Vec<Uuid>, Vec<Uuid>) =
+                    batch.spooled.iter().copied().partition(|uuid| {
+                        batch
+                            .final_results
+                            .get(uuid)
+                            .is_some_and(|r| r.result == crate::event_v1::EventStatus::Retry)
+                    });
+                self.settle_spooled(&settled, None);
+                self.park_spooled(&parked);
+                // Only a batch that delivered something shows PostHog is
+                // taking events again; one answered with nothing but `retry`
+                // must not re-arm its own parked events.
+                if settled.iter().any(|uuid| {
+                    !batch
+                        .final_results
+                        .get(uuid)
+                        .is_some_and(|r| r.result == crate::event_v1::EventStatus::Drop)
+                }) {
+                    self.spool_delivered();
+                }
+                self.dead_letter_settled(
+                    &mut batch,
+                    DeadLetterReason::RetriesExhausted {
+                        status: http_status,
+                    },
+                    http_status,
+                );
             }
             Step::Fail(e) => {
                 if self.options.on_error.is_empty() {
@@ -866,7 +1409,11 @@ impl Pipeline {
                         lost,
                     );
                 }
+                self.count_settled(&batch, batch.pending.len());
                 dec_len(&self.len, batch.pending.len());
+                self.settle_spooled(&batch.spooled, Some(&e));
+                let reason = dead_letter_reason(&e, http_status);
+                self.dead_letter_settled(&mut batch, reason, http_status);
             }
             Step::Backoff(delay) => {
                 if deadline.is_some() {
@@ -875,7 +1422,9 @@ impl Pipeline {
                         batch.pending.len()
                     );
                     dec_len(&self.len, batch.pending.len());
+                    self.dead_letter_settled(&mut batch, DeadLetterReason::Shutdown, http_status);
                 } else {
+                    self.options.stats.events_retried(batch.pending.len());
                     batch.attempt += 1;
                     batch.next_at = self.clock.now() + delay;
                     self.retries.push_back(batch);
@@ -903,19 +1452,70 @@ impl Pipeline {
         // `Some` is the shutdown/disconnect path: attempts are final (drop on
         // failure), and any batch still pending once the deadline passes is
         // dropped rather than attempted.
-        for batch in std::mem::take(&mut self.retries) {
+        for mut batch in std::mem::take(&mut self.retries) {
             if deadline.is_some_and(|d| self.clock.now() >= d) {
                 warn!(
                     "posthog-rs: shutdown timeout reached; dropping {} undelivered event(s)",
                     batch.pending.len()
                 );
                 dec_len(&self.len, batch.pending.len());
+                self.dead_letter_settled(&mut batch, DeadLetterReason::Shutdown, None);
             } else {
                 self.attempt(batch, deadline);
             }
         }
     }
 
+    /// Hand a settled batch's undelivered events to the dead-letter sink,
+    /// grouped by why each was lost: its `drop`/`retry` verdict (from a
+    /// response with `status`), or `pending_reason` for events still without
+    /// a final verdict.
+    fn dead_letter_settled(
+        &self,
+        batch: &mut RetryBatch,
+        pending_reason: DeadLetterReason,
+        status: Option<u16>,
+    ) {
+        use crate::event_v1::EventStatus;
+
+        if batch.dead_letters.is_empty() {
+            return;
+        }
+        let pending: std::collections::HashSet<Uuid> =
+            batch.pending.iter().map(|e| e.uuid).collect();
+        let (mut rejected, mut exhausted, mut unsent) = (Vec::new(), Vec::new(), Vec::new());
+        for event in std::mem::take(&mut batch.dead_letters) {
+            let uuid = event.uuid();
+            if pending.contains(&uuid) {
+                unsent.push(event);
+                continue;
+            }
+            match batch.final_results.get(&uuid).map(|r| &r.result) {
+                Some(EventStatus::Drop) => rejected.push(event),
+                Some(EventStatus::Retry) => exhausted.push(event),
+                _ => {}
+            }
+        }
+        let historical = batch.historical_migration;
+        self.dead_letter(rejected, DeadLetterReason::Rejected { status }, historical);
+        self.dead_letter(
+            exhausted,
+            DeadLetterReason::RetriesExhausted { status },
+            historical,
+        );
+        self.dead_letter(unsent, pending_reason, historical);
+    }
+
+    /// Count a settled batch's per-event verdicts, plus `unsent` events that
+    /// never got one, in the client's delivery stats.
+    fn count_settled(&self, batch: &RetryBatch, unsent: usize) {
+        let rejected = undelivered_results(&batch.final_results);
+        self.options
+            .stats
+            .events_sent(batch.final_results.len() - rejected);
+        self.options.stats.events_rejected(rejected + unsent);
+    }
+
     /// Fire the `on_error` hooks for a terminal capture outcome. `error` is
     /// `None` only for a `2xx` whose events weren't persisted after retries.
     fn fire_capture(
@@ -959,30 +1559,39 @@ fn undelivered_results(results: &HashMap<Uuid, crate::event_v1::EventResult>) ->
 #[cfg(not(feature = "capture-v1"))]
 struct RetryBatch {
     body: Vec<u8>,
+    /// Size of `body` before compression, for the delivery stats.
+    uncompressed_len: usize,
     encoding: Option<&'static str>,
     count: usize,
     historical_migration: bool,
     attempt: u32,
     next_at: Instant,
+    /// UUIDs of the spooled events this batch settles (empty without a spool).
+    spooled: Vec<Uuid>,
+    /// The batch's events, for the dead-letter sink (empty without a sink).
+    dead_letters: Vec<Event>,
 }
 
 #[cfg(not(feature = "capture-v1"))]
 struct Pipeline {
-    http: reqwest::blocking::Client,
+    http: SharedTransport,
     options: ClientOptions,
     url_base: String,
     clock: Arc<dyn Clock>,
-    len: Arc<AtomicUsize>,
+    len: Arc<QueueLen>,
     retries: VecDeque<RetryBatch>,
+    spool: Option<Spool>,
 }
 
 #[cfg(not(feature = "capture-v1"))]
 impl Pipeline {
-    fn new(options: &ClientOptions, clock: Arc<dyn Clock>, len: Arc<AtomicUsize>) -> Self {
-        let http = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_secs(options.request_timeout_seconds))
-            .build()
-            .unwrap_or_default();
+    fn new(
+        options: &ClientOptions,
+        clock: Arc<dyn Clock>,
+        len: Arc<QueueLen>,
+        spool: Option<Spool>,
+    ) -> Self {
+        let http = options.http_transport.clone();
         let url_base = options
             .endpoints()
             .build_url(crate::endpoints::Endpoint::Batch);
@@ -993,6 +1602,7 @@ impl Pipeline {
             clock,
             len,
             retries: VecDeque::new(),
+            spool,
         }
     }
 
@@ -1004,89 +1614,106 @@ impl Pipeline {
     ) {
         let defaults = self.options.capture_defaults();
         let count = events.len();
-        let (payload, kept) = match super::v0_capture::build_batch_payload(
+        let spooled = self.spooled_uuids(&events);
+        let events = super::v0_capture::prepare_batch(events, &defaults, &self.options.before_send);
+        let kept = events.len();
+        // Events dropped by before_send are terminal; account for them now so the
+        // batch tracks (and logs) only what is actually in flight.
+        dec_len(&self.len, count - kept);
+        self.options.stats.events_dropped_before_send(count - kept);
+        if events.is_empty() {
+            self.settle_spooled(&spooled, None);
+            return;
+        }
+        let dead_letters = self.dead_letter_copies(&events);
+        let payload = match super::v0_capture::build_batch_payload(
             events,
             self.options.api_key.clone(),
             historical_migration,
             self.clock.now_utc(),
-            &defaults,
-            &self.options.before_send,
         ) {
-            Ok(Some(pair)) => pair,
-            Ok(None) => {
-                // Every event dropped by before_send (terminal).
-                dec_len(&self.len, count);
-                return;
-            }
+            Ok(payload) => payload,
             Err(e) => {
                 if self.options.on_error.is_empty() {
-                    warn!("posthog-rs: dropping {count} event(s), serialization failed: {e}");
+                    warn!("posthog-rs: dropping {kept} event(s), serialization failed: {e}");
                 } else {
                     let err = Error::Serialization(e.to_string());
-                    self.fire_capture(Some(&err), None, 1, historical_migration, count);
+                    self.fire_capture(Some(&err), None, 1, historical_migration, kept);
                 }
-                dec_len(&self.len, count);
+                self.options.stats.events_rejected(kept);
+                dec_len(&self.len, kept);
+                self.settle_spooled(&spooled, None);
+                self.dead_letter(
+                    dead_letters,
+                    DeadLetterReason::Serialization,
+                    historical_migration,
+                );
                 return;
             }
         };
-        // Events dropped by before_send are terminal; account for them now so the
-        // batch tracks (and logs) only what is actually in flight.
-        dec_len(&self.len, count - kept);
+        let uncompressed_len = payload.len();
         let (body, encoding) = super::v0_capture::encode_body(&self.options, payload);
         let batch = RetryBatch {
             body,
+            uncompressed_len,
             encoding,
             count: kept,
             historical_migration,
             attempt: 1,
             next_at: self.clock.now(),
+            spooled,
+            dead_letters,
         };
         self.attempt(batch, deadline);
     }
 
     fn attempt(&mut self, mut batch: RetryBatch, deadline: Option<Instant>) {
-        use super::get_default_user_agent;
         use super::retry::{v0_after_response, v0_after_transport_error, Step};
-        use reqwest::header::{CONTENT_TYPE, USER_AGENT};
 
         // v0 capture reads the compression hint from the query param, not the header.
         let url = match batch.encoding {
             Some(token) => format!("{}?compression={token}", self.url_base),
             None => self.url_base.clone(),
         };
-        let mut request = self
-            .http
-            .post(&url)
-            .header(CONTENT_TYPE, "application/json")
-            .header(USER_AGENT, get_default_user_agent())
-            .body(batch.body.clone());
-        if let Some(token) = batch.encoding {
-            request = request.header(reqwest::header::CONTENT_ENCODING, token);
-        }
-        let request = super::v0_capture::apply_extra_headers(&self.options, request);
-        let request = bound_request(
-            request,
+        let request = super::v0_capture::build_request(
+            &self.options,
+            &url,
+            batch.body.clone(),
+            batch.encoding,
+        )
+        .with_timeout(request_timeout(
             deadline,
             self.clock.now(),
             self.options.request_timeout_seconds,
-        );
+        ));
 
+        self.options
+            .stats
+            .bytes_sent(batch.uncompressed_len, batch.body.len());
         let mut http_status: Option<u16> = None;
-        let step = match request.send() {
+        let step = match self.http.send(request) {
             Err(e) => v0_after_transport_error(&self.options, batch.attempt, e.to_string()),
             Ok(response) => {
-                let status = response.status().as_u16();
+                let status = response.status;
                 http_status = Some(status);
-                let retry_after = super::retry::parse_retry_after(response.headers());
-                let body = response
-                    .text()
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                v0_after_response(&self.options, batch.attempt, status, retry_after, &body)
+                let retry_after = super::retry::parse_retry_after(&response);
+                v0_after_response(
+                    &self.options,
+                    batch.attempt,
+                    status,
+                    retry_after,
+                    &response.text(),
+                )
             }
         };
 
         match step {
-            Step::Done => dec_len(&self.len, batch.count),
+            Step::Done => {
+                self.options.stats.events_sent(batch.count);
+                dec_len(&self.len, batch.count);
+                self.settle_spooled(&batch.spooled, None);
+                self.spool_delivered();
+            }
             Step::Fail(e) => {
                 if self.options.on_error.is_empty() {
                     warn!("posthog-rs: dropping {} event(s): {e}", batch.count);
@@ -1099,7 +1726,14 @@ impl Pipeline {
                         batch.count,
                     );
                 }
+                self.options.stats.events_rejected(batch.count);
                 dec_len(&self.len, batch.count);
+                self.settle_spooled(&batch.spooled, Some(&e));
+                self.dead_letter(
+                    batch.dead_letters,
+                    dead_letter_reason(&e, http_status),
+                    batch.historical_migration,
+                );
             }
             Step::Backoff(delay) => {
                 if deadline.is_some() {
@@ -1108,7 +1742,13 @@ impl Pipeline {
                         batch.count
                     );
                     dec_len(&self.len, batch.count);
+                    self.dead_letter(
+                        batch.dead_letters,
+                        DeadLetterReason::Shutdown,
+                        batch.historical_migration,
+                    );
                 } else {
+                    self.options.stats.events_retried(batch.count);
                     batch.attempt += 1;
                     batch.next_at = self.clock.now() + delay;
                     self.retries.push_back(batch);
@@ -1143,6 +1783,11 @@ impl Pipeline {
                     batch.count
                 );
                 dec_len(&self.len, batch.count);
+                self.dead_letter(
+                    batch.dead_letters,
+                    DeadLetterReason::Shutdown,
+                    batch.historical_migration,
+                );
             } else {
                 self.attempt(batch, deadline);
             }
@@ -1296,7 +1941,8 @@ mod tests {
         let (tx, rx) = mpsc::channel::<Control>();
         let (ftx, frx) = mpsc::channel::<()>();
         let (stx, srx) = mpsc::channel::<()>();
-        let len = AtomicUsize::new(3); // 1 capture + 2 historical events reserved
+        let len = QueueLen::default();
+        len.count.store(3, Ordering::Release); // 1 capture + 2 historical events reserved
         tx.send(Control::Flush(Completion::Blocking(ftx))).unwrap();
         tx.send(Control::Shutdown(Completion::Blocking(stx)))
             .unwrap();
@@ -1310,12 +1956,12 @@ mod tests {
         .unwrap();
         drop(tx);
 
-        drain_pending_completions(&rx, &len);
+        drain_pending_completions(&rx, &len, None, None);
 
         assert!(frx.recv().is_ok(), "flush completion was not signaled");
         assert!(srx.recv().is_ok(), "shutdown completion was not signaled");
         assert_eq!(
-            len.load(Ordering::Acquire),
+            len.count.load(Ordering::Acquire),
             0,
             "dropped events left counted as pending"
         );
@@ -1429,6 +2075,215 @@ mod tests {
         handle.shutdown_blocking();
     }
 
+    #[test]
+    fn stats_count_queue_full_before_send_and_retries() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST);
+            then.status(503);
+        });
+        let options = options(server.base_url())
+            .max_queue_size(2usize)
+            .before_send(|event| (event.event_name() != "drop").then_some(event))
+            .build()
+            .unwrap();
+        let stats = Arc::clone(&options.stats);
+        let handle = TransportHandle::spawn_with_clock(options, Arc::new(ManualClock::new()));
+
+        handle.enqueue(Event::new("keep", "user-1"));
+        handle.enqueue(Event::new("drop", "user-1"));
+        handle.enqueue(Event::new("overflow", "user-1"));
+        handle.flush_blocking();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.events_enqueued, 2);
+        assert_eq!(snapshot.events_dropped_queue_full, 1);
+        assert_eq!(snapshot.events_dropped_before_send, 1);
+        assert_eq!(snapshot.events_retried, 1);
+        assert_eq!(snapshot.events_sent, 0);
+        assert!(snapshot.bytes_sent > 0);
+
+        handle.shutdown_blocking();
+    }
+
+    type Recorded<T> = Arc<Mutex<Vec<T>>>;
+
+    /// Options whose `before_send` hook records the name of each event the
+    /// worker sends and whose `on_error` hook records each queue overflow.
+    fn overflow_options(
+        base_url: String,
+        max_queue_size: usize,
+        policy: OverflowPolicy,
+    ) -> (
+        ClientOptions,
+        Recorded<String>,
+        Recorded<(usize, OverflowPolicy)>,
+    ) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let overflows = Arc::new(Mutex::new(Vec::new()));
+        let (sent_sink, overflow_sink) = (Arc::clone(&sent), Arc::clone(&overflows));
+        let options = options(base_url)
+            .max_queue_size(max_queue_size)
+            .overflow_policy(policy)
+            .before_send(move |event| {
+                sent_sink
+                    .lock()
+                    .unwrap()
+                    .push(event.event_name().to_string());
+                Some(event)
+            })
+            .on_error(move |failure| {
+                if let PostHogError::QueueOverflow(o) = failure {
+                    overflow_sink
+                        .lock()
+                        .unwrap()
+                        .push((o.dropped(), o.policy()));
+                }
+            })
+            .build()
+            .unwrap();
+        (options, sent, overflows)
+    }
+
+    #[test]
+    fn overflow_drop_newest_reports_dropped_events() {
+        let server = MockServer::start();
+        ok_mock(&server);
+        let (options, sent, overflows) =
+            overflow_options(server.base_url(), 2, OverflowPolicy::DropNewest);
+        let handle = TransportHandle::spawn_with_clock(options, Arc::new(ManualClock::new()));
+
+        for name in ["a", "b", "c"] {
+            handle.enqueue(Event::new(name, "user-1"));
+        }
+        handle.enqueue_historical(vec![Event::new("h1", "user-1"), Event::new("h2", "user-1")]);
+        handle.flush_blocking();
+
+        assert_eq!(*sent.lock().unwrap(), ["a", "b"]);
+        assert_eq!(
+            *overflows.lock().unwrap(),
+            [
+                (1, OverflowPolicy::DropNewest),
+                (2, OverflowPolicy::DropNewest)
+            ]
+        );
+        handle.shutdown_blocking();
+    }
+
+    #[test]
+    fn overflow_drop_oldest_evicts_unsent_events() {
+        let server = MockServer::start();
+        ok_mock(&server);
+        let (options, sent, overflows) =
+            overflow_options(server.base_url(), 2, OverflowPolicy::DropOldest);
+        let stats = Arc::clone(&options.stats);
+        let handle = TransportHandle::spawn_with_clock(options, Arc::new(ManualClock::new()));
+
+        for name in ["a", "b", "c", "d"] {
+            handle.enqueue(Event::new(name, "user-1"));
+        }
+        handle.flush_blocking();
+
+        assert_eq!(*sent.lock().unwrap(), ["c", "d"]);
+        let dropped: usize = overflows.lock().unwrap().iter().map(|o| o.0).sum();
+        assert_eq!(dropped, 2);
+        assert_eq!(stats.snapshot().events_dropped_queue_full, 2);
+        assert_eq!(handle.pending(), 0);
+        handle.shutdown_blocking();
+    }
+
+    #[test]
+    fn overflow_block_waits_for_space_until_its_timeout() {
+        let server = MockServer::start();
+        ok_mock(&server);
+        let timeout = Duration::from_millis(1000);
+        let (options, sent, overflows) =
+            overflow_options(server.base_url(), 1, OverflowPolicy::Block { timeout });
+        let handle = Arc::new(TransportHandle::spawn_with_clock(
+            options,
+            Arc::new(ManualClock::new()),
+        ));
+
+        // Nothing frees the slot: the second event is dropped after the timeout.
+        handle.enqueue(Event::new("a", "user-1"));
+        let started = Instant::now();
+        handle.enqueue(Event::new("b", "user-1"));
+        assert!(started.elapsed() >= timeout);
+        assert_eq!(
+            *overflows.lock().unwrap(),
+            [(1, OverflowPolicy::Block { timeout })]
+        );
+
+        // A flush mid-wait frees the slot, so the third event gets in.
+        let flusher = {
+            let handle = Arc::clone(&handle);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handle.flush_blocking();
+            })
+        };
+        handle.enqueue(Event::new("c", "user-1"));
+        flusher.join().unwrap();
+        handle.flush_blocking();
+
+        assert_eq!(*sent.lock().unwrap(), ["a", "c"]);
+        assert_eq!(overflows.lock().unwrap().len(), 1);
+        handle.shutdown_blocking();
+    }
+
+    #[cfg(feature = "async-client")]
+    #[tokio::test]
+    async fn enqueue_async_waits_for_space() {
+        let server = MockServer::start();
+        ok_mock(&server);
+        let (options, sent, overflows) =
+            overflow_options(server.base_url(), 1, OverflowPolicy::DropNewest);
+        let handle = Arc::new(TransportHandle::spawn_with_clock(
+            options,
+            Arc::new(ManualClock::new()),
+        ));
+
+        handle.enqueue(Event::new("a", "user-1"));
+        let flusher = {
+            let handle = Arc::clone(&handle);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handle.flush_blocking();
+            })
+        };
+        handle.enqueue_async(Event::new("b", "user-1")).await;
+        flusher.join().unwrap();
+        handle.flush_blocking();
+
+        assert_eq!(*sent.lock().unwrap(), ["a", "b"]);
+        assert!(overflows.lock().unwrap().is_empty());
+        handle.shutdown_blocking();
+    }
+
+    #[cfg(not(feature = "capture-v1"))]
+    #[test]
+    fn stats_count_sent_events_and_compressed_bytes() {
+        let server = MockServer::start();
+        ok_mock(&server);
+        let options = options(server.base_url())
+            .capture_compression(crate::client::CaptureCompression::Gzip)
+            .build()
+            .unwrap();
+        let stats = Arc::clone(&options.stats);
+        let handle = TransportHandle::spawn_with_clock(options, Arc::new(ManualClock::new()));
+
+        for _ in 0..20 {
+            handle.enqueue(Event::new("page_viewed", "user-1"));
+        }
+        handle.flush_blocking();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.events_sent, 20);
+        assert!(snapshot.bytes_sent < snapshot.bytes_sent_uncompressed);
+
+        handle.shutdown_blocking();
+    }
+
     #[test]
     fn shutdown_timeout_bounds_a_stalled_in_flight_send() {
         // Endpoint accepts then stalls far past shutdown_timeout_ms. The per-request
@@ -1675,6 +2530,100 @@ mod tests {
         assert!(has_error, "a terminal reject carries the underlying error");
     }
 
+    /// Dead-letter sink recording each hand-off as (event names, reason,
+    /// historical_migration).
+    #[derive(Clone, Default)]
+    struct RecordingSink(Recorded<(Vec<String>, DeadLetterReason, bool)>);
+
+    impl crate::DeadLetterSink for RecordingSink {
+        fn dead_letter(
+            &self,
+            events: Vec<Event>,
+            reason: DeadLetterReason,
+            historical_migration: bool,
+        ) {
+            let names = events.iter().map(|e| e.event_name().to_string()).collect();
+            self.0
+                .lock()
+                .unwrap()
+                .push((names, reason, historical_migration));
+        }
+    }
+
+    #[test]
+    fn dead_letter_sink_receives_rejected_batches() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST);
+            then.status(400).body("bad request");
+        });
+        let sink = RecordingSink::default();
+        let handle = TransportHandle::spawn_with_clock(
+            options(server.base_url())
+                .dead_letter_sink(sink.clone())
+                .build()
+                .unwrap(),
+            Arc::new(ManualClock::new()),
+        );
+
+        handle.enqueue(Event::new("a", "user-1"));
+        handle.enqueue(Event::new("b", "user-1"));
+        handle.enqueue_historical(vec![Event::new("h", "user-1")]);
+        handle.flush_blocking();
+        handle.shutdown_blocking();
+
+        let rejected = DeadLetterReason::Rejected { status: Some(400) };
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [
+                (vec!["h".to_string()], rejected, true),
+                (vec!["a".to_string(), "b".to_string()], rejected, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn dead_letter_sink_receives_events_abandoned_at_shutdown() {
+        // The 503 leaves "retried" held for a retry and "buffered" arrives after
+        // the flush; a zero shutdown timeout abandons both.
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST);
+            then.status(503);
+        });
+        let sink = RecordingSink::default();
+        let handle = TransportHandle::spawn_with_clock(
+            options(server.base_url())
+                .shutdown_timeout_ms(0u64)
+                .dead_letter_sink(sink.clone())
+                .build()
+                .unwrap(),
+            Arc::new(ManualClock::new()),
+        );
+
+        handle.enqueue(Event::new("retried", "user-1"));
+        handle.flush_blocking();
+        handle.enqueue(Event::new("buffered", "user-1"));
+        handle.shutdown_blocking();
+
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [
+                (
+                    vec!["retried".to_string()],
+                    DeadLetterReason::Shutdown,
+                    false
+                ),
+                (
+                    vec!["buffered".to_string()],
+                    DeadLetterReason::Shutdown,
+                    false
+                ),
+            ]
+        );
+        assert_eq!(handle.pending(), 0);
+    }
+
     /// The lost tally must count `retry` and `drop` verdicts (events the backend
     /// will not persist) while excluding delivered `ok`/`warning` — a batch that
     /// mixes a drop with a retry must report both, not just whatever remained in
@@ -1770,23 +2719,22 @@ mod tests {
         let recorded = Arc::new(Mutex::new(Vec::<(Option<u16>, bool, usize, usize)>::new()));
         let sink = recorded.clone();
         let clock = ManualClock::new();
-        let handle = TransportHandle::spawn_with_clock(
-            options(server.base_url())
-                .max_capture_attempts(1u32)
-                .on_error(move |failure| {
-                    if let PostHogError::Capture(c) = failure {
-                        sink.lock().unwrap_or_else(|p| p.into_inner()).push((
-                            c.status(),
-                            c.error().is_some(),
-                            c.event_count(),
-                            c.event_results().len(),
-                        ));
-                    }
-                })
-                .build()
-                .unwrap(),
-            Arc::new(clock.clone()),
-        );
+        let options = options(server.base_url())
+            .max_capture_attempts(1u32)
+            .on_error(move |failure| {
+                if let PostHogError::Capture(c) = failure {
+                    sink.lock().unwrap_or_else(|p| p.into_inner()).push((
+                        c.status(),
+                        c.error().is_some(),
+                        c.event_count(),
+                        c.event_results().len(),
+                    ));
+                }
+            })
+            .build()
+            .unwrap();
+        let stats = Arc::clone(&options.stats);
+        let handle = TransportHandle::spawn_with_clock(options, Arc::new(clock.clone()));
 
         let mut e1 = Event::new("e1", "user-1");
         e1.set_uuid(u1);
@@ -1807,5 +2755,63 @@ mod tests {
         assert!(!has_error, "a 2xx is not an error");
         assert_eq!(lost, 2, "counts the dropped event and the final retry");
         assert_eq!(results, 3, "all verdicts reported, including the ok");
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.events_sent, 1);
+        assert_eq!(snapshot.events_rejected, 2);
+    }
+
+    #[cfg(feature = "capture-v1")]
+    #[test]
+    fn dead_letter_sink_groups_v1_verdicts() {
+        let u1 = Uuid::now_v7();
+        let u2 = Uuid::now_v7();
+        let u3 = Uuid::now_v7();
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST);
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(serde_json::json!({
+                    "results": {
+                        u1.to_string(): { "result": "ok" },
+                        u2.to_string(): { "result": "drop", "details": "not_persisted" },
+                        u3.to_string(): { "result": "retry", "details": "not_persisted" }
+                    }
+                }));
+        });
+        let sink = RecordingSink::default();
+        let handle = TransportHandle::spawn_with_clock(
+            options(server.base_url())
+                .max_capture_attempts(1u32)
+                .dead_letter_sink(sink.clone())
+                .build()
+                .unwrap(),
+            Arc::new(ManualClock::new()),
+        );
+
+        for (name, uuid) in [("e1", u1), ("e2", u2), ("e3", u3)] {
+            let mut event = Event::new(name, "user-1");
+            event.set_uuid(uuid);
+            handle.enqueue(event);
+        }
+        handle.flush_blocking();
+        handle.shutdown_blocking();
+
+        let status = Some(200);
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [
+                (
+                    vec!["e2".to_string()],
+                    DeadLetterReason::Rejected { status },
+                    false
+                ),
+                (
+                    vec!["e3".to_string()],
+                    DeadLetterReason::RetriesExhausted { status },
+                    false
+                ),
+            ]
+        );
     }
 }
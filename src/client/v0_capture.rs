@@ -2,18 +2,16 @@
 //! Each client keeps only the I/O; this module owns event preparation and
 //! payload construction.
 
-// The transport worker is always blocking reqwest, even for the async client,
-// so the v0 request helpers operate on the blocking RequestBuilder.
 use chrono::{DateTime, Utc};
-use reqwest::blocking::RequestBuilder;
 
 use super::{
     common::{apply_before_send_hooks, apply_capture_defaults, apply_runtime_context},
-    BeforeSendHook, CaptureDefaults, ClientOptions,
+    get_default_user_agent, BeforeSendHook, CaptureDefaults, ClientOptions,
 };
 use crate::endpoints::Endpoint;
 use crate::error::Error;
 use crate::event::{BatchRequest, Event, InnerEvent};
+use crate::http::HttpRequest;
 
 // ---------------------------------------------------------------------------
 // Event preparation
@@ -33,21 +31,18 @@ pub(crate) fn prepare_event(event: &mut Event, defaults: &CaptureDefaults) {
 // Payload building
 // ---------------------------------------------------------------------------
 
-/// Build the JSON body for a V0 batch capture request.
+/// Prepare events for a V0 batch: apply client defaults and `before_send`.
 ///
-/// Returns the serialized body together with the number of events that survived
-/// `before_send` filtering, so the caller can account for filtered-out events as
-/// terminal and track only what is actually in flight. `Ok(None)` means every
-/// event was dropped by `before_send`.
-pub(crate) fn build_batch_payload(
+/// Returns the events that survived `before_send` filtering, in their final
+/// form, so the caller can account for filtered-out events as terminal, track
+/// only what is actually in flight, and keep copies of what it sends (the
+/// worker's dead-letter copies).
+pub(crate) fn prepare_batch(
     events: Vec<Event>,
-    api_key: String,
-    historical_migration: bool,
-    sent_at: DateTime<Utc>,
     defaults: &CaptureDefaults,
     before_send: &[BeforeSendHook],
-) -> Result<Option<(String, usize)>, Error> {
-    let inner_events: Vec<InnerEvent> = events
+) -> Vec<Event> {
+    events
         .into_iter()
         .filter_map(|mut event| {
             prepare_event(&mut event, defaults);
@@ -56,25 +51,27 @@ pub(crate) fn build_batch_payload(
                 // events, drop everything outside the allowlist so a hook cannot
                 // reintroduce properties it strips.
                 event.apply_minimal_flag_called_allowlist();
-                InnerEvent::new_for_batch(event)
+                event
             })
         })
-        .collect();
-
-    if inner_events.is_empty() {
-        return Ok(None);
-    }
+        .collect()
+}
 
-    let kept = inner_events.len();
+/// Build the JSON body for a V0 batch capture request from events already
+/// run through [`prepare_batch`].
+pub(crate) fn build_batch_payload(
+    events: Vec<Event>,
+    api_key: String,
+    historical_migration: bool,
+    sent_at: DateTime<Utc>,
+) -> Result<String, Error> {
     let batch_request = BatchRequest {
         api_key,
         historical_migration,
         sent_at: sent_at.to_rfc3339(),
-        batch: inner_events,
+        batch: events.into_iter().map(InnerEvent::new_for_batch).collect(),
     };
-    serde_json::to_string(&batch_request)
-        .map(|json| Some((json, kept)))
-        .map_err(|e| Error::Serialization(e.to_string()))
+    serde_json::to_string(&batch_request).map_err(|e| Error::Serialization(e.to_string()))
 }
 
 /// Encode the V0 JSON body, compressing when configured. Returns the bytes and
@@ -120,17 +117,17 @@ pub(crate) fn prepare_immediate(
     historical_migration: bool,
 ) -> Result<Option<PreparedV0>, Error> {
     let defaults = options.capture_defaults();
-    let Some((json, kept)) = build_batch_payload(
+    let events = prepare_batch(events, &defaults, &options.before_send);
+    if events.is_empty() {
+        return Ok(None);
+    }
+    let kept = events.len();
+    let json = build_batch_payload(
         events,
         options.api_key.clone(),
         historical_migration,
         Utc::now(),
-        &defaults,
-        &options.before_send,
-    )?
-    else {
-        return Ok(None);
-    };
+    )?;
 
     let base_url = options.endpoints().build_url(Endpoint::Batch);
     let (body, encoding) = encode_body(options, json);
@@ -151,15 +148,24 @@ pub(crate) fn prepare_immediate(
 // Header helpers
 // ---------------------------------------------------------------------------
 
-/// Apply test-harness extra headers to a V0 request.
-pub(crate) fn apply_extra_headers(
+/// Build one V0 batch request. Shared by the transport worker and both
+/// clients' immediate capture; test-harness extra headers are applied last.
+pub(crate) fn build_request(
     #[allow(unused_variables)] options: &ClientOptions,
-    #[allow(unused_mut)] mut request: RequestBuilder,
-) -> RequestBuilder {
+    url: &str,
+    body: Vec<u8>,
+    encoding: Option<&'static str>,
+) -> HttpRequest {
+    let mut request = HttpRequest::post(url, body)
+        .with_header("content-type", "application/json")
+        .with_header("user-agent", get_default_user_agent());
+    if let Some(token) = encoding {
+        request = request.with_header("content-encoding", token);
+    }
     #[cfg(feature = "test-harness")]
     if let Some(ref extra) = options.extra_capture_headers {
         for (k, v) in extra {
-            request = request.header(k.as_str(), v.as_str());
+            request = request.with_header(k, v.as_str());
         }
     }
     request
@@ -205,6 +211,7 @@ mod tests {
             &CaptureDefaults {
                 disable_geoip: true,
                 is_server: true,
+                super_properties: Default::default(),
             },
         );
         // Mirrors the no-hooks case in `build_batch_payload`, where the trim runs
@@ -259,6 +266,7 @@ mod tests {
             &CaptureDefaults {
                 disable_geoip: true,
                 is_server: true,
+                super_properties: Default::default(),
             },
         );
         // No minimization marker -> the full shape is preserved, including the
@@ -289,21 +297,19 @@ mod tests {
             Some(event)
         })];
 
-        let (json_body, kept) = build_batch_payload(
+        let events = prepare_batch(
             vec![event],
-            "phc_test".to_string(),
-            false,
-            Utc::now(),
             &CaptureDefaults {
                 disable_geoip: true,
                 is_server: true,
+                super_properties: Default::default(),
             },
             &hooks,
-        )
-        .unwrap()
-        .expect("event should not be dropped");
+        );
+        assert_eq!(events.len(), 1, "event should not be dropped");
+        let json_body =
+            build_batch_payload(events, "phc_test".to_string(), false, Utc::now()).unwrap();
 
-        assert_eq!(kept, 1);
         let parsed: serde_json::Value = serde_json::from_str(&json_body).unwrap();
         let properties = &parsed["batch"][0]["properties"];
         assert!(
@@ -0,0 +1,65 @@
+//! Cohort membership known outside PostHog's flag definitions.
+//!
+//! `/flags/definitions` only carries cohorts whose membership follows from
+//! person properties. Static cohorts (uploaded lists) and behavioral cohorts
+//! (based on events) are missing or cannot be evaluated locally, so a flag
+//! targeting them is inconclusive and falls back to remote `/flags`. An
+//! application that already knows who is in those cohorts can answer through
+//! a [`CohortMembershipProvider`] and keep such flags local.
+
+use tracing::error;
+
+/// Answers cohort membership that local evaluation cannot work out itself.
+///
+/// Local evaluation asks the provider when a flag filters on a cohort that is
+/// not in the flag definitions, or whose filters cannot be evaluated with the
+/// properties at hand. Cohorts it can evaluate are never looked up. Set one
+/// with
+/// [`ClientOptionsBuilder::cohort_membership_provider`](crate::ClientOptionsBuilder::cohort_membership_provider),
+/// [`LocalEvaluator::with_cohort_membership_provider`](crate::LocalEvaluator::with_cohort_membership_provider)
+/// or [`EvaluationContext::cohort_membership`](crate::EvaluationContext::cohort_membership).
+///
+/// The provider is called on the evaluating thread — tokio's blocking thread
+/// pool in the async client — once per cohort filter it answers, so keep it
+/// quick: look memberships up in memory rather than querying a database per
+/// call. Panics are caught and treated as `None`.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+///
+/// use posthog_rs::CohortMembershipProvider;
+///
+/// struct BetaTesters(HashSet<String>);
+///
+/// impl CohortMembershipProvider for BetaTesters {
+///     fn is_member(&self, cohort_id: &str, distinct_id: &str) -> Option<bool> {
+///         (cohort_id == "42").then(|| self.0.contains(distinct_id))
+///     }
+/// }
+/// ```
+pub trait CohortMembershipProvider: Send + Sync + 'static {
+    /// Whether the person `distinct_id` is in the cohort `cohort_id`, or
+    /// `None` when the provider does not know, which leaves the flag
+    /// inconclusive.
+    fn is_member(&self, cohort_id: &str, distinct_id: &str) -> Option<bool>;
+}
+
+/// Ask `provider` about `distinct_id`, catching a panicking provider.
+pub(crate) fn ask_provider(
+    provider: &dyn CohortMembershipProvider,
+    cohort_id: &str,
+    distinct_id: &str,
+) -> Option<bool> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        provider.is_member(cohort_id, distinct_id)
+    }))
+    .unwrap_or_else(|_| {
+        error!(
+            cohort_id,
+            "panic in PostHog cohort membership provider; ignoring"
+        );
+        None
+    })
+}
@@ -0,0 +1,1282 @@
+//! Feature flag definitions compiled for evaluation.
+//!
+//! Compiling a definition does everything that does not depend on the person
+//! being evaluated exactly once: regexes are built, date, semver and numeric
+//! targets are parsed, and conditions are put in the order they are tried.
+//! Evaluating a compiled flag then only borrows from it.
+//!
+//! [`FlagSnapshot`](crate::FlagSnapshot) compiles every definition when the
+//! cache is updated. The free `match_*` functions in
+//! [`feature_flags`](crate::feature_flags) compile the definition they are
+//! handed on each call, so both paths share these semantics.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::cohort_membership::{ask_provider, CohortMembershipProvider};
+use crate::feature_flags::{
+    compare_values, compute_caret_bounds, compute_tilde_bounds, hash_key, holdout_hash,
+    matching_variant, parse_absolute_date, parse_date_value, parse_relative_duration, parse_semver,
+    parse_semver_wildcard, parse_target_semver, value_to_string, CohortDefinition, FeatureFlag,
+    FlagDetail, FlagReason, FlagValue, InconclusiveMatchError, MultivariateVariant, Property,
+    PropertyGroup, PropertyGroupType, PropertyGroupValue, SemverTuple, ROLLOUT_HASH_SALT,
+    VARIANT_HASH_SALT,
+};
+use crate::flag_explanation::{
+    ConditionKind, ConditionOutcome, FlagExplanation, Recorder, VariantExplanation,
+};
+
+/// Builds the [`Regex`] for a `regex`/`not_regex` pattern; `None` when the
+/// pattern is invalid.
+pub(crate) type CompileRegex = fn(&str) -> Option<Regex>;
+
+/// Where a compiled flag looks up the cohorts, flags and group types its
+/// conditions refer to.
+pub(crate) trait Definitions {
+    /// The group type name for a group type index.
+    fn group_type(&self, index: i32) -> Option<&str>;
+    /// The cohort with ID `id`.
+    fn cohort(&self, id: &str) -> Option<Cow<'_, CompiledCohort>>;
+    /// The flag with key `key`, for `$feature/<key>` dependencies.
+    fn flag(&self, key: &str) -> Option<Cow<'_, CompiledFlag>>;
+}
+
+/// Who a flag is evaluated for, and against which definitions.
+pub(crate) struct Environment<'a, D> {
+    definitions: &'a D,
+    distinct_id: &'a str,
+    /// Bucketed on instead of the distinct ID by flags whose bucketing
+    /// identifier is the device.
+    device_id: Option<&'a str>,
+    groups: &'a HashMap<String, String>,
+    group_properties: &'a HashMap<String, HashMap<String, Value>>,
+    /// Whether cohort and `$feature/` properties are resolved through
+    /// `definitions`. When `false` they are matched like any other property.
+    resolve_references: bool,
+    /// Asked about cohorts `definitions` cannot answer for.
+    cohort_membership: Option<&'a dyn CohortMembershipProvider>,
+}
+
+impl<'a, D: Definitions> Environment<'a, D> {
+    pub(crate) fn new(
+        definitions: &'a D,
+        distinct_id: &'a str,
+        groups: &'a HashMap<String, String>,
+        group_properties: &'a HashMap<String, HashMap<String, Value>>,
+    ) -> Self {
+        Self {
+            definitions,
+            distinct_id,
+            device_id: None,
+            groups,
+            group_properties,
+            resolve_references: true,
+            cohort_membership: None,
+        }
+    }
+
+    /// The same environment, asking `provider` about cohorts that are
+    /// missing from the definitions or cannot be evaluated locally.
+    pub(crate) fn with_cohort_membership(
+        self,
+        provider: Option<&'a dyn CohortMembershipProvider>,
+    ) -> Self {
+        Self {
+            cohort_membership: provider,
+            ..self
+        }
+    }
+
+    /// The same environment, bucketing flags whose bucketing identifier is
+    /// the device on `device_id`.
+    pub(crate) fn with_device_id(self, device_id: Option<&'a str>) -> Self {
+        Self { device_id, ..self }
+    }
+
+    /// The same environment, matching cohort and `$feature/` properties like
+    /// any other property.
+    pub(crate) fn without_references(self) -> Self {
+        Self {
+            resolve_references: false,
+            ..self
+        }
+    }
+}
+
+/// Follows a flag evaluation step by step. Every method does nothing by
+/// default, so evaluating with `()` costs nothing; the
+/// [`Recorder`] behind [`CompiledFlag::explain`] keeps everything.
+///
+/// For each condition tried, `condition` is called first and
+/// `condition_outcome` last.
+pub(crate) trait Observer {
+    fn condition(&mut self, _kind: ConditionKind, _index: usize) {}
+    /// The group type (`None` for persons) and bucketing ID of the current
+    /// condition, as far as they are known.
+    fn target(&mut self, _group_type: Option<&str>, _bucketing_id: Option<&str>) {}
+    fn property<D: Definitions>(
+        &mut self,
+        _matcher: &PropertyMatcher,
+        _properties: &HashMap<String, Value>,
+        _env: &Environment<'_, D>,
+        _result: &Result<bool, InconclusiveMatchError>,
+    ) {
+    }
+    fn rollout(&mut self, _percentage: f64, _hash: f64, _included: bool) {}
+    fn condition_outcome(&mut self, _outcome: impl FnOnce() -> ConditionOutcome) {}
+    fn variant(&mut self, _variant: impl FnOnce() -> VariantExplanation) {}
+}
+
+impl Observer for () {}
+
+/// What an evaluated flag serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Served<'f> {
+    Disabled,
+    Enabled,
+    Variant(&'f str),
+}
+
+impl Served<'_> {
+    pub(crate) fn to_flag_value(self) -> FlagValue {
+        match self {
+            Served::Disabled => FlagValue::Boolean(false),
+            Served::Enabled => FlagValue::Boolean(true),
+            Served::Variant(variant) => FlagValue::String(variant.to_string()),
+        }
+    }
+
+    /// Whether a `$feature/<key>` dependency expecting `expected` is met.
+    fn satisfies(self, expected: &Value) -> bool {
+        match (self, expected) {
+            (Served::Disabled, Value::Bool(expected)) => !expected,
+            (Served::Enabled, Value::Bool(expected)) => *expected,
+            (Served::Variant(variant), Value::String(expected)) => {
+                variant.eq_ignore_ascii_case(expected)
+            }
+            // A boolean flag never equals a specific variant.
+            (Served::Enabled, Value::String(s)) => s.is_empty() || s == "true",
+            (Served::Disabled, Value::String(s)) => s.is_empty() || s == "false",
+            // Any variant counts as enabled.
+            (Served::Variant(variant), Value::Bool(true)) => !variant.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Why an evaluated flag serves what it does, as a `/flags?v=2` reason code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason {
+    ConditionMatch(usize),
+    OutOfRolloutBound(usize),
+    NoConditionMatch,
+    FlagDisabled,
+    SuperConditionValue,
+    HoldoutConditionValue,
+}
+
+/// The result of evaluating a compiled flag, borrowing its variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Outcome<'f> {
+    pub(crate) served: Served<'f>,
+    pub(crate) reason: Reason,
+}
+
+impl Outcome<'_> {
+    pub(crate) fn value(&self) -> FlagValue {
+        self.served.to_flag_value()
+    }
+
+    pub(crate) fn detail(&self, key: &str) -> FlagDetail {
+        let reason = match self.reason {
+            Reason::ConditionMatch(index) => FlagReason::condition_match(index),
+            Reason::OutOfRolloutBound(index) => FlagReason::out_of_rollout_bound(index),
+            Reason::NoConditionMatch => FlagReason::no_condition_match(),
+            Reason::FlagDisabled => FlagReason::flag_disabled(),
+            Reason::SuperConditionValue => FlagReason::super_condition_value(),
+            Reason::HoldoutConditionValue => FlagReason::holdout_condition_value(),
+        };
+        FlagDetail::local(key, self.value(), reason)
+    }
+}
+
+/// Outcome of evaluating a single condition group, mirroring the PostHog Rust
+/// evaluation engine's tri-state so the flag loop can distinguish a
+/// property-filter miss (always fall through) from a rollout exclusion (which
+/// can short-circuit when `early_exit` is enabled).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConditionMatch {
+    /// Property filters matched (or there were none) and the rollout included
+    /// the user — the flag matches.
+    Match,
+    /// A property filter did not match — always continue to the next group.
+    NoMatch,
+    /// Property filters matched (or there were none) but the rollout excluded
+    /// the user.
+    OutOfRolloutBound,
+}
+
+/// A [`FeatureFlag`] ready for evaluation.
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledFlag {
+    key: String,
+    active: bool,
+    early_exit: bool,
+    /// Whether persons are bucketed on their device ID, when one is passed
+    /// in, rather than their distinct ID.
+    bucket_on_device: bool,
+    /// The first super group, which decides the flag on its own when the
+    /// person has any property it filters on.
+    super_condition: Option<CompiledCondition>,
+    holdout: Option<CompiledHoldout>,
+    /// The flag's group type index, which super groups and holdouts target.
+    aggregation: Option<i32>,
+    /// Conditions in the order they are tried: variant overrides first.
+    conditions: Vec<CompiledCondition>,
+    variants: Vec<MultivariateVariant>,
+}
+
+/// The first holdout group of a flag. Like the server, only its rollout
+/// percentage and variant are evaluated.
+#[derive(Debug, Clone)]
+struct CompiledHoldout {
+    /// Holdouts with property filters are not evaluated yet, and never match.
+    has_properties: bool,
+    rollout_percentage: Option<f64>,
+    /// The `holdout-<id>` variant served to held-out users.
+    variant: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledCondition {
+    /// Position in the flag's `filters.groups`, reported in reasons.
+    index: usize,
+    properties: Vec<PropertyMatcher>,
+    rollout_percentage: Option<f64>,
+    /// The condition's variant override, kept only when it names one of the
+    /// flag's variants.
+    variant: Option<String>,
+    /// The condition's group type index, falling back to the flag's. `None`
+    /// targets persons.
+    aggregation: Option<i32>,
+}
+
+impl CompiledFlag {
+    pub(crate) fn new(flag: &FeatureFlag, compile_regex: CompileRegex) -> Self {
+        let filters = &flag.filters;
+        let variants = filters
+            .multivariate
+            .as_ref()
+            .map(|multivariate| multivariate.variants.clone())
+            .unwrap_or_default();
+        let mut conditions: Vec<CompiledCondition> = filters
+            .groups
+            .iter()
+            .enumerate()
+            .map(|(index, condition)| CompiledCondition {
+                index,
+                properties: condition
+                    .properties
+                    .iter()
+                    .map(|property| PropertyMatcher::new(property, compile_regex))
+                    .collect(),
+                rollout_percentage: condition.rollout_percentage,
+                variant: condition
+                    .variant
+                    .clone()
+                    .filter(|variant| variants.iter().any(|v| &v.key == variant)),
+                aggregation: condition
+                    .aggregation_group_type_index
+                    .or(filters.aggregation_group_type_index),
+            })
+            .collect();
+        // Variant overrides are tried first, including ones naming an unknown
+        // variant, which then serve the bucketed variant instead.
+        let has_override: Vec<bool> = filters.groups.iter().map(|c| c.variant.is_some()).collect();
+        conditions.sort_by_key(|c| !has_override[c.index]);
+        let super_condition = filters
+            .super_groups
+            .as_ref()
+            .and_then(|groups| groups.first())
+            .map(|condition| CompiledCondition {
+                index: 0,
+                properties: condition
+                    .properties
+                    .iter()
+                    .map(|property| PropertyMatcher::new(property, compile_regex))
+                    .collect(),
+                rollout_percentage: condition.rollout_percentage,
+                variant: None,
+                aggregation: filters.aggregation_group_type_index,
+            });
+        let holdout = filters
+            .holdout_groups
+            .as_ref()
+            .and_then(|groups| groups.first())
+            .map(|condition| CompiledHoldout {
+                has_properties: !condition.properties.is_empty(),
+                rollout_percentage: condition.rollout_percentage,
+                variant: condition.variant.clone(),
+            });
+
+        Self {
+            key: flag.key.clone(),
+            active: flag.active,
+            early_exit: filters.early_exit,
+            bucket_on_device: flag.bucketing_identifier.as_deref() == Some("device_id"),
+            super_condition,
+            holdout,
+            aggregation: filters.aggregation_group_type_index,
+            conditions,
+            variants,
+        }
+    }
+
+    /// Evaluate the flag for the person or groups in `env`.
+    ///
+    /// A condition targeting a group is bucketed on the group key and matched
+    /// against that group's properties; it is skipped when the group type is
+    /// unknown or no group of that type was passed in, and is inconclusive
+    /// when the group's properties are missing.
+    pub(crate) fn evaluate<D: Definitions>(
+        &self,
+        person_properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+    ) -> Result<Outcome<'_>, InconclusiveMatchError> {
+        self.evaluate_observed(person_properties, env, &mut ())
+    }
+
+    /// Evaluate the flag like [`evaluate`](Self::evaluate), recording each
+    /// step.
+    pub(crate) fn explain<D: Definitions>(
+        &self,
+        person_properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+    ) -> FlagExplanation {
+        let mut recorder = Recorder::default();
+        let evaluated = self
+            .evaluate_observed(person_properties, env, &mut recorder)
+            .ok()
+            .map(|outcome| (outcome.value(), outcome.detail(&self.key).reason));
+        recorder.finish(&self.key, self.active, evaluated)
+    }
+
+    fn evaluate_observed<D: Definitions, O: Observer>(
+        &self,
+        person_properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+        observer: &mut O,
+    ) -> Result<Outcome<'_>, InconclusiveMatchError> {
+        if !self.active {
+            return Ok(Outcome {
+                served: Served::Disabled,
+                reason: Reason::FlagDisabled,
+            });
+        }
+
+        let mut is_inconclusive = false;
+        let person_bucketing = match env.device_id {
+            Some(device_id) if self.bucket_on_device => device_id,
+            _ => env.distinct_id,
+        };
+
+        if let Some(condition) = &self.super_condition {
+            observer.condition(ConditionKind::Super, 0);
+            match target(
+                condition.aggregation,
+                person_bucketing,
+                true,
+                person_properties,
+                env,
+                observer,
+            ) {
+                Some((bucketing, Some(properties))) => {
+                    // Like the server, the super condition only applies when
+                    // the person has one of its properties, e.g. once they
+                    // opted in or out of an early access feature.
+                    let relevant = condition
+                        .properties
+                        .iter()
+                        .any(|matcher| properties.contains_key(&matcher.property().key));
+                    if relevant {
+                        let result =
+                            condition.evaluate(&self.key, bucketing, properties, env, observer);
+                        observer.condition_outcome(|| ConditionOutcome::from_match(&result));
+                        let served = match result? {
+                            ConditionMatch::Match => Served::Enabled,
+                            _ => Served::Disabled,
+                        };
+                        return Ok(Outcome {
+                            served,
+                            reason: Reason::SuperConditionValue,
+                        });
+                    }
+                    observer.condition_outcome(|| {
+                        ConditionOutcome::Skipped(
+                            "none of the super condition's properties were passed in".to_string(),
+                        )
+                    });
+                }
+                Some((_, None)) => is_inconclusive = true,
+                None => {}
+            }
+        }
+
+        if let Some(holdout) = &self.holdout {
+            observer.condition(ConditionKind::Holdout, 0);
+            if let Some((bucketing, _)) = target(
+                self.aggregation,
+                person_bucketing,
+                false,
+                person_properties,
+                env,
+                observer,
+            ) {
+                if holdout.has_properties {
+                    observer.condition_outcome(|| {
+                        ConditionOutcome::Skipped(
+                            "holdouts with property filters are not evaluated".to_string(),
+                        )
+                    });
+                } else {
+                    let included = match holdout.rollout_percentage {
+                        Some(percentage) => {
+                            let hash = holdout_hash(bucketing);
+                            let included = hash <= percentage / 100.0;
+                            observer.rollout(percentage, hash, included);
+                            included
+                        }
+                        None => true,
+                    };
+                    if included {
+                        observer.condition_outcome(|| ConditionOutcome::Matched);
+                        return Ok(Outcome {
+                            served: self.serve_variant(
+                                holdout.variant.as_deref(),
+                                bucketing,
+                                observer,
+                            ),
+                            reason: Reason::HoldoutConditionValue,
+                        });
+                    }
+                    observer.condition_outcome(|| ConditionOutcome::OutOfRolloutBound);
+                }
+            }
+        }
+
+        let mut out_of_rollout_index: Option<usize> = None;
+
+        for condition in &self.conditions {
+            observer.condition(ConditionKind::Release, condition.index);
+            let Some((bucketing, properties)) = target(
+                condition.aggregation,
+                person_bucketing,
+                true,
+                person_properties,
+                env,
+                observer,
+            ) else {
+                continue;
+            };
+            let Some(properties) = properties else {
+                // Try other conditions, surface inconclusive if nothing else
+                // matches.
+                is_inconclusive = true;
+                continue;
+            };
+
+            let result = condition.evaluate(&self.key, bucketing, properties, env, observer);
+            observer.condition_outcome(|| ConditionOutcome::from_match(&result));
+            match result {
+                Ok(ConditionMatch::Match) => {
+                    return Ok(Outcome {
+                        served: self.serve_variant(
+                            condition.variant.as_deref(),
+                            bucketing,
+                            observer,
+                        ),
+                        reason: Reason::ConditionMatch(condition.index),
+                    });
+                }
+                Ok(ConditionMatch::OutOfRolloutBound) => {
+                    let index = *out_of_rollout_index.get_or_insert(condition.index);
+                    // The user's properties matched this group but the rollout
+                    // excluded them. With early_exit enabled the flag is
+                    // definitively disabled; otherwise fall through to later
+                    // groups. Only short-circuit when no prior group was
+                    // inconclusive — an inconclusive result means we can't
+                    // evaluate locally and must fall back to the server, so it
+                    // takes priority over early_exit.
+                    if self.early_exit && !is_inconclusive {
+                        return Ok(Outcome {
+                            served: Served::Disabled,
+                            reason: Reason::OutOfRolloutBound(index),
+                        });
+                    }
+                }
+                Ok(ConditionMatch::NoMatch) => continue,
+                Err(_) => {
+                    is_inconclusive = true;
+                }
+            }
+        }
+
+        if is_inconclusive {
+            return Err(InconclusiveMatchError::new(
+                "Can't determine if feature flag is enabled or not with given properties",
+            ));
+        }
+
+        // Like the server, a rollout exclusion outranks a plain property miss.
+        let reason = match out_of_rollout_index {
+            Some(index) => Reason::OutOfRolloutBound(index),
+            None => Reason::NoConditionMatch,
+        };
+        Ok(Outcome {
+            served: Served::Disabled,
+            reason,
+        })
+    }
+
+    /// What a matching condition or holdout serves: its variant override,
+    /// the bucketed variant of a multivariate flag, or `true`.
+    fn serve_variant<'f, O: Observer>(
+        &'f self,
+        variant_override: Option<&'f str>,
+        bucketing: &str,
+        observer: &mut O,
+    ) -> Served<'f> {
+        if let Some(variant) = variant_override {
+            observer.variant(|| VariantExplanation {
+                variant: Some(variant.to_string()),
+                hash: None,
+                overridden: true,
+            });
+            return Served::Variant(variant);
+        }
+        if self.variants.is_empty() {
+            return Served::Enabled;
+        }
+        let hash = hash_key(&self.key, bucketing, VARIANT_HASH_SALT);
+        let variant = matching_variant(&self.variants, hash);
+        observer.variant(|| VariantExplanation {
+            variant: variant.map(str::to_string),
+            hash: Some(hash),
+            overridden: false,
+        });
+        variant.map_or(Served::Enabled, Served::Variant)
+    }
+}
+
+/// The bucketing ID of the person or group `aggregation` targets, which is
+/// `person_bucketing` for persons, and the properties conditions on them are
+/// matched against; `None` when those were not passed in for a group, which
+/// leaves the condition inconclusive if it `needs_properties`. `None`
+/// altogether when the group type is unknown or no group of that type was
+/// passed in, so the condition is skipped.
+fn target<'e, D: Definitions, O: Observer>(
+    aggregation: Option<i32>,
+    person_bucketing: &'e str,
+    needs_properties: bool,
+    person_properties: &'e HashMap<String, Value>,
+    env: &'e Environment<'_, D>,
+    observer: &mut O,
+) -> Option<(&'e str, Option<&'e HashMap<String, Value>>)> {
+    let Some(index) = aggregation else {
+        observer.target(None, Some(person_bucketing));
+        return Some((person_bucketing, Some(person_properties)));
+    };
+    let Some(group_type) = env.definitions.group_type(index) else {
+        observer.condition_outcome(|| {
+            ConditionOutcome::Skipped(format!(
+                "group type index {} is not in the group type mapping",
+                index
+            ))
+        });
+        return None;
+    };
+    let Some(group_key) = env.groups.get(group_type) else {
+        observer.target(Some(group_type), None);
+        observer.condition_outcome(|| {
+            ConditionOutcome::Skipped(format!("no '{}' group was passed in", group_type))
+        });
+        return None;
+    };
+    observer.target(Some(group_type), Some(group_key));
+    let properties = env.group_properties.get(group_type);
+    if properties.is_none() && needs_properties {
+        observer.condition_outcome(|| {
+            ConditionOutcome::Inconclusive(format!(
+                "no properties were passed in for the '{}' group",
+                group_type
+            ))
+        });
+    }
+    Some((group_key.as_str(), properties))
+}
+
+impl ConditionOutcome {
+    fn from_match(result: &Result<ConditionMatch, InconclusiveMatchError>) -> Self {
+        match result {
+            Ok(ConditionMatch::Match) => ConditionOutcome::Matched,
+            Ok(ConditionMatch::NoMatch) => ConditionOutcome::PropertiesNotMatched,
+            Ok(ConditionMatch::OutOfRolloutBound) => ConditionOutcome::OutOfRolloutBound,
+            Err(e) => ConditionOutcome::Inconclusive(e.message.clone()),
+        }
+    }
+}
+
+impl CompiledCondition {
+    fn evaluate<D: Definitions, O: Observer>(
+        &self,
+        flag_key: &str,
+        bucketing: &str,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+        observer: &mut O,
+    ) -> Result<ConditionMatch, InconclusiveMatchError> {
+        for property in &self.properties {
+            let result = property.matches_in(properties, env);
+            observer.property(property, properties, env, &result);
+            if !result? {
+                return Ok(ConditionMatch::NoMatch);
+            }
+        }
+
+        if let Some(percentage) = self.rollout_percentage {
+            let hash = hash_key(flag_key, bucketing, ROLLOUT_HASH_SALT);
+            let excluded = hash > percentage / 100.0;
+            observer.rollout(percentage, hash, !excluded);
+            if excluded {
+                return Ok(ConditionMatch::OutOfRolloutBound);
+            }
+        }
+
+        Ok(ConditionMatch::Match)
+    }
+}
+
+/// A cohort's property filters ready for evaluation.
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledCohort {
+    filters: FilterGroup,
+}
+
+impl CompiledCohort {
+    pub(crate) fn new(cohort: &CohortDefinition, compile_regex: CompileRegex) -> Self {
+        Self {
+            filters: FilterGroup::new(&cohort.property_group(), compile_regex),
+        }
+    }
+}
+
+/// A [`PropertyGroup`] ready for evaluation.
+#[derive(Debug, Clone)]
+struct FilterGroup {
+    /// `true` for `OR` groups, `false` for `AND` groups.
+    any: bool,
+    values: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    Property {
+        matcher: PropertyMatcher,
+        negated: bool,
+    },
+    Group(FilterGroup),
+}
+
+/// The cohorts being resolved, innermost first, to detect cohorts that
+/// refer back to themselves.
+struct Resolving<'r> {
+    cohort_id: &'r str,
+    outer: Option<&'r Resolving<'r>>,
+}
+
+impl Resolving<'_> {
+    fn contains(&self, cohort_id: &str) -> bool {
+        self.cohort_id == cohort_id || self.outer.is_some_and(|outer| outer.contains(cohort_id))
+    }
+}
+
+impl FilterGroup {
+    fn new(group: &PropertyGroup, compile_regex: CompileRegex) -> Self {
+        Self {
+            any: group.group_type == PropertyGroupType::Or,
+            values: group
+                .values
+                .iter()
+                .map(|value| match value {
+                    PropertyGroupValue::Property { property, negated } => Filter::Property {
+                        matcher: PropertyMatcher::new(property, compile_regex),
+                        negated: *negated,
+                    },
+                    PropertyGroupValue::Group(group) => {
+                        Filter::Group(FilterGroup::new(group, compile_regex))
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Match the group inside the cohort `resolving.cohort_id`.
+    ///
+    /// Like PostHog's other SDKs, a value that settles the group (a match in
+    /// an `OR` group, a miss in an `AND` group) wins over inconclusive
+    /// values; otherwise the first inconclusive value is the result. An empty
+    /// group of either kind matches everyone.
+    fn matches<D: Definitions>(
+        &self,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+        resolving: &Resolving<'_>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        if self.values.is_empty() {
+            return Ok(true);
+        }
+        let mut inconclusive = None;
+        for filter in &self.values {
+            let result = match filter {
+                Filter::Property { matcher, negated } => matcher
+                    .matches_in_cohort(properties, env, resolving)
+                    .map(|matches| matches != *negated),
+                Filter::Group(group) => group.matches(properties, env, resolving),
+            };
+            match result {
+                Ok(matches) if matches == self.any => return Ok(matches),
+                Ok(_) => {}
+                Err(e) => {
+                    inconclusive.get_or_insert(e);
+                }
+            }
+        }
+        match inconclusive {
+            Some(e) => Err(e),
+            None => Ok(!self.any),
+        }
+    }
+}
+
+/// A [`Property`] filter with its target parsed for its operator.
+#[derive(Debug, Clone)]
+pub(crate) struct PropertyMatcher {
+    property: Property,
+    reference: Reference,
+    comparison: Comparison,
+}
+
+/// Definitions a property refers to, resolved when the environment allows.
+#[derive(Debug, Clone)]
+enum Reference {
+    None,
+    /// Membership of the cohort whose ID is the property's value.
+    Cohort,
+    /// The value of the flag with this key (`$feature/<key>`).
+    Flag(String),
+}
+
+#[derive(Debug, Clone)]
+enum Comparison {
+    Exact,
+    IsNot,
+    IsSet,
+    IsNotSet,
+    IContains {
+        /// The target, lowercased.
+        needle: String,
+        negated: bool,
+    },
+    Regex {
+        /// `None` when the pattern does not compile.
+        regex: Option<Regex>,
+        negated: bool,
+    },
+    Numeric {
+        operator: NumericOperator,
+        /// The target as a number, when it is one.
+        number: Option<f64>,
+        /// The target as text, compared with non-numeric values.
+        text: String,
+    },
+    Date {
+        before: bool,
+        /// `None` when the target does not parse.
+        target: Option<DateTarget>,
+    },
+    Semver {
+        operator: SemverOperator,
+        /// Inclusive lower and exclusive upper bound for ranges, or the
+        /// target version twice. `None` when the target does not parse.
+        bounds: Option<(SemverTuple, SemverTuple)>,
+    },
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumericOperator {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateTarget {
+    Absolute(DateTime<Utc>),
+    /// A relative date such as `-7d`, resolved against the time of evaluation.
+    Relative(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SemverOperator {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// `semver_tilde` and `semver_caret`.
+    Range,
+    Wildcard,
+}
+
+impl PropertyMatcher {
+    pub(crate) fn new(property: &Property, compile_regex: CompileRegex) -> Self {
+        let reference = if property.property_type.as_deref() == Some("cohort") {
+            Reference::Cohort
+        } else if let Some(key) = property.key.strip_prefix("$feature/") {
+            Reference::Flag(key.to_string())
+        } else {
+            Reference::None
+        };
+        Self {
+            comparison: Comparison::new(property, compile_regex),
+            property: property.clone(),
+            reference,
+        }
+    }
+
+    /// Match against `properties`, resolving cohort and `$feature/`
+    /// properties through `env` when it allows.
+    pub(crate) fn matches_in<D: Definitions>(
+        &self,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        if !env.resolve_references {
+            return self.matches(properties);
+        }
+        match &self.reference {
+            Reference::None => self.matches(properties),
+            Reference::Cohort => self.matches_cohort(properties, env),
+            Reference::Flag(key) => self.matches_flag(key, env),
+        }
+    }
+
+    pub(crate) fn property(&self) -> &Property {
+        &self.property
+    }
+
+    /// The value this filter compares against, for explanations: the
+    /// property from `properties`, or the served value of a `$feature/`
+    /// dependency. `None` for cohort filters.
+    pub(crate) fn actual_value<D: Definitions>(
+        &self,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+    ) -> Option<Value> {
+        match &self.reference {
+            Reference::Cohort if env.resolve_references => None,
+            Reference::Flag(key) if env.resolve_references => {
+                let flag = env.definitions.flag(key)?;
+                let empty_properties = HashMap::new();
+                let dependency_env = Environment {
+                    resolve_references: false,
+                    ..*env
+                };
+                let served = flag
+                    .evaluate(&empty_properties, &dependency_env)
+                    .ok()?
+                    .served;
+                Some(match served {
+                    Served::Disabled => Value::Bool(false),
+                    Served::Enabled => Value::Bool(true),
+                    Served::Variant(variant) => Value::String(variant.to_string()),
+                })
+            }
+            _ => properties.get(&self.property.key).cloned(),
+        }
+    }
+
+    /// Evaluate cohort membership.
+    fn matches_cohort<D: Definitions>(
+        &self,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        let cohort_id = self.cohort_id()?;
+        let is_in_cohort = cohort_membership(&cohort_id, properties, env, None)?;
+
+        Ok(match self.property.operator.as_str() {
+            "in" => is_in_cohort,
+            "not_in" => !is_in_cohort,
+            op => {
+                return Err(InconclusiveMatchError::new(&format!(
+                    "Unknown cohort operator: {}",
+                    op
+                )));
+            }
+        })
+    }
+
+    /// Match a filter of the cohort `resolving.cohort_id`. Cohort filters
+    /// resolve the cohort they name, which matches its members unless the
+    /// operator is `not_in`; other filters only look at `properties`.
+    fn matches_in_cohort<D: Definitions>(
+        &self,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+        resolving: &Resolving<'_>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        if let Reference::Cohort = self.reference {
+            let cohort_id = self.cohort_id()?;
+            let is_in_cohort = cohort_membership(&cohort_id, properties, env, Some(resolving))?;
+            return Ok(is_in_cohort != (self.property.operator == "not_in"));
+        }
+        self.matches(properties).map_err(|e| {
+            // If we can't evaluate a cohort property, the cohort membership
+            // is inconclusive
+            InconclusiveMatchError::new(&format!(
+                "Cannot evaluate cohort '{}' property '{}': {}",
+                resolving.cohort_id, self.property.key, e.message
+            ))
+        })
+    }
+
+    /// The ID of the cohort a cohort filter names, given as a string or a
+    /// number.
+    fn cohort_id(&self) -> Result<Cow<'_, str>, InconclusiveMatchError> {
+        match &self.property.value {
+            Value::String(_) | Value::Number(_) => Ok(value_to_string(&self.property.value)),
+            _ => Err(InconclusiveMatchError::new(
+                "Cohort ID must be a string or a number",
+            )),
+        }
+    }
+
+    /// Evaluate a flag dependency.
+    fn matches_flag<D: Definitions>(
+        &self,
+        key: &str,
+        env: &Environment<'_, D>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        let flag = env.definitions.flag(key).ok_or_else(|| {
+            InconclusiveMatchError::new(&format!("Flag '{}' not found in local cache", key))
+        })?;
+
+        // Evaluate the dependent flag for this user with empty properties and
+        // without resolving its own references, to avoid recursion. Group
+        // context flows through so dependent group/mixed flags can resolve.
+        let empty_properties = HashMap::new();
+        let dependency_env = Environment {
+            resolve_references: false,
+            ..*env
+        };
+        let served = flag.evaluate(&empty_properties, &dependency_env)?.served;
+        let matches = served.satisfies(&self.property.value);
+
+        Ok(match self.property.operator.as_str() {
+            "exact" => matches,
+            "is_not" => !matches,
+            op => {
+                return Err(InconclusiveMatchError::new(&format!(
+                    "Unknown flag dependency operator: {}",
+                    op
+                )));
+            }
+        })
+    }
+
+    /// Match against `properties` alone.
+    pub(crate) fn matches(
+        &self,
+        properties: &HashMap<String, Value>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        let property = &self.property;
+        let Some(value) = properties.get(&property.key) else {
+            return match self.comparison {
+                Comparison::IsNotSet => Ok(true),
+                Comparison::IsSet => Ok(false),
+                // For other operators, missing property is inconclusive
+                _ => Err(InconclusiveMatchError::new(&format!(
+                    "Property '{}' not found in provided properties",
+                    property.key
+                ))),
+            };
+        };
+
+        Ok(match &self.comparison {
+            Comparison::Exact => matches_any(&property.value, value),
+            Comparison::IsNot => !matches_any(&property.value, value),
+            // We already know the property exists
+            Comparison::IsSet => true,
+            Comparison::IsNotSet => false,
+            Comparison::IContains { needle, negated } => {
+                contains_ignore_case(&value_to_string(value), needle) != *negated
+            }
+            Comparison::Regex { regex, negated } => match regex {
+                Some(regex) => regex.is_match(&value_to_string(value)) != *negated,
+                None => *negated,
+            },
+            Comparison::Numeric {
+                operator,
+                number,
+                text,
+            } => match (number, as_number(value)) {
+                (Some(target), Some(value)) => operator.compare(&value, target),
+                _ => operator.compare(&*value_to_string(value), text.as_str()),
+            },
+            Comparison::Date { before, target } => {
+                let target_date = match target {
+                    Some(DateTarget::Absolute(date)) => *date,
+                    Some(DateTarget::Relative(ago)) => Utc::now() - *ago,
+                    None => {
+                        return Err(InconclusiveMatchError::new(&format!(
+                            "Unable to parse target date value: {:?}",
+                            property.value
+                        )))
+                    }
+                };
+                let property_date = parse_date_value(value).ok_or_else(|| {
+                    InconclusiveMatchError::new(&format!(
+                        "Unable to parse property date value for '{}': {:?}",
+                        property.key, value
+                    ))
+                })?;
+                if *before {
+                    property_date < target_date
+                } else {
+                    property_date > target_date
+                }
+            }
+            Comparison::Semver { operator, bounds } => {
+                let version = parse_semver(&value_to_string(value)).ok_or_else(|| {
+                    InconclusiveMatchError::new(&format!(
+                        "Unable to parse property semver value for '{}': {:?}",
+                        property.key, value
+                    ))
+                })?;
+                let Some((lower, upper)) = bounds else {
+                    let what = if *operator == SemverOperator::Wildcard {
+                        "target semver wildcard pattern"
+                    } else {
+                        "target semver value"
+                    };
+                    return Err(InconclusiveMatchError::new(&format!(
+                        "Unable to parse {}: {:?}",
+                        what, property.value
+                    )));
+                };
+                match operator {
+                    SemverOperator::Eq => version == *lower,
+                    SemverOperator::Neq => version != *lower,
+                    SemverOperator::Gt => version > *lower,
+                    SemverOperator::Gte => version >= *lower,
+                    SemverOperator::Lt => version < *lower,
+                    SemverOperator::Lte => version <= *lower,
+                    SemverOperator::Range | SemverOperator::Wildcard => {
+                        version >= *lower && version < *upper
+                    }
+                }
+            }
+            Comparison::Unknown => {
+                return Err(InconclusiveMatchError::new(&format!(
+                    "Unknown operator: {}",
+                    property.operator
+                )));
+            }
+        })
+    }
+}
+
+impl Comparison {
+    fn new(property: &Property, compile_regex: CompileRegex) -> Self {
+        let target = &property.value;
+        let numeric = |operator| Comparison::Numeric {
+            operator,
+            number: as_number(target),
+            text: value_to_string(target).into_owned(),
+        };
+        let semver = |operator| Comparison::Semver {
+            operator,
+            bounds: parse_target_semver(target)
+                .ok()
+                .map(|version| (version, version)),
+        };
+        let range = |bounds: fn(SemverTuple) -> (SemverTuple, SemverTuple)| Comparison::Semver {
+            operator: SemverOperator::Range,
+            bounds: parse_target_semver(target).ok().map(bounds),
+        };
+
+        match property.operator.as_str() {
+            "exact" => Comparison::Exact,
+            "is_not" => Comparison::IsNot,
+            "is_set" => Comparison::IsSet,
+            "is_not_set" => Comparison::IsNotSet,
+            "icontains" | "not_icontains" => Comparison::IContains {
+                needle: value_to_string(target).to_lowercase(),
+                negated: property.operator == "not_icontains",
+            },
+            "regex" | "not_regex" => Comparison::Regex {
+                regex: compile_regex(&value_to_string(target)),
+                negated: property.operator == "not_regex",
+            },
+            "gt" => numeric(NumericOperator::Gt),
+            "gte" => numeric(NumericOperator::Gte),
+            "lt" => numeric(NumericOperator::Lt),
+            "lte" => numeric(NumericOperator::Lte),
+            "is_date_before" | "is_date_after" => Comparison::Date {
+                before: property.operator == "is_date_before",
+                target: parse_date_target(target),
+            },
+            "semver_eq" => semver(SemverOperator::Eq),
+            "semver_neq" => semver(SemverOperator::Neq),
+            "semver_gt" => semver(SemverOperator::Gt),
+            "semver_gte" => semver(SemverOperator::Gte),
+            "semver_lt" => semver(SemverOperator::Lt),
+            "semver_lte" => semver(SemverOperator::Lte),
+            "semver_tilde" => range(compute_tilde_bounds),
+            "semver_caret" => range(compute_caret_bounds),
+            "semver_wildcard" => Comparison::Semver {
+                operator: SemverOperator::Wildcard,
+                bounds: parse_semver_wildcard(&value_to_string(target)),
+            },
+            _ => Comparison::Unknown,
+        }
+    }
+}
+
+impl NumericOperator {
+    fn compare<T: PartialOrd + ?Sized>(self, value: &T, target: &T) -> bool {
+        match self {
+            NumericOperator::Gt => value > target,
+            NumericOperator::Gte => value >= target,
+            NumericOperator::Lt => value < target,
+            NumericOperator::Lte => value <= target,
+        }
+    }
+}
+
+/// `exact` matching: any element of an array target, otherwise the target.
+fn matches_any(target: &Value, value: &Value) -> bool {
+    match target.as_array() {
+        Some(targets) => targets.iter().any(|target| compare_values(target, value)),
+        None => compare_values(target, value),
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// Whether `haystack` contains `needle`, which is already lowercased,
+/// ignoring case. Allocates only for non-ASCII text.
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    if !(haystack.is_ascii() && needle.is_ascii()) {
+        return haystack.to_lowercase().contains(needle);
+    }
+    let (haystack, needle) = (haystack.as_bytes(), needle.as_bytes());
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Whether the person or group with `properties` is in the cohort
+/// `cohort_id`, resolving the cohorts it refers to in turn. The environment's
+/// cohort membership provider answers for cohorts that are missing or
+/// inconclusive.
+fn cohort_membership<D: Definitions>(
+    cohort_id: &str,
+    properties: &HashMap<String, Value>,
+    env: &Environment<'_, D>,
+    outer: Option<&Resolving<'_>>,
+) -> Result<bool, InconclusiveMatchError> {
+    if outer.is_some_and(|outer| outer.contains(cohort_id)) {
+        return Err(InconclusiveMatchError::new(&format!(
+            "Cohort '{}' refers to itself through its cohort filters",
+            cohort_id
+        )));
+    }
+    let provided = || {
+        env.cohort_membership
+            .and_then(|provider| ask_provider(provider, cohort_id, env.distinct_id))
+    };
+    let Some(cohort) = env.definitions.cohort(cohort_id) else {
+        return provided().ok_or_else(|| {
+            InconclusiveMatchError::new(&format!("Cohort '{}' not found in local cache", cohort_id))
+        });
+    };
+    let resolving = Resolving { cohort_id, outer };
+    cohort
+        .filters
+        .matches(properties, env, &resolving)
+        .or_else(|e| provided().ok_or(e))
+}
+
+/// A date target, keeping relative dates relative so they are resolved when
+/// the flag is evaluated rather than when it is compiled.
+fn parse_date_target(value: &Value) -> Option<DateTarget> {
+    let date = value.as_str()?;
+    if date.starts_with('-') && date.len() > 1 {
+        if let Some(ago) = parse_relative_duration(date) {
+            return Some(DateTarget::Relative(ago));
+        }
+    }
+    parse_absolute_date(date).map(DateTarget::Absolute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn property(key: &str, operator: &str, value: Value) -> PropertyMatcher {
+        let property = Property {
+            key: key.to_string(),
+            value,
+            operator: operator.to_string(),
+            property_type: None,
+        };
+        PropertyMatcher::new(&property, |pattern| Regex::new(pattern).ok())
+    }
+
+    #[test]
+    fn relative_date_targets_are_resolved_at_evaluation_time() {
+        let matcher = property("last_seen", "is_date_after", json!("-1h"));
+        let Comparison::Date { target, .. } = &matcher.comparison else {
+            panic!("expected a date comparison");
+        };
+        assert!(matches!(target, Some(DateTarget::Relative(_))));
+
+        let recent = HashMap::from([("last_seen".to_string(), json!(Utc::now().to_rfc3339()))]);
+        assert!(matcher.matches(&recent).unwrap());
+    }
+
+    #[test]
+    fn case_insensitive_contains_handles_ascii_and_unicode() {
+        assert!(contains_ignore_case("Jane@Example.COM", "example.com"));
+        assert!(contains_ignore_case("ZÜRICH", "zü"));
+        assert!(contains_ignore_case("anything", ""));
+        assert!(!contains_ignore_case("ex", "example"));
+    }
+}
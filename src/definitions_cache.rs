@@ -0,0 +1,161 @@
+//! On-disk copy of the local-evaluation flag definitions.
+//!
+//! A poller configured with [`DefinitionsCacheOptions`] writes every
+//! definitions response it accepts, with its ETag, to a file, and loads that
+//! file into its [`FlagCache`] on startup before the first poll. A service
+//! that boots while PostHog is unreachable then evaluates flags from the last
+//! definitions it saw instead of starting empty.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use crate::local_evaluation::{FlagCache, LocalEvaluationResponse};
+
+/// Where, and for how long, local-evaluation flag definitions are persisted.
+///
+/// The file's modification time is the definitions' age: it is rewritten on
+/// every changed response and touched on every `304 Not Modified`, so it
+/// records when PostHog last confirmed the definitions. On startup a file
+/// older than [`max_age`](Self::max_age) is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use posthog_rs::{ClientOptionsBuilder, DefinitionsCacheOptions};
+///
+/// let options = ClientOptionsBuilder::default()
+///     .api_key("phc_project_api_key".to_string())
+///     .secret_key("phx_personal_api_key")
+///     .enable_local_evaluation(true)
+///     .flag_definitions_cache(
+///         DefinitionsCacheOptions::new("/var/lib/my-service/posthog-flags.json")
+///             .max_age(Duration::from_secs(24 * 60 * 60)),
+///     )
+///     .build()
+///     .unwrap();
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefinitionsCacheOptions {
+    path: PathBuf,
+    max_age: Option<Duration>,
+}
+
+/// The persisted file: the definitions response body, verbatim, under the
+/// ETag it was served with.
+#[derive(Deserialize)]
+struct Persisted {
+    #[serde(default)]
+    etag: Option<String>,
+    definitions: LocalEvaluationResponse,
+}
+
+impl DefinitionsCacheOptions {
+    /// Persist definitions to the file at `path`. Its directory must exist.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            max_age: None,
+        }
+    }
+
+    /// Ignore a persisted file older than `max_age` on startup (default: no
+    /// limit).
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The file definitions are persisted to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the persisted definitions into `cache`, returning the ETag they
+    /// were served with. Returns `None` when nothing was loaded.
+    pub(crate) fn restore(&self, cache: &FlagCache) -> Option<String> {
+        match self.read() {
+            Ok(Some(persisted)) => {
+                info!(
+                    path = %self.path.display(),
+                    flag_count = persisted.definitions.flags.len(),
+                    "Loaded persisted flag definitions"
+                );
+                cache.update(persisted.definitions);
+                persisted.etag
+            }
+            Ok(None) => None,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!(path = %self.path.display(), "No persisted flag definitions");
+                None
+            }
+            Err(e) => {
+                warn!(path = %self.path.display(), error = %e, "Ignoring unreadable persisted flag definitions");
+                None
+            }
+        }
+    }
+
+    fn read(&self) -> io::Result<Option<Persisted>> {
+        let file = File::open(&self.path)?;
+        let age = file
+            .metadata()?
+            .modified()?
+            .elapsed()
+            .unwrap_or(Duration::ZERO);
+        if let Some(max_age) = self.max_age.filter(|max_age| age > *max_age) {
+            warn!(
+                path = %self.path.display(),
+                age_secs = age.as_secs(),
+                max_age_secs = max_age.as_secs(),
+                "Ignoring stale persisted flag definitions"
+            );
+            return Ok(None);
+        }
+        serde_json::from_reader(io::BufReader::new(file))
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Persist a definitions response `body` that parsed successfully, served
+    /// with `etag`. The file is replaced atomically, so a crash mid-write
+    /// leaves the previous definitions in place.
+    pub(crate) fn save(&self, body: &[u8], etag: Option<&str>) {
+        if let Err(e) = self.write(body, etag) {
+            warn!(path = %self.path.display(), error = %e, "Failed to persist flag definitions");
+        }
+    }
+
+    fn write(&self, body: &[u8], etag: Option<&str>) -> io::Result<()> {
+        // The body already parsed as JSON, so it is embedded as-is rather than
+        // re-serialized.
+        let mut contents = Vec::with_capacity(body.len() + 64);
+        contents.extend_from_slice(b"{\"etag\":");
+        serde_json::to_writer(&mut contents, &etag)?;
+        contents.extend_from_slice(b",\"definitions\":");
+        contents.extend_from_slice(body);
+        contents.push(b'}');
+
+        let mut staging = self.path.clone().into_os_string();
+        staging.push(".tmp");
+        fs::write(&staging, contents)?;
+        fs::rename(&staging, &self.path)
+    }
+
+    /// Record that PostHog confirmed the persisted definitions are current.
+    pub(crate) fn touch(&self) {
+        let result = File::options()
+            .write(true)
+            .open(&self.path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        if let Err(e) = result {
+            debug!(path = %self.path.display(), error = %e, "Failed to refresh persisted flag definitions");
+        }
+    }
+}
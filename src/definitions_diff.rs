@@ -0,0 +1,278 @@
+//! Change notifications for local-evaluation flag definitions.
+//!
+//! Every time a [`FlagCache`](crate::FlagCache) swaps in new definitions —
+//! from the poller, a persisted copy, or a static file — it compares them
+//! with the ones they replace and hands the resulting
+//! [`FlagDefinitionsDiff`] to each subscriber registered with
+//! [`FlagCache::on_change`](crate::FlagCache::on_change). Updates that change
+//! nothing are not reported.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::error;
+
+use crate::feature_flags::FeatureFlag;
+use crate::local_evaluation::Cohort;
+
+/// What changed between two sets of flag definitions.
+///
+/// Every list holds flag keys (or, for cohorts, cohort IDs) in sorted order.
+/// A flag appears in [`added`](Self::added) or [`removed`](Self::removed)
+/// alone; the remaining flag lists only cover flags present before and after
+/// the update, and one flag can appear in several of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlagDefinitionsDiff {
+    /// Flags that were not defined before.
+    pub added: Vec<String>,
+    /// Flags that are no longer defined.
+    pub removed: Vec<String>,
+    /// Flags that went from inactive to active.
+    pub activated: Vec<String>,
+    /// Flags that went from active to inactive, such as a flipped kill switch.
+    pub deactivated: Vec<String>,
+    /// Flags whose release conditions, rollout percentages, variant split,
+    /// aggregation, bucketing identifier, holdouts or super conditions
+    /// changed.
+    pub rollout_changed: Vec<String>,
+    /// Flags whose payloads changed.
+    pub payloads_changed: Vec<String>,
+    /// Cohorts that were not defined before.
+    pub cohorts_added: Vec<String>,
+    /// Cohorts that are no longer defined.
+    pub cohorts_removed: Vec<String>,
+    /// Cohorts whose name or properties changed.
+    pub cohorts_changed: Vec<String>,
+}
+
+impl FlagDefinitionsDiff {
+    /// Compare the definitions being replaced with their replacement.
+    pub(crate) fn between(
+        old_flags: &HashMap<String, FeatureFlag>,
+        new_flags: &HashMap<String, FeatureFlag>,
+        old_cohorts: &HashMap<String, Cohort>,
+        new_cohorts: &HashMap<String, Cohort>,
+    ) -> Self {
+        let mut diff = Self::default();
+
+        for (key, new) in new_flags {
+            let Some(old) = old_flags.get(key) else {
+                diff.added.push(key.clone());
+                continue;
+            };
+            match (old.active, new.active) {
+                (false, true) => diff.activated.push(key.clone()),
+                (true, false) => diff.deactivated.push(key.clone()),
+                _ => {}
+            }
+            let (old_filters, new_filters) = (&old.filters, &new.filters);
+            if old_filters.groups != new_filters.groups
+                || old_filters.multivariate != new_filters.multivariate
+                || old_filters.aggregation_group_type_index
+                    != new_filters.aggregation_group_type_index
+                || old_filters.early_exit != new_filters.early_exit
+                || old_filters.holdout_groups != new_filters.holdout_groups
+                || old_filters.super_groups != new_filters.super_groups
+                || old.bucketing_identifier != new.bucketing_identifier
+            {
+                diff.rollout_changed.push(key.clone());
+            }
+            if old_filters.payloads != new_filters.payloads {
+                diff.payloads_changed.push(key.clone());
+            }
+        }
+        diff.removed = missing_from(old_flags, new_flags);
+
+        for (id, new) in new_cohorts {
+            match old_cohorts.get(id) {
+                None => diff.cohorts_added.push(id.clone()),
+                Some(old) if old != new => diff.cohorts_changed.push(id.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.cohorts_removed = missing_from(old_cohorts, new_cohorts);
+
+        for list in [
+            &mut diff.added,
+            &mut diff.activated,
+            &mut diff.deactivated,
+            &mut diff.rollout_changed,
+            &mut diff.payloads_changed,
+            &mut diff.cohorts_added,
+            &mut diff.cohorts_changed,
+        ] {
+            list.sort();
+        }
+        diff
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `key` was added, removed or changed in any way.
+    pub fn affects_flag(&self, key: &str) -> bool {
+        [
+            &self.added,
+            &self.removed,
+            &self.activated,
+            &self.deactivated,
+            &self.rollout_changed,
+            &self.payloads_changed,
+        ]
+        .iter()
+        .any(|list| list.iter().any(|k| k == key))
+    }
+}
+
+/// Keys of `old` that `new` no longer has, sorted.
+fn missing_from<V>(old: &HashMap<String, V>, new: &HashMap<String, V>) -> Vec<String> {
+    let mut missing: Vec<String> = old
+        .keys()
+        .filter(|key| !new.contains_key(*key))
+        .cloned()
+        .collect();
+    missing.sort();
+    missing
+}
+
+type SharedDefinitionsListener = Arc<dyn Fn(&FlagDefinitionsDiff) + Send + Sync>;
+
+/// A callback registered with [`FlagCache::on_change`](crate::FlagCache::on_change).
+#[derive(Clone)]
+pub(crate) struct DefinitionsListener(SharedDefinitionsListener);
+
+impl DefinitionsListener {
+    pub(crate) fn new<F>(listener: F) -> Self
+    where
+        F: Fn(&FlagDefinitionsDiff) + Send + Sync + 'static,
+    {
+        Self(Arc::new(listener))
+    }
+}
+
+/// Hand `diff` to every listener in registration order. A panicking listener
+/// is logged and skipped, like an `on_error` hook.
+pub(crate) fn notify_listeners(listeners: &[DefinitionsListener], diff: &FlagDefinitionsDiff) {
+    for listener in listeners {
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (listener.0)(diff))).is_err() {
+            error!("panic in PostHog flag definitions listener; ignoring");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature_flags::{FeatureFlagCondition, FeatureFlagFilters};
+    use serde_json::json;
+
+    fn flag(key: &str, active: bool, rollout: f64) -> (String, FeatureFlag) {
+        let flag = FeatureFlag {
+            key: key.to_string(),
+            active,
+            filters: FeatureFlagFilters {
+                groups: vec![FeatureFlagCondition {
+                    properties: vec![],
+                    rollout_percentage: Some(rollout),
+                    variant: None,
+                    aggregation_group_type_index: None,
+                }],
+                ..Default::default()
+            },
+            has_experiment: None,
+            bucketing_identifier: None,
+        };
+        (key.to_string(), flag)
+    }
+
+    fn cohort(id: &str, name: &str) -> (String, Cohort) {
+        let cohort = Cohort {
+            id: id.to_string(),
+            name: name.to_string(),
+            properties: json!({}),
+        };
+        (id.to_string(), cohort)
+    }
+
+    #[test]
+    fn reports_each_kind_of_change() {
+        let old_flags = HashMap::from([
+            flag("kill-switch", true, 100.0),
+            flag("launching", false, 100.0),
+            flag("ramp", true, 10.0),
+            flag("retired", true, 100.0),
+        ]);
+        let mut with_payload = flag("launching", true, 100.0);
+        with_payload
+            .1
+            .filters
+            .payloads
+            .insert("true".into(), json!({ "color": "blue" }));
+        let new_flags = HashMap::from([
+            flag("kill-switch", false, 100.0),
+            with_payload,
+            flag("ramp", true, 50.0),
+            flag("brand-new", true, 100.0),
+        ]);
+        let old_cohorts = HashMap::from([cohort("1", "Beta"), cohort("2", "Staff")]);
+        let new_cohorts = HashMap::from([cohort("1", "Beta testers"), cohort("3", "Trial")]);
+
+        let diff = FlagDefinitionsDiff::between(&old_flags, &new_flags, &old_cohorts, &new_cohorts);
+
+        assert_eq!(diff.added, ["brand-new"]);
+        assert_eq!(diff.removed, ["retired"]);
+        assert_eq!(diff.activated, ["launching"]);
+        assert_eq!(diff.deactivated, ["kill-switch"]);
+        assert_eq!(diff.rollout_changed, ["ramp"]);
+        assert_eq!(diff.payloads_changed, ["launching"]);
+        assert_eq!(diff.cohorts_added, ["3"]);
+        assert_eq!(diff.cohorts_removed, ["2"]);
+        assert_eq!(diff.cohorts_changed, ["1"]);
+        assert!(diff.affects_flag("kill-switch"));
+        assert!(!diff.affects_flag("unknown"));
+    }
+
+    #[test]
+    fn holdout_and_super_condition_changes_are_rollout_changes() {
+        let old_flags = HashMap::from([flag("held-out", true, 100.0), flag("beta", true, 100.0)]);
+        let mut held_out = flag("held-out", true, 100.0);
+        held_out.1.filters.holdout_groups = Some(vec![FeatureFlagCondition {
+            properties: vec![],
+            rollout_percentage: Some(10.0),
+            variant: Some("holdout-1".to_string()),
+            aggregation_group_type_index: None,
+        }]);
+        let mut beta = flag("beta", true, 100.0);
+        beta.1.filters.super_groups = Some(vec![]);
+        let new_flags = HashMap::from([held_out, beta]);
+
+        let diff =
+            FlagDefinitionsDiff::between(&old_flags, &new_flags, &HashMap::new(), &HashMap::new());
+
+        assert_eq!(diff.rollout_changed, ["beta", "held-out"]);
+    }
+
+    #[test]
+    fn bucketing_identifier_changes_are_rollout_changes() {
+        let old_flags = HashMap::from([flag("onboarding", true, 50.0)]);
+        let mut by_device = flag("onboarding", true, 50.0);
+        by_device.1.bucketing_identifier = Some("device_id".to_string());
+        let new_flags = HashMap::from([by_device]);
+
+        let diff =
+            FlagDefinitionsDiff::between(&old_flags, &new_flags, &HashMap::new(), &HashMap::new());
+
+        assert_eq!(diff.rollout_changed, ["onboarding"]);
+    }
+
+    #[test]
+    fn identical_definitions_produce_an_empty_diff() {
+        let flags = HashMap::from([flag("a", true, 100.0)]);
+        let cohorts = HashMap::from([cohort("1", "Beta")]);
+
+        assert!(FlagDefinitionsDiff::between(&flags, &flags, &cohorts, &cohorts).is_empty());
+    }
+}
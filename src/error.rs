@@ -21,6 +21,7 @@ impl Display for Error {
             Error::BillingLimitExceeded(msg) => {
                 write!(f, "Billing Limit Exceeded: {msg}")
             }
+            Error::Io(e) => write!(f, "I/O Error: {e}"),
         }
     }
 }
@@ -58,6 +59,8 @@ pub enum Error {
     Unauthorized,
     /// HTTP 402 — billing quota exceeded (non-retryable)
     BillingLimitExceeded(String),
+    /// Reading a local file, such as a flag definitions file, failed
+    Io(std::io::Error),
 }
 
 impl Error {
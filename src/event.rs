@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use semver::Version;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::client::CRATE_VERSION;
@@ -413,6 +413,67 @@ impl InnerEvent {
     }
 }
 
+/// On-disk form of an [`Event`] in the persistent spool, borrowed for writing.
+///
+/// Unlike the wire formats it keeps the groups and the minimal-flag-called
+/// marker, so a replayed event goes through the capture pipeline exactly as
+/// the original would have. Read back as [`SpooledEvent`].
+#[derive(Serialize)]
+pub(crate) struct SpooledEventRef<'a> {
+    event: &'a str,
+    distinct_id: &'a str,
+    properties: &'a HashMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    groups: &'a HashMap<String, String>,
+    timestamp: Option<NaiveDateTime>,
+    uuid: Uuid,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    minimal_flag_called: bool,
+}
+
+impl<'a> From<&'a Event> for SpooledEventRef<'a> {
+    fn from(event: &'a Event) -> Self {
+        Self {
+            event: &event.event,
+            distinct_id: &event.distinct_id,
+            properties: &event.properties,
+            groups: &event.groups,
+            timestamp: event.timestamp,
+            uuid: event.uuid,
+            minimal_flag_called: event.minimal_flag_called,
+        }
+    }
+}
+
+/// An [`Event`] read back from the persistent spool.
+#[derive(Deserialize)]
+pub(crate) struct SpooledEvent {
+    event: String,
+    distinct_id: String,
+    #[serde(default)]
+    properties: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    groups: HashMap<String, String>,
+    timestamp: Option<NaiveDateTime>,
+    uuid: Uuid,
+    #[serde(default)]
+    minimal_flag_called: bool,
+}
+
+impl From<SpooledEvent> for Event {
+    fn from(spooled: SpooledEvent) -> Self {
+        Self {
+            event: spooled.event,
+            distinct_id: spooled.distinct_id,
+            properties: spooled.properties,
+            groups: spooled.groups,
+            timestamp: spooled.timestamp,
+            uuid: spooled.uuid,
+            minimal_flag_called: spooled.minimal_flag_called,
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use uuid::Uuid;
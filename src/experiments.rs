@@ -0,0 +1,177 @@
+//! Experiment assignments read from multivariate feature flags.
+//!
+//! An experiment in PostHog is a multivariate flag whose variants are the
+//! arms of the test, optionally preceded by a holdout group that keeps a
+//! share of users out of every arm. [`ExperimentAssignment`] is what a user
+//! was assigned to, and reading it with
+//! [`Client::get_experiment_variant`](crate::Client::get_experiment_variant)
+//! or [`FeatureFlagEvaluations::get_experiment`](crate::FeatureFlagEvaluations::get_experiment)
+//! records the exposure the experiment's results are computed from.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::Error;
+
+/// The reason code of a flag served to one of its holdout groups.
+pub(crate) const HOLDOUT_REASON_CODE: &str = "holdout_condition_value";
+
+/// The experiment arm a user was assigned to.
+///
+/// # Examples
+///
+/// ```
+/// # fn example(flags: &posthog_rs::FeatureFlagEvaluations) {
+/// let assignment = flags.get_experiment("checkout-redesign");
+/// match assignment.as_ref().and_then(|a| a.arm()) {
+///     Some("test") => { /* new checkout */ }
+///     _ => { /* control, holdout or not enrolled */ }
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ExperimentAssignment {
+    /// The experiment's feature flag key.
+    pub flag_key: String,
+    /// The variant served, including holdout variants. `None` when the flag
+    /// is disabled for the user or served `true` without a variant, so the
+    /// user takes no part in the experiment.
+    pub variant: Option<String>,
+    /// The payload configured for the served variant.
+    pub payload: Option<Value>,
+    /// Whether the user is in one of the experiment's holdout groups rather
+    /// than in one of its arms, as told by the `holdout_condition_value`
+    /// evaluation reason.
+    pub in_holdout: bool,
+    /// Why the flag evaluated to this variant, as reported by `/flags` or
+    /// local evaluation.
+    pub reason: Option<String>,
+}
+
+impl ExperimentAssignment {
+    pub(crate) fn new(
+        flag_key: &str,
+        variant: Option<String>,
+        payload: Option<Value>,
+        reason: Option<String>,
+        reason_code: Option<&str>,
+    ) -> Self {
+        let in_holdout = variant.is_some() && reason_code == Some(HOLDOUT_REASON_CODE);
+        Self {
+            flag_key: flag_key.to_string(),
+            variant,
+            payload,
+            in_holdout,
+            reason,
+        }
+    }
+
+    /// The experiment arm the user is in: the served variant, unless the
+    /// user is held out.
+    pub fn arm(&self) -> Option<&str> {
+        if self.in_holdout {
+            return None;
+        }
+        self.variant.as_deref()
+    }
+
+    /// Whether the user is in an arm of the experiment, as opposed to held
+    /// out or not enrolled.
+    pub fn is_enrolled(&self) -> bool {
+        self.arm().is_some()
+    }
+
+    /// The variant's payload deserialized into `T`, or `None` when the
+    /// variant has no payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] when the payload does not match `T`.
+    pub fn payload_as<T: DeserializeOwned>(&self) -> Result<Option<T>, Error> {
+        self.payload
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| {
+                Error::Serialization(format!(
+                    "Failed to parse payload of experiment '{}': {e}",
+                    self.flag_key
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn holdout_variants_are_not_experiment_arms() {
+        let held_out = ExperimentAssignment::new(
+            "exp",
+            Some("holdout-4".into()),
+            None,
+            None,
+            Some(HOLDOUT_REASON_CODE),
+        );
+        assert!(held_out.in_holdout);
+        assert_eq!(held_out.arm(), None);
+        assert!(!held_out.is_enrolled());
+
+        let enrolled = ExperimentAssignment::new(
+            "exp",
+            Some("test".into()),
+            None,
+            None,
+            Some("condition_match"),
+        );
+        assert!(!enrolled.in_holdout);
+        assert_eq!(enrolled.arm(), Some("test"));
+        assert!(enrolled.is_enrolled());
+
+        // A variant merely named like a holdout is still an arm.
+        let lookalike = ExperimentAssignment::new(
+            "exp",
+            Some("holdout-copy".into()),
+            None,
+            None,
+            Some("condition_match"),
+        );
+        assert!(!lookalike.in_holdout);
+        assert_eq!(lookalike.arm(), Some("holdout-copy"));
+
+        let excluded = ExperimentAssignment::new("exp", None, None, None, None);
+        assert!(!excluded.is_enrolled());
+    }
+
+    #[test]
+    fn payloads_deserialize_into_the_requested_type() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Banner {
+            headline: String,
+        }
+
+        let assignment = ExperimentAssignment::new(
+            "exp",
+            Some("test".into()),
+            Some(json!({ "headline": "Buy now" })),
+            None,
+            None,
+        );
+        assert_eq!(
+            assignment.payload_as::<Banner>().unwrap(),
+            Some(Banner {
+                headline: "Buy now".into()
+            })
+        );
+        assert!(assignment.payload_as::<Vec<u8>>().is_err());
+
+        let without_payload =
+            ExperimentAssignment::new("exp", Some("test".into()), None, None, None);
+        assert_eq!(without_payload.payload_as::<Banner>().unwrap(), None);
+    }
+}
@@ -16,7 +16,9 @@ use std::sync::{Arc, Mutex};
 
 use serde_json::{json, Value};
 
+use crate::experiments::ExperimentAssignment;
 use crate::feature_flags::FlagValue;
+use crate::flags_cache::FlagsCacheStatus;
 
 /// One evaluated flag inside a [`FeatureFlagEvaluations`] snapshot.
 ///
@@ -30,7 +32,12 @@ pub(crate) struct EvaluatedFlagRecord {
     pub id: Option<u64>,
     pub version: Option<u32>,
     pub reason: Option<String>,
+    /// The machine-readable reason code, such as `holdout_condition_value`.
+    pub reason_code: Option<String>,
     pub locally_evaluated: bool,
+    /// Set when the value came from the client's
+    /// [`FlagOverrides`](crate::FlagOverrides) rather than an evaluation.
+    pub overridden: bool,
     /// Server-reported experiment linkage for this flag. Tri-state: `Some(bool)`
     /// when reported, `None` when unknown. Drives `$feature_flag_has_experiment`
     /// and, with the gate below, event minimization.
@@ -97,6 +104,11 @@ pub struct EvaluateFlagsOptions {
     /// network call, [`only`](FeatureFlagEvaluations::only) trims which flags
     /// get attached to a captured event after evaluation.
     pub flag_keys: Option<Vec<String>>,
+    /// The device the flags are evaluated on. Sent to `/flags` and used by
+    /// local evaluation for flags that bucket on the device ID instead of the
+    /// distinct ID, so their value survives an anonymous user logging in.
+    /// Recorded as `$device_id` on `$feature_flag_called` events.
+    pub device_id: Option<String>,
 }
 
 /// A snapshot of evaluated feature flags for one `distinct_id`.
@@ -119,6 +131,8 @@ pub struct FeatureFlagEvaluations {
     evaluated_at: Option<i64>,
     errors_while_computing: bool,
     quota_limited: bool,
+    flags_cache_status: Option<FlagsCacheStatus>,
+    device_id: Option<String>,
     accessed: Mutex<HashSet<String>>,
 }
 
@@ -145,10 +159,25 @@ impl FeatureFlagEvaluations {
             evaluated_at,
             errors_while_computing,
             quota_limited,
+            flags_cache_status: None,
+            device_id: None,
             accessed: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Record the device the flags were evaluated on, for
+    /// `$feature_flag_called` events.
+    pub(crate) fn with_device_id(mut self, device_id: Option<String>) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    /// Record where the snapshot's `/flags` results came from.
+    pub(crate) fn with_flags_cache_status(mut self, status: Option<FlagsCacheStatus>) -> Self {
+        self.flags_cache_status = status;
+        self
+    }
+
     /// Construct an empty snapshot used when no `distinct_id` was resolvable.
     /// The empty `distinct_id` short-circuits event firing inside
     /// [`record_access`](Self::record_access).
@@ -207,6 +236,51 @@ impl FeatureFlagEvaluations {
         self.flags.get(key).and_then(|f| f.payload.clone())
     }
 
+    /// The experiment assignment for the multivariate flag `key`, or `None`
+    /// when the flag is not in the snapshot.
+    ///
+    /// Records the access and fires (deduplicated) `$feature_flag_called`,
+    /// the exposure event experiment results are computed from, so calling
+    /// this repeatedly for the same user and variant counts one exposure.
+    #[must_use]
+    pub fn get_experiment(&self, key: &str) -> Option<ExperimentAssignment> {
+        self.record_access(key);
+        let flag = self.flags.get(key)?;
+        let variant = if flag.enabled {
+            flag.variant.clone()
+        } else {
+            None
+        };
+        Some(ExperimentAssignment::new(
+            key,
+            variant,
+            flag.payload.clone(),
+            flag.reason.clone(),
+            flag.reason_code.as_deref(),
+        ))
+    }
+
+    /// Why `key` has the value it has in this snapshot — the reason reported by
+    /// `/flags` or local evaluation (such as `"Matched condition set 1"`), or
+    /// `"Overridden locally"` for a flag forced by
+    /// [`FlagOverrides`](crate::FlagOverrides).
+    ///
+    /// Like [`get_flag_payload`](Self::get_flag_payload), this does not count
+    /// as an access and does not fire any event.
+    #[must_use]
+    pub fn get_flag_reason(&self, key: &str) -> Option<&str> {
+        self.flags.get(key).and_then(|f| f.reason.as_deref())
+    }
+
+    /// Whether the client's [`FlagsCacheOptions`](crate::FlagsCacheOptions)
+    /// cache served this snapshot's `/flags` results, fresh or stale, or
+    /// requested them. `None` when no cache is configured or `/flags` was
+    /// not consulted.
+    #[must_use]
+    pub fn flags_cache_status(&self) -> Option<FlagsCacheStatus> {
+        self.flags_cache_status
+    }
+
     /// All flag keys present in this snapshot.
     #[must_use]
     pub fn keys(&self) -> Vec<String> {
@@ -295,6 +369,8 @@ impl FeatureFlagEvaluations {
             evaluated_at: self.evaluated_at,
             errors_while_computing: self.errors_while_computing,
             quota_limited: self.quota_limited,
+            flags_cache_status: self.flags_cache_status,
+            device_id: self.device_id.clone(),
             accessed: Mutex::new(self.snapshot_accessed()),
         }
     }
@@ -378,12 +454,19 @@ impl FeatureFlagEvaluations {
                     props.insert("$feature_flag_reason".into(), json!(reason));
                 }
             }
+            if flag.overridden {
+                props.insert("$feature_flag_overridden".into(), json!(true));
+            }
         }
 
         if let Some(request_id) = &self.request_id {
             props.insert("$feature_flag_request_id".into(), json!(request_id));
         }
 
+        if let Some(device_id) = &self.device_id {
+            props.insert("$device_id".into(), json!(device_id));
+        }
+
         if !locally_evaluated {
             if let Some(evaluated_at) = self.evaluated_at {
                 props.insert("$feature_flag_evaluated_at".into(), json!(evaluated_at));
@@ -423,6 +506,8 @@ impl std::fmt::Debug for FeatureFlagEvaluations {
             .field("evaluated_at", &self.evaluated_at)
             .field("errors_while_computing", &self.errors_while_computing)
             .field("quota_limited", &self.quota_limited)
+            .field("flags_cache_status", &self.flags_cache_status)
+            .field("device_id", &self.device_id)
             .finish_non_exhaustive()
     }
 }
@@ -481,7 +566,9 @@ mod tests {
             id: Some(42),
             version: Some(7),
             reason: Some("condition match".into()),
+            reason_code: None,
             locally_evaluated,
+            overridden: false,
             has_experiment: None,
             minimal_flag_called_events: false,
         }
@@ -529,6 +616,26 @@ mod tests {
         assert_eq!(props.get("$feature_flag_request_id"), Some(&json!("req-1")));
     }
 
+    #[test]
+    fn get_experiment_fires_exposure_with_the_variant() {
+        let host = Arc::new(RecordingHost::default());
+        let snap = build(
+            Arc::clone(&host) as Arc<dyn FeatureFlagEvaluationsHost>,
+            "u1",
+        );
+
+        let assignment = snap.get_experiment("alpha").unwrap();
+        assert_eq!(assignment.arm(), Some("test"));
+        assert_eq!(assignment.reason.as_deref(), Some("condition match"));
+        assert!(!snap.get_experiment("beta").unwrap().is_enrolled());
+        assert!(snap.get_experiment("missing").is_none());
+
+        let captured = host.captured.lock().unwrap();
+        assert_eq!(captured.len(), 3);
+        assert_eq!(captured[0].key, "alpha");
+        assert_eq!(captured[0].response, Some(FlagValue::String("test".into())));
+    }
+
     #[test]
     fn get_flag_payload_does_not_record_access_or_fire_event() {
         let host = Arc::new(RecordingHost::default());
@@ -557,6 +664,7 @@ mod tests {
             "gamma".into(),
             EvaluatedFlagRecord {
                 reason: Some("Evaluated locally".into()),
+                reason_code: None,
                 ..record("gamma", true, None, true)
             },
         );
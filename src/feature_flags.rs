@@ -1,24 +1,37 @@
+use crate::cohort_membership::CohortMembershipProvider;
+use crate::compiled_flags::{
+    CompiledCohort, CompiledFlag, Definitions, Environment, PropertyMatcher,
+};
 use chrono::{DateTime, NaiveDate, Utc};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Mutex, OnceLock};
 
-/// Global cache for compiled regexes to avoid recompilation on every flag evaluation
+/// Global cache for compiled regexes. The free `match_*` functions compile the
+/// definition they are given on every call; this keeps them from rebuilding
+/// the same regexes each time. [`FlagCache`](crate::FlagCache) snapshots own
+/// their regexes instead.
 static REGEX_CACHE: OnceLock<Mutex<HashMap<String, Option<Regex>>>> = OnceLock::new();
 
 /// Salt used for rollout percentage hashing. Intentionally empty to match PostHog's
 /// consistent hashing algorithm across all SDKs. This ensures the same user gets
 /// the same rollout decision regardless of which SDK evaluates the flag.
-const ROLLOUT_HASH_SALT: &str = "";
+pub(crate) const ROLLOUT_HASH_SALT: &str = "";
 
 /// Salt used for multivariate variant selection. Uses "variant" to ensure consistent
 /// variant assignment across all PostHog SDKs for the same user/flag combination.
-const VARIANT_HASH_SALT: &str = "variant";
+pub(crate) const VARIANT_HASH_SALT: &str = "variant";
 
-fn get_cached_regex(pattern: &str) -> Option<Regex> {
+/// Prefix of the holdout hash. Holdouts are shared between experiments, so
+/// unlike [`hash_key`] the holdout hash leaves out the flag key and a user
+/// is held out of every experiment using the same holdout.
+pub(crate) const HOLDOUT_HASH_PREFIX: &str = "holdout-";
+
+pub(crate) fn get_cached_regex(pattern: &str) -> Option<Regex> {
     let cache = REGEX_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
     let mut cache_guard = match cache.lock() {
         Ok(guard) => guard,
@@ -89,6 +102,24 @@ impl Default for FlagValue {
     }
 }
 
+impl From<bool> for FlagValue {
+    fn from(enabled: bool) -> Self {
+        FlagValue::Boolean(enabled)
+    }
+}
+
+impl From<&str> for FlagValue {
+    fn from(variant: &str) -> Self {
+        FlagValue::String(variant.to_string())
+    }
+}
+
+impl From<String> for FlagValue {
+    fn from(variant: String) -> Self {
+        FlagValue::String(variant)
+    }
+}
+
 /// A feature flag definition from PostHog.
 ///
 /// Contains all the information needed to evaluate whether a flag should be
@@ -108,10 +139,16 @@ pub struct FeatureFlag {
     /// the `$feature_flag_has_experiment` property and event minimization.
     #[serde(default)]
     pub has_experiment: Option<bool>,
+    /// What the flag's rollout and variant hashes bucket persons on:
+    /// `"device_id"` to keep anonymous and identified sessions on the same
+    /// device in the same bucket, or `None`/`"distinct_id"` for the distinct
+    /// ID. Group-targeted conditions always bucket on the group key.
+    #[serde(default)]
+    pub bucketing_identifier: Option<String>,
 }
 
 /// Targeting rules and configuration for a feature flag.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct FeatureFlagFilters {
     /// List of condition groups (evaluated with OR logic between groups)
     #[serde(default)]
@@ -133,13 +170,24 @@ pub struct FeatureFlagFilters {
     /// `false`, which preserves the legacy fall-through behavior.
     #[serde(default)]
     pub early_exit: bool,
+    /// Early access enrollment overrides. When the person has any property
+    /// the first super group filters on (e.g. `$feature_enrollment/<key>`),
+    /// that group alone decides the flag; otherwise evaluation continues with
+    /// the holdout and release conditions.
+    #[serde(default)]
+    pub super_groups: Option<Vec<FeatureFlagCondition>>,
+    /// Experiment holdouts, tried after super groups and before release
+    /// conditions. Users within the first holdout group's rollout are served
+    /// its `holdout-<id>` variant.
+    #[serde(default)]
+    pub holdout_groups: Option<Vec<FeatureFlagCondition>>,
 }
 
 /// A single condition group within a feature flag's targeting rules.
 ///
 /// All properties within a condition must match (AND logic), and the user
 /// must fall within the rollout percentage to be included.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FeatureFlagCondition {
     /// Property filters that must all match (AND logic)
     #[serde(default)]
@@ -158,7 +206,7 @@ pub struct FeatureFlagCondition {
 /// A property filter used in feature flag targeting.
 ///
 /// Supports various operators for matching user properties against expected values.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Property {
     /// The property key to match (e.g., "email", "country", "$feature/other-flag")
     pub key: String,
@@ -207,10 +255,13 @@ impl CohortDefinition {
         }
     }
 
-    /// Parse the properties from the JSON structure.
+    /// Parse the properties from the JSON structure into a flat list.
     ///
     /// PostHog cohort properties come in format:
     /// `{"type": "AND", "values": [{"type": "property", "key": "...", "value": "...", "operator": "..."}]}`.
+    #[deprecated(
+        note = "use `property_group` instead; the flat list drops OR groups, nested groups and negation"
+    )]
     pub fn parse_properties(&self) -> Vec<Property> {
         // If it's an array, treat it as direct property list
         if let Some(arr) = self.properties.as_array() {
@@ -250,6 +301,94 @@ impl CohortDefinition {
 
         Vec::new()
     }
+
+    /// Parse the properties into the boolean tree PostHog evaluates.
+    ///
+    /// An object such as `{"type": "OR", "values": [...]}` becomes a group
+    /// whose values are property filters or further groups; a property
+    /// filter with `"negation": true` is negated. A plain array of property
+    /// filters is an `AND` group. Entries that are neither a property filter
+    /// nor a group are skipped.
+    pub fn property_group(&self) -> PropertyGroup {
+        match &self.properties {
+            serde_json::Value::Array(values) => PropertyGroup {
+                group_type: PropertyGroupType::And,
+                values: values
+                    .iter()
+                    .filter_map(PropertyGroupValue::parse)
+                    .collect(),
+            },
+            value => PropertyGroup::parse(value).unwrap_or_default(),
+        }
+    }
+}
+
+/// How the values of a [`PropertyGroup`] combine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PropertyGroupType {
+    /// Every value must match.
+    #[default]
+    And,
+    /// At least one value must match.
+    Or,
+}
+
+/// A boolean tree of property filters, as PostHog defines cohorts.
+///
+/// An empty group matches everyone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropertyGroup {
+    /// How [`values`](Self::values) combine.
+    pub group_type: PropertyGroupType,
+    /// The filters and nested groups.
+    pub values: Vec<PropertyGroupValue>,
+}
+
+/// A member of a [`PropertyGroup`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyGroupValue {
+    /// A property filter. `negated` inverts whether it matches. A filter of
+    /// type `cohort` matches members of the cohort whose ID is its value.
+    Property {
+        /// The filter.
+        property: Property,
+        /// Whether the filter is negated.
+        negated: bool,
+    },
+    /// A nested group.
+    Group(PropertyGroup),
+}
+
+impl PropertyGroup {
+    /// Parse `{"type": "AND" | "OR", "values": [...]}`.
+    fn parse(value: &serde_json::Value) -> Option<Self> {
+        let values = value.get("values")?.as_array()?;
+        let group_type = match value.get("type").and_then(|t| t.as_str()) {
+            Some(t) if t.eq_ignore_ascii_case("or") => PropertyGroupType::Or,
+            _ => PropertyGroupType::And,
+        };
+        Some(Self {
+            group_type,
+            values: values
+                .iter()
+                .filter_map(PropertyGroupValue::parse)
+                .collect(),
+        })
+    }
+}
+
+impl PropertyGroupValue {
+    fn parse(value: &serde_json::Value) -> Option<Self> {
+        if let Some(group) = PropertyGroup::parse(value) {
+            return Some(PropertyGroupValue::Group(group));
+        }
+        let property = serde_json::from_value::<Property>(value.clone()).ok()?;
+        let negated = value
+            .get("negation")
+            .and_then(|n| n.as_bool())
+            .unwrap_or(false);
+        Some(PropertyGroupValue::Property { property, negated })
+    }
 }
 
 /// Context for evaluating properties that may depend on cohorts or other flags.
@@ -267,6 +406,10 @@ pub struct EvaluationContext<'a> {
     pub flags: &'a HashMap<String, FeatureFlag>,
     /// Distinct ID used for person-targeted flag bucketing.
     pub distinct_id: &'a str,
+    /// Device ID that person-targeted flags whose
+    /// [`bucketing_identifier`](FeatureFlag::bucketing_identifier) is
+    /// `"device_id"` bucket on. Without one they bucket on the distinct ID.
+    pub device_id: Option<&'a str>,
     /// Group keys for group-targeted flags, keyed by group type.
     pub groups: &'a HashMap<String, String>,
     /// Group properties for group-targeted flags, keyed by group type and then
@@ -274,17 +417,21 @@ pub struct EvaluationContext<'a> {
     pub group_properties: &'a HashMap<String, HashMap<String, serde_json::Value>>,
     /// Mapping from PostHog group type index to group type name.
     pub group_type_mapping: &'a HashMap<String, String>,
+    /// Asked about cohorts missing from [`cohorts`](Self::cohorts) or whose
+    /// filters cannot be evaluated with the given properties, such as static
+    /// and behavioral cohorts.
+    pub cohort_membership: Option<&'a dyn CohortMembershipProvider>,
 }
 
 /// Configuration for multivariate (A/B/n) feature flags.
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct MultivariateFilter {
     /// List of variants with their rollout percentages
     pub variants: Vec<MultivariateVariant>,
 }
 
 /// A single variant in a multivariate feature flag.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MultivariateVariant {
     /// Unique key for this variant (e.g., "control", "test", "variant-a")
     pub key: String,
@@ -359,15 +506,7 @@ impl FeatureFlagsResponse {
                 let mut payloads = HashMap::new();
 
                 for (key, detail) in flags {
-                    if detail.enabled {
-                        if let Some(variant) = detail.variant {
-                            feature_flags.insert(key.clone(), FlagValue::String(variant));
-                        } else {
-                            feature_flags.insert(key.clone(), FlagValue::Boolean(true));
-                        }
-                    } else {
-                        feature_flags.insert(key.clone(), FlagValue::Boolean(false));
-                    }
+                    feature_flags.insert(key.clone(), detail.value());
 
                     if let Some(metadata) = detail.metadata {
                         if let Some(payload) = metadata.payload {
@@ -408,7 +547,7 @@ pub struct FlagDetail {
 }
 
 /// Explains why a feature flag evaluated to a particular value.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FlagReason {
     /// Reason code (e.g., "condition_match", "out_of_rollout_bound")
     pub code: String,
@@ -420,6 +559,86 @@ pub struct FlagReason {
     pub description: Option<String>,
 }
 
+impl FlagDetail {
+    /// The flag's value: `String(variant)` for a matched multivariate flag,
+    /// otherwise `Boolean(enabled)`.
+    pub fn value(&self) -> FlagValue {
+        match (&self.variant, self.enabled) {
+            (Some(variant), true) => FlagValue::String(variant.clone()),
+            (_, enabled) => FlagValue::Boolean(enabled),
+        }
+    }
+
+    /// Detail for a locally evaluated flag.
+    pub(crate) fn local(key: &str, value: FlagValue, reason: FlagReason) -> Self {
+        let (enabled, variant) = match value {
+            FlagValue::Boolean(enabled) => (enabled, None),
+            FlagValue::String(variant) => (true, Some(variant)),
+        };
+        Self {
+            key: key.to_string(),
+            enabled,
+            variant,
+            reason: Some(reason),
+            metadata: None,
+        }
+    }
+}
+
+impl FlagReason {
+    fn new(code: &str, condition_index: Option<usize>, description: String) -> Self {
+        Self {
+            code: code.to_string(),
+            condition_index,
+            description: Some(description),
+        }
+    }
+
+    pub(crate) fn condition_match(index: usize) -> Self {
+        Self::new(
+            "condition_match",
+            Some(index),
+            format!("Matched condition set {}", index + 1),
+        )
+    }
+
+    pub(crate) fn out_of_rollout_bound(index: usize) -> Self {
+        Self::new(
+            "out_of_rollout_bound",
+            Some(index),
+            "Out of rollout bound".to_string(),
+        )
+    }
+
+    pub(crate) fn no_condition_match() -> Self {
+        Self::new(
+            "no_condition_match",
+            None,
+            "No matching condition set".to_string(),
+        )
+    }
+
+    pub(crate) fn flag_disabled() -> Self {
+        Self::new("flag_disabled", None, "Flag is disabled".to_string())
+    }
+
+    pub(crate) fn super_condition_value() -> Self {
+        Self::new(
+            "super_condition_value",
+            Some(0),
+            "Super condition value".to_string(),
+        )
+    }
+
+    pub(crate) fn holdout_condition_value() -> Self {
+        Self::new(
+            "holdout_condition_value",
+            None,
+            "Holdout condition value".to_string(),
+        )
+    }
+}
+
 /// Metadata about a feature flag from the PostHog server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlagMetadata {
@@ -447,91 +666,49 @@ const LONG_SCALE: f64 = 0xFFFFFFFFFFFFFFFu64 as f64; // Must be exactly 15 F's t
 /// key, distinct_id, and salt combination. This ensures users get consistent
 /// flag values across requests.
 pub fn hash_key(key: &str, distinct_id: &str, salt: &str) -> f64 {
-    let hash_key = format!("{key}.{distinct_id}{salt}");
     let mut hasher = Sha1::new();
-    hasher.update(hash_key.as_bytes());
+    hasher.update(key.as_bytes());
+    hasher.update(b".");
+    hasher.update(distinct_id.as_bytes());
+    hasher.update(salt.as_bytes());
+    digest_fraction(hasher)
+}
+
+/// The holdout hash of `bucketing_id`, in the range [0, 1).
+pub(crate) fn holdout_hash(bucketing_id: &str) -> f64 {
+    let mut hasher = Sha1::new();
+    hasher.update(HOLDOUT_HASH_PREFIX.as_bytes());
+    hasher.update(bucketing_id.as_bytes());
+    digest_fraction(hasher)
+}
+
+fn digest_fraction(hasher: Sha1) -> f64 {
     let result = hasher.finalize();
-    let hex_str = format!("{result:x}");
-    let hash_val = u64::from_str_radix(&hex_str[..15], 16).unwrap_or(0);
+    // The first 15 hex digits of the digest, i.e. its top 60 bits.
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&result[..8]);
+    let hash_val = u64::from_be_bytes(prefix) >> 4;
     hash_val as f64 / LONG_SCALE
 }
 
-/// Determine which variant a user should see for a multivariate flag.
+/// Determine which of a multivariate flag's variants a user should see.
 ///
-/// Uses consistent hashing to assign users to variants based on their
-/// rollout percentages. Returns `None` if the flag has no variants or
-/// the user doesn't fall into any variant bucket.
-pub fn get_matching_variant(flag: &FeatureFlag, distinct_id: &str) -> Option<String> {
-    let hash_value = hash_key(&flag.key, distinct_id, VARIANT_HASH_SALT);
-    let variants = flag.filters.multivariate.as_ref()?.variants.as_slice();
-
+/// `hash_value` is the user's [`hash_key`] for the flag, salted with
+/// [`VARIANT_HASH_SALT`]; variants split the hash range by their rollout
+/// percentages. Returns `None` if the flag has no variants or the user
+/// doesn't fall into any variant bucket.
+pub(crate) fn matching_variant(variants: &[MultivariateVariant], hash_value: f64) -> Option<&str> {
     let mut value_min = 0.0;
     for variant in variants {
         let value_max = value_min + variant.rollout_percentage / 100.0;
         if hash_value >= value_min && hash_value < value_max {
-            return Some(variant.key.clone());
+            return Some(&variant.key);
         }
         value_min = value_max;
     }
     None
 }
 
-/// Result of resolving a condition's effective bucketing + properties.
-enum ConditionTarget<'a> {
-    /// Use these for bucketing and property matching.
-    Use {
-        bucketing: String,
-        properties: &'a HashMap<String, serde_json::Value>,
-    },
-    /// Skip this condition (group type unknown or required group not passed in).
-    Skip,
-    /// Required group properties not provided — try other conditions, surface
-    /// inconclusive if nothing else matches.
-    Inconclusive,
-}
-
-/// Resolve effective bucketing id and properties for a single condition based on
-/// the (possibly per-condition) aggregation group type index. Pure-person flags
-/// fall through with `distinct_id` and `person_properties`. Group conditions
-/// (either flag-level or per-condition aggregation) require the corresponding
-/// group key and group properties to be present.
-fn resolve_condition_target<'a>(
-    condition: &FeatureFlagCondition,
-    flag_aggregation: Option<i32>,
-    distinct_id: &str,
-    person_properties: &'a HashMap<String, serde_json::Value>,
-    groups: &HashMap<String, String>,
-    group_properties: &'a HashMap<String, HashMap<String, serde_json::Value>>,
-    group_type_mapping: &HashMap<String, String>,
-) -> ConditionTarget<'a> {
-    // Per-condition aggregation falls back to the flag-level value when absent.
-    // The two together drive whether this condition is person- or group-targeted.
-    let effective_aggregation = condition.aggregation_group_type_index.or(flag_aggregation);
-
-    match effective_aggregation {
-        None => ConditionTarget::Use {
-            bucketing: distinct_id.to_string(),
-            properties: person_properties,
-        },
-        Some(idx) => {
-            let key = idx.to_string();
-            let Some(group_type) = group_type_mapping.get(&key) else {
-                return ConditionTarget::Skip;
-            };
-            let Some(group_key) = groups.get(group_type) else {
-                return ConditionTarget::Skip;
-            };
-            let Some(props) = group_properties.get(group_type) else {
-                return ConditionTarget::Inconclusive;
-            };
-            ConditionTarget::Use {
-                bucketing: group_key.clone(),
-                properties: props,
-            }
-        }
-    }
-}
-
 /// Evaluate a feature flag definition against person and optional group
 /// context.
 ///
@@ -565,128 +742,21 @@ pub fn match_feature_flag(
     group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
     group_type_mapping: &HashMap<String, String>,
 ) -> Result<FlagValue, InconclusiveMatchError> {
-    if !flag.active {
-        return Ok(FlagValue::Boolean(false));
-    }
-
-    let conditions = &flag.filters.groups;
-    let flag_aggregation = flag.filters.aggregation_group_type_index;
-
-    // Sort conditions to evaluate variant overrides first
-    let mut sorted_conditions = conditions.clone();
-    sorted_conditions.sort_by_key(|c| if c.variant.is_some() { 0 } else { 1 });
-
-    let mut is_inconclusive = false;
-
-    for condition in sorted_conditions {
-        let (effective_bucketing, effective_properties) = match resolve_condition_target(
-            &condition,
-            flag_aggregation,
-            distinct_id,
-            person_properties,
-            groups,
-            group_properties,
-            group_type_mapping,
-        ) {
-            ConditionTarget::Use {
-                bucketing,
-                properties,
-            } => (bucketing, properties),
-            ConditionTarget::Skip => continue,
-            ConditionTarget::Inconclusive => {
-                is_inconclusive = true;
-                continue;
-            }
-        };
-
-        match is_condition_match(flag, &effective_bucketing, &condition, effective_properties) {
-            Ok(ConditionMatch::Match) => {
-                if let Some(variant_override) = &condition.variant {
-                    // Check if variant is valid
-                    if let Some(ref multivariate) = flag.filters.multivariate {
-                        let valid_variants: Vec<String> = multivariate
-                            .variants
-                            .iter()
-                            .map(|v| v.key.clone())
-                            .collect();
-
-                        if valid_variants.contains(variant_override) {
-                            return Ok(FlagValue::String(variant_override.clone()));
-                        }
-                    }
-                }
-
-                // Try to get matching variant or return true
-                if let Some(variant) = get_matching_variant(flag, &effective_bucketing) {
-                    return Ok(FlagValue::String(variant));
-                }
-                return Ok(FlagValue::Boolean(true));
-            }
-            Ok(ConditionMatch::OutOfRolloutBound) => {
-                // The user's properties matched this group but the rollout
-                // excluded them. With early_exit enabled the flag is
-                // definitively disabled; otherwise fall through to later groups.
-                // Only short-circuit when no prior group was inconclusive — an
-                // inconclusive result means we can't evaluate locally and must
-                // fall back to the server, so it takes priority over early_exit.
-                if flag.filters.early_exit && !is_inconclusive {
-                    return Ok(FlagValue::Boolean(false));
-                }
-            }
-            Ok(ConditionMatch::NoMatch) => continue,
-            Err(_) => {
-                is_inconclusive = true;
-            }
-        }
-    }
-
-    if is_inconclusive {
-        return Err(InconclusiveMatchError::new(
-            "Can't determine if feature flag is enabled or not with given properties",
-        ));
-    }
-
-    Ok(FlagValue::Boolean(false))
-}
-
-/// Outcome of evaluating a single condition group, mirroring the PostHog Rust
-/// evaluation engine's tri-state so the local loop can distinguish a
-/// property-filter miss (always fall through) from a rollout exclusion (which
-/// can short-circuit when `early_exit` is enabled).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ConditionMatch {
-    /// Property filters matched (or there were none) and the rollout included
-    /// the user — the flag matches.
-    Match,
-    /// A property filter did not match — always continue to the next group.
-    NoMatch,
-    /// Property filters matched (or there were none) but the rollout excluded
-    /// the user.
-    OutOfRolloutBound,
-}
-
-fn is_condition_match(
-    flag: &FeatureFlag,
-    bucketing_id: &str,
-    condition: &FeatureFlagCondition,
-    properties: &HashMap<String, serde_json::Value>,
-) -> Result<ConditionMatch, InconclusiveMatchError> {
-    // Check properties first
-    for prop in &condition.properties {
-        if !match_property(prop, properties)? {
-            return Ok(ConditionMatch::NoMatch);
-        }
-    }
-
-    // If all properties match (or no properties), check rollout percentage
-    if let Some(rollout_percentage) = condition.rollout_percentage {
-        let hash_value = hash_key(&flag.key, bucketing_id, ROLLOUT_HASH_SALT);
-        if hash_value > (rollout_percentage / 100.0) {
-            return Ok(ConditionMatch::OutOfRolloutBound);
-        }
-    }
-
-    Ok(ConditionMatch::Match)
+    let (cohorts, flags) = (HashMap::new(), HashMap::new());
+    let ctx = EvaluationContext {
+        cohorts: &cohorts,
+        flags: &flags,
+        distinct_id,
+        device_id: None,
+        groups,
+        group_properties,
+        group_type_mapping,
+        cohort_membership: None,
+    };
+    let env = Environment::new(&ctx, distinct_id, groups, group_properties).without_references();
+    CompiledFlag::new(flag, get_cached_regex)
+        .evaluate(person_properties, &env)
+        .map(|outcome| outcome.value())
 }
 
 /// Match a feature flag with full context (cohorts, other flags).
@@ -696,6 +766,9 @@ fn is_condition_match(
 /// properties are looked up from `ctx.group_properties` when a condition (or
 /// the flag itself) targets a group via `aggregation_group_type_index`.
 ///
+/// Use [`match_feature_flag_detailed`] to also learn why the flag evaluated
+/// to its value.
+///
 /// # Errors
 ///
 /// Returns [`InconclusiveMatchError`] when local evaluation cannot determine a
@@ -706,119 +779,47 @@ pub fn match_feature_flag_with_context(
     person_properties: &HashMap<String, serde_json::Value>,
     ctx: &EvaluationContext,
 ) -> Result<FlagValue, InconclusiveMatchError> {
-    if !flag.active {
-        return Ok(FlagValue::Boolean(false));
-    }
-
-    let conditions = &flag.filters.groups;
-    let flag_aggregation = flag.filters.aggregation_group_type_index;
-
-    // Sort conditions to evaluate variant overrides first
-    let mut sorted_conditions = conditions.clone();
-    sorted_conditions.sort_by_key(|c| if c.variant.is_some() { 0 } else { 1 });
-
-    let mut is_inconclusive = false;
-
-    for condition in sorted_conditions {
-        let (effective_bucketing, effective_properties) = match resolve_condition_target(
-            &condition,
-            flag_aggregation,
-            ctx.distinct_id,
-            person_properties,
-            ctx.groups,
-            ctx.group_properties,
-            ctx.group_type_mapping,
-        ) {
-            ConditionTarget::Use {
-                bucketing,
-                properties,
-            } => (bucketing, properties),
-            ConditionTarget::Skip => continue,
-            ConditionTarget::Inconclusive => {
-                is_inconclusive = true;
-                continue;
-            }
-        };
-
-        match is_condition_match_with_context(
-            flag,
-            &effective_bucketing,
-            &condition,
-            effective_properties,
-            ctx,
-        ) {
-            Ok(ConditionMatch::Match) => {
-                if let Some(variant_override) = &condition.variant {
-                    // Check if variant is valid
-                    if let Some(ref multivariate) = flag.filters.multivariate {
-                        let valid_variants: Vec<String> = multivariate
-                            .variants
-                            .iter()
-                            .map(|v| v.key.clone())
-                            .collect();
-
-                        if valid_variants.contains(variant_override) {
-                            return Ok(FlagValue::String(variant_override.clone()));
-                        }
-                    }
-                }
-
-                // Try to get matching variant or return true
-                if let Some(variant) = get_matching_variant(flag, &effective_bucketing) {
-                    return Ok(FlagValue::String(variant));
-                }
-                return Ok(FlagValue::Boolean(true));
-            }
-            Ok(ConditionMatch::OutOfRolloutBound) => {
-                // The user's properties matched this group but the rollout
-                // excluded them. With early_exit enabled the flag is
-                // definitively disabled; otherwise fall through to later groups.
-                // Only short-circuit when no prior group was inconclusive — an
-                // inconclusive result means we can't evaluate locally and must
-                // fall back to the server, so it takes priority over early_exit.
-                if flag.filters.early_exit && !is_inconclusive {
-                    return Ok(FlagValue::Boolean(false));
-                }
-            }
-            Ok(ConditionMatch::NoMatch) => continue,
-            Err(_) => {
-                is_inconclusive = true;
-            }
-        }
-    }
-
-    if is_inconclusive {
-        return Err(InconclusiveMatchError::new(
-            "Can't determine if feature flag is enabled or not with given properties",
-        ));
-    }
-
-    Ok(FlagValue::Boolean(false))
+    match_feature_flag_detailed(flag, person_properties, ctx).map(|detail| detail.value())
 }
 
-fn is_condition_match_with_context(
+/// Match a feature flag with full context, like
+/// [`match_feature_flag_with_context`], and report why it evaluated to its
+/// value the way `/flags?v=2` does.
+///
+/// The returned [`FlagDetail`] carries a [`FlagReason`] with one of the
+/// server's reason codes:
+///
+/// - `condition_match`: the condition set at `condition_index` matched,
+/// - `out_of_rollout_bound`: the condition set at `condition_index` matched
+///   the properties but its rollout percentage excluded the user,
+/// - `no_condition_match`: no condition set matched,
+/// - `flag_disabled`: the flag is inactive,
+/// - `super_condition_value`: the person has a property of the flag's first
+///   super group, an early access enrollment override, which decided the
+///   flag on its own,
+/// - `holdout_condition_value`: the user is within the first holdout group's
+///   rollout and is served its holdout variant.
+///
+/// `condition_index` is the position of the condition set in the flag's
+/// `filters.groups`, and `0` for super conditions. Local definitions carry no flag ID or version, so
+/// `metadata` is `None`.
+///
+/// # Errors
+///
+/// Returns [`InconclusiveMatchError`] when local evaluation cannot determine a
+/// result with the provided context.
+#[must_use = "feature flag evaluation result should be used"]
+pub fn match_feature_flag_detailed(
     flag: &FeatureFlag,
-    bucketing_id: &str,
-    condition: &FeatureFlagCondition,
-    properties: &HashMap<String, serde_json::Value>,
+    person_properties: &HashMap<String, serde_json::Value>,
     ctx: &EvaluationContext,
-) -> Result<ConditionMatch, InconclusiveMatchError> {
-    // Check properties first (using context-aware matching for cohorts/flag dependencies)
-    for prop in &condition.properties {
-        if !match_property_with_context(prop, properties, ctx)? {
-            return Ok(ConditionMatch::NoMatch);
-        }
-    }
-
-    // If all properties match (or no properties), check rollout percentage
-    if let Some(rollout_percentage) = condition.rollout_percentage {
-        let hash_value = hash_key(&flag.key, bucketing_id, ROLLOUT_HASH_SALT);
-        if hash_value > (rollout_percentage / 100.0) {
-            return Ok(ConditionMatch::OutOfRolloutBound);
-        }
-    }
-
-    Ok(ConditionMatch::Match)
+) -> Result<FlagDetail, InconclusiveMatchError> {
+    let env = Environment::new(ctx, ctx.distinct_id, ctx.groups, ctx.group_properties)
+        .with_device_id(ctx.device_id)
+        .with_cohort_membership(ctx.cohort_membership);
+    CompiledFlag::new(flag, get_cached_regex)
+        .evaluate(person_properties, &env)
+        .map(|outcome| outcome.detail(&flag.key))
 }
 
 /// Match a property with additional context for cohorts and flag dependencies.
@@ -836,133 +837,40 @@ pub fn match_property_with_context(
     properties: &HashMap<String, serde_json::Value>,
     ctx: &EvaluationContext,
 ) -> Result<bool, InconclusiveMatchError> {
-    // Check if this is a cohort membership check
-    if property.property_type.as_deref() == Some("cohort") {
-        return match_cohort_property(property, properties, ctx);
-    }
-
-    // Check if this is a flag dependency check
-    if property.key.starts_with("$feature/") {
-        return match_flag_dependency_property(property, ctx);
-    }
-
-    // Fall back to regular property matching
-    match_property(property, properties)
+    let env = Environment::new(ctx, ctx.distinct_id, ctx.groups, ctx.group_properties)
+        .with_device_id(ctx.device_id)
+        .with_cohort_membership(ctx.cohort_membership);
+    PropertyMatcher::new(property, get_cached_regex).matches_in(properties, &env)
 }
 
-/// Evaluate cohort membership
-fn match_cohort_property(
-    property: &Property,
-    properties: &HashMap<String, serde_json::Value>,
-    ctx: &EvaluationContext,
-) -> Result<bool, InconclusiveMatchError> {
-    let cohort_id = property
-        .value
-        .as_str()
-        .ok_or_else(|| InconclusiveMatchError::new("Cohort ID must be a string"))?;
-
-    let cohort = ctx.cohorts.get(cohort_id).ok_or_else(|| {
-        InconclusiveMatchError::new(&format!("Cohort '{}' not found in local cache", cohort_id))
-    })?;
-
-    // Parse and evaluate all cohort properties against the user's properties
-    let cohort_properties = cohort.parse_properties();
-    let mut is_in_cohort = true;
-    for cohort_prop in &cohort_properties {
-        match match_property(cohort_prop, properties) {
-            Ok(true) => continue,
-            Ok(false) => {
-                is_in_cohort = false;
-                break;
-            }
-            Err(e) => {
-                // If we can't evaluate a cohort property, the cohort membership is inconclusive
-                return Err(InconclusiveMatchError::new(&format!(
-                    "Cannot evaluate cohort '{}' property '{}': {}",
-                    cohort_id, cohort_prop.key, e.message
-                )));
-            }
-        }
+/// Definitions referenced from an [`EvaluationContext`] are compiled as they
+/// are looked up.
+impl Definitions for EvaluationContext<'_> {
+    fn group_type(&self, index: i32) -> Option<&str> {
+        self.group_type_mapping
+            .get(&index.to_string())
+            .map(String::as_str)
     }
 
-    // Handle "in" vs "not_in" operator
-    Ok(match property.operator.as_str() {
-        "in" => is_in_cohort,
-        "not_in" => !is_in_cohort,
-        op => {
-            return Err(InconclusiveMatchError::new(&format!(
-                "Unknown cohort operator: {}",
-                op
-            )));
-        }
-    })
-}
-
-/// Evaluate flag dependency
-fn match_flag_dependency_property(
-    property: &Property,
-    ctx: &EvaluationContext,
-) -> Result<bool, InconclusiveMatchError> {
-    // Extract flag key from "$feature/flag-key"
-    let flag_key = property
-        .key
-        .strip_prefix("$feature/")
-        .ok_or_else(|| InconclusiveMatchError::new("Invalid flag dependency format"))?;
-
-    let flag = ctx.flags.get(flag_key).ok_or_else(|| {
-        InconclusiveMatchError::new(&format!("Flag '{}' not found in local cache", flag_key))
-    })?;
-
-    // Evaluate the dependent flag for this user (with empty properties to avoid recursion issues).
-    // Group context flows through from the outer ctx so dependent group/mixed flags can resolve.
-    let empty_props = HashMap::new();
-    let flag_value = match_feature_flag(
-        flag,
-        ctx.distinct_id,
-        &empty_props,
-        ctx.groups,
-        ctx.group_properties,
-        ctx.group_type_mapping,
-    )?;
-
-    // Compare the flag value with the expected value
-    let expected = &property.value;
-
-    let matches = match (&flag_value, expected) {
-        (FlagValue::Boolean(b), serde_json::Value::Bool(expected_b)) => b == expected_b,
-        (FlagValue::String(s), serde_json::Value::String(expected_s)) => {
-            s.eq_ignore_ascii_case(expected_s)
-        }
-        (FlagValue::Boolean(true), serde_json::Value::String(s)) => {
-            // Flag is enabled (boolean true) but we're checking for a specific variant
-            // This should not match
-            s.is_empty() || s == "true"
-        }
-        (FlagValue::Boolean(false), serde_json::Value::String(s)) => s.is_empty() || s == "false",
-        (FlagValue::String(s), serde_json::Value::Bool(true)) => {
-            // Flag returns a variant string, checking for "enabled" (any variant is enabled)
-            !s.is_empty()
-        }
-        (FlagValue::String(_), serde_json::Value::Bool(false)) => false,
-        _ => false,
-    };
+    fn cohort(&self, id: &str) -> Option<Cow<'_, CompiledCohort>> {
+        let cohort = self.cohorts.get(id)?;
+        Some(Cow::Owned(CompiledCohort::new(cohort, get_cached_regex)))
+    }
 
-    // Handle different operators
-    Ok(match property.operator.as_str() {
-        "exact" => matches,
-        "is_not" => !matches,
-        op => {
-            return Err(InconclusiveMatchError::new(&format!(
-                "Unknown flag dependency operator: {}",
-                op
-            )));
-        }
-    })
+    fn flag(&self, key: &str) -> Option<Cow<'_, CompiledFlag>> {
+        let flag = self.flags.get(key)?;
+        Some(Cow::Owned(CompiledFlag::new(flag, get_cached_regex)))
+    }
 }
 
 /// Parse a relative date string like "-7d", "-24h", "-2w", "-3m", "-1y"
 /// Returns the DateTime<Utc> that the relative date represents
 fn parse_relative_date(value: &str) -> Option<DateTime<Utc>> {
+    parse_relative_duration(value).map(|duration| Utc::now() - duration)
+}
+
+/// Parse how long ago a relative date string like "-7d" lies.
+pub(crate) fn parse_relative_duration(value: &str) -> Option<chrono::Duration> {
     let value = value.trim();
     // Need at least 3 chars: "-", digit(s), and unit (e.g., "-7d")
     if value.len() < 3 || !value.starts_with('-') {
@@ -981,11 +889,11 @@ fn parse_relative_date(value: &str) -> Option<DateTime<Utc>> {
         _ => return None,
     };
 
-    Some(Utc::now() - duration)
+    Some(duration)
 }
 
 /// Parse a date value from a string (ISO date, ISO datetime, or relative date)
-fn parse_date_value(value: &serde_json::Value) -> Option<DateTime<Utc>> {
+pub(crate) fn parse_date_value(value: &serde_json::Value) -> Option<DateTime<Utc>> {
     let date_str = value.as_str()?;
 
     // Try relative date first (e.g., "-7d")
@@ -995,6 +903,11 @@ fn parse_date_value(value: &serde_json::Value) -> Option<DateTime<Utc>> {
         }
     }
 
+    parse_absolute_date(date_str)
+}
+
+/// Parse an ISO date or datetime string.
+pub(crate) fn parse_absolute_date(date_str: &str) -> Option<DateTime<Utc>> {
     // Try ISO datetime with timezone (e.g., "2024-06-15T10:30:00Z")
     if let Ok(dt) = DateTime::parse_from_rfc3339(date_str) {
         return Some(dt.with_timezone(&Utc));
@@ -1013,7 +926,7 @@ fn parse_date_value(value: &serde_json::Value) -> Option<DateTime<Utc>> {
 }
 
 /// A parsed semantic version as (major, minor, patch)
-type SemverTuple = (u64, u64, u64);
+pub(crate) type SemverTuple = (u64, u64, u64);
 
 /// Parse a semantic version string into a (major, minor, patch) tuple.
 ///
@@ -1026,7 +939,7 @@ type SemverTuple = (u64, u64, u64);
 /// 6. Ignore extra components beyond the third (e.g., "1.2.3.4" → (1, 2, 3))
 /// 7. Return None for invalid input (empty string, non-numeric parts, leading dot,
 ///    or numeric components with leading zeros per semver 2.0.0 §2)
-fn parse_semver(value: &str) -> Option<SemverTuple> {
+pub(crate) fn parse_semver(value: &str) -> Option<SemverTuple> {
     let value = value.trim();
     if value.is_empty() {
         return None;
@@ -1053,14 +966,10 @@ fn parse_semver(value: &str) -> Option<SemverTuple> {
     }
 
     // Split on dots and parse components
-    let parts: Vec<&str> = value.split('.').collect();
-    if parts.is_empty() {
-        return None;
-    }
-
-    let major = parse_semver_numeric(parts.first()?)?;
-    let minor = parts.get(1).map_or(Some(0), |s| parse_semver_numeric(s))?;
-    let patch = parts.get(2).map_or(Some(0), |s| parse_semver_numeric(s))?;
+    let mut parts = value.split('.');
+    let major = parse_semver_numeric(parts.next()?)?;
+    let minor = parts.next().map_or(Some(0), parse_semver_numeric)?;
+    let patch = parts.next().map_or(Some(0), parse_semver_numeric)?;
 
     Some((major, minor, patch))
 }
@@ -1081,7 +990,7 @@ fn parse_semver_numeric(part: &str) -> Option<u64> {
 
 /// Parse a wildcard pattern like "1.*" or "1.2.*" and return (lower_bound, upper_bound)
 /// Returns None if the pattern is invalid
-fn parse_semver_wildcard(pattern: &str) -> Option<(SemverTuple, SemverTuple)> {
+pub(crate) fn parse_semver_wildcard(pattern: &str) -> Option<(SemverTuple, SemverTuple)> {
     let pattern = pattern.trim();
     if pattern.is_empty() {
         return None;
@@ -1115,7 +1024,7 @@ fn parse_semver_wildcard(pattern: &str) -> Option<(SemverTuple, SemverTuple)> {
 }
 
 /// Compute bounds for tilde range: ~X.Y.Z means >=X.Y.Z and <X.(Y+1).0
-fn compute_tilde_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
+pub(crate) fn compute_tilde_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
     let (major, minor, patch) = version;
     ((major, minor, patch), (major, minor + 1, 0))
 }
@@ -1124,7 +1033,7 @@ fn compute_tilde_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
 /// - ^X.Y.Z where X > 0: >=X.Y.Z <(X+1).0.0
 /// - ^0.Y.Z where Y > 0: >=0.Y.Z <0.(Y+1).0
 /// - ^0.0.Z: >=0.0.Z <0.0.(Z+1)
-fn compute_caret_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
+pub(crate) fn compute_caret_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
     let (major, minor, patch) = version;
     if major > 0 {
         ((major, minor, patch), (major + 1, 0, 0))
@@ -1135,7 +1044,7 @@ fn compute_caret_bounds(version: SemverTuple) -> (SemverTuple, SemverTuple) {
     }
 }
 
-fn parse_target_semver(
+pub(crate) fn parse_target_semver(
     target_value: &serde_json::Value,
 ) -> Result<SemverTuple, InconclusiveMatchError> {
     let target_str = value_to_string(target_value);
@@ -1147,221 +1056,33 @@ fn parse_target_semver(
     })
 }
 
-fn match_property(
-    property: &Property,
-    properties: &HashMap<String, serde_json::Value>,
-) -> Result<bool, InconclusiveMatchError> {
-    let value = match properties.get(&property.key) {
-        Some(v) => v,
-        None => {
-            // Handle is_not_set operator
-            if property.operator == "is_not_set" {
-                return Ok(true);
-            }
-            // Handle is_set operator
-            if property.operator == "is_set" {
-                return Ok(false);
-            }
-            // For other operators, missing property is inconclusive
-            return Err(InconclusiveMatchError::new(&format!(
-                "Property '{}' not found in provided properties",
-                property.key
-            )));
-        }
-    };
-
-    let parse_property_semver = || {
-        let prop_str = value_to_string(value);
-        parse_semver(&prop_str).ok_or_else(|| {
-            InconclusiveMatchError::new(&format!(
-                "Unable to parse property semver value for '{}': {:?}",
-                property.key, value
-            ))
-        })
-    };
-    let parse_semver_operands = || {
-        Ok((
-            parse_property_semver()?,
-            parse_target_semver(&property.value)?,
-        ))
-    };
-
-    Ok(match property.operator.as_str() {
-        "exact" => {
-            if property.value.is_array() {
-                if let Some(arr) = property.value.as_array() {
-                    for val in arr {
-                        if compare_values(val, value) {
-                            return Ok(true);
-                        }
-                    }
-                    return Ok(false);
-                }
-            }
-            compare_values(&property.value, value)
-        }
-        "is_not" => {
-            if property.value.is_array() {
-                if let Some(arr) = property.value.as_array() {
-                    for val in arr {
-                        if compare_values(val, value) {
-                            return Ok(false);
-                        }
-                    }
-                    return Ok(true);
-                }
-            }
-            !compare_values(&property.value, value)
-        }
-        "is_set" => true,      // We already know the property exists
-        "is_not_set" => false, // We already know the property exists
-        "icontains" => {
-            let prop_str = value_to_string(value);
-            let search_str = value_to_string(&property.value);
-            prop_str.to_lowercase().contains(&search_str.to_lowercase())
-        }
-        "not_icontains" => {
-            let prop_str = value_to_string(value);
-            let search_str = value_to_string(&property.value);
-            !prop_str.to_lowercase().contains(&search_str.to_lowercase())
-        }
-        "regex" => {
-            let prop_str = value_to_string(value);
-            let regex_str = value_to_string(&property.value);
-            get_cached_regex(&regex_str)
-                .map(|re| re.is_match(&prop_str))
-                .unwrap_or(false)
-        }
-        "not_regex" => {
-            let prop_str = value_to_string(value);
-            let regex_str = value_to_string(&property.value);
-            get_cached_regex(&regex_str)
-                .map(|re| !re.is_match(&prop_str))
-                .unwrap_or(true)
-        }
-        "gt" | "gte" | "lt" | "lte" => compare_numeric(&property.operator, &property.value, value),
-        "is_date_before" | "is_date_after" => {
-            let target_date = parse_date_value(&property.value).ok_or_else(|| {
-                InconclusiveMatchError::new(&format!(
-                    "Unable to parse target date value: {:?}",
-                    property.value
-                ))
-            })?;
-
-            let prop_date = parse_date_value(value).ok_or_else(|| {
-                InconclusiveMatchError::new(&format!(
-                    "Unable to parse property date value for '{}': {:?}",
-                    property.key, value
-                ))
-            })?;
-
-            if property.operator == "is_date_before" {
-                prop_date < target_date
-            } else {
-                prop_date > target_date
-            }
-        }
-        // Semver comparison operators
-        "semver_eq" | "semver_neq" | "semver_gt" | "semver_gte" | "semver_lt" | "semver_lte" => {
-            let (prop_version, target_version) = parse_semver_operands()?;
-
-            match property.operator.as_str() {
-                "semver_eq" => prop_version == target_version,
-                "semver_neq" => prop_version != target_version,
-                "semver_gt" => prop_version > target_version,
-                "semver_gte" => prop_version >= target_version,
-                "semver_lt" => prop_version < target_version,
-                "semver_lte" => prop_version <= target_version,
-                _ => unreachable!(),
-            }
-        }
-        "semver_tilde" => {
-            let (prop_version, target_version) = parse_semver_operands()?;
-            let (lower, upper) = compute_tilde_bounds(target_version);
-            prop_version >= lower && prop_version < upper
-        }
-        "semver_caret" => {
-            let (prop_version, target_version) = parse_semver_operands()?;
-            let (lower, upper) = compute_caret_bounds(target_version);
-            prop_version >= lower && prop_version < upper
-        }
-        "semver_wildcard" => {
-            let prop_version = parse_property_semver()?;
-            let target_str = value_to_string(&property.value);
-
-            let (lower, upper) = parse_semver_wildcard(&target_str).ok_or_else(|| {
-                InconclusiveMatchError::new(&format!(
-                    "Unable to parse target semver wildcard pattern: {:?}",
-                    property.value
-                ))
-            })?;
-
-            prop_version >= lower && prop_version < upper
-        }
-        unknown => {
-            return Err(InconclusiveMatchError::new(&format!(
-                "Unknown operator: {}",
-                unknown
-            )));
-        }
-    })
-}
-
-fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+pub(crate) fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> bool {
     // Case-insensitive string comparison
     if let (Some(a_str), Some(b_str)) = (a.as_str(), b.as_str()) {
         return a_str.eq_ignore_ascii_case(b_str);
     }
 
+    // Like the server, booleans equal their string form, so a person's
+    // `$feature_enrollment/<key>: true` matches an enrollment filter on
+    // `"true"`.
+    match (a, b) {
+        (serde_json::Value::Bool(flag), serde_json::Value::String(s))
+        | (serde_json::Value::String(s), serde_json::Value::Bool(flag)) => {
+            return s.eq_ignore_ascii_case(if *flag { "true" } else { "false" });
+        }
+        _ => {}
+    }
+
     // Direct comparison for other types
     a == b
 }
 
-fn value_to_string(value: &serde_json::Value) -> String {
+pub(crate) fn value_to_string(value: &serde_json::Value) -> Cow<'_, str> {
     match value {
-        serde_json::Value::String(s) => s.clone(),
-        serde_json::Value::Number(n) => n.to_string(),
-        serde_json::Value::Bool(b) => b.to_string(),
-        _ => value.to_string(),
-    }
-}
-
-fn compare_numeric(
-    operator: &str,
-    property_value: &serde_json::Value,
-    value: &serde_json::Value,
-) -> bool {
-    let prop_num = match property_value {
-        serde_json::Value::Number(n) => n.as_f64(),
-        serde_json::Value::String(s) => s.parse::<f64>().ok(),
-        _ => None,
-    };
-
-    let val_num = match value {
-        serde_json::Value::Number(n) => n.as_f64(),
-        serde_json::Value::String(s) => s.parse::<f64>().ok(),
-        _ => None,
-    };
-
-    if let (Some(prop), Some(val)) = (prop_num, val_num) {
-        match operator {
-            "gt" => val > prop,
-            "gte" => val >= prop,
-            "lt" => val < prop,
-            "lte" => val <= prop,
-            _ => false,
-        }
-    } else {
-        // Fall back to string comparison
-        let prop_str = value_to_string(property_value);
-        let val_str = value_to_string(value);
-        match operator {
-            "gt" => val_str > prop_str,
-            "gte" => val_str >= prop_str,
-            "lt" => val_str < prop_str,
-            "lte" => val_str <= prop_str,
-            _ => false,
-        }
+        serde_json::Value::String(s) => Cow::Borrowed(s),
+        serde_json::Value::Number(n) => Cow::Owned(n.to_string()),
+        serde_json::Value::Bool(b) => Cow::Borrowed(if *b { "true" } else { "false" }),
+        _ => Cow::Owned(value.to_string()),
     }
 }
 
@@ -1370,6 +1091,13 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    fn match_property(
+        property: &Property,
+        properties: &HashMap<String, serde_json::Value>,
+    ) -> Result<bool, InconclusiveMatchError> {
+        PropertyMatcher::new(property, get_cached_regex).matches(properties)
+    }
+
     /// Test salt constant to avoid CodeQL warnings about empty cryptographic values
     const TEST_SALT: &str = "test-salt";
 
@@ -1393,6 +1121,7 @@ mod tests {
             key: "test-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![FeatureFlagCondition {
                     properties: vec![],
@@ -1404,6 +1133,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -1444,6 +1175,7 @@ mod tests {
             key: "test-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![FeatureFlagCondition {
                     properties: vec![],
@@ -1466,6 +1198,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -1494,6 +1228,7 @@ mod tests {
             key: "inactive-flag".to_string(),
             active: false,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![FeatureFlagCondition {
                     properties: vec![],
@@ -1505,6 +1240,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -1527,6 +1264,7 @@ mod tests {
             key: "rollout-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![FeatureFlagCondition {
                     properties: vec![],
@@ -1538,6 +1276,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -1669,12 +1409,15 @@ mod tests {
             key: "empty-groups".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![],
                 multivariate: None,
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -1983,9 +1726,11 @@ mod tests {
             cohorts: &cohorts,
             flags: &HashMap::new(),
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
         assert!(match_property_with_context(&prop, &properties, &ctx).unwrap());
 
@@ -2024,9 +1769,11 @@ mod tests {
             cohorts: &cohorts,
             flags: &HashMap::new(),
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
         // User with status = active should NOT be in the blocked cohort (so not_in returns true)
         assert!(match_property_with_context(&prop, &properties, &ctx).unwrap());
@@ -2052,9 +1799,11 @@ mod tests {
             cohorts: &cohorts,
             flags: &HashMap::new(),
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
 
         let result = match_property_with_context(&prop, &properties, &ctx);
@@ -2062,6 +1811,203 @@ mod tests {
         assert!(result.unwrap_err().message.contains("Cohort"));
     }
 
+    fn in_cohort(
+        cohorts: &[(&str, serde_json::Value)],
+        cohort_id: &str,
+        properties: serde_json::Value,
+    ) -> Result<bool, InconclusiveMatchError> {
+        let cohorts: HashMap<String, CohortDefinition> = cohorts
+            .iter()
+            .map(|(id, properties)| {
+                let cohort = CohortDefinition {
+                    id: id.to_string(),
+                    properties: properties.clone(),
+                };
+                (id.to_string(), cohort)
+            })
+            .collect();
+        let prop = Property {
+            key: "id".to_string(),
+            value: json!(cohort_id),
+            operator: "in".to_string(),
+            property_type: Some("cohort".to_string()),
+        };
+        let properties = serde_json::from_value(properties).unwrap();
+        let ctx = EvaluationContext {
+            cohorts: &cohorts,
+            flags: &HashMap::new(),
+            distinct_id: "user-123",
+            device_id: None,
+            groups: &HashMap::new(),
+            group_properties: &HashMap::new(),
+            group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
+        };
+        match_property_with_context(&prop, &properties, &ctx)
+    }
+
+    fn person_property(key: &str, value: &str) -> serde_json::Value {
+        json!({ "key": key, "type": "person", "value": value, "operator": "exact" })
+    }
+
+    #[test]
+    fn test_cohort_or_groups_of_and_groups() {
+        let cohort = json!({
+            "type": "OR",
+            "values": [
+                { "type": "AND", "values": [person_property("country", "US"), person_property("plan", "pro")] },
+                { "type": "AND", "values": [person_property("email", "ceo@example.com")] }
+            ]
+        });
+        let cohorts = [("1", cohort)];
+
+        let person =
+            |country, plan, email| json!({ "country": country, "plan": plan, "email": email });
+        assert!(in_cohort(&cohorts, "1", person("US", "pro", "a@example.com")).unwrap());
+        assert!(in_cohort(&cohorts, "1", person("UK", "free", "ceo@example.com")).unwrap());
+        assert!(!in_cohort(&cohorts, "1", person("US", "free", "a@example.com")).unwrap());
+    }
+
+    #[test]
+    fn test_cohort_negated_properties() {
+        let mut blocked = person_property("status", "blocked");
+        blocked["negation"] = json!(true);
+        let cohorts = [(
+            "1",
+            json!({ "type": "AND", "values": [person_property("country", "US"), blocked] }),
+        )];
+
+        assert!(in_cohort(
+            &cohorts,
+            "1",
+            json!({ "country": "US", "status": "active" })
+        )
+        .unwrap());
+        assert!(!in_cohort(
+            &cohorts,
+            "1",
+            json!({ "country": "US", "status": "blocked" })
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_cohort_settled_groups_ignore_inconclusive_values() {
+        let or_cohort = json!({
+            "type": "OR",
+            "values": [person_property("email", "ceo@example.com"), person_property("plan", "pro")]
+        });
+        let and_cohort = json!({
+            "type": "AND",
+            "values": [person_property("email", "ceo@example.com"), person_property("plan", "pro")]
+        });
+        let cohorts = [("or", or_cohort), ("and", and_cohort)];
+
+        // `email` is missing, but `plan` alone settles both groups.
+        assert!(in_cohort(&cohorts, "or", json!({ "plan": "pro" })).unwrap());
+        assert!(!in_cohort(&cohorts, "and", json!({ "plan": "free" })).unwrap());
+        assert!(in_cohort(&cohorts, "or", json!({ "plan": "free" })).is_err());
+        assert!(in_cohort(&cohorts, "and", json!({ "plan": "pro" })).is_err());
+    }
+
+    #[test]
+    fn test_cohort_empty_groups_match_everyone() {
+        let cohorts = [
+            ("or", json!({ "type": "OR", "values": [] })),
+            ("and", json!({ "type": "AND", "values": [] })),
+            (
+                "nested",
+                json!({ "type": "AND", "values": [
+                    { "type": "OR", "values": [] },
+                    person_property("plan", "pro")
+                ] }),
+            ),
+        ];
+
+        assert!(in_cohort(&cohorts, "or", json!({})).unwrap());
+        assert!(in_cohort(&cohorts, "and", json!({})).unwrap());
+        assert!(in_cohort(&cohorts, "nested", json!({ "plan": "pro" })).unwrap());
+    }
+
+    #[test]
+    fn test_cohorts_resolve_the_cohorts_they_refer_to() {
+        let cohorts = [
+            (
+                "1",
+                json!({ "type": "AND", "values": [
+                    { "key": "id", "type": "cohort", "value": 2 },
+                    person_property("plan", "pro")
+                ] }),
+            ),
+            (
+                "2",
+                json!({ "type": "OR", "values": [person_property("country", "US")] }),
+            ),
+            (
+                "3",
+                json!({ "type": "AND", "values": [
+                    { "key": "id", "type": "cohort", "value": 2, "negation": true }
+                ] }),
+            ),
+        ];
+
+        assert!(in_cohort(&cohorts, "1", json!({ "country": "US", "plan": "pro" })).unwrap());
+        assert!(!in_cohort(&cohorts, "1", json!({ "country": "UK", "plan": "pro" })).unwrap());
+        assert!(in_cohort(&cohorts, "3", json!({ "country": "UK" })).unwrap());
+    }
+
+    #[test]
+    fn test_cohort_reference_cycles_are_inconclusive() {
+        let refers_to = |id: &str| json!({ "type": "AND", "values": [{ "key": "id", "type": "cohort", "value": id }] });
+        let cohorts = [
+            ("1", refers_to("2")),
+            ("2", refers_to("1")),
+            ("3", refers_to("3")),
+        ];
+
+        for id in ["1", "3"] {
+            let error = in_cohort(&cohorts, id, json!({})).unwrap_err();
+            assert!(
+                error.message.contains("refers to itself"),
+                "{}",
+                error.message
+            );
+        }
+    }
+
+    #[test]
+    fn test_cohort_property_group_parses_nested_groups() {
+        let cohort = CohortDefinition {
+            id: "1".to_string(),
+            properties: json!({
+                "type": "OR",
+                "values": [
+                    { "type": "AND", "values": [
+                        { "key": "plan", "type": "person", "value": "pro", "negation": true }
+                    ] },
+                    { "key": "id", "type": "cohort", "value": 2 }
+                ]
+            }),
+        };
+
+        let group = cohort.property_group();
+
+        assert_eq!(group.group_type, PropertyGroupType::Or);
+        let [PropertyGroupValue::Group(inner), PropertyGroupValue::Property {
+            property,
+            negated: false,
+        }] = group.values.as_slice()
+        else {
+            panic!("unexpected tree: {:?}", group);
+        };
+        assert_eq!(inner.group_type, PropertyGroupType::And);
+        assert!(matches!(
+            inner.values.as_slice(),
+            [PropertyGroupValue::Property { negated: true, .. }]
+        ));
+        assert_eq!(property.property_type.as_deref(), Some("cohort"));
+    }
+
     // ==================== Tests for flag dependencies ====================
 
     #[test]
@@ -2073,6 +2019,7 @@ mod tests {
                 key: "prerequisite-flag".to_string(),
                 active: true,
                 has_experiment: None,
+                bucketing_identifier: None,
                 filters: FeatureFlagFilters {
                     groups: vec![FeatureFlagCondition {
                         properties: vec![],
@@ -2084,6 +2031,8 @@ mod tests {
                     payloads: HashMap::new(),
                     aggregation_group_type_index: None,
                     early_exit: false,
+                    super_groups: None,
+                    holdout_groups: None,
                 },
             },
         );
@@ -2101,9 +2050,11 @@ mod tests {
             cohorts: &HashMap::new(),
             flags: &flags,
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
 
         // The prerequisite flag is enabled for user-123, so this should match
@@ -2119,12 +2070,15 @@ mod tests {
                 key: "disabled-flag".to_string(),
                 active: false, // Flag is inactive
                 has_experiment: None,
+                bucketing_identifier: None,
                 filters: FeatureFlagFilters {
                     groups: vec![],
                     multivariate: None,
                     payloads: HashMap::new(),
                     aggregation_group_type_index: None,
                     early_exit: false,
+                    super_groups: None,
+                    holdout_groups: None,
                 },
             },
         );
@@ -2142,9 +2096,11 @@ mod tests {
             cohorts: &HashMap::new(),
             flags: &flags,
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
 
         // The flag is disabled, so checking for true should fail
@@ -2160,6 +2116,7 @@ mod tests {
                 key: "ab-test-flag".to_string(),
                 active: true,
                 has_experiment: None,
+                bucketing_identifier: None,
                 filters: FeatureFlagFilters {
                     groups: vec![FeatureFlagCondition {
                         properties: vec![],
@@ -2182,6 +2139,8 @@ mod tests {
                     payloads: HashMap::new(),
                     aggregation_group_type_index: None,
                     early_exit: false,
+                    super_groups: None,
+                    holdout_groups: None,
                 },
             },
         );
@@ -2199,9 +2158,11 @@ mod tests {
             cohorts: &HashMap::new(),
             flags: &flags,
             distinct_id: "user-gets-control", // This distinct_id should deterministically get "control"
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
 
         // The result depends on the hash - we just check it doesn't error
@@ -2225,9 +2186,11 @@ mod tests {
             cohorts: &HashMap::new(),
             flags: &flags,
             distinct_id: "user-123",
+            device_id: None,
             groups: &HashMap::new(),
             group_properties: &HashMap::new(),
             group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
         };
 
         let result = match_property_with_context(&prop, &properties, &ctx);
@@ -3274,6 +3237,7 @@ mod tests {
             key: "early-exit-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![
                     // Group 1: matches on properties (none) but rollout excludes
@@ -3296,6 +3260,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit,
+                super_groups: None,
+                holdout_groups: None,
             },
         }
     }
@@ -3394,6 +3360,7 @@ mod tests {
             key: "early-exit-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![
                     FeatureFlagCondition {
@@ -3418,6 +3385,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: true,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -3446,9 +3415,11 @@ mod tests {
                     cohorts: &HashMap::new(),
                     flags: &HashMap::new(),
                     distinct_id: "user-123",
+                    device_id: None,
                     groups: &HashMap::new(),
                     group_properties: &HashMap::new(),
                     group_type_mapping: &HashMap::new(),
+                    cohort_membership: None,
                 };
                 let result = match_feature_flag_with_context(&flag, &HashMap::new(), &ctx).unwrap();
                 assert_eq!(result, $expected);
@@ -3472,7 +3443,7 @@ mod tests {
         // Group 1: group-targeted (aggregation_group_type_index = 0). The
         // group_type_mapping resolves "0" → "company" and groups supplies the
         // company key, but group_properties has no entry for "company" →
-        // ConditionTarget::Inconclusive → is_inconclusive = true.
+        // the condition is inconclusive → is_inconclusive = true.
         // Group 2: person-targeted, rollout 0% → OutOfRolloutBound.
         // With early_exit = true, the !is_inconclusive guard must prevent
         // short-circuiting, and the overall result must be InconclusiveMatchError.
@@ -3480,6 +3451,7 @@ mod tests {
             key: "early-exit-flag".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![
                     FeatureFlagCondition {
@@ -3499,6 +3471,8 @@ mod tests {
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: true,
+                super_groups: None,
+                holdout_groups: None,
             },
         };
 
@@ -3523,4 +3497,186 @@ mod tests {
             result
         );
     }
+
+    fn experiment_flag(key: &str, holdout_properties: serde_json::Value) -> FeatureFlag {
+        serde_json::from_value(json!({
+            "key": key,
+            "active": true,
+            "filters": {
+                "groups": [{ "properties": [], "rollout_percentage": 100 }],
+                "multivariate": { "variants": [
+                    { "key": "control", "rollout_percentage": 50 },
+                    { "key": "test", "rollout_percentage": 50 }
+                ] },
+                "holdout_groups": [{
+                    "properties": holdout_properties,
+                    "rollout_percentage": 50,
+                    "variant": "holdout-4"
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn enrollment_flag() -> FeatureFlag {
+        serde_json::from_value(json!({
+            "key": "beta-feature",
+            "active": true,
+            "filters": {
+                "groups": [{ "properties": [], "rollout_percentage": 0 }],
+                "super_groups": [{
+                    "properties": [{
+                        "key": "$feature_enrollment/beta-feature",
+                        "type": "person",
+                        "value": ["true"],
+                        "operator": "exact"
+                    }],
+                    "rollout_percentage": 100
+                }]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn detail_for(
+        flag: &FeatureFlag,
+        distinct_id: &str,
+        person_properties: serde_json::Value,
+    ) -> FlagDetail {
+        let ctx = EvaluationContext {
+            cohorts: &HashMap::new(),
+            flags: &HashMap::new(),
+            distinct_id,
+            device_id: None,
+            groups: &HashMap::new(),
+            group_properties: &HashMap::new(),
+            group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
+        };
+        let person_properties = serde_json::from_value(person_properties).unwrap();
+        match_feature_flag_detailed(flag, &person_properties, &ctx).unwrap()
+    }
+
+    #[test]
+    fn test_holdout_hash_matches_the_server() {
+        // sha1("holdout-" + distinct_id), without the flag key.
+        assert!((holdout_hash("user-1") - 0.17805599206573022).abs() < 1e-12);
+        assert!((holdout_hash("user-2") - 0.928387330790184).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_holdouts_serve_their_variant_within_the_rollout() {
+        for key in ["checkout-experiment", "pricing-experiment"] {
+            let flag = experiment_flag(key, json!([]));
+
+            let held_out = detail_for(&flag, "user-1", json!({}));
+            assert_eq!(held_out.value(), FlagValue::from("holdout-4"));
+            assert_eq!(held_out.reason, Some(FlagReason::holdout_condition_value()));
+
+            let enrolled = detail_for(&flag, "user-2", json!({}));
+            assert!(matches!(
+                enrolled.value(),
+                FlagValue::String(variant) if variant == "control" || variant == "test"
+            ));
+            assert_eq!(enrolled.reason, Some(FlagReason::condition_match(0)));
+        }
+    }
+
+    #[test]
+    fn test_holdouts_with_property_filters_never_match() {
+        let flag = experiment_flag(
+            "checkout-experiment",
+            json!([{ "key": "plan", "value": "pro", "operator": "exact", "type": "person" }]),
+        );
+
+        let detail = detail_for(&flag, "user-1", json!({ "plan": "pro" }));
+        assert_eq!(detail.reason, Some(FlagReason::condition_match(0)));
+    }
+
+    #[test]
+    fn test_super_conditions_decide_when_their_properties_are_set() {
+        let flag = enrollment_flag();
+
+        let opted_in = detail_for(
+            &flag,
+            "user-1",
+            json!({ "$feature_enrollment/beta-feature": true }),
+        );
+        assert_eq!(opted_in.value(), FlagValue::Boolean(true));
+        assert_eq!(opted_in.reason, Some(FlagReason::super_condition_value()));
+
+        let opted_out = detail_for(
+            &flag,
+            "user-1",
+            json!({ "$feature_enrollment/beta-feature": false }),
+        );
+        assert_eq!(opted_out.value(), FlagValue::Boolean(false));
+        assert_eq!(opted_out.reason, Some(FlagReason::super_condition_value()));
+
+        let not_enrolled = detail_for(&flag, "user-1", json!({}));
+        assert_eq!(not_enrolled.value(), FlagValue::Boolean(false));
+        assert_eq!(
+            not_enrolled.reason,
+            Some(FlagReason::out_of_rollout_bound(0))
+        );
+    }
+
+    fn device_flag(bucketing_identifier: Option<&str>) -> FeatureFlag {
+        serde_json::from_value(json!({
+            "key": "device-flag",
+            "active": true,
+            "bucketing_identifier": bucketing_identifier,
+            "filters": { "groups": [{ "properties": [], "rollout_percentage": 50 }] }
+        }))
+        .unwrap()
+    }
+
+    fn match_on_device(
+        flag: &FeatureFlag,
+        distinct_id: &str,
+        device_id: Option<&str>,
+    ) -> FlagValue {
+        let ctx = EvaluationContext {
+            cohorts: &HashMap::new(),
+            flags: &HashMap::new(),
+            distinct_id,
+            device_id,
+            groups: &HashMap::new(),
+            group_properties: &HashMap::new(),
+            group_type_mapping: &HashMap::new(),
+            cohort_membership: None,
+        };
+        match_feature_flag_with_context(flag, &HashMap::new(), &ctx).unwrap()
+    }
+
+    #[test]
+    fn test_device_bucketed_flags_hash_the_device_id() {
+        let flag = device_flag(Some("device_id"));
+
+        // Bucketed on the distinct ID alone, anon-1 is in the rollout and
+        // user-2 is not; their devices decide instead.
+        assert_eq!(
+            match_on_device(&flag, "anon-1", Some("device-9")),
+            FlagValue::Boolean(false)
+        );
+        assert_eq!(
+            match_on_device(&flag, "user-2", Some("device-1")),
+            FlagValue::Boolean(true)
+        );
+        assert_eq!(
+            match_on_device(&flag, "anon-1", None),
+            FlagValue::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn test_distinct_id_bucketed_flags_ignore_the_device_id() {
+        for bucketing_identifier in [None, Some("distinct_id")] {
+            let flag = device_flag(bucketing_identifier);
+            assert_eq!(
+                match_on_device(&flag, "user-2", Some("device-1")),
+                FlagValue::Boolean(false)
+            );
+        }
+    }
 }
@@ -0,0 +1,264 @@
+//! Step-by-step traces of local flag evaluation.
+//!
+//! [`LocalEvaluator::explain`](crate::LocalEvaluator::explain) evaluates a
+//! flag exactly like [`LocalEvaluator::evaluate_flag`](crate::LocalEvaluator::evaluate_flag)
+//! while recording every condition group it tried: the property filters with
+//! the values they were compared against, the rollout hash against its
+//! threshold and the variant bucket. The trace answers "why does this user
+//! (not) see this flag?" without reproducing the hashing by hand.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::compiled_flags::{Definitions, Environment, Observer, PropertyMatcher};
+use crate::feature_flags::{FlagReason, FlagValue, InconclusiveMatchError, Property};
+
+/// How a flag was evaluated for one user, condition by condition.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct FlagExplanation {
+    /// The flag's key.
+    pub key: String,
+    /// Whether the flag is active. Inactive flags evaluate no conditions.
+    pub active: bool,
+    /// The value the flag evaluated to, or `None` when local evaluation was
+    /// inconclusive; the conditions marked
+    /// [`Inconclusive`](ConditionOutcome::Inconclusive) say why.
+    pub value: Option<FlagValue>,
+    /// Why the flag evaluated to [`value`](Self::value), with the same codes
+    /// as [`LocalEvaluator::evaluate_flag_detailed`](crate::LocalEvaluator::evaluate_flag_detailed).
+    pub reason: Option<FlagReason>,
+    /// The condition groups in the order they were tried: super conditions,
+    /// holdouts, then release conditions with variant overrides first.
+    /// Evaluation stops at the deciding condition, so later ones are not
+    /// listed.
+    pub conditions: Vec<ConditionExplanation>,
+    /// How the variant was picked, for multivariate flags and variant
+    /// overrides that matched.
+    pub variant: Option<VariantExplanation>,
+}
+
+/// One condition group of a [`FlagExplanation`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct ConditionExplanation {
+    /// Which of the flag's condition lists the condition is from.
+    pub kind: ConditionKind,
+    /// Position of the condition in its list, e.g. the flag's
+    /// `filters.groups` for release conditions.
+    pub index: usize,
+    /// The group type the condition targets, or `None` for persons and
+    /// group type indexes missing from the group type mapping.
+    pub group_type: Option<String>,
+    /// The distinct ID, device ID or group key the rollout and variant
+    /// hashes used.
+    /// `None` when no group of the targeted type was passed in.
+    pub bucketing_id: Option<String>,
+    /// The property filters that were checked, in order. Matching stops at
+    /// the first filter that does not match or is inconclusive.
+    pub properties: Vec<PropertyExplanation>,
+    /// The rollout check, when the properties matched and the condition has
+    /// a rollout percentage.
+    pub rollout: Option<RolloutExplanation>,
+    /// What the condition decided.
+    pub outcome: ConditionOutcome,
+}
+
+/// Which of a flag's condition lists a [`ConditionExplanation`] is from.
+/// Super conditions are tried first, then holdouts, then release conditions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ConditionKind {
+    /// An early access enrollment override from `filters.super_groups`.
+    Super,
+    /// An experiment holdout from `filters.holdout_groups`.
+    Holdout,
+    /// A release condition from `filters.groups`.
+    Release,
+}
+
+/// What a condition group decided.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ConditionOutcome {
+    /// Properties and rollout matched: the flag is enabled by this condition.
+    Matched,
+    /// A property filter did not match.
+    PropertiesNotMatched,
+    /// The properties matched but the user's rollout hash is above the
+    /// rollout threshold.
+    OutOfRolloutBound,
+    /// The condition does not apply, e.g. it targets a group that was not
+    /// passed in.
+    Skipped(String),
+    /// The condition could not be evaluated locally, e.g. because a property
+    /// it filters on was not passed in.
+    Inconclusive(String),
+}
+
+/// One property filter of a [`ConditionExplanation`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct PropertyExplanation {
+    /// The filter, with its key, operator and expected value.
+    pub property: Property,
+    /// The value the filter was compared against: the passed-in property, or
+    /// for `$feature/<key>` dependencies the dependency's value. `None` when
+    /// there was none, and for cohort filters.
+    pub actual: Option<Value>,
+    /// Whether the filter matched.
+    pub outcome: PropertyOutcome,
+}
+
+/// Whether a property filter matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum PropertyOutcome {
+    /// The filter matched.
+    Matched,
+    /// The filter did not match.
+    NotMatched,
+    /// The filter could not be evaluated, with the reason.
+    Inconclusive(String),
+}
+
+/// The rollout check of a [`ConditionExplanation`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct RolloutExplanation {
+    /// The condition's rollout percentage, from 0 to 100.
+    pub percentage: f64,
+    /// The user's rollout hash, from 0 to 1.
+    pub hash: f64,
+    /// Whether the hash is within `percentage / 100`.
+    pub included: bool,
+}
+
+/// How a [`FlagExplanation`]'s variant was picked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct VariantExplanation {
+    /// The variant served, or `None` when the hash fell outside every
+    /// variant's bucket and the flag served `true`.
+    pub variant: Option<String>,
+    /// The user's variant hash, from 0 to 1. Variants split this range in
+    /// order by their rollout percentages. `None` for variant overrides.
+    pub hash: Option<f64>,
+    /// Whether the matching condition's variant override picked the variant.
+    pub overridden: bool,
+}
+
+/// An [`Observer`] that records a [`FlagExplanation`].
+#[derive(Default)]
+pub(crate) struct Recorder {
+    conditions: Vec<ConditionExplanation>,
+    current: Option<PendingCondition>,
+    variant: Option<VariantExplanation>,
+}
+
+/// The condition being evaluated, until its outcome is known.
+struct PendingCondition {
+    kind: ConditionKind,
+    index: usize,
+    group_type: Option<String>,
+    bucketing_id: Option<String>,
+    properties: Vec<PropertyExplanation>,
+    rollout: Option<RolloutExplanation>,
+}
+
+impl Recorder {
+    pub(crate) fn finish(
+        self,
+        key: &str,
+        active: bool,
+        evaluated: Option<(FlagValue, Option<FlagReason>)>,
+    ) -> FlagExplanation {
+        let (value, reason) = match evaluated {
+            Some((value, reason)) => (Some(value), reason),
+            None => (None, None),
+        };
+        FlagExplanation {
+            key: key.to_string(),
+            active,
+            value,
+            reason,
+            conditions: self.conditions,
+            variant: self.variant,
+        }
+    }
+}
+
+impl Observer for Recorder {
+    fn condition(&mut self, kind: ConditionKind, index: usize) {
+        self.current = Some(PendingCondition {
+            kind,
+            index,
+            group_type: None,
+            bucketing_id: None,
+            properties: Vec::new(),
+            rollout: None,
+        });
+    }
+
+    fn target(&mut self, group_type: Option<&str>, bucketing_id: Option<&str>) {
+        if let Some(current) = &mut self.current {
+            current.group_type = group_type.map(str::to_string);
+            current.bucketing_id = bucketing_id.map(str::to_string);
+        }
+    }
+
+    fn property<D: Definitions>(
+        &mut self,
+        matcher: &PropertyMatcher,
+        properties: &HashMap<String, Value>,
+        env: &Environment<'_, D>,
+        result: &Result<bool, InconclusiveMatchError>,
+    ) {
+        let Some(current) = &mut self.current else {
+            return;
+        };
+        let outcome = match result {
+            Ok(true) => PropertyOutcome::Matched,
+            Ok(false) => PropertyOutcome::NotMatched,
+            Err(e) => PropertyOutcome::Inconclusive(e.message.clone()),
+        };
+        current.properties.push(PropertyExplanation {
+            property: matcher.property().clone(),
+            actual: matcher.actual_value(properties, env),
+            outcome,
+        });
+    }
+
+    fn rollout(&mut self, percentage: f64, hash: f64, included: bool) {
+        if let Some(current) = &mut self.current {
+            current.rollout = Some(RolloutExplanation {
+                percentage,
+                hash,
+                included,
+            });
+        }
+    }
+
+    fn condition_outcome(&mut self, outcome: impl FnOnce() -> ConditionOutcome) {
+        if let Some(current) = self.current.take() {
+            self.conditions.push(ConditionExplanation {
+                kind: current.kind,
+                index: current.index,
+                group_type: current.group_type,
+                bucketing_id: current.bucketing_id,
+                properties: current.properties,
+                rollout: current.rollout,
+                outcome: outcome(),
+            });
+        }
+    }
+
+    fn variant(&mut self, variant: impl FnOnce() -> VariantExplanation) {
+        self.variant = Some(variant());
+    }
+}
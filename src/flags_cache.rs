@@ -0,0 +1,401 @@
+//! Per-user cache of remote `/flags` responses.
+//!
+//! Without local evaluation every [`Client::evaluate_flags`](crate::Client::evaluate_flags)
+//! and [`Client::get_feature_flags`](crate::Client::get_feature_flags) call
+//! asks `/flags`, and a call made while PostHog is slow or unreachable gets
+//! nothing back. A client configured with [`FlagsCacheOptions`] reuses a
+//! response for the same request until its TTL runs out, and falls back to
+//! the last response it has when `/flags` fails or is quota limited.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tracing::debug;
+
+use crate::feature_flags::FeatureFlagsResponse;
+use crate::Error;
+
+/// How long, and for how many requests, remote `/flags` responses are cached.
+///
+/// Responses are cached per request: the distinct ID, groups, person and
+/// group properties, flag keys and GeoIP setting all have to match. A fresh
+/// response, younger than the [`ttl`](Self::new), is served without a
+/// request. An older one is only served when `/flags` fails or answers
+/// `quotaLimited`, so a flaky connection degrades to slightly outdated flags
+/// instead of none.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use posthog_rs::{ClientOptionsBuilder, FlagsCacheOptions};
+///
+/// let options = ClientOptionsBuilder::default()
+///     .api_key("phc_project_api_key".to_string())
+///     .flags_cache(
+///         FlagsCacheOptions::new(Duration::from_secs(30))
+///             .max_entries(50_000)
+///             .max_stale(Duration::from_secs(60 * 60)),
+///     )
+///     .build()
+///     .unwrap();
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlagsCacheOptions {
+    ttl: Duration,
+    max_entries: usize,
+    max_stale: Option<Duration>,
+}
+
+impl FlagsCacheOptions {
+    /// Serve cached responses for `ttl` after they were received.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_entries: 10_000,
+            max_stale: None,
+        }
+    }
+
+    /// Keep at most `max_entries` responses (default: 10,000), evicting the
+    /// oldest first.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Stop serving a response in place of a failed request once it is
+    /// `max_stale` past its TTL (default: no limit).
+    pub fn max_stale(mut self, max_stale: Duration) -> Self {
+        self.max_stale = Some(max_stale);
+        self
+    }
+}
+
+/// Where the `/flags` results of a [`FeatureFlagEvaluations`](crate::FeatureFlagEvaluations)
+/// snapshot came from, when the client caches them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlagsCacheStatus {
+    /// A fresh cached response was served without a request.
+    Hit,
+    /// The response was requested from `/flags` and cached.
+    Miss,
+    /// `/flags` failed or was quota limited, and a cached response past its
+    /// TTL was served instead. Its request ID is dropped, so
+    /// `$feature_flag_called` events never carry the ID of an earlier
+    /// request.
+    Stale,
+}
+
+/// Identifies a `/flags` request. The hash covers the whole payload; the
+/// distinct ID is kept alongside so a collision can never serve one person's
+/// flags to another.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FlagsCacheKey {
+    distinct_id: String,
+    payload_hash: u64,
+}
+
+impl FlagsCacheKey {
+    /// The key of the `/flags` request `payload`, ignoring its API key.
+    pub(crate) fn new(distinct_id: &str, payload: &Value) -> Self {
+        let mut hasher = DefaultHasher::new();
+        match payload {
+            Value::Object(fields) => {
+                let mut fields: Vec<_> = fields
+                    .iter()
+                    .filter(|(key, _)| key.as_str() != "api_key")
+                    .collect();
+                fields.sort_unstable_by_key(|(key, _)| *key);
+                for (key, value) in fields {
+                    key.hash(&mut hasher);
+                    hash_value(value, &mut hasher);
+                }
+            }
+            other => hash_value(other, &mut hasher),
+        }
+        Self {
+            distinct_id: distinct_id.to_string(),
+            payload_hash: hasher.finish(),
+        }
+    }
+}
+
+/// Hash `value` independently of the order of its object keys, which follows
+/// `HashMap` iteration when properties are serialized.
+fn hash_value(value: &Value, hasher: &mut DefaultHasher) {
+    match value {
+        Value::Null => 0u8.hash(hasher),
+        Value::Bool(b) => (1u8, b).hash(hasher),
+        Value::Number(n) => (2u8, n.to_string()).hash(hasher),
+        Value::String(s) => (3u8, s).hash(hasher),
+        Value::Array(values) => {
+            (4u8, values.len()).hash(hasher);
+            for value in values {
+                hash_value(value, hasher);
+            }
+        }
+        Value::Object(fields) => {
+            (5u8, fields.len()).hash(hasher);
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_unstable_by_key(|(key, _)| *key);
+            for (key, value) in fields {
+                key.hash(hasher);
+                hash_value(value, hasher);
+            }
+        }
+    }
+}
+
+struct Entry {
+    response: FeatureFlagsResponse,
+    received_at: Instant,
+    /// When the entry was stored, matching its latest position in
+    /// [`Entries::order`].
+    sequence: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<FlagsCacheKey, Entry>,
+    /// Keys in the order they were stored, oldest first. A key stored again
+    /// is queued again; positions whose sequence no longer matches the entry
+    /// are skipped on eviction and compacted away.
+    order: VecDeque<(FlagsCacheKey, u64)>,
+    next_sequence: u64,
+}
+
+/// The cache a client keeps when configured with [`FlagsCacheOptions`].
+pub(crate) struct FlagsCache {
+    options: FlagsCacheOptions,
+    entries: Mutex<Entries>,
+}
+
+impl FlagsCache {
+    pub(crate) fn new(options: FlagsCacheOptions) -> Self {
+        Self {
+            options,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// The cached response for `key`, if it is still within its TTL.
+    pub(crate) fn fresh(&self, key: &FlagsCacheKey) -> Option<FeatureFlagsResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let entry = entries.entries.get(key)?;
+        (entry.received_at.elapsed() < self.options.ttl).then(|| entry.response.clone())
+    }
+
+    /// Cache the outcome of the `/flags` request `key`, or fall back to a
+    /// stale response when it failed or was quota limited.
+    pub(crate) fn settle(
+        &self,
+        key: FlagsCacheKey,
+        result: Result<FeatureFlagsResponse, Error>,
+    ) -> Result<(FeatureFlagsResponse, FlagsCacheStatus), Error> {
+        match result {
+            Ok(response) if !is_quota_limited(&response) => {
+                self.store(key, response.clone());
+                Ok((response, FlagsCacheStatus::Miss))
+            }
+            result => match self.stale(&key) {
+                Some(mut stale) => {
+                    debug!(
+                        distinct_id = key.distinct_id,
+                        "/flags request failed; serving stale cached response"
+                    );
+                    if let FeatureFlagsResponse::V2 { request_id, .. } = &mut stale {
+                        *request_id = None;
+                    }
+                    Ok((stale, FlagsCacheStatus::Stale))
+                }
+                None => result.map(|response| (response, FlagsCacheStatus::Miss)),
+            },
+        }
+    }
+
+    fn stale(&self, key: &FlagsCacheKey) -> Option<FeatureFlagsResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let entry = entries.entries.get(key)?;
+        let usable = match self.options.max_stale {
+            Some(max_stale) => entry.received_at.elapsed() < self.options.ttl + max_stale,
+            None => true,
+        };
+        usable.then(|| entry.response.clone())
+    }
+
+    fn store(&self, key: FlagsCacheKey, response: FeatureFlagsResponse) {
+        if self.options.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|p| p.into_inner());
+        let Entries {
+            entries,
+            order,
+            next_sequence,
+        } = &mut *entries;
+        if !entries.contains_key(&key) && entries.len() >= self.options.max_entries {
+            while let Some((oldest, sequence)) = order.pop_front() {
+                if is_current(entries, &oldest, sequence) {
+                    entries.remove(&oldest);
+                    break;
+                }
+            }
+        }
+        let sequence = *next_sequence;
+        *next_sequence += 1;
+        entries.insert(
+            key.clone(),
+            Entry {
+                response,
+                received_at: Instant::now(),
+                sequence,
+            },
+        );
+        order.push_back((key, sequence));
+        if order.len() > 2 * entries.len() {
+            order.retain(|(key, sequence)| is_current(entries, key, *sequence));
+        }
+    }
+}
+
+/// Whether `sequence` is the latest position of `key` in [`Entries::order`].
+fn is_current(entries: &HashMap<FlagsCacheKey, Entry>, key: &FlagsCacheKey, sequence: u64) -> bool {
+    entries
+        .get(key)
+        .is_some_and(|entry| entry.sequence == sequence)
+}
+
+fn is_quota_limited(response: &FeatureFlagsResponse) -> bool {
+    matches!(
+        response,
+        FeatureFlagsResponse::V2 {
+            quota_limited: true,
+            ..
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn response(enabled: bool) -> FeatureFlagsResponse {
+        serde_json::from_value(json!({
+            "featureFlags": { "flag": enabled },
+            "featureFlagPayloads": {}
+        }))
+        .unwrap()
+    }
+
+    fn value(response: &FeatureFlagsResponse) -> Value {
+        json!(response.clone().normalize().0["flag"])
+    }
+
+    fn key(distinct_id: &str) -> FlagsCacheKey {
+        FlagsCacheKey::new(distinct_id, &json!({ "distinct_id": distinct_id }))
+    }
+
+    fn failure() -> Result<FeatureFlagsResponse, Error> {
+        Err(Error::Connection("timed out".to_string()))
+    }
+
+    #[test]
+    fn keys_ignore_the_api_key_and_property_order() {
+        let a = json!({ "api_key": "a", "person_properties": { "plan": "pro", "seats": 3 } });
+        let b = json!({ "person_properties": { "seats": 3, "plan": "pro" }, "api_key": "b" });
+        let c = json!({ "person_properties": { "seats": 4, "plan": "pro" } });
+
+        assert_eq!(FlagsCacheKey::new("u", &a), FlagsCacheKey::new("u", &b));
+        assert_ne!(FlagsCacheKey::new("u", &a), FlagsCacheKey::new("u", &c));
+        assert_ne!(FlagsCacheKey::new("u", &a), FlagsCacheKey::new("v", &a));
+    }
+
+    #[test]
+    fn responses_are_fresh_until_their_ttl() {
+        let cache = FlagsCache::new(FlagsCacheOptions::new(Duration::from_secs(60)));
+        assert!(cache.fresh(&key("u")).is_none());
+
+        let (_, status) = cache.settle(key("u"), Ok(response(true))).unwrap();
+        assert_eq!(status, FlagsCacheStatus::Miss);
+        assert_eq!(value(&cache.fresh(&key("u")).unwrap()), json!(true));
+        assert!(cache.fresh(&key("v")).is_none());
+
+        let expired = FlagsCache::new(FlagsCacheOptions::new(Duration::ZERO));
+        expired.settle(key("u"), Ok(response(true))).unwrap();
+        assert!(expired.fresh(&key("u")).is_none());
+    }
+
+    #[test]
+    fn failures_and_quota_limits_fall_back_to_stale_responses() {
+        let cache = FlagsCache::new(FlagsCacheOptions::new(Duration::ZERO));
+        assert!(cache.settle(key("u"), failure()).is_err());
+
+        cache.settle(key("u"), Ok(response(true))).unwrap();
+        let (stale, status) = cache.settle(key("u"), failure()).unwrap();
+        assert_eq!(status, FlagsCacheStatus::Stale);
+        assert_eq!(value(&stale), json!(true));
+
+        let quota_limited = serde_json::from_value(json!({ "flags": {}, "quotaLimited": true }));
+        let (stale, status) = cache.settle(key("u"), Ok(quota_limited.unwrap())).unwrap();
+        assert_eq!(status, FlagsCacheStatus::Stale);
+        assert_eq!(value(&stale), json!(true));
+
+        let bounded =
+            FlagsCache::new(FlagsCacheOptions::new(Duration::ZERO).max_stale(Duration::ZERO));
+        bounded.settle(key("u"), Ok(response(true))).unwrap();
+        assert!(bounded.settle(key("u"), failure()).is_err());
+    }
+
+    #[test]
+    fn the_oldest_response_is_evicted_when_full() {
+        let cache = FlagsCache::new(FlagsCacheOptions::new(Duration::from_secs(60)).max_entries(2));
+        for distinct_id in ["a", "b", "c"] {
+            cache.settle(key(distinct_id), Ok(response(true))).unwrap();
+        }
+
+        assert!(cache.fresh(&key("a")).is_none());
+        assert!(cache.fresh(&key("b")).is_some());
+        assert!(cache.fresh(&key("c")).is_some());
+
+        // Storing `b` again makes `c` the oldest.
+        for distinct_id in ["b", "b", "b", "d"] {
+            cache.settle(key(distinct_id), Ok(response(true))).unwrap();
+        }
+        assert!(cache.fresh(&key("b")).is_some());
+        assert!(cache.fresh(&key("c")).is_none());
+        assert!(cache.fresh(&key("d")).is_some());
+    }
+
+    #[test]
+    fn stale_responses_drop_their_request_id() {
+        let cache = FlagsCache::new(FlagsCacheOptions::new(Duration::ZERO));
+        let fresh = serde_json::from_value(json!({ "flags": {}, "requestId": "req-1" }));
+        let (served, _) = cache.settle(key("u"), Ok(fresh.unwrap())).unwrap();
+        assert!(matches!(
+            served,
+            FeatureFlagsResponse::V2 {
+                request_id: Some(_),
+                ..
+            }
+        ));
+
+        let (stale, status) = cache.settle(key("u"), failure()).unwrap();
+        assert_eq!(status, FlagsCacheStatus::Stale);
+        assert!(matches!(
+            stale,
+            FeatureFlagsResponse::V2 {
+                request_id: None,
+                ..
+            }
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 #[cfg(feature = "error-tracking")]
 use std::error::Error as StdError;
 use std::sync::OnceLock;
@@ -5,7 +6,7 @@ use std::sync::OnceLock;
 #[cfg(feature = "error-tracking")]
 use crate::error_tracking::CaptureExceptionOptions;
 
-use crate::{client, Client, ClientOptions, Error, Event};
+use crate::{client, Client, ClientOptions, Error, Event, IdentifyOptions};
 
 static GLOBAL_CLIENT: OnceLock<Client> = OnceLock::new();
 static GLOBAL_DISABLE: OnceLock<bool> = OnceLock::new();
@@ -115,6 +116,93 @@ pub fn capture(event: Event) {
     }
 }
 
+/// The global client for the person-update helpers. `Ok(None)` when global
+/// initialization is disabled, so those helpers no-op like [`capture`].
+fn person_client() -> Result<Option<&'static Client>, Error> {
+    match GLOBAL_CLIENT.get() {
+        Some(client) => Ok(Some(client)),
+        None if is_disabled() => Ok(None),
+        None => Err(Error::NotInitialized),
+    }
+}
+
+/// Identify a person using the global client. See [`Client::identify`].
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if `init_global` has not run. No-op
+/// returning `Ok(())` if the global client is [disabled](disable).
+pub fn identify<S: Into<String>>(distinct_id: S, options: IdentifyOptions) -> Result<(), Error> {
+    match person_client()? {
+        Some(client) => client.identify(distinct_id, options),
+        None => Ok(()),
+    }
+}
+
+/// Link `alias` to `distinct_id` using the global client. See [`Client::alias`].
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if `init_global` has not run. No-op
+/// returning `Ok(())` if the global client is [disabled](disable).
+pub fn alias<D: Into<String>, A: Into<String>>(distinct_id: D, alias: A) -> Result<(), Error> {
+    match person_client()? {
+        Some(client) => client.alias(distinct_id, alias),
+        None => Ok(()),
+    }
+}
+
+/// Set person properties using the global client. See
+/// [`Client::set_person_properties`].
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if `init_global` has not run. No-op
+/// returning `Ok(())` if the global client is [disabled](disable).
+pub fn set_person_properties<S: Into<String>>(
+    distinct_id: S,
+    properties: HashMap<String, serde_json::Value>,
+) -> Result<(), Error> {
+    match person_client()? {
+        Some(client) => client.set_person_properties(distinct_id, properties),
+        None => Ok(()),
+    }
+}
+
+/// Set person properties only where unset, using the global client. See
+/// [`Client::set_person_properties_once`].
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if `init_global` has not run. No-op
+/// returning `Ok(())` if the global client is [disabled](disable).
+pub fn set_person_properties_once<S: Into<String>>(
+    distinct_id: S,
+    properties: HashMap<String, serde_json::Value>,
+) -> Result<(), Error> {
+    match person_client()? {
+        Some(client) => client.set_person_properties_once(distinct_id, properties),
+        None => Ok(()),
+    }
+}
+
+/// Remove person properties using the global client. See
+/// [`Client::unset_person_properties`].
+///
+/// # Errors
+///
+/// Returns [`Error::NotInitialized`] if `init_global` has not run. No-op
+/// returning `Ok(())` if the global client is [disabled](disable).
+pub fn unset_person_properties<S: Into<String>>(
+    distinct_id: S,
+    keys: Vec<String>,
+) -> Result<(), Error> {
+    match person_client()? {
+        Some(client) => client.unset_person_properties(distinct_id, keys),
+        None => Ok(()),
+    }
+}
+
 /// Flush the global client's queued events, awaiting the worker's next delivery
 /// attempt. No-op if `init_global` has not run.
 ///
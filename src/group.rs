@@ -0,0 +1,141 @@
+//! Group analytics: creating and updating group properties.
+//!
+//! [`Event::add_group`](crate::Event::add_group) attaches a group to an event;
+//! a [`Group`] instead describes the group itself and converts into a
+//! `$groupidentify` event that records its `$group_set` properties. Because it
+//! is an ordinary [`Event`], it can be sent with `capture`, `capture_batch`, or
+//! `capture_immediate` on either capture pipeline.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Error, Event};
+
+const GROUP_IDENTIFY_EVENT: &str = "$groupidentify";
+
+/// A group and the properties to record on it.
+///
+/// Convert into an [`Event`] (`Event::from(group)`) to send it through any
+/// capture API, or use [`Client::group_identify`] for the common case.
+///
+/// # Examples
+///
+/// ```
+/// use posthog_rs::{Event, Group};
+///
+/// # fn example() -> Result<(), posthog_rs::Error> {
+/// let group = Group::new("company", "acme")
+///     .set("name", "Acme Inc.")?
+///     .set("employees", 42)?;
+/// let event = Event::from(group);
+/// assert_eq!(event.event_name(), "$groupidentify");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Client::group_identify`]: crate::Client::group_identify
+#[derive(Debug, Clone)]
+pub struct Group {
+    group_type: String,
+    group_key: String,
+    properties: HashMap<String, Value>,
+    distinct_id: Option<String>,
+}
+
+impl Group {
+    /// Describe the group `group_key` of type `group_type` (for example
+    /// `"company"` and `"acme"`), with no properties yet.
+    pub fn new<T: Into<String>, K: Into<String>>(group_type: T, group_key: K) -> Self {
+        Self {
+            group_type: group_type.into(),
+            group_key: group_key.into(),
+            properties: HashMap::new(),
+            distinct_id: None,
+        }
+    }
+
+    /// Set a group property, overwriting any existing value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if `value` cannot be serialized.
+    pub fn set<K: Into<String>, V: Serialize>(mut self, key: K, value: V) -> Result<Self, Error> {
+        let value = serde_json::to_value(value).map_err(|e| Error::Serialization(e.to_string()))?;
+        self.properties.insert(key.into(), value);
+        Ok(self)
+    }
+
+    /// Set all `properties` at once, merging into any already set.
+    pub fn properties(mut self, properties: HashMap<String, Value>) -> Self {
+        self.properties.extend(properties);
+        self
+    }
+
+    /// Override the event's distinct ID. Defaults to
+    /// `$<group_type>_<group_key>`, which keeps the update personless.
+    pub fn distinct_id<S: Into<String>>(mut self, distinct_id: S) -> Self {
+        self.distinct_id = Some(distinct_id.into());
+        self
+    }
+}
+
+impl From<Group> for Event {
+    /// Build the `$groupidentify` event recording the group's `$group_set`.
+    fn from(group: Group) -> Self {
+        let Group {
+            group_type,
+            group_key,
+            properties,
+            distinct_id,
+        } = group;
+
+        let distinct_id = distinct_id.unwrap_or_else(|| format!("${group_type}_{group_key}"));
+        let mut event = Event::new(GROUP_IDENTIFY_EVENT.to_string(), distinct_id);
+        event.insert_prop_default("$group_type", Value::String(group_type));
+        event.insert_prop_default("$group_key", Value::String(group_key));
+        event.insert_prop_default(
+            "$group_set",
+            Value::Object(properties.into_iter().collect()),
+        );
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn group_converts_to_groupidentify_event() {
+        let group = Group::new("company", "acme")
+            .set("name", "Acme Inc.")
+            .unwrap()
+            .properties(HashMap::from([("employees".to_string(), json!(42))]));
+
+        let event = Event::from(group);
+
+        assert_eq!(event.event_name(), "$groupidentify");
+        assert_eq!(event.distinct_id(), "$company_acme");
+        let props = event.properties();
+        assert_eq!(props.get("$group_type"), Some(&json!("company")));
+        assert_eq!(props.get("$group_key"), Some(&json!("acme")));
+        assert_eq!(
+            props.get("$group_set"),
+            Some(&json!({"name": "Acme Inc.", "employees": 42}))
+        );
+        // Group updates don't attach `$groups` or force person processing.
+        assert!(event.groups().is_empty());
+        assert!(!props.contains_key("$process_person_profile"));
+    }
+
+    #[test]
+    fn group_distinct_id_override() {
+        let event = Event::from(Group::new("company", "acme").distinct_id("user-1"));
+
+        assert_eq!(event.distinct_id(), "user-1");
+        assert_eq!(event.properties().get("$group_set"), Some(&json!({})));
+    }
+}
@@ -0,0 +1,450 @@
+//! Pluggable HTTP layer.
+//!
+//! Every request the SDK makes — capture batches, immediate capture, remote
+//! `/flags` and the local-evaluation definitions pollers — goes through an
+//! [`HttpTransport`]. [`ReqwestTransport`] is the default; supply your own with
+//! [`ClientOptionsBuilder::http_transport`](crate::ClientOptionsBuilder::http_transport)
+//! to route requests through a custom HTTP stack (DNS, egress proxy, request
+//! signing) or an in-memory fake in tests.
+
+use std::fmt;
+#[cfg(feature = "async-client")]
+use std::future::Future;
+#[cfg(feature = "async-client")]
+use std::pin::Pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// HTTP method of an [`HttpRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HttpMethod {
+    /// `GET`, used by the flag definitions pollers.
+    Get,
+    /// `POST`, used by capture and remote `/flags` requests.
+    Post,
+}
+
+impl HttpMethod {
+    /// The method name as sent on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+        }
+    }
+}
+
+/// A request the SDK asks an [`HttpTransport`] to send.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    /// Request method.
+    pub method: HttpMethod,
+    /// Absolute request URL, including any query string.
+    pub url: String,
+    /// Request headers, in insertion order. Names are lowercase.
+    pub headers: Vec<(String, String)>,
+    /// Request body. Empty for `GET`.
+    pub body: Vec<u8>,
+    /// Time budget for the whole request. Transports should fail with
+    /// [`TransportErrorKind::Timeout`] once it is spent; `None` means no limit.
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub(crate) fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: HttpMethod::Get,
+            url: url.into(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    pub(crate) fn post(url: impl Into<String>, body: Vec<u8>) -> Self {
+        Self {
+            method: HttpMethod::Post,
+            url: url.into(),
+            headers: Vec::new(),
+            body,
+            timeout: None,
+        }
+    }
+
+    /// Set `name` to `value`, replacing an earlier value for the same header.
+    pub(crate) fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        let name = name.to_ascii_lowercase();
+        let value = value.into();
+        match self.headers.iter_mut().find(|(n, _)| *n == name) {
+            Some(slot) => slot.1 = value,
+            None => self.headers.push((name, value)),
+        }
+        self
+    }
+
+    /// Copy every header of a prebuilt `HeaderMap` onto the request.
+    #[cfg(feature = "capture-v1")]
+    pub(crate) fn with_header_map(mut self, headers: &reqwest::header::HeaderMap) -> Self {
+        for (name, value) in headers {
+            if let Ok(value) = value.to_str() {
+                self = self.with_header(name.as_str(), value);
+            }
+        }
+        self
+    }
+
+    pub(crate) fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Value of the header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// A response returned by an [`HttpTransport`].
+///
+/// Any status — including `4xx`/`5xx` — is a response; the SDK applies its own
+/// retry and error handling to it. Only failures to get a response at all are
+/// a [`TransportError`].
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// Response headers. Looked up case-insensitively by [`HttpResponse::header`].
+    pub headers: Vec<(String, String)>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Create a response with `status`, `body` and no headers.
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add a response header.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Value of the header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Whether the status is `2xx`.
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&self.status)
+    }
+
+    /// The body decoded as UTF-8, replacing invalid sequences.
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// The status as reported in error messages, e.g. `503 Service Unavailable`.
+    pub(crate) fn status_line(&self) -> String {
+        match reqwest::StatusCode::from_u16(self.status) {
+            Ok(status) => status.to_string(),
+            Err(_) => self.status.to_string(),
+        }
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Why an [`HttpTransport`] could not get a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransportErrorKind {
+    /// The request's [`timeout`](HttpRequest::timeout) elapsed.
+    Timeout,
+    /// No connection could be established (refused, DNS failure, TLS
+    /// handshake). Remote `/flags` requests do not retry these.
+    Connect,
+    /// The connection was reset or closed before the response completed.
+    Interrupted,
+    /// Any other failure.
+    Other,
+}
+
+/// Failure to get a response from an [`HttpTransport`].
+#[derive(Debug, Clone)]
+pub struct TransportError {
+    kind: TransportErrorKind,
+    message: String,
+}
+
+impl TransportError {
+    /// Create an error of `kind` described by `message`.
+    pub fn new(kind: TransportErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// The failure category.
+    pub fn kind(&self) -> TransportErrorKind {
+        self.kind
+    }
+
+    /// Whether a remote `/flags` request should be retried after this error.
+    pub(crate) fn is_retryable(&self) -> bool {
+        self.kind != TransportErrorKind::Connect
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+/// Future returned by [`HttpTransport::send_async`].
+#[cfg(feature = "async-client")]
+pub type HttpFuture = Pin<Box<dyn Future<Output = Result<HttpResponse, TransportError>> + Send>>;
+
+/// Sends the SDK's HTTP requests.
+///
+/// Implementations must be thread-safe: the background capture worker, the
+/// definitions poller and callers evaluating flags share one transport and may
+/// send concurrently. [`send`](Self::send) is called from plain threads (the
+/// capture worker, the blocking client and [`FlagPoller`](crate::FlagPoller)),
+/// never from inside an async runtime.
+pub trait HttpTransport: Send + Sync + 'static {
+    /// Send `request` and wait for the complete response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransportError`] when no response was received. A response
+    /// with an error status is `Ok`.
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError>;
+
+    /// Send `request` from the async client and
+    /// [`AsyncFlagPoller`](crate::AsyncFlagPoller).
+    ///
+    /// The default runs [`send`](Self::send) on tokio's blocking thread pool.
+    /// Override it when the underlying HTTP stack is natively async.
+    #[cfg(feature = "async-client")]
+    fn send_async(self: Arc<Self>, request: HttpRequest) -> HttpFuture {
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || self.send(request))
+                .await
+                .unwrap_or_else(|e| {
+                    Err(TransportError::new(
+                        TransportErrorKind::Other,
+                        e.to_string(),
+                    ))
+                })
+        })
+    }
+}
+
+/// The default [`HttpTransport`], backed by `reqwest` with rustls.
+///
+/// Wrap it to decorate requests (for example, to add a signature header) while
+/// keeping the default connection handling.
+pub struct ReqwestTransport {
+    // Built on first blocking send, on the thread doing the sending, so an
+    // async-only client never spins up reqwest's blocking runtime.
+    blocking: OnceLock<reqwest::blocking::Client>,
+    #[cfg(feature = "async-client")]
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Create a transport with reqwest's default connection settings.
+    pub fn new() -> Self {
+        Self {
+            blocking: OnceLock::new(),
+            #[cfg(feature = "async-client")]
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ReqwestTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReqwestTransport").finish_non_exhaustive()
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let client = self.blocking.get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .build()
+                .unwrap_or_default()
+        });
+        let mut builder = match request.method {
+            HttpMethod::Get => client.get(&request.url),
+            HttpMethod::Post => client.post(&request.url).body(request.body),
+        };
+        for (name, value) in &request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = builder.send().map_err(transport_error)?;
+        let status = response.status().as_u16();
+        let headers = response_headers(response.headers());
+        let body = response.bytes().map_err(transport_error)?.to_vec();
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    #[cfg(feature = "async-client")]
+    fn send_async(self: Arc<Self>, request: HttpRequest) -> HttpFuture {
+        Box::pin(async move {
+            let mut builder = match request.method {
+                HttpMethod::Get => self.client.get(&request.url),
+                HttpMethod::Post => self.client.post(&request.url).body(request.body),
+            };
+            for (name, value) in &request.headers {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+            if let Some(timeout) = request.timeout {
+                builder = builder.timeout(timeout);
+            }
+            let response = builder.send().await.map_err(transport_error)?;
+            let status = response.status().as_u16();
+            let headers = response_headers(response.headers());
+            let body = response.bytes().await.map_err(transport_error)?.to_vec();
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+fn response_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Classify a reqwest failure. An I/O error in the source chain decides the
+/// kind: a reset, timeout or early EOF interrupted a live connection, anything
+/// else kept one from being established.
+fn transport_error(err: reqwest::Error) -> TransportError {
+    let kind = if err.is_timeout() {
+        TransportErrorKind::Timeout
+    } else {
+        io_error_kind(&err).unwrap_or_else(|| {
+            if err
+                .to_string()
+                .to_lowercase()
+                .contains("connection refused")
+            {
+                TransportErrorKind::Connect
+            } else {
+                TransportErrorKind::Other
+            }
+        })
+    };
+    TransportError::new(kind, err.to_string())
+}
+
+fn io_error_kind(err: &reqwest::Error) -> Option<TransportErrorKind> {
+    let mut source = std::error::Error::source(err);
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            return Some(match io_error.kind() {
+                std::io::ErrorKind::TimedOut => TransportErrorKind::Timeout,
+                std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::UnexpectedEof => {
+                    TransportErrorKind::Interrupted
+                }
+                _ => TransportErrorKind::Connect,
+            });
+        }
+        source = std::error::Error::source(error);
+    }
+    None
+}
+
+/// Shared handle to the configured transport, stored on
+/// [`ClientOptions`](crate::ClientOptions).
+#[derive(Clone)]
+pub(crate) struct SharedTransport(pub(crate) Arc<dyn HttpTransport>);
+
+impl SharedTransport {
+    pub(crate) fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        self.0.send(request)
+    }
+
+    #[cfg(feature = "async-client")]
+    pub(crate) fn send_async(&self, request: HttpRequest) -> HttpFuture {
+        self.0.clone().send_async(request)
+    }
+}
+
+impl Default for SharedTransport {
+    fn default() -> Self {
+        Self(Arc::new(ReqwestTransport::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_matched_case_insensitively_and_replaced() {
+        let request = HttpRequest::get("http://localhost")
+            .with_header("Content-Type", "text/plain")
+            .with_header("content-type", "application/json");
+        assert_eq!(request.headers.len(), 1);
+        assert_eq!(request.header("CONTENT-TYPE"), Some("application/json"));
+
+        let response = HttpResponse::new(429, "").with_header("Retry-After", "5");
+        assert_eq!(response.header("retry-after"), Some("5"));
+        assert_eq!(response.status_line(), "429 Too Many Requests");
+    }
+
+    #[test]
+    fn only_connect_failures_are_not_retryable() {
+        for (kind, retryable) in [
+            (TransportErrorKind::Timeout, true),
+            (TransportErrorKind::Interrupted, true),
+            (TransportErrorKind::Other, true),
+            (TransportErrorKind::Connect, false),
+        ] {
+            assert_eq!(
+                TransportError::new(kind, "boom").is_retryable(),
+                retryable,
+                "{:?}",
+                kind
+            );
+        }
+    }
+}
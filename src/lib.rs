@@ -87,8 +87,12 @@
 //! before advancing its own durable state (for example, a server-side importer
 //! committing an upstream offset); prefer fire-and-forget everywhere else.
 mod client;
+mod cohort_membership;
+mod compiled_flags;
 mod compression;
 mod constants;
+mod definitions_cache;
+mod definitions_diff;
 mod endpoints;
 mod error;
 #[cfg(feature = "error-tracking")]
@@ -96,10 +100,19 @@ mod error_tracking;
 mod event;
 #[cfg(feature = "capture-v1")]
 mod event_v1;
+mod experiments;
 mod feature_flag_evaluations;
 mod feature_flags;
+mod flag_explanation;
+mod flags_cache;
 mod global;
+mod group;
+mod http;
 mod local_evaluation;
+#[cfg(feature = "mock-server")]
+mod mock_server;
+mod person;
+mod property_providers;
 
 // Public interface - any change to this is breaking!
 // Client
@@ -111,7 +124,15 @@ pub use client::Client;
 pub use client::ClientOptions;
 pub use client::ClientOptionsBuilder;
 pub use client::ClientOptionsBuilderError;
-pub use client::{CaptureFailure, FlagsFailure, LocalEvaluationFailure, PostHogError};
+pub use client::ClientStats;
+pub use client::FlagOverrides;
+pub use client::FlagRequestStats;
+pub use client::OverflowPolicy;
+pub use client::SpoolOptions;
+pub use client::{
+    CaptureFailure, FlagsFailure, LocalEvaluationFailure, PostHogError, QueueOverflowFailure,
+};
+pub use client::{DeadLetterReason, DeadLetterSink, NdjsonDeadLetterSink};
 
 // Endpoints
 pub use endpoints::{
@@ -136,25 +157,54 @@ pub use event::Event;
 pub use event_v1::{CaptureResponse, EventResult, EventStatus, V1ErrorResponse};
 
 // Feature Flags
+pub use experiments::ExperimentAssignment;
 pub use feature_flag_evaluations::{EvaluateFlagsOptions, FeatureFlagEvaluations};
 pub use feature_flags::{
-    match_feature_flag, match_feature_flag_with_context, match_property_with_context,
-    CohortDefinition, EvaluationContext, FeatureFlag, FeatureFlagCondition, FeatureFlagFilters,
-    FeatureFlagsResponse, FlagDetail, FlagMetadata, FlagReason, FlagValue, InconclusiveMatchError,
-    MultivariateFilter, MultivariateVariant, Property,
+    match_feature_flag, match_feature_flag_detailed, match_feature_flag_with_context,
+    match_property_with_context, CohortDefinition, EvaluationContext, FeatureFlag,
+    FeatureFlagCondition, FeatureFlagFilters, FeatureFlagsResponse, FlagDetail, FlagMetadata,
+    FlagReason, FlagValue, InconclusiveMatchError, MultivariateFilter, MultivariateVariant,
+    Property, PropertyGroup, PropertyGroupType, PropertyGroupValue,
+};
+pub use flag_explanation::{
+    ConditionExplanation, ConditionKind, ConditionOutcome, FlagExplanation, PropertyExplanation,
+    PropertyOutcome, RolloutExplanation, VariantExplanation,
+};
+pub use flags_cache::{FlagsCacheOptions, FlagsCacheStatus};
+
+// HTTP transport
+#[cfg(feature = "async-client")]
+pub use http::HttpFuture;
+pub use http::{
+    HttpMethod, HttpRequest, HttpResponse, HttpTransport, ReqwestTransport, TransportError,
+    TransportErrorKind,
 };
 
 // Local Evaluation
+pub use cohort_membership::CohortMembershipProvider;
+pub use definitions_cache::DefinitionsCacheOptions;
+pub use definitions_diff::FlagDefinitionsDiff;
 pub use local_evaluation::{
-    Cohort, FlagCache, FlagPoller, LocalEvaluationConfig, LocalEvaluationResponse, LocalEvaluator,
+    Cohort, FlagCache, FlagPoller, FlagSnapshot, LocalEvaluationConfig, LocalEvaluationResponse,
+    LocalEvaluator,
 };
+pub use property_providers::{FlagPropertyKeys, GroupPropertyProvider, PersonPropertyProvider};
 
 #[cfg(feature = "async-client")]
 pub use local_evaluation::AsyncFlagPoller;
 
+// Mock server
+#[cfg(feature = "mock-server")]
+pub use mock_server::{MockPostHog, MockResponse, MockRoute};
+
+// Person and group updates
+pub use group::Group;
+pub use person::IdentifyOptions;
+
 // We expose global convenience functions (capture/flush/shutdown) that use a
 // global client. flush/shutdown matter because the global singleton lives in a
 // `static`, whose `Drop` never runs — they must be called to drain on exit.
+pub use global::alias;
 pub use global::capture;
 #[cfg(feature = "error-tracking")]
 pub use global::capture_exception;
@@ -162,6 +212,10 @@ pub use global::capture_exception;
 pub use global::capture_exception_with;
 pub use global::disable as disable_global;
 pub use global::flush;
+pub use global::identify;
 pub use global::init_global_client as init_global;
 pub use global::is_disabled as global_is_disabled;
+pub use global::set_person_properties;
+pub use global::set_person_properties_once;
 pub use global::shutdown;
+pub use global::unset_person_properties;
@@ -1,28 +1,174 @@
-use crate::client::{apply_on_error_hooks, get_default_user_agent, OnErrorHook};
+use crate::client::{apply_on_error_hooks, get_default_user_agent, OnErrorHook, Stats};
+use crate::cohort_membership::CohortMembershipProvider;
+use crate::compiled_flags::{
+    CompileRegex, CompiledCohort, CompiledFlag, Definitions, Environment, Outcome,
+};
+use crate::definitions_cache::DefinitionsCacheOptions;
+use crate::definitions_diff::{notify_listeners, DefinitionsListener, FlagDefinitionsDiff};
 use crate::feature_flags::{
-    match_feature_flag, match_feature_flag_with_context, CohortDefinition, EvaluationContext,
-    FeatureFlag, FlagValue, InconclusiveMatchError,
+    CohortDefinition, FeatureFlag, FlagDetail, FlagValue, InconclusiveMatchError,
+};
+use crate::flag_explanation::FlagExplanation;
+use crate::http::{HttpRequest, HttpResponse, HttpTransport, SharedTransport, TransportError};
+use crate::property_providers::{
+    load_group_properties, load_person_properties, FlagPropertyKeys, GroupPropertyProvider,
+    PersonPropertyProvider,
 };
 use crate::{Error, LocalEvaluationFailure, PostHogError};
-use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH, USER_AGENT};
-use reqwest::StatusCode;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, error, info, instrument, trace, warn};
 
-/// Extract the ETag header value from a response's headers.
-/// Returns None if the header is missing, invalid UTF-8, or empty.
-fn extract_etag(headers: &HeaderMap) -> Option<String> {
-    headers
-        .get(ETAG)
-        .and_then(|v| v.to_str().ok())
+/// Extract the ETag header value from a response.
+/// Returns None if the header is missing or empty.
+fn extract_etag(response: &HttpResponse) -> Option<String> {
+    response
+        .header("etag")
         .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
 }
 
+/// Build a definitions request, conditional on `etag` when one is known.
+fn definitions_request(config: &LocalEvaluationConfig, etag: Option<&str>) -> HttpRequest {
+    let url = format!(
+        "{}/flags/definitions/?send_cohorts",
+        config.api_host.trim_end_matches('/')
+    );
+    let mut request = HttpRequest::get(url)
+        .with_header(
+            "authorization",
+            format!("Bearer {}", config.personal_api_key),
+        )
+        .with_header("x-posthog-project-api-key", config.project_api_key.as_str())
+        .with_header("user-agent", get_default_user_agent())
+        .with_timeout(config.request_timeout);
+    if let Some(etag) = etag {
+        request = request.with_header("if-none-match", etag);
+    }
+    request
+}
+
+/// Apply the outcome of a background poll to the cache, and to the persisted
+/// copy when there is one. Failures are logged and reported; the previous
+/// definitions stay in place. Returns whether the poll succeeded (a `304`
+/// counts).
+fn apply_poll_result(
+    result: Result<HttpResponse, TransportError>,
+    cache: &FlagCache,
+    last_etag: &mut Option<String>,
+    persisted: Option<&DefinitionsCacheOptions>,
+    on_error: &[OnErrorHook],
+) -> bool {
+    match result {
+        Ok(response) => {
+            let status = response.status;
+            if status == 304 {
+                debug!("Flag definitions unchanged (304 Not Modified)");
+                if let Some(persisted) = persisted {
+                    persisted.touch();
+                }
+                true
+            } else if response.is_success() {
+                match serde_json::from_slice::<LocalEvaluationResponse>(&response.body) {
+                    Ok(data) => {
+                        trace!("Successfully fetched flag definitions");
+                        cache.update(data);
+                        *last_etag = extract_etag(&response);
+                        if let Some(persisted) = persisted {
+                            persisted.save(&response.body, last_etag.as_deref());
+                        }
+                        true
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to parse flag response");
+                        let err = Error::Serialization(e.to_string());
+                        report_local_eval_error(on_error, Some(status), &err);
+                        false
+                    }
+                }
+            } else {
+                warn!(status = %response.status_line(), "Failed to fetch flags");
+                let err = Error::Connection(format!("HTTP {}", response.status_line()));
+                report_local_eval_error(on_error, Some(status), &err);
+                false
+            }
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to fetch flags");
+            let err = Error::Connection(e.to_string());
+            report_local_eval_error(on_error, None, &err);
+            false
+        }
+    }
+}
+
+/// Stamp a poll outcome on the owning client's stats, if there is one.
+fn record_poll(stats: Option<&Stats>, success: bool) {
+    if let Some(stats) = stats {
+        stats.definitions_poll(success);
+    }
+}
+
+/// Apply the outcome of an explicit `load_flags` to the cache, and to the
+/// persisted copy when there is one, surfacing any failure to the caller.
+fn apply_load_result(
+    result: Result<HttpResponse, TransportError>,
+    cache: &FlagCache,
+    persisted: Option<&DefinitionsCacheOptions>,
+    on_error: &[OnErrorHook],
+) -> Result<(), Error> {
+    let response = match result {
+        Ok(r) => r,
+        Err(e) => {
+            error!(error = %e, "Connection error loading flags");
+            let err = Error::Connection(e.to_string());
+            report_local_eval_error(on_error, None, &err);
+            return Err(err);
+        }
+    };
+
+    if !response.is_success() {
+        error!(status = %response.status_line(), "HTTP error loading flags");
+        let err = Error::Connection(format!("HTTP {}", response.status_line()));
+        report_local_eval_error(on_error, Some(response.status), &err);
+        return Err(err);
+    }
+
+    let data = match serde_json::from_slice::<LocalEvaluationResponse>(&response.body) {
+        Ok(d) => d,
+        Err(e) => {
+            error!(error = %e, "Failed to parse flag response");
+            let err = Error::Serialization(e.to_string());
+            report_local_eval_error(on_error, Some(response.status), &err);
+            return Err(err);
+        }
+    };
+
+    cache.update(data);
+    if let Some(persisted) = persisted {
+        persisted.save(&response.body, extract_etag(&response).as_deref());
+    }
+    Ok(())
+}
+
+/// Log the outcome of a poller's initial request made on top of persisted
+/// definitions. A failure is not fatal: the persisted definitions stay in use.
+fn log_initial_refresh(success: bool) {
+    if success {
+        info!("Initial flag definitions loaded successfully");
+    } else {
+        warn!("Failed to refresh persisted flag definitions, will retry on next poll");
+    }
+}
+
 /// Sleep up to `duration`, waking early when `stop_signal` is set. Returns
 /// `true` if a stop was requested (either already pending or observed while
 /// waiting). Polling in short steps keeps shutdown latency bounded even when
@@ -73,11 +219,30 @@ pub struct LocalEvaluationResponse {
     pub minimal_flag_called_events: bool,
 }
 
+impl LocalEvaluationResponse {
+    /// Parse definitions in the `/flags/definitions` response format, such as
+    /// a file saved from that endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] when reading fails and [`Error::Serialization`]
+    /// when the JSON does not parse.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        serde_json::from_reader(reader).map_err(|e| {
+            if e.is_io() {
+                Error::Io(io::Error::from(e))
+            } else {
+                Error::Serialization(format!("Failed to parse flag definitions: {e}"))
+            }
+        })
+    }
+}
+
 /// A cohort definition for local evaluation.
 ///
 /// Cohorts are groups of users defined by property filters, used for
 /// targeting feature flags to specific user segments.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Cohort {
     /// Unique identifier for this cohort
     pub id: String,
@@ -89,19 +254,16 @@ pub struct Cohort {
 
 /// Thread-safe cache for feature flag definitions.
 ///
-/// Stores feature flags, group type mappings, and cohort definitions that have
-/// been fetched from the PostHog API. The cache is shared between the poller
-/// (which updates it) and the evaluator (which reads from it).
+/// Holds the most recent [`FlagSnapshot`] of the flags, group type mappings,
+/// and cohort definitions fetched from the PostHog API. The cache is shared
+/// between the poller (which updates it) and the evaluator (which reads from
+/// it). Every update swaps in a whole new snapshot, so readers never see a
+/// partial update and never wait while one is compiled.
 #[derive(Clone)]
 pub struct FlagCache {
-    flags: Arc<RwLock<HashMap<String, FeatureFlag>>>,
-    group_type_mapping: Arc<RwLock<HashMap<String, String>>>,
-    cohorts: Arc<RwLock<HashMap<String, Cohort>>>,
-    /// The `minimal_flag_called_events` gate from the most recent definitions
-    /// poll. Read once when a local evaluation succeeds and pinned onto that
-    /// flag's record, so the minimization decision reflects the definitions
-    /// snapshot that produced the value.
-    minimal_flag_called_events: Arc<AtomicBool>,
+    snapshot: Arc<RwLock<Arc<FlagSnapshot>>>,
+    /// Callbacks told what changed on every [`update`](Self::update).
+    listeners: Arc<RwLock<Vec<DefinitionsListener>>>,
 }
 
 impl Default for FlagCache {
@@ -114,105 +276,585 @@ impl FlagCache {
     /// Create an empty shared flag cache.
     pub fn new() -> Self {
         Self {
-            flags: Arc::new(RwLock::new(HashMap::new())),
-            group_type_mapping: Arc::new(RwLock::new(HashMap::new())),
-            cohorts: Arc::new(RwLock::new(HashMap::new())),
-            minimal_flag_called_events: Arc::new(AtomicBool::new(false)),
+            snapshot: Arc::new(RwLock::new(Arc::default())),
+            listeners: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
     /// Replace cached flags, group type mappings, and cohorts from a local
     /// evaluation API response.
+    ///
+    /// The new definitions are compiled before they are swapped in;
+    /// evaluations already running finish against the snapshot they started
+    /// with. Listeners registered with [`on_change`](Self::on_change) are told
+    /// what changed once the new definitions are in place.
     pub fn update(&self, response: LocalEvaluationResponse) {
         let flag_count = response.flags.len();
-        let mut flags = self.flags.write().unwrap();
-        flags.clear();
-        for flag in response.flags {
-            flags.insert(flag.key.clone(), flag);
+        let snapshot = Arc::new(FlagSnapshot::new(response));
+        let previous =
+            std::mem::replace(&mut *self.snapshot.write().unwrap(), Arc::clone(&snapshot));
+        debug!(flag_count, "Updated flag cache");
+
+        // Only pay for the comparison when someone is listening. Listeners run
+        // after the lock is released, so they can read the cache they were
+        // notified about.
+        let listeners = self.listeners.read().unwrap().clone();
+        if listeners.is_empty() {
+            return;
+        }
+        let diff = FlagDefinitionsDiff::between(
+            &previous.flags,
+            &snapshot.flags,
+            &previous.cohorts,
+            &snapshot.cohorts,
+        );
+        if !diff.is_empty() {
+            notify_listeners(&listeners, &diff);
         }
+    }
 
-        let mut mapping = self.group_type_mapping.write().unwrap();
-        *mapping = response.group_type_mapping;
+    /// Call `listener` with a [`FlagDefinitionsDiff`] each time an
+    /// [`update`](Self::update) changes the cached definitions, whether they
+    /// came from the poller, a persisted copy or a static file. Updates that
+    /// change nothing, such as a poll answered with `304 Not Modified`, are
+    /// not reported.
+    ///
+    /// The listener runs synchronously on the thread applying the update —
+    /// usually the poller's — so keep it cheap. Listeners are shared by every
+    /// clone of this cache and stay registered for its lifetime. Panics are
+    /// caught and ignored.
+    pub fn on_change<F>(&self, listener: F)
+    where
+        F: Fn(&FlagDefinitionsDiff) + Send + Sync + 'static,
+    {
+        self.listeners
+            .write()
+            .unwrap()
+            .push(DefinitionsListener::new(listener));
+    }
 
-        let mut cohorts = self.cohorts.write().unwrap();
-        *cohorts = response.cohorts;
+    /// Replace the cached definitions with ones read from `reader`, in the
+    /// `/flags/definitions` response format. The cache is left untouched if
+    /// they fail to parse.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from [`LocalEvaluationResponse::from_reader`].
+    pub fn load_from_reader<R: Read>(&self, reader: R) -> Result<(), Error> {
+        self.update(LocalEvaluationResponse::from_reader(reader)?);
+        Ok(())
+    }
 
-        self.minimal_flag_called_events
-            .store(response.minimal_flag_called_events, Ordering::Relaxed);
+    /// Replace the cached definitions with the contents of the file at
+    /// `path`, such as a checked-in copy of a `/flags/definitions` response.
+    /// No network access or secret key is involved.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] when the file cannot be read and
+    /// [`Error::Serialization`] when it does not parse; the cache is left
+    /// untouched either way.
+    pub fn load_from_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = File::open(path).map_err(Error::Io)?;
+        self.load_from_reader(BufReader::new(file))
+    }
 
-        debug!(flag_count, "Updated flag cache");
+    /// The current definitions. The snapshot stays valid, and unchanged, for
+    /// as long as it is held, even when the cache is updated meanwhile.
+    pub fn snapshot(&self) -> Arc<FlagSnapshot> {
+        Arc::clone(&self.snapshot.read().unwrap())
     }
 
     /// Whether the most recent definitions poll enabled minimal
     /// `$feature_flag_called` events. `false` until definitions load, so a
     /// missing signal always yields full events.
     pub fn minimal_flag_called_events(&self) -> bool {
-        self.minimal_flag_called_events.load(Ordering::Relaxed)
+        self.snapshot().minimal_flag_called_events()
     }
 
-    /// Return a cached feature flag by key.
-    pub fn get_flag(&self, key: &str) -> Option<FeatureFlag> {
-        self.flags.read().unwrap().get(key).cloned()
+    /// The property keys the flag `key` filters on in the current
+    /// definitions, to fetch them before evaluating. `None` when the flag is
+    /// not defined. See [`FlagSnapshot::property_keys`].
+    pub fn property_keys(&self, key: &str) -> Option<FlagPropertyKeys> {
+        self.snapshot().property_keys(key)
     }
 
-    /// Return a flag's `has_experiment` signal without cloning the full
-    /// [`FeatureFlag`] (its filters can hold nested `Vec`s and JSON values).
-    pub(crate) fn has_experiment(&self, key: &str) -> Option<bool> {
-        self.flags
-            .read()
-            .unwrap()
-            .get(key)
-            .and_then(|f| f.has_experiment)
+    /// Return a cached feature flag by key.
+    pub fn get_flag(&self, key: &str) -> Option<FeatureFlag> {
+        self.snapshot().flag(key).cloned()
     }
 
     /// Return all cached feature flag definitions.
     pub fn get_all_flags(&self) -> Vec<FeatureFlag> {
-        self.flags.read().unwrap().values().cloned().collect()
+        self.snapshot().flags().cloned().collect()
     }
 
     /// Return a cached cohort by ID.
     pub fn get_cohort(&self, id: &str) -> Option<Cohort> {
-        self.cohorts.read().unwrap().get(id).cloned()
+        self.snapshot().cohort(id).cloned()
     }
 
     /// Return all cached cohorts, keyed by cohort ID.
     pub fn get_all_cohorts(&self) -> HashMap<String, Cohort> {
-        self.cohorts.read().unwrap().clone()
+        self.snapshot().cohorts.clone()
     }
 
     /// Get all cohorts as CohortDefinitions for evaluation context
     pub fn get_cohort_definitions(&self) -> HashMap<String, CohortDefinition> {
-        self.cohorts
-            .read()
-            .unwrap()
+        self.snapshot()
+            .cohorts
             .iter()
-            .map(|(k, v)| {
-                (
-                    k.clone(),
-                    CohortDefinition {
-                        id: v.id.clone(),
-                        properties: v.properties.clone(),
-                    },
-                )
-            })
+            .map(|(k, v)| (k.clone(), cohort_definition(v)))
             .collect()
     }
 
     /// Get all flags as a HashMap for evaluation context
     pub fn get_flags_map(&self) -> HashMap<String, FeatureFlag> {
-        self.flags.read().unwrap().clone()
+        self.snapshot().flags.clone()
     }
 
     /// Get the group type mapping (group type index → group type name).
     pub fn get_group_type_mapping(&self) -> HashMap<String, String> {
-        self.group_type_mapping.read().unwrap().clone()
+        self.snapshot().group_type_mapping.clone()
     }
 
     /// Remove all cached flags, group type mappings, and cohorts.
     pub fn clear(&self) {
-        self.flags.write().unwrap().clear();
-        self.group_type_mapping.write().unwrap().clear();
-        self.cohorts.write().unwrap().clear();
+        *self.snapshot.write().unwrap() = Arc::default();
+    }
+}
+
+fn cohort_definition(cohort: &Cohort) -> CohortDefinition {
+    CohortDefinition {
+        id: cohort.id.clone(),
+        properties: cohort.properties.clone(),
+    }
+}
+
+/// An immutable copy of the cached definitions, compiled for evaluation.
+///
+/// Building a snapshot compiles regexes and parses the date, semver and
+/// numeric targets of every release condition and cohort once, so evaluating
+/// against it borrows instead of cloning or re-parsing. Get the current one
+/// with [`FlagCache::snapshot`].
+#[derive(Debug, Default)]
+pub struct FlagSnapshot {
+    flags: HashMap<String, FeatureFlag>,
+    cohorts: HashMap<String, Cohort>,
+    group_type_mapping: HashMap<String, String>,
+    /// The `minimal_flag_called_events` gate these definitions came with.
+    /// Read once when a local evaluation succeeds and pinned onto that flag's
+    /// record, so the minimization decision reflects the definitions that
+    /// produced the value.
+    minimal_flag_called_events: bool,
+    compiled_flags: HashMap<String, CompiledFlag>,
+    compiled_cohorts: HashMap<String, CompiledCohort>,
+    /// `group_type_mapping` keyed by group type index.
+    group_types: HashMap<i32, String>,
+}
+
+impl FlagSnapshot {
+    fn new(response: LocalEvaluationResponse) -> Self {
+        let compile_regex: CompileRegex = |pattern| Regex::new(pattern).ok();
+        let flags: HashMap<String, FeatureFlag> = response
+            .flags
+            .into_iter()
+            .map(|flag| (flag.key.clone(), flag))
+            .collect();
+        let compiled_flags = flags
+            .iter()
+            .map(|(key, flag)| (key.clone(), CompiledFlag::new(flag, compile_regex)))
+            .collect();
+        let compiled_cohorts = response
+            .cohorts
+            .iter()
+            .map(|(id, cohort)| {
+                let compiled = CompiledCohort::new(&cohort_definition(cohort), compile_regex);
+                (id.clone(), compiled)
+            })
+            .collect();
+        let group_types = response
+            .group_type_mapping
+            .iter()
+            .filter_map(|(index, name)| Some((index.parse().ok()?, name.clone())))
+            .collect();
+
+        Self {
+            flags,
+            cohorts: response.cohorts,
+            group_type_mapping: response.group_type_mapping,
+            minimal_flag_called_events: response.minimal_flag_called_events,
+            compiled_flags,
+            compiled_cohorts,
+            group_types,
+        }
+    }
+
+    /// The flag with key `key`.
+    pub fn flag(&self, key: &str) -> Option<&FeatureFlag> {
+        self.flags.get(key)
+    }
+
+    /// Every flag, in no particular order.
+    pub fn flags(&self) -> impl Iterator<Item = &FeatureFlag> {
+        self.flags.values()
+    }
+
+    /// The cohort with ID `id`.
+    pub fn cohort(&self, id: &str) -> Option<&Cohort> {
+        self.cohorts.get(id)
+    }
+
+    /// The group type mapping (group type index → group type name).
+    pub fn group_type_mapping(&self) -> &HashMap<String, String> {
+        &self.group_type_mapping
+    }
+
+    /// Whether these definitions enabled minimal `$feature_flag_called`
+    /// events.
+    pub fn minimal_flag_called_events(&self) -> bool {
+        self.minimal_flag_called_events
+    }
+
+    /// The property keys the flag `key` filters on, including those of the
+    /// cohorts it targets. `None` when the flag is not defined.
+    ///
+    /// Pass these properties to evaluation to keep the flag from being
+    /// inconclusive, or load them with a [`PersonPropertyProvider`] or
+    /// [`GroupPropertyProvider`].
+    pub fn property_keys(&self, key: &str) -> Option<FlagPropertyKeys> {
+        let flag = self.flags.get(key)?;
+        Some(FlagPropertyKeys::of(
+            flag,
+            |id| Some(cohort_definition(self.cohorts.get(id)?).property_group()),
+            |index| self.group_types.get(&index).map(String::as_str),
+        ))
+    }
+
+    /// Evaluate the flag `key`, handing its outcome to `finish`. `Ok(None)`
+    /// when the flag is not defined.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn evaluate<T>(
+        &self,
+        key: &str,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+        resolve_references: bool,
+        providers: &Providers,
+        finish: impl FnOnce(&Outcome<'_>) -> T,
+    ) -> Result<Option<T>, InconclusiveMatchError> {
+        let Some(flag) = self.compiled_flags.get(key) else {
+            trace!(key, "Flag not found in local cache");
+            return Ok(None);
+        };
+        let evaluate =
+            |person_properties: &HashMap<String, serde_json::Value>,
+             group_properties: &HashMap<String, HashMap<String, serde_json::Value>>| {
+                let env = self.environment(distinct_id, None, groups, group_properties, providers);
+                let env = if resolve_references {
+                    env
+                } else {
+                    env.without_references()
+                };
+                flag.evaluate(person_properties, &env)
+            };
+        let mut result = evaluate(person_properties, group_properties);
+        if result.is_err() {
+            if let Some((person_properties, group_properties)) = self.load_missing_properties(
+                [key],
+                distinct_id,
+                person_properties,
+                groups,
+                group_properties,
+                providers,
+            ) {
+                result = evaluate(&person_properties, &group_properties);
+            }
+        }
+        trace!(key, ?result, "Local flag evaluation");
+        result.map(|outcome| Some(finish(&outcome)))
+    }
+
+    /// Evaluate the flag `key` like [`LocalEvaluator::evaluate_flag`], recording
+    /// each step. `None` when the flag is not defined.
+    pub fn explain(
+        &self,
+        key: &str,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+    ) -> Option<FlagExplanation> {
+        self.explain_with(
+            key,
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            &Providers::default(),
+        )
+    }
+
+    /// [`explain`](Self::explain), consulting `providers` like evaluation
+    /// does. When loaded properties settle an inconclusive flag, the
+    /// explanation shows the evaluation with them.
+    pub(crate) fn explain_with(
+        &self,
+        key: &str,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+        providers: &Providers,
+    ) -> Option<FlagExplanation> {
+        let flag = self.compiled_flags.get(key)?;
+        let env = self.environment(distinct_id, None, groups, group_properties, providers);
+        let explanation = flag.explain(person_properties, &env);
+        if explanation.value.is_some() {
+            return Some(explanation);
+        }
+        match self.load_missing_properties(
+            [key],
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            providers,
+        ) {
+            Some((person_properties, group_properties)) => {
+                let env = self.environment(distinct_id, None, groups, &group_properties, providers);
+                Some(flag.explain(&person_properties, &env))
+            }
+            None => Some(explanation),
+        }
+    }
+
+    /// Evaluate every flag, handing each outcome and flag key to `finish`.
+    /// Flags that bucket on the device use `device_id` when it is given.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn evaluate_all<T>(
+        &self,
+        distinct_id: &str,
+        device_id: Option<&str>,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+        providers: &Providers,
+        finish: impl Fn(&str, &Outcome<'_>) -> T,
+    ) -> HashMap<String, Result<T, InconclusiveMatchError>> {
+        let env = self.environment(distinct_id, device_id, groups, group_properties, providers);
+        let mut results: HashMap<_, _> = self
+            .compiled_flags
+            .iter()
+            .map(|(key, flag)| {
+                let result = flag
+                    .evaluate(person_properties, &env)
+                    .map(|outcome| finish(key, &outcome));
+                (key.clone(), result)
+            })
+            .collect();
+
+        // Load what the inconclusive flags are missing once, then evaluate
+        // just those flags again.
+        let inconclusive: Vec<&str> = self
+            .compiled_flags
+            .keys()
+            .filter(|key| matches!(results.get(*key), Some(Err(_))))
+            .map(String::as_str)
+            .collect();
+        if !inconclusive.is_empty() {
+            if let Some((person_properties, group_properties)) = self.load_missing_properties(
+                inconclusive.iter().copied(),
+                distinct_id,
+                person_properties,
+                groups,
+                group_properties,
+                providers,
+            ) {
+                let env =
+                    self.environment(distinct_id, device_id, groups, &group_properties, providers);
+                for key in inconclusive {
+                    let result = self.compiled_flags[key]
+                        .evaluate(&person_properties, &env)
+                        .map(|outcome| finish(key, &outcome));
+                    results.insert(key.to_string(), result);
+                }
+            }
+        }
+        debug!(flag_count = results.len(), "Evaluated all local flags");
+        results
+    }
+
+    /// Evaluate every flag like [`LocalEvaluator::evaluate_all_flags_detailed`].
+    pub(crate) fn evaluate_all_detailed(
+        &self,
+        distinct_id: &str,
+        device_id: Option<&str>,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+        providers: &Providers,
+    ) -> HashMap<String, Result<FlagDetail, InconclusiveMatchError>> {
+        self.evaluate_all(
+            distinct_id,
+            device_id,
+            person_properties,
+            groups,
+            group_properties,
+            providers,
+            |key, outcome| outcome.detail(key),
+        )
+    }
+
+    fn environment<'a>(
+        &'a self,
+        distinct_id: &'a str,
+        device_id: Option<&'a str>,
+        groups: &'a HashMap<String, String>,
+        group_properties: &'a HashMap<String, HashMap<String, serde_json::Value>>,
+        providers: &'a Providers,
+    ) -> Environment<'a, Self> {
+        Environment::new(self, distinct_id, groups, group_properties)
+            .with_device_id(device_id)
+            .with_cohort_membership(providers.cohort_membership.as_deref())
+    }
+
+    /// Load the properties the flags `keys` filter on but were not passed
+    /// in, from the property providers, and merge them into copies of the
+    /// passed-in properties. `None` when nothing was loaded.
+    fn load_missing_properties<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k str>,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+        providers: &Providers,
+    ) -> Option<PropertiesPair> {
+        if providers.person_properties.is_none() && providers.group_properties.is_none() {
+            return None;
+        }
+        let mut needed = FlagPropertyKeys::default();
+        for key in keys {
+            needed.extend(self.property_keys(key).unwrap_or_default());
+        }
+
+        let mut loaded_person = None;
+        if let Some(provider) = providers.person_properties.as_deref() {
+            let missing: Vec<String> = needed
+                .person
+                .into_iter()
+                .filter(|key| !person_properties.contains_key(key))
+                .collect();
+            if !missing.is_empty() {
+                let loaded = only(
+                    load_person_properties(provider, distinct_id, &missing),
+                    &missing,
+                );
+                debug!(
+                    requested = missing.len(),
+                    loaded = loaded.len(),
+                    "Loaded person properties"
+                );
+                if !loaded.is_empty() {
+                    let mut merged = person_properties.clone();
+                    merged.extend(loaded);
+                    loaded_person = Some(merged);
+                }
+            }
+        }
+
+        let mut loaded_groups: Option<HashMap<String, HashMap<String, serde_json::Value>>> = None;
+        if let Some(provider) = providers.group_properties.as_deref() {
+            for (group_type, keys) in needed.groups {
+                let Some(group_key) = groups.get(&group_type) else {
+                    continue;
+                };
+                let given = group_properties.get(&group_type);
+                let missing: Vec<String> = keys
+                    .into_iter()
+                    .filter(|key| !given.is_some_and(|given| given.contains_key(key)))
+                    .collect();
+                if missing.is_empty() {
+                    continue;
+                }
+                let loaded = only(
+                    load_group_properties(provider, &group_type, group_key, &missing),
+                    &missing,
+                );
+                debug!(
+                    group_type,
+                    requested = missing.len(),
+                    loaded = loaded.len(),
+                    "Loaded group properties"
+                );
+                if !loaded.is_empty() {
+                    loaded_groups
+                        .get_or_insert_with(|| group_properties.clone())
+                        .entry(group_type)
+                        .or_default()
+                        .extend(loaded);
+                }
+            }
+        }
+
+        if loaded_person.is_none() && loaded_groups.is_none() {
+            return None;
+        }
+        Some((
+            loaded_person.unwrap_or_else(|| person_properties.clone()),
+            loaded_groups.unwrap_or_else(|| group_properties.clone()),
+        ))
+    }
+}
+
+/// Person properties and group properties by group type.
+type PropertiesPair = (
+    HashMap<String, serde_json::Value>,
+    HashMap<String, HashMap<String, serde_json::Value>>,
+);
+
+/// The entries of `loaded` that were asked for.
+fn only(
+    mut loaded: HashMap<String, serde_json::Value>,
+    requested: &[String],
+) -> HashMap<String, serde_json::Value> {
+    loaded.retain(|key, _| requested.contains(key));
+    loaded
+}
+
+/// The callbacks local evaluation consults about what the definitions and
+/// the passed-in properties leave open.
+#[derive(Clone, Default)]
+pub(crate) struct Providers {
+    pub(crate) cohort_membership: Option<Arc<dyn CohortMembershipProvider>>,
+    pub(crate) person_properties: Option<Arc<dyn PersonPropertyProvider>>,
+    pub(crate) group_properties: Option<Arc<dyn GroupPropertyProvider>>,
+}
+
+impl Providers {
+    /// Whether any provider is set.
+    #[cfg(feature = "async-client")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cohort_membership.is_none()
+            && self.person_properties.is_none()
+            && self.group_properties.is_none()
+    }
+}
+
+impl Definitions for FlagSnapshot {
+    fn group_type(&self, index: i32) -> Option<&str> {
+        self.group_types.get(&index).map(String::as_str)
+    }
+
+    fn cohort(&self, id: &str) -> Option<Cow<'_, CompiledCohort>> {
+        self.compiled_cohorts.get(id).map(Cow::Borrowed)
+    }
+
+    fn flag(&self, key: &str) -> Option<Cow<'_, CompiledFlag>> {
+        self.compiled_flags.get(key).map(Cow::Borrowed)
     }
 }
 
@@ -244,13 +886,17 @@ pub struct LocalEvaluationConfig {
 pub struct FlagPoller {
     config: LocalEvaluationConfig,
     cache: FlagCache,
-    client: reqwest::blocking::Client,
+    transport: SharedTransport,
     stop_signal: Arc<AtomicBool>,
     thread_handle: Option<std::thread::JoinHandle<()>>,
     /// Observability hooks, injected by the client builder before `start`.
     /// Kept here rather than on `LocalEvaluationConfig` so the public config
     /// struct stays unchanged.
     on_error: Vec<OnErrorHook>,
+    /// The owning client's delivery stats, injected like `on_error`.
+    stats: Option<Arc<Stats>>,
+    /// Where fetched definitions are persisted and restored from.
+    definitions_cache: Option<DefinitionsCacheOptions>,
 }
 
 impl FlagPoller {
@@ -261,21 +907,40 @@ impl FlagPoller {
     /// - `config`: Credentials, host, polling interval, and request timeout.
     /// - `cache`: Shared cache updated by the poller.
     pub fn new(config: LocalEvaluationConfig, cache: FlagCache) -> Self {
-        let client = reqwest::blocking::Client::builder()
-            .timeout(config.request_timeout)
-            .build()
-            .unwrap();
-
         Self {
             config,
             cache,
-            client,
+            transport: SharedTransport::default(),
             stop_signal: Arc::new(AtomicBool::new(false)),
             thread_handle: None,
             on_error: Vec::new(),
+            stats: None,
+            definitions_cache: None,
         }
     }
 
+    /// Fetch definitions through `transport` instead of the default
+    /// [`ReqwestTransport`](crate::ReqwestTransport).
+    pub fn with_http_transport<T: HttpTransport>(mut self, transport: T) -> Self {
+        self.transport = SharedTransport(Arc::new(transport));
+        self
+    }
+
+    /// Persist fetched definitions as `options` describes, and load the
+    /// persisted copy into the cache when the poller starts, before its first
+    /// request.
+    pub fn with_definitions_cache(mut self, options: DefinitionsCacheOptions) -> Self {
+        self.definitions_cache = Some(options);
+        self
+    }
+
+    /// Share the client's transport. Called by the client builder before
+    /// [`FlagPoller::start`].
+    #[cfg_attr(feature = "async-client", allow(dead_code))]
+    pub(crate) fn set_http_transport(&mut self, transport: SharedTransport) {
+        self.transport = transport;
+    }
+
     /// Register `on_error` hooks. Called by the client builder before
     /// [`FlagPoller::start`]; not part of the public flag-poller API.
     // Only the blocking client (built when `async-client` is off) injects hooks.
@@ -284,9 +949,18 @@ impl FlagPoller {
         self.on_error = hooks;
     }
 
+    /// Record poll outcomes in the client's stats. Called by the client
+    /// builder before [`FlagPoller::start`].
+    #[cfg_attr(feature = "async-client", allow(dead_code))]
+    pub(crate) fn set_stats(&mut self, stats: Arc<Stats>) {
+        self.stats = Some(stats);
+    }
+
     /// Start the polling thread.
     ///
-    /// Performs an initial synchronous load, then refreshes definitions in the
+    /// Loads persisted definitions if a
+    /// [definitions cache](FlagPoller::with_definitions_cache) is set, performs
+    /// an initial synchronous load, then refreshes definitions in the
     /// background until [`FlagPoller::stop`] is called or the poller is dropped.
     pub fn start(&mut self) {
         info!(
@@ -294,83 +968,53 @@ impl FlagPoller {
             "Starting feature flag poller"
         );
 
-        // Initial load
-        match self.load_flags() {
-            Ok(()) => info!("Initial flag definitions loaded successfully"),
-            Err(e) => warn!(error = %e, "Failed to load initial flags, will retry on next poll"),
+        let mut last_etag = self.restore_definitions();
+        if last_etag.is_some() {
+            // Initial load, conditional on the persisted definitions' ETag.
+            let request = definitions_request(&self.config, last_etag.as_deref());
+            let success = apply_poll_result(
+                self.transport.send(request),
+                &self.cache,
+                &mut last_etag,
+                self.definitions_cache.as_ref(),
+                &self.on_error,
+            );
+            record_poll(self.stats.as_deref(), success);
+            log_initial_refresh(success);
+        } else {
+            // Initial load
+            match self.load_flags() {
+                Ok(()) => info!("Initial flag definitions loaded successfully"),
+                Err(e) => {
+                    warn!(error = %e, "Failed to load initial flags, will retry on next poll")
+                }
+            }
         }
 
         let config = self.config.clone();
         let cache = self.cache.clone();
         let stop_signal = self.stop_signal.clone();
         let on_error = self.on_error.clone();
+        let stats = self.stats.clone();
+        let definitions_cache = self.definitions_cache.clone();
 
-        let handle = std::thread::spawn(move || {
-            let client = reqwest::blocking::Client::builder()
-                .timeout(config.request_timeout)
-                .build()
-                .unwrap();
-
-            let mut last_etag: Option<String> = None;
+        let transport = self.transport.clone();
 
-            loop {
-                if sleep_until_stop(&stop_signal, config.poll_interval) {
-                    debug!("Flag poller received stop signal");
-                    break;
-                }
-
-                let url = format!(
-                    "{}/flags/definitions/?send_cohorts",
-                    config.api_host.trim_end_matches('/')
-                );
-
-                let mut request = client
-                    .get(&url)
-                    .header(
-                        "Authorization",
-                        format!("Bearer {}", config.personal_api_key),
-                    )
-                    .header("X-PostHog-Project-Api-Key", &config.project_api_key)
-                    .header(USER_AGENT, get_default_user_agent());
-
-                if let Some(ref etag) = last_etag {
-                    request = request.header(IF_NONE_MATCH, etag.as_str());
-                }
-
-                match request.send() {
-                    Ok(response) => {
-                        let status = response.status();
-                        if status == StatusCode::NOT_MODIFIED {
-                            debug!("Flag definitions unchanged (304 Not Modified)");
-                        } else if status.is_success() {
-                            // Extract ETag before consuming the response body
-                            let new_etag = extract_etag(response.headers());
-
-                            match response.json::<LocalEvaluationResponse>() {
-                                Ok(data) => {
-                                    trace!("Successfully fetched flag definitions");
-                                    cache.update(data);
-                                    last_etag = new_etag;
-                                }
-                                Err(e) => {
-                                    warn!(error = %e, "Failed to parse flag response");
-                                    let err = Error::Serialization(e.to_string());
-                                    report_local_eval_error(&on_error, Some(status.as_u16()), &err);
-                                }
-                            }
-                        } else {
-                            warn!(status = %status, "Failed to fetch flags");
-                            let err = Error::Connection(format!("HTTP {}", status));
-                            report_local_eval_error(&on_error, Some(status.as_u16()), &err);
-                        }
-                    }
-                    Err(e) => {
-                        warn!(error = %e, "Failed to fetch flags");
-                        let err = Error::Connection(e.to_string());
-                        report_local_eval_error(&on_error, None, &err);
-                    }
-                }
+        let handle = std::thread::spawn(move || loop {
+            if sleep_until_stop(&stop_signal, config.poll_interval) {
+                debug!("Flag poller received stop signal");
+                break;
             }
+
+            let request = definitions_request(&config, last_etag.as_deref());
+            let success = apply_poll_result(
+                transport.send(request),
+                &cache,
+                &mut last_etag,
+                definitions_cache.as_ref(),
+                &on_error,
+            );
+            record_poll(stats.as_deref(), success);
         });
 
         self.thread_handle = Some(handle);
@@ -385,52 +1029,20 @@ impl FlagPoller {
     /// parsed.
     #[instrument(skip(self), level = "debug")]
     pub fn load_flags(&self) -> Result<(), Error> {
-        let url = format!(
-            "{}/flags/definitions/?send_cohorts",
-            self.config.api_host.trim_end_matches('/')
+        let request = definitions_request(&self.config, None);
+        let result = apply_load_result(
+            self.transport.send(request),
+            &self.cache,
+            self.definitions_cache.as_ref(),
+            &self.on_error,
         );
+        record_poll(self.stats.as_deref(), result.is_ok());
+        result
+    }
 
-        let response = match self
-            .client
-            .get(&url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.personal_api_key),
-            )
-            .header("X-PostHog-Project-Api-Key", &self.config.project_api_key)
-            .header(USER_AGENT, get_default_user_agent())
-            .send()
-        {
-            Ok(r) => r,
-            Err(e) => {
-                error!(error = %e, "Connection error loading flags");
-                let err = Error::Connection(e.to_string());
-                report_local_eval_error(&self.on_error, None, &err);
-                return Err(err);
-            }
-        };
-
-        if !response.status().is_success() {
-            let status = response.status();
-            error!(status = %status, "HTTP error loading flags");
-            let err = Error::Connection(format!("HTTP {}", status));
-            report_local_eval_error(&self.on_error, Some(status.as_u16()), &err);
-            return Err(err);
-        }
-
-        let status = response.status().as_u16();
-        let data = match response.json::<LocalEvaluationResponse>() {
-            Ok(d) => d,
-            Err(e) => {
-                error!(error = %e, "Failed to parse flag response");
-                let err = Error::Serialization(e.to_string());
-                report_local_eval_error(&self.on_error, Some(status), &err);
-                return Err(err);
-            }
-        };
-
-        self.cache.update(data);
-        Ok(())
+    /// Load the persisted definitions, if any, returning their ETag.
+    fn restore_definitions(&self) -> Option<String> {
+        self.definitions_cache.as_ref()?.restore(&self.cache)
     }
 
     /// Stop the polling thread and wait for it to exit.
@@ -458,7 +1070,7 @@ impl Drop for FlagPoller {
 pub struct AsyncFlagPoller {
     config: LocalEvaluationConfig,
     cache: FlagCache,
-    client: reqwest::Client,
+    transport: SharedTransport,
     stop_signal: Arc<AtomicBool>,
     task_handle: Option<tokio::task::JoinHandle<()>>,
     is_running: Arc<tokio::sync::RwLock<bool>>,
@@ -466,6 +1078,10 @@ pub struct AsyncFlagPoller {
     /// Kept here rather than on `LocalEvaluationConfig` so the public config
     /// struct stays unchanged.
     on_error: Vec<OnErrorHook>,
+    /// The owning client's delivery stats, injected like `on_error`.
+    stats: Option<Arc<Stats>>,
+    /// Where fetched definitions are persisted and restored from.
+    definitions_cache: Option<DefinitionsCacheOptions>,
 }
 
 #[cfg(feature = "async-client")]
@@ -477,31 +1093,57 @@ impl AsyncFlagPoller {
     /// - `config`: Credentials, host, polling interval, and request timeout.
     /// - `cache`: Shared cache updated by the poller.
     pub fn new(config: LocalEvaluationConfig, cache: FlagCache) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(config.request_timeout)
-            .build()
-            .unwrap();
-
         Self {
             config,
             cache,
-            client,
+            transport: SharedTransport::default(),
             stop_signal: Arc::new(AtomicBool::new(false)),
             task_handle: None,
             is_running: Arc::new(tokio::sync::RwLock::new(false)),
             on_error: Vec::new(),
+            stats: None,
+            definitions_cache: None,
         }
     }
 
+    /// Fetch definitions through `transport` instead of the default
+    /// [`ReqwestTransport`](crate::ReqwestTransport).
+    pub fn with_http_transport<T: HttpTransport>(mut self, transport: T) -> Self {
+        self.transport = SharedTransport(Arc::new(transport));
+        self
+    }
+
+    /// Persist fetched definitions as `options` describes, and load the
+    /// persisted copy into the cache when the poller starts, before its first
+    /// request.
+    pub fn with_definitions_cache(mut self, options: DefinitionsCacheOptions) -> Self {
+        self.definitions_cache = Some(options);
+        self
+    }
+
+    /// Share the client's transport. Called by the client builder before
+    /// [`AsyncFlagPoller::start`].
+    pub(crate) fn set_http_transport(&mut self, transport: SharedTransport) {
+        self.transport = transport;
+    }
+
     /// Register `on_error` hooks. Called by the client builder before
     /// [`AsyncFlagPoller::start`]; not part of the public flag-poller API.
     pub(crate) fn set_on_error(&mut self, hooks: Vec<OnErrorHook>) {
         self.on_error = hooks;
     }
 
+    /// Record poll outcomes in the client's stats. Called by the client
+    /// builder before [`AsyncFlagPoller::start`].
+    pub(crate) fn set_stats(&mut self, stats: Arc<Stats>) {
+        self.stats = Some(stats);
+    }
+
     /// Start the polling task.
     ///
-    /// Performs an initial async load, then refreshes definitions in the
+    /// Loads persisted definitions if a
+    /// [definitions cache](AsyncFlagPoller::with_definitions_cache) is set,
+    /// performs an initial async load, then refreshes definitions in the
     /// background until [`AsyncFlagPoller::stop`] is called or the poller is
     /// dropped.
     pub async fn start(&mut self) {
@@ -520,25 +1162,42 @@ impl AsyncFlagPoller {
             "Starting async feature flag poller"
         );
 
-        // Initial load
-        match self.load_flags().await {
-            Ok(()) => info!("Initial flag definitions loaded successfully"),
-            Err(e) => warn!(error = %e, "Failed to load initial flags, will retry on next poll"),
+        let mut last_etag = self.restore_definitions();
+        if last_etag.is_some() {
+            // Initial load, conditional on the persisted definitions' ETag.
+            let request = definitions_request(&self.config, last_etag.as_deref());
+            let success = apply_poll_result(
+                self.transport.send_async(request).await,
+                &self.cache,
+                &mut last_etag,
+                self.definitions_cache.as_ref(),
+                &self.on_error,
+            );
+            record_poll(self.stats.as_deref(), success);
+            log_initial_refresh(success);
+        } else {
+            // Initial load
+            match self.load_flags().await {
+                Ok(()) => info!("Initial flag definitions loaded successfully"),
+                Err(e) => {
+                    warn!(error = %e, "Failed to load initial flags, will retry on next poll")
+                }
+            }
         }
 
         let config = self.config.clone();
         let cache = self.cache.clone();
         let stop_signal = self.stop_signal.clone();
         let is_running = self.is_running.clone();
-        let client = self.client.clone();
+        let transport = self.transport.clone();
         let on_error = self.on_error.clone();
+        let stats = self.stats.clone();
+        let definitions_cache = self.definitions_cache.clone();
 
         let task = tokio::spawn(async move {
             let mut interval = tokio::time::interval(config.poll_interval);
             interval.tick().await; // Skip the first immediate tick
 
-            let mut last_etag: Option<String> = None;
-
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
@@ -547,54 +1206,15 @@ impl AsyncFlagPoller {
                             break;
                         }
 
-                        let url = format!(
-                            "{}/flags/definitions/?send_cohorts",
-                            config.api_host.trim_end_matches('/')
+                        let request = definitions_request(&config, last_etag.as_deref());
+                        let success = apply_poll_result(
+                            transport.send_async(request).await,
+                            &cache,
+                            &mut last_etag,
+                            definitions_cache.as_ref(),
+                            &on_error,
                         );
-
-                        let mut request = client
-                            .get(&url)
-                            .header("Authorization", format!("Bearer {}", config.personal_api_key))
-                            .header("X-PostHog-Project-Api-Key", &config.project_api_key)
-                            .header(USER_AGENT, get_default_user_agent());
-
-                        if let Some(ref etag) = last_etag {
-                            request = request.header(IF_NONE_MATCH, etag.as_str());
-                        }
-
-                        match request.send().await {
-                            Ok(response) => {
-                                let status = response.status();
-                                if status == StatusCode::NOT_MODIFIED {
-                                    debug!("Flag definitions unchanged (304 Not Modified)");
-                                } else if status.is_success() {
-                                    // Extract ETag before consuming the response body
-                                    let new_etag = extract_etag(response.headers());
-
-                                    match response.json::<LocalEvaluationResponse>().await {
-                                        Ok(data) => {
-                                            trace!("Successfully fetched flag definitions");
-                                            cache.update(data);
-                                            last_etag = new_etag;
-                                        }
-                                        Err(e) => {
-                                            warn!(error = %e, "Failed to parse flag response");
-                                            let err = Error::Serialization(e.to_string());
-                                            report_local_eval_error(&on_error, Some(status.as_u16()), &err);
-                                        }
-                                    }
-                                } else {
-                                    warn!(status = %status, "Failed to fetch flags");
-                                    let err = Error::Connection(format!("HTTP {}", status));
-                                    report_local_eval_error(&on_error, Some(status.as_u16()), &err);
-                                }
-                            }
-                            Err(e) => {
-                                warn!(error = %e, "Failed to fetch flags");
-                                let err = Error::Connection(e.to_string());
-                                report_local_eval_error(&on_error, None, &err);
-                            }
-                        }
+                        record_poll(stats.as_deref(), success);
                     }
                 }
             }
@@ -615,53 +1235,21 @@ impl AsyncFlagPoller {
     /// parsed.
     #[instrument(skip(self), level = "debug")]
     pub async fn load_flags(&self) -> Result<(), Error> {
-        let url = format!(
-            "{}/flags/definitions/?send_cohorts",
-            self.config.api_host.trim_end_matches('/')
+        let request = definitions_request(&self.config, None);
+        let result = self.transport.send_async(request).await;
+        let result = apply_load_result(
+            result,
+            &self.cache,
+            self.definitions_cache.as_ref(),
+            &self.on_error,
         );
+        record_poll(self.stats.as_deref(), result.is_ok());
+        result
+    }
 
-        let response = match self
-            .client
-            .get(&url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.personal_api_key),
-            )
-            .header("X-PostHog-Project-Api-Key", &self.config.project_api_key)
-            .header(USER_AGENT, get_default_user_agent())
-            .send()
-            .await
-        {
-            Ok(r) => r,
-            Err(e) => {
-                error!(error = %e, "Connection error loading flags");
-                let err = Error::Connection(e.to_string());
-                report_local_eval_error(&self.on_error, None, &err);
-                return Err(err);
-            }
-        };
-
-        if !response.status().is_success() {
-            let status = response.status();
-            error!(status = %status, "HTTP error loading flags");
-            let err = Error::Connection(format!("HTTP {}", status));
-            report_local_eval_error(&self.on_error, Some(status.as_u16()), &err);
-            return Err(err);
-        }
-
-        let status = response.status().as_u16();
-        let data = match response.json::<LocalEvaluationResponse>().await {
-            Ok(d) => d,
-            Err(e) => {
-                error!(error = %e, "Failed to parse flag response");
-                let err = Error::Serialization(e.to_string());
-                report_local_eval_error(&self.on_error, Some(status), &err);
-                return Err(err);
-            }
-        };
-
-        self.cache.update(data);
-        Ok(())
+    /// Load the persisted definitions, if any, returning their ETag.
+    fn restore_definitions(&self) -> Option<String> {
+        self.definitions_cache.as_ref()?.restore(&self.cache)
     }
 
     /// Stop the polling task.
@@ -690,6 +1278,75 @@ impl Drop for AsyncFlagPoller {
     }
 }
 
+/// Load the static definitions file at `path` into `cache`, logging the
+/// outcome. A missing or malformed file leaves the cache as it was. Returns
+/// the version of the file that was read, for [`DefinitionsFileWatcher`].
+pub(crate) fn load_definitions_file(path: &Path, cache: &FlagCache) -> Option<FileVersion> {
+    // Taken before reading, so a write racing the load is seen as a change.
+    let version = file_version(path);
+    match cache.load_from_file(path) {
+        Ok(()) => info!(path = %path.display(), "Loaded flag definitions from file"),
+        Err(e) => {
+            error!(path = %path.display(), error = %e, "Failed to load flag definitions file")
+        }
+    }
+    version
+}
+
+/// What identifies a version of a file: its modification time and size.
+pub(crate) type FileVersion = (SystemTime, u64);
+
+fn file_version(path: &Path) -> Option<FileVersion> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Reloads a static definitions file into a [`FlagCache`] whenever its
+/// modification time or size changes, until dropped.
+pub(crate) struct DefinitionsFileWatcher {
+    stop_signal: Arc<AtomicBool>,
+    thread_handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl DefinitionsFileWatcher {
+    /// Check `path` every `interval`, reloading it once it differs from the
+    /// `loaded` version.
+    pub(crate) fn spawn(
+        path: PathBuf,
+        cache: FlagCache,
+        loaded: Option<FileVersion>,
+        interval: Duration,
+    ) -> Self {
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let stop = stop_signal.clone();
+        let handle = std::thread::spawn(move || {
+            let mut loaded = loaded;
+            while !sleep_until_stop(&stop, interval) {
+                let current = file_version(&path);
+                if current.is_some() && current != loaded {
+                    debug!(path = %path.display(), "Flag definitions file changed, reloading");
+                    // A failed reload (say, of a half-written file) is retried
+                    // on the next change.
+                    loaded = load_definitions_file(&path, &cache);
+                }
+            }
+        });
+        Self {
+            stop_signal,
+            thread_handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for DefinitionsFileWatcher {
+    fn drop(&mut self) {
+        self.stop_signal.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.thread_handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
 /// Evaluates feature flags using locally cached definitions.
 ///
 /// The evaluator reads from a [`FlagCache`] to determine flag values without
@@ -698,12 +1355,50 @@ impl Drop for AsyncFlagPoller {
 #[derive(Clone)]
 pub struct LocalEvaluator {
     cache: FlagCache,
+    providers: Providers,
 }
 
 impl LocalEvaluator {
     /// Create an evaluator backed by a shared [`FlagCache`].
     pub fn new(cache: FlagCache) -> Self {
-        Self { cache }
+        Self {
+            cache,
+            providers: Providers::default(),
+        }
+    }
+
+    /// Ask `provider` about cohorts that are missing from the flag
+    /// definitions or cannot be evaluated locally, such as static and
+    /// behavioral cohorts, instead of leaving flags that target them
+    /// inconclusive.
+    pub fn with_cohort_membership_provider<P: CohortMembershipProvider>(
+        mut self,
+        provider: P,
+    ) -> Self {
+        self.providers.cohort_membership = Some(Arc::new(provider));
+        self
+    }
+
+    /// Load person properties a flag filters on but that were not passed in
+    /// from `provider` before giving up on the flag as inconclusive.
+    pub fn with_person_property_provider<P: PersonPropertyProvider>(mut self, provider: P) -> Self {
+        self.providers.person_properties = Some(Arc::new(provider));
+        self
+    }
+
+    /// Load group properties a flag filters on but that were not passed in
+    /// from `provider` before giving up on the flag as inconclusive.
+    pub fn with_group_property_provider<P: GroupPropertyProvider>(mut self, provider: P) -> Self {
+        self.providers.group_properties = Some(Arc::new(provider));
+        self
+    }
+
+    pub(crate) fn with_providers(self, providers: Providers) -> Self {
+        Self { providers, ..self }
+    }
+
+    pub(crate) fn providers(&self) -> &Providers {
+        &self.providers
     }
 
     /// Access the underlying flag cache (e.g. to read group type mappings).
@@ -728,43 +1423,62 @@ impl LocalEvaluator {
     ///
     /// Returns [`InconclusiveMatchError`] when required properties, cohorts, or
     /// dependent flags are unavailable locally.
+    pub fn evaluate_flag(
+        &self,
+        key: &str,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+    ) -> Result<Option<FlagValue>, InconclusiveMatchError> {
+        self.cache.snapshot().evaluate(
+            key,
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            true,
+            &self.providers,
+            |outcome| outcome.value(),
+        )
+    }
+
+    /// Evaluate a feature flag locally like [`evaluate_flag`](Self::evaluate_flag),
+    /// also reporting why it evaluated to its value.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Some(detail))` when the flag is present and evaluated, with the
+    /// same reason codes and condition index as `/flags?v=2` (see
+    /// [`match_feature_flag_detailed`](crate::match_feature_flag_detailed)),
+    /// and `Ok(None)` when the flag is absent from the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InconclusiveMatchError`] when required properties, cohorts, or
+    /// dependent flags are unavailable locally.
     #[instrument(
         skip(self, person_properties, groups, group_properties),
         level = "trace"
     )]
-    pub fn evaluate_flag(
+    pub fn evaluate_flag_detailed(
         &self,
         key: &str,
         distinct_id: &str,
         person_properties: &HashMap<String, serde_json::Value>,
         groups: &HashMap<String, String>,
         group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
-    ) -> Result<Option<FlagValue>, InconclusiveMatchError> {
-        match self.cache.get_flag(key) {
-            Some(flag) => {
-                // Build evaluation context with cohorts, flags, and group info
-                let cohorts = self.cache.get_cohort_definitions();
-                let flags = self.cache.get_flags_map();
-                let group_type_mapping = self.cache.get_group_type_mapping();
-
-                let ctx = EvaluationContext {
-                    cohorts: &cohorts,
-                    flags: &flags,
-                    distinct_id,
-                    groups,
-                    group_properties,
-                    group_type_mapping: &group_type_mapping,
-                };
-
-                let result = match_feature_flag_with_context(&flag, person_properties, &ctx);
-                trace!(key, ?result, "Local flag evaluation");
-                result.map(Some)
-            }
-            None => {
-                trace!(key, "Flag not found in local cache");
-                Ok(None)
-            }
-        }
+    ) -> Result<Option<FlagDetail>, InconclusiveMatchError> {
+        self.cache.snapshot().evaluate(
+            key,
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            true,
+            &self.providers,
+            |outcome| outcome.detail(key),
+        )
     }
 
     /// Evaluate a feature flag locally without cohort or flag dependency
@@ -794,25 +1508,45 @@ impl LocalEvaluator {
         groups: &HashMap<String, String>,
         group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
     ) -> Result<Option<FlagValue>, InconclusiveMatchError> {
-        match self.cache.get_flag(key) {
-            Some(flag) => {
-                let group_type_mapping = self.cache.get_group_type_mapping();
-                let result = match_feature_flag(
-                    &flag,
-                    distinct_id,
-                    person_properties,
-                    groups,
-                    group_properties,
-                    &group_type_mapping,
-                );
-                trace!(key, ?result, "Local flag evaluation (simple)");
-                result.map(Some)
-            }
-            None => {
-                trace!(key, "Flag not found in local cache");
-                Ok(None)
-            }
-        }
+        self.cache.snapshot().evaluate(
+            key,
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            false,
+            &self.providers,
+            |outcome| outcome.value(),
+        )
+    }
+
+    /// Evaluate a feature flag locally like [`evaluate_flag`](Self::evaluate_flag),
+    /// recording every condition it tried.
+    ///
+    /// The [`FlagExplanation`] lists each condition group with its property
+    /// filters and the values they were compared against, the rollout hash
+    /// against its threshold and the variant bucket, so it shows why a user
+    /// does or does not see a flag. An inconclusive evaluation is not an
+    /// error here: the explanation has no value and names the condition that
+    /// could not be evaluated.
+    ///
+    /// Returns `None` when the flag is absent from the cache.
+    pub fn explain(
+        &self,
+        key: &str,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+    ) -> Option<FlagExplanation> {
+        self.cache.snapshot().explain_with(
+            key,
+            distinct_id,
+            person_properties,
+            groups,
+            group_properties,
+            &self.providers,
+        )
     }
 
     /// Get all flags and evaluate them with full context support.
@@ -820,39 +1554,45 @@ impl LocalEvaluator {
     /// The returned map is keyed by feature flag key. Each value can be an
     /// inconclusive error if that particular flag could not be evaluated from
     /// the supplied context.
+    pub fn evaluate_all_flags(
+        &self,
+        distinct_id: &str,
+        person_properties: &HashMap<String, serde_json::Value>,
+        groups: &HashMap<String, String>,
+        group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
+    ) -> HashMap<String, Result<FlagValue, InconclusiveMatchError>> {
+        self.cache.snapshot().evaluate_all(
+            distinct_id,
+            None,
+            person_properties,
+            groups,
+            group_properties,
+            &self.providers,
+            |_, outcome| outcome.value(),
+        )
+    }
+
+    /// Evaluate every cached flag like [`evaluate_all_flags`](Self::evaluate_all_flags),
+    /// also reporting why each evaluated to its value (see
+    /// [`evaluate_flag_detailed`](Self::evaluate_flag_detailed)).
     #[instrument(
         skip(self, person_properties, groups, group_properties),
         level = "debug"
     )]
-    pub fn evaluate_all_flags(
+    pub fn evaluate_all_flags_detailed(
         &self,
         distinct_id: &str,
         person_properties: &HashMap<String, serde_json::Value>,
         groups: &HashMap<String, String>,
         group_properties: &HashMap<String, HashMap<String, serde_json::Value>>,
-    ) -> HashMap<String, Result<FlagValue, InconclusiveMatchError>> {
-        let mut results = HashMap::new();
-
-        // Build evaluation context once for all flags
-        let cohorts = self.cache.get_cohort_definitions();
-        let flags = self.cache.get_flags_map();
-        let group_type_mapping = self.cache.get_group_type_mapping();
-
-        let ctx = EvaluationContext {
-            cohorts: &cohorts,
-            flags: &flags,
+    ) -> HashMap<String, Result<FlagDetail, InconclusiveMatchError>> {
+        self.cache.snapshot().evaluate_all_detailed(
             distinct_id,
+            None,
+            person_properties,
             groups,
             group_properties,
-            group_type_mapping: &group_type_mapping,
-        };
-
-        for flag in self.cache.get_all_flags() {
-            let result = match_feature_flag_with_context(&flag, person_properties, &ctx);
-            results.insert(flag.key.clone(), result);
-        }
-
-        debug!(flag_count = results.len(), "Evaluated all local flags");
-        results
+            &self.providers,
+        )
     }
 }
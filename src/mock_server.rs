@@ -0,0 +1,522 @@
+//! In-process PostHog server for integration tests, behind the `mock-server`
+//! feature.
+//!
+//! [`MockPostHog`] listens on a loopback port and implements the endpoints the
+//! SDK talks to: v0 capture (`/batch/`, `/i/v0/e/`), v1 capture
+//! (`/i/v1/analytics/events`), remote evaluation (`/flags/?v=2`) and the
+//! local-evaluation definitions endpoint (`/flags/definitions/`). Flags are
+//! evaluated from programmable [`LocalEvaluationResponse`] definitions, and
+//! canned [`MockResponse`]s can be queued per [`MockRoute`] to exercise retry
+//! and backoff. It is a plain HTTP/1.1 server on a std thread, so it serves both
+//! the async and the blocking client.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use crate::feature_flags::{FlagMetadata, FlagValue};
+use crate::local_evaluation::{FlagCache, LocalEvaluationResponse, Providers};
+
+/// How long a connection may stay idle before the server gives up on it.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The v1 capture path. Served in every build, since the v1 pipeline itself
+/// only exists with `capture-v1`.
+const V1_CAPTURE_PATH: &str = "/i/v1/analytics/events";
+
+/// A group of endpoints served by [`MockPostHog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MockRoute {
+    /// Every capture endpoint: `/batch/`, `/i/v0/e/` and `/i/v1/analytics/events`.
+    Capture,
+    /// Remote flag evaluation (`/flags/?v=2`).
+    Flags,
+    /// Local-evaluation definitions (`/flags/definitions/`).
+    FlagDefinitions,
+}
+
+impl MockRoute {
+    fn from_path(path: &str) -> Option<Self> {
+        let path = path.trim_end_matches('/');
+        match path {
+            "/batch" | "/i/v0/e" | V1_CAPTURE_PATH => Some(MockRoute::Capture),
+            "/flags" => Some(MockRoute::Flags),
+            "/flags/definitions" => Some(MockRoute::FlagDefinitions),
+            _ => None,
+        }
+    }
+}
+
+/// A canned response queued with [`MockPostHog::respond_next`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl MockResponse {
+    /// A response with `status` and an empty JSON object body.
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: "{}".to_string(),
+        }
+    }
+
+    /// Add a `Retry-After` header of `seconds`.
+    pub fn with_retry_after(self, seconds: u64) -> Self {
+        self.with_header("Retry-After", seconds.to_string())
+    }
+
+    /// Add a response header.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Replace the response body.
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    definitions: Option<LocalEvaluationResponse>,
+    events: Vec<Value>,
+    hits: HashMap<MockRoute, usize>,
+    queued: HashMap<MockRoute, VecDeque<MockResponse>>,
+}
+
+/// In-process PostHog server for end-to-end tests without external services.
+///
+/// Point a client at it with `ClientOptionsBuilder::host(server.base_url())`.
+/// Accepted capture requests are decompressed and their events kept in
+/// [`events`](Self::events). The server shuts down when dropped.
+///
+/// ```no_run
+/// use posthog_rs::{MockPostHog, MockResponse, MockRoute};
+///
+/// let server = MockPostHog::start();
+/// // The first capture attempt is rejected; the retry is accepted.
+/// server.respond_next(MockRoute::Capture, MockResponse::status(503).with_retry_after(1));
+/// let options = posthog_rs::ClientOptionsBuilder::default()
+///     .api_key("phc_test".to_string())
+///     .host(server.base_url())
+///     .build()
+///     .unwrap();
+/// ```
+pub struct MockPostHog {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl MockPostHog {
+    /// Bind a loopback port and start serving.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no loopback port can be bound.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock PostHog server");
+        let addr = listener.local_addr().expect("mock PostHog server address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let acceptor = {
+            let state = state.clone();
+            let shutdown = shutdown.clone();
+            std::thread::Builder::new()
+                .name("posthog-mock-server".to_string())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if shutdown.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let Ok(stream) = stream else { continue };
+                        let state = state.clone();
+                        std::thread::spawn(move || serve(stream, &state));
+                    }
+                })
+                .expect("spawn mock PostHog server thread")
+        };
+
+        Self {
+            addr,
+            state,
+            shutdown,
+            acceptor: Some(acceptor),
+        }
+    }
+
+    /// Base URL to pass as the client `host`, e.g. `http://127.0.0.1:49152`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Serve `definitions` from `/flags/definitions/` and evaluate `/flags`
+    /// requests against them. Until this is called both endpoints report no
+    /// flags.
+    pub fn set_flag_definitions(&self, definitions: LocalEvaluationResponse) {
+        self.lock().definitions = Some(definitions);
+    }
+
+    /// Events received by the capture endpoints, in arrival order.
+    pub fn events(&self) -> Vec<Value> {
+        self.lock().events.clone()
+    }
+
+    /// Forget the received events.
+    pub fn clear_events(&self) {
+        self.lock().events.clear();
+    }
+
+    /// Number of requests received for `route`, including ones answered from
+    /// the [`respond_next`](Self::respond_next) queue.
+    pub fn hits(&self, route: MockRoute) -> usize {
+        self.lock().hits.get(&route).copied().unwrap_or(0)
+    }
+
+    /// Answer the next request to `route` with `response` instead of handling
+    /// it. Queued responses are served in order, one per request; capture
+    /// requests answered this way do not record their events.
+    pub fn respond_next(&self, route: MockRoute, response: MockResponse) {
+        self.lock()
+            .queued
+            .entry(route)
+            .or_default()
+            .push_back(response);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for MockPostHog {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake the acceptor so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for MockPostHog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockPostHog")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+fn lock(state: &Mutex<MockState>) -> MutexGuard<'_, MockState> {
+    state.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Handle one request and close the connection.
+fn serve(stream: TcpStream, state: &Mutex<MockState>) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let Some(request) = read_request(&mut BufReader::new(stream)) else {
+        return;
+    };
+    let response = handle(&request, state);
+    let _ = write_response(&mut writer, &response);
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &MockResponse) -> std::io::Result<()> {
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status,
+        reason,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(response.body.as_bytes())?;
+    stream.flush()
+}
+
+fn handle(request: &Request, state: &Mutex<MockState>) -> MockResponse {
+    let Some(route) = MockRoute::from_path(&request.path) else {
+        return MockResponse::status(404).with_body(r#"{"detail":"Not found"}"#);
+    };
+
+    let mut state = lock(state);
+    *state.hits.entry(route).or_insert(0) += 1;
+    if let Some(response) = state.queued.get_mut(&route).and_then(VecDeque::pop_front) {
+        return response;
+    }
+
+    match route {
+        MockRoute::Capture => handle_capture(request, &mut state),
+        MockRoute::Flags => handle_flags(request, state.definitions.as_ref()),
+        MockRoute::FlagDefinitions => handle_definitions(request, state.definitions.as_ref()),
+    }
+}
+
+fn handle_capture(request: &Request, state: &mut MockState) -> MockResponse {
+    if request.method != "POST" {
+        return MockResponse::status(405);
+    }
+    let body = match decode_body(request) {
+        Ok(body) => body,
+        Err(e) => return invalid_payload(&e),
+    };
+    let events = match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Object(mut body)) => match body.remove("batch") {
+            Some(Value::Array(batch)) => batch,
+            // `/i/v0/e/` also accepts a single event object.
+            Some(_) => return invalid_payload("`batch` must be an array"),
+            None => vec![Value::Object(body)],
+        },
+        Ok(Value::Array(batch)) => batch,
+        Ok(_) => return invalid_payload("expected a JSON object or array"),
+        Err(e) => return invalid_payload(&e.to_string()),
+    };
+
+    let body = if request.path.starts_with(V1_CAPTURE_PATH) {
+        // v1 reports a result per event; the SDK drops events it has no result for.
+        let results: Map<String, Value> = events
+            .iter()
+            .filter_map(|e| e["uuid"].as_str())
+            .map(|uuid| (uuid.to_string(), json!({ "result": "ok" })))
+            .collect();
+        json!({ "results": results })
+    } else {
+        json!({ "status": "Ok" })
+    };
+    state.events.extend(events);
+    MockResponse::status(200).with_body(body.to_string())
+}
+
+fn invalid_payload(description: &str) -> MockResponse {
+    MockResponse::status(400).with_body(
+        json!({ "error": "invalid_payload", "error_description": description }).to_string(),
+    )
+}
+
+/// Decompress a capture body according to `Content-Encoding`, falling back to
+/// the v0 `?compression=` query parameter.
+fn decode_body(request: &Request) -> Result<Vec<u8>, String> {
+    let encoding = request.header("content-encoding").or_else(|| {
+        request
+            .query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("compression="))
+    });
+    let data = request.body.as_slice();
+    let mut out = Vec::new();
+    let result = match encoding {
+        None | Some("identity") => return Ok(data.to_vec()),
+        Some("gzip") | Some("gzip-js") => flate2::read::GzDecoder::new(data).read_to_end(&mut out),
+        Some("deflate") => flate2::read::ZlibDecoder::new(data).read_to_end(&mut out),
+        #[cfg(feature = "capture-v1")]
+        Some("br") => brotli::Decompressor::new(data, 4096).read_to_end(&mut out),
+        #[cfg(feature = "capture-v1")]
+        Some("zstd") => return zstd::stream::decode_all(data).map_err(|e| e.to_string()),
+        Some(other) => return Err(format!("unsupported content encoding `{other}`")),
+    };
+    result.map(|_| out).map_err(|e| e.to_string())
+}
+
+fn handle_flags(request: &Request, definitions: Option<&LocalEvaluationResponse>) -> MockResponse {
+    if request.method != "POST" {
+        return MockResponse::status(405);
+    }
+    let payload: Value = match serde_json::from_slice(&request.body) {
+        Ok(payload) => payload,
+        Err(e) => return invalid_payload(&e.to_string()),
+    };
+    let Some(distinct_id) = payload["distinct_id"].as_str() else {
+        return invalid_payload("missing `distinct_id`");
+    };
+    let person_properties: HashMap<String, Value> = field(&payload, "person_properties");
+    let groups: HashMap<String, String> = field(&payload, "groups");
+    let group_properties: HashMap<String, HashMap<String, Value>> =
+        field(&payload, "group_properties");
+    let only_keys: Option<Vec<String>> = field(&payload, "flag_keys_to_evaluate");
+    let device_id = payload["device_id"].as_str();
+
+    let cache = FlagCache::new();
+    if let Some(definitions) = definitions {
+        cache.update(definitions.clone());
+    }
+    let evaluated = cache.snapshot().evaluate_all_detailed(
+        distinct_id,
+        device_id,
+        &person_properties,
+        &groups,
+        &group_properties,
+        &Providers::default(),
+    );
+
+    let mut flags = HashMap::new();
+    let mut errors_while_computing_flags = false;
+    for (key, result) in evaluated {
+        if only_keys.as_ref().is_some_and(|keys| !keys.contains(&key)) {
+            continue;
+        }
+        let Ok(mut detail) = result else {
+            errors_while_computing_flags = true;
+            continue;
+        };
+        let Some(flag) = cache.get_flag(&key) else {
+            continue;
+        };
+        let payload_key = match detail.value() {
+            FlagValue::Boolean(enabled) => enabled.then(|| "true".to_string()),
+            FlagValue::String(variant) => Some(variant),
+        };
+        detail.metadata = Some(FlagMetadata {
+            id: 0,
+            version: 1,
+            description: None,
+            payload: payload_key.and_then(|k| flag.filters.payloads.get(&k).cloned()),
+            has_experiment: flag.has_experiment,
+        });
+        flags.insert(key, detail);
+    }
+
+    let body = json!({
+        "flags": flags,
+        "errorsWhileComputingFlags": errors_while_computing_flags,
+        "requestId": uuid::Uuid::now_v7().to_string(),
+        "minimalFlagCalledEvents": definitions.is_some_and(|d| d.minimal_flag_called_events),
+    });
+    MockResponse::status(200).with_body(body.to_string())
+}
+
+/// `payload[name]`, or the default when it is absent or malformed.
+fn field<T: DeserializeOwned + Default>(payload: &Value, name: &str) -> T {
+    serde_json::from_value(payload[name].clone()).unwrap_or_default()
+}
+
+fn handle_definitions(
+    request: &Request,
+    definitions: Option<&LocalEvaluationResponse>,
+) -> MockResponse {
+    if request.method != "GET" {
+        return MockResponse::status(405);
+    }
+    if request.header("authorization").is_none() {
+        return MockResponse::status(401)
+            .with_body(r#"{"detail":"Authentication credentials were not provided."}"#);
+    }
+    let body = match definitions {
+        Some(definitions) => serde_json::to_string(definitions).unwrap_or_default(),
+        None => json!({ "flags": [], "group_type_mapping": {}, "cohorts": {} }).to_string(),
+    };
+    MockResponse::status(200).with_body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_ignore_trailing_slash_and_query() {
+        assert_eq!(MockRoute::from_path("/batch/"), Some(MockRoute::Capture));
+        assert_eq!(MockRoute::from_path("/i/v0/e"), Some(MockRoute::Capture));
+        assert_eq!(
+            MockRoute::from_path("/i/v1/analytics/events"),
+            Some(MockRoute::Capture)
+        );
+        assert_eq!(MockRoute::from_path("/flags/"), Some(MockRoute::Flags));
+        assert_eq!(
+            MockRoute::from_path("/flags/definitions/"),
+            Some(MockRoute::FlagDefinitions)
+        );
+        assert_eq!(MockRoute::from_path("/decide/"), None);
+    }
+
+    #[test]
+    fn decodes_gzip_capture_bodies() {
+        let body = crate::compression::gzip(br#"{"batch":[]}"#).unwrap();
+        let request = Request {
+            method: "POST".to_string(),
+            path: "/batch/".to_string(),
+            query: "compression=gzip".to_string(),
+            headers: HashMap::new(),
+            body,
+        };
+        assert_eq!(decode_body(&request).unwrap(), br#"{"batch":[]}"#);
+    }
+}
@@ -0,0 +1,231 @@
+//! Typed builders for person-updating events.
+//!
+//! PostHog updates person profiles from ordinary events that carry reserved
+//! names and properties: `$identify` (with `$set`/`$set_once` and an optional
+//! `$anon_distinct_id` to merge), `$create_alias`, and `$set`/`$set_once`/
+//! `$unset` updates. The client methods built on these helpers
+//! ([`Client::identify`](crate::Client::identify) and friends) enqueue the
+//! resulting [`Event`] through the same background transport as `capture`, so
+//! they are batched, retried, and passed through `before_send` like any other
+//! event.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Error, Event};
+
+const IDENTIFY_EVENT: &str = "$identify";
+const CREATE_ALIAS_EVENT: &str = "$create_alias";
+const SET_EVENT: &str = "$set";
+const SET_ONCE_EVENT: &str = "$set_once";
+
+const SET_PROP: &str = "$set";
+const SET_ONCE_PROP: &str = "$set_once";
+const UNSET_PROP: &str = "$unset";
+const ANON_DISTINCT_ID_PROP: &str = "$anon_distinct_id";
+
+/// Person properties and merge options for [`Client::identify`].
+///
+/// Properties added with [`set`](Self::set) overwrite the person's existing
+/// values; those added with [`set_once`](Self::set_once) are only written when
+/// the person doesn't already have them.
+///
+/// # Examples
+///
+/// ```
+/// use posthog_rs::IdentifyOptions;
+///
+/// # fn example() -> Result<(), posthog_rs::Error> {
+/// let options = IdentifyOptions::new()
+///     .set("email", "jane@example.com")?
+///     .set_once("first_seen_plan", "free")?
+///     .anon_distinct_id("anon-device-123");
+/// # let _ = options;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Client::identify`]: crate::Client::identify
+#[derive(Debug, Clone, Default)]
+pub struct IdentifyOptions {
+    set: HashMap<String, Value>,
+    set_once: HashMap<String, Value>,
+    anon_distinct_id: Option<String>,
+}
+
+impl IdentifyOptions {
+    /// Create an empty options set: no properties and no anonymous ID to merge.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a person property, overwriting any existing value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if `value` cannot be serialized.
+    pub fn set<K: Into<String>, V: Serialize>(mut self, key: K, value: V) -> Result<Self, Error> {
+        self.set.insert(key.into(), to_value(value)?);
+        Ok(self)
+    }
+
+    /// Set a person property only if the person does not already have it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if `value` cannot be serialized.
+    pub fn set_once<K: Into<String>, V: Serialize>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Result<Self, Error> {
+        self.set_once.insert(key.into(), to_value(value)?);
+        Ok(self)
+    }
+
+    /// Merge a previously anonymous distinct ID (for example a device ID used
+    /// before login) into the identified person. Sent as `$anon_distinct_id`.
+    pub fn anon_distinct_id<S: Into<String>>(mut self, anon_distinct_id: S) -> Self {
+        self.anon_distinct_id = Some(anon_distinct_id.into());
+        self
+    }
+}
+
+fn to_value<V: Serialize>(value: V) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(|e| Error::Serialization(e.to_string()))
+}
+
+fn object(properties: HashMap<String, Value>) -> Value {
+    Value::Object(properties.into_iter().collect())
+}
+
+/// Build an `$identify` event for `distinct_id`.
+pub(crate) fn identify_event(
+    distinct_id: String,
+    options: IdentifyOptions,
+) -> Result<Event, Error> {
+    let IdentifyOptions {
+        set,
+        set_once,
+        anon_distinct_id,
+    } = options;
+
+    let mut event = Event::new(IDENTIFY_EVENT.to_string(), distinct_id);
+    event.insert_prop(SET_PROP, object(set))?;
+    if !set_once.is_empty() {
+        event.insert_prop(SET_ONCE_PROP, object(set_once))?;
+    }
+    if let Some(anon_distinct_id) = anon_distinct_id {
+        event.insert_prop(ANON_DISTINCT_ID_PROP, anon_distinct_id)?;
+    }
+    Ok(event)
+}
+
+/// Build a `$create_alias` event linking `alias` to `distinct_id`.
+pub(crate) fn alias_event(distinct_id: String, alias: String) -> Result<Event, Error> {
+    let mut event = Event::new(CREATE_ALIAS_EVENT.to_string(), distinct_id.clone());
+    event.insert_prop("distinct_id", distinct_id)?;
+    event.insert_prop("alias", alias)?;
+    Ok(event)
+}
+
+/// Build a `$set` event that overwrites the given person properties.
+pub(crate) fn set_event(
+    distinct_id: String,
+    properties: HashMap<String, Value>,
+) -> Result<Event, Error> {
+    let mut event = Event::new(SET_EVENT.to_string(), distinct_id);
+    event.insert_prop(SET_PROP, object(properties))?;
+    Ok(event)
+}
+
+/// Build a `$set_once` event that writes only the person properties not
+/// already present.
+pub(crate) fn set_once_event(
+    distinct_id: String,
+    properties: HashMap<String, Value>,
+) -> Result<Event, Error> {
+    let mut event = Event::new(SET_ONCE_EVENT.to_string(), distinct_id);
+    event.insert_prop(SET_ONCE_PROP, object(properties))?;
+    Ok(event)
+}
+
+/// Build a `$set` event that removes the given person properties via `$unset`.
+pub(crate) fn unset_event(distinct_id: String, keys: Vec<String>) -> Result<Event, Error> {
+    let mut event = Event::new(SET_EVENT.to_string(), distinct_id);
+    event.insert_prop(UNSET_PROP, keys)?;
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn identify_event_carries_set_set_once_and_anon_distinct_id() {
+        let options = IdentifyOptions::new()
+            .set("email", "jane@example.com")
+            .unwrap()
+            .set_once("first_plan", "free")
+            .unwrap()
+            .anon_distinct_id("anon-1");
+
+        let event = identify_event("user-1".to_string(), options).unwrap();
+
+        assert_eq!(event.event_name(), "$identify");
+        assert_eq!(event.distinct_id(), "user-1");
+        let props = event.properties();
+        assert_eq!(
+            props.get("$set"),
+            Some(&json!({"email": "jane@example.com"}))
+        );
+        assert_eq!(props.get("$set_once"), Some(&json!({"first_plan": "free"})));
+        assert_eq!(props.get("$anon_distinct_id"), Some(&json!("anon-1")));
+    }
+
+    #[test]
+    fn identify_event_without_options_sends_empty_set_only() {
+        let event = identify_event("user-1".to_string(), IdentifyOptions::new()).unwrap();
+
+        let props = event.properties();
+        assert_eq!(props.get("$set"), Some(&json!({})));
+        assert!(!props.contains_key("$set_once"));
+        assert!(!props.contains_key("$anon_distinct_id"));
+    }
+
+    #[test]
+    fn alias_event_links_alias_to_distinct_id() {
+        let event = alias_event("user-1".to_string(), "legacy-42".to_string()).unwrap();
+
+        assert_eq!(event.event_name(), "$create_alias");
+        assert_eq!(event.distinct_id(), "user-1");
+        assert_eq!(
+            event.properties().get("distinct_id"),
+            Some(&json!("user-1"))
+        );
+        assert_eq!(event.properties().get("alias"), Some(&json!("legacy-42")));
+    }
+
+    #[test]
+    fn person_property_events_use_reserved_names() {
+        let props = HashMap::from([("plan".to_string(), json!("pro"))]);
+
+        let set = set_event("user-1".to_string(), props.clone()).unwrap();
+        assert_eq!(set.event_name(), "$set");
+        assert_eq!(set.properties().get("$set"), Some(&json!({"plan": "pro"})));
+
+        let set_once = set_once_event("user-1".to_string(), props).unwrap();
+        assert_eq!(set_once.event_name(), "$set_once");
+        assert_eq!(
+            set_once.properties().get("$set_once"),
+            Some(&json!({"plan": "pro"}))
+        );
+
+        let unset = unset_event("user-1".to_string(), vec!["plan".to_string()]).unwrap();
+        assert_eq!(unset.event_name(), "$set");
+        assert_eq!(unset.properties().get("$unset"), Some(&json!(["plan"])));
+    }
+}
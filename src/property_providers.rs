@@ -0,0 +1,213 @@
+//! Person and group properties loaded on demand during local evaluation.
+//!
+//! Local evaluation can only match the properties it is handed; a condition
+//! on any other property is inconclusive and sends the flag to remote
+//! `/flags`. With a [`PersonPropertyProvider`] or [`GroupPropertyProvider`]
+//! set, an inconclusive flag first has the properties it needs but was not
+//! given loaded from the application's own store, and is evaluated again.
+//! [`FlagPropertyKeys`] lists those properties up front, for callers that
+//! would rather pass them in the first place.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde_json::Value;
+use tracing::error;
+
+use crate::feature_flags::{FeatureFlag, Property, PropertyGroup, PropertyGroupValue};
+
+/// Loads person properties that local evaluation needs but was not given.
+///
+/// Called on the evaluating thread, at most once per evaluation, with every
+/// missing key the inconclusive flags filter on. The async client evaluates
+/// on tokio's blocking thread pool whenever a provider is set, so a provider
+/// may block on I/O without stalling the runtime. Panics are caught and
+/// treated as an empty answer. Set one with
+/// [`ClientOptionsBuilder::person_property_provider`](crate::ClientOptionsBuilder::person_property_provider)
+/// or [`LocalEvaluator::with_person_property_provider`](crate::LocalEvaluator::with_person_property_provider).
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use posthog_rs::PersonPropertyProvider;
+/// use serde_json::{json, Value};
+///
+/// struct Plans(HashMap<String, String>);
+///
+/// impl PersonPropertyProvider for Plans {
+///     fn person_properties(&self, distinct_id: &str, keys: &[String]) -> HashMap<String, Value> {
+///         let mut properties = HashMap::new();
+///         if keys.iter().any(|key| key == "plan") {
+///             if let Some(plan) = self.0.get(distinct_id) {
+///                 properties.insert("plan".to_string(), json!(plan));
+///             }
+///         }
+///         properties
+///     }
+/// }
+/// ```
+pub trait PersonPropertyProvider: Send + Sync + 'static {
+    /// The properties `keys` of the person `distinct_id`. Keys left out of
+    /// the returned map stay missing; other keys are ignored.
+    fn person_properties(&self, distinct_id: &str, keys: &[String]) -> HashMap<String, Value>;
+}
+
+/// Loads group properties that local evaluation needs but was not given,
+/// like [`PersonPropertyProvider`] does for persons.
+///
+/// Only groups passed to the evaluation are looked up, since the group key
+/// is needed to know which group to load.
+pub trait GroupPropertyProvider: Send + Sync + 'static {
+    /// The properties `keys` of the group `group_key` of type `group_type`.
+    /// Keys left out of the returned map stay missing; other keys are
+    /// ignored.
+    fn group_properties(
+        &self,
+        group_type: &str,
+        group_key: &str,
+        keys: &[String],
+    ) -> HashMap<String, Value>;
+}
+
+/// The property keys a flag's release conditions and super condition filter
+/// on, including those of the cohorts they target.
+///
+/// Get them with [`FlagCache::property_keys`](crate::FlagCache::property_keys)
+/// or [`FlagSnapshot::property_keys`](crate::FlagSnapshot::property_keys).
+/// Flags that a flag depends on through `$feature/` filters are evaluated
+/// without properties, so their keys are not included, and neither are
+/// behavioral cohort filters, which match events rather than properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlagPropertyKeys {
+    /// Person property keys.
+    pub person: BTreeSet<String>,
+    /// Group property keys, by group type name. Conditions on group type
+    /// indexes missing from the group type mapping are left out.
+    pub groups: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl FlagPropertyKeys {
+    /// Collect the keys of `flag`, resolving cohort filters with `cohort`
+    /// and group type indexes with `group_type`.
+    pub(crate) fn of<'c>(
+        flag: &FeatureFlag,
+        cohort: impl Fn(&str) -> Option<PropertyGroup>,
+        group_type: impl Fn(i32) -> Option<&'c str>,
+    ) -> Self {
+        let mut keys = Self::default();
+        let super_condition = flag.filters.super_groups.iter().flatten().take(1);
+        for condition in flag.filters.groups.iter().chain(super_condition) {
+            let target = match condition
+                .aggregation_group_type_index
+                .or(flag.filters.aggregation_group_type_index)
+            {
+                None => &mut keys.person,
+                Some(index) => match group_type(index) {
+                    Some(name) => keys.groups.entry(name.to_string()).or_default(),
+                    None => continue,
+                },
+            };
+            let mut resolved = HashSet::new();
+            for property in &condition.properties {
+                collect(property, &cohort, &mut resolved, target);
+            }
+        }
+        keys
+    }
+
+    /// Whether the flag filters on no properties at all.
+    pub fn is_empty(&self) -> bool {
+        self.person.is_empty() && self.groups.values().all(BTreeSet::is_empty)
+    }
+
+    /// Merge `other` into these keys.
+    pub(crate) fn extend(&mut self, other: FlagPropertyKeys) {
+        self.person.extend(other.person);
+        for (group_type, keys) in other.groups {
+            self.groups.entry(group_type).or_default().extend(keys);
+        }
+    }
+}
+
+/// Add the property keys `property` filters on to `keys`, following cohort
+/// filters into the cohorts not yet `resolved`.
+fn collect(
+    property: &Property,
+    cohort: &dyn Fn(&str) -> Option<PropertyGroup>,
+    resolved: &mut HashSet<String>,
+    keys: &mut BTreeSet<String>,
+) {
+    match property.property_type.as_deref() {
+        Some("cohort") => {
+            let id = match &property.value {
+                Value::String(id) => id.clone(),
+                Value::Number(id) => id.to_string(),
+                _ => return,
+            };
+            if !resolved.insert(id.clone()) {
+                return;
+            }
+            if let Some(group) = cohort(&id) {
+                collect_group(&group, cohort, resolved, keys);
+            }
+        }
+        Some("behavioral") => {}
+        _ if property.key.starts_with("$feature/") => {}
+        _ => {
+            keys.insert(property.key.clone());
+        }
+    }
+}
+
+fn collect_group(
+    group: &PropertyGroup,
+    cohort: &dyn Fn(&str) -> Option<PropertyGroup>,
+    resolved: &mut HashSet<String>,
+    keys: &mut BTreeSet<String>,
+) {
+    for value in &group.values {
+        match value {
+            PropertyGroupValue::Property { property, .. } => {
+                collect(property, cohort, resolved, keys)
+            }
+            PropertyGroupValue::Group(group) => collect_group(group, cohort, resolved, keys),
+        }
+    }
+}
+
+/// Ask `provider` for `keys` of the person `distinct_id`, catching a
+/// panicking provider.
+pub(crate) fn load_person_properties(
+    provider: &dyn PersonPropertyProvider,
+    distinct_id: &str,
+    keys: &[String],
+) -> HashMap<String, Value> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        provider.person_properties(distinct_id, keys)
+    }))
+    .unwrap_or_else(|_| {
+        error!("panic in PostHog person property provider; ignoring");
+        HashMap::new()
+    })
+}
+
+/// Ask `provider` for `keys` of a group, catching a panicking provider.
+pub(crate) fn load_group_properties(
+    provider: &dyn GroupPropertyProvider,
+    group_type: &str,
+    group_key: &str,
+    keys: &[String],
+) -> HashMap<String, Value> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        provider.group_properties(group_type, group_key, keys)
+    }))
+    .unwrap_or_else(|_| {
+        error!(
+            group_type,
+            "panic in PostHog group property provider; ignoring"
+        );
+        HashMap::new()
+    })
+}
@@ -1,3 +1,63 @@
+// Each test crate uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptionsBuilder, EvaluateFlagsOptions};
+use serde_json::{json, Value};
+
 pub fn default_user_agent() -> String {
     format!("posthog-rs/{}", env!("CARGO_PKG_VERSION"))
 }
+
+/// A builder for a client of the test project sending to `host`.
+pub fn options_builder(host: &str) -> ClientOptionsBuilder {
+    let mut builder = ClientOptionsBuilder::default();
+    builder.api_key("phc_test".to_string()).host(host);
+    builder
+}
+
+/// A temporary file path unique to the calling test thread.
+pub fn temp_path(file_name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "posthog-{}-{:?}-{file_name}",
+        std::process::id(),
+        std::thread::current().id()
+    ))
+}
+
+/// A flag definitions file path unique to the calling test thread.
+pub fn definitions_path(name: &str) -> PathBuf {
+    temp_path(&format!("{name}.json"))
+}
+
+/// Answers every `/flags` request with `body`.
+pub fn flags_mock(server: &MockServer, body: Value) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST).path("/flags/");
+        then.status(200).json_body(body);
+    })
+}
+
+/// A `/flags` response without any flag.
+pub fn no_flags() -> Value {
+    json!({ "featureFlags": {}, "featureFlagPayloads": {} })
+}
+
+/// Evaluation options for a person on the `pro` plan.
+pub fn pro_user() -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        person_properties: Some(HashMap::from([("plan".to_string(), json!("pro"))])),
+        ..Default::default()
+    }
+}
+
+/// Evaluation options that never ask PostHog.
+pub fn only_local() -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        only_evaluate_locally: true,
+        ..Default::default()
+    }
+}
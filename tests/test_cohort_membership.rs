@@ -0,0 +1,177 @@
+//! Cohorts local evaluation cannot evaluate itself — static cohorts missing
+//! from the definitions, behavioral cohorts — are answered by a
+//! `CohortMembershipProvider` instead of falling back to remote `/flags`.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{definitions_path, flags_mock, no_flags, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{
+    match_feature_flag_with_context, CohortMembershipProvider, EvaluationContext, FlagCache,
+    FlagValue, LocalEvaluationResponse, LocalEvaluator,
+};
+use serde_json::{json, Value};
+
+/// Knows the members of cohort 7 (static) and cohort 8 (behavioral).
+struct KnownMembers;
+
+impl CohortMembershipProvider for KnownMembers {
+    fn is_member(&self, cohort_id: &str, distinct_id: &str) -> Option<bool> {
+        match (cohort_id, distinct_id) {
+            ("7" | "8", "member") => Some(true),
+            ("7" | "8", "outsider") => Some(false),
+            _ => None,
+        }
+    }
+}
+
+fn cohort_flag(key: &str, cohort_id: u32) -> Value {
+    json!({
+        "key": key,
+        "active": true,
+        "filters": { "groups": [{
+            "properties": [{ "key": "id", "type": "cohort", "value": cohort_id, "operator": "in" }],
+            "rollout_percentage": 100.0
+        }] }
+    })
+}
+
+/// Cohort 7 is static, so the definitions leave it out; cohort 8 filters on
+/// events.
+fn definitions() -> Value {
+    json!({
+        "flags": [cohort_flag("static-cohort-flag", 7), cohort_flag("behavioral-cohort-flag", 8)],
+        "cohorts": {
+            "8": {
+                "id": "8",
+                "name": "Active last month",
+                "properties": { "type": "AND", "values": [{
+                    "key": "$pageview",
+                    "type": "behavioral",
+                    "value": "performed_event",
+                    "operator": "exact",
+                    "time_value": 30,
+                    "time_interval": "day"
+                }] }
+            }
+        }
+    })
+}
+
+fn cache() -> FlagCache {
+    let cache = FlagCache::new();
+    cache
+        .load_from_reader(definitions().to_string().as_bytes())
+        .unwrap();
+    cache
+}
+
+fn evaluate(evaluator: &LocalEvaluator, key: &str, distinct_id: &str) -> Option<FlagValue> {
+    evaluator
+        .evaluate_flag(
+            key,
+            distinct_id,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .ok()
+        .flatten()
+}
+
+#[test]
+fn the_provider_answers_for_static_and_behavioral_cohorts() {
+    let plain = LocalEvaluator::new(cache());
+    let provided = LocalEvaluator::new(cache()).with_cohort_membership_provider(KnownMembers);
+
+    for key in ["static-cohort-flag", "behavioral-cohort-flag"] {
+        assert_eq!(evaluate(&plain, key, "member"), None, "{}", key);
+        assert_eq!(
+            evaluate(&provided, key, "member"),
+            Some(FlagValue::Boolean(true)),
+            "{}",
+            key
+        );
+        assert_eq!(
+            evaluate(&provided, key, "outsider"),
+            Some(FlagValue::Boolean(false)),
+            "{}",
+            key
+        );
+        assert_eq!(evaluate(&provided, key, "stranger"), None, "{}", key);
+    }
+}
+
+#[test]
+fn evaluation_contexts_consult_their_provider() {
+    let response: LocalEvaluationResponse = serde_json::from_value(definitions()).unwrap();
+    let flag = &response.flags[0];
+    let (groups, group_properties, group_type_mapping) =
+        (HashMap::new(), HashMap::new(), HashMap::new());
+    let (cohorts, flags) = (HashMap::new(), HashMap::new());
+    let ctx = |cohort_membership| EvaluationContext {
+        cohorts: &cohorts,
+        flags: &flags,
+        distinct_id: "member",
+        device_id: None,
+        groups: &groups,
+        group_properties: &group_properties,
+        group_type_mapping: &group_type_mapping,
+        cohort_membership,
+    };
+
+    assert!(match_feature_flag_with_context(flag, &HashMap::new(), &ctx(None)).is_err());
+    assert_eq!(
+        match_feature_flag_with_context(flag, &HashMap::new(), &ctx(Some(&KnownMembers))).unwrap(),
+        FlagValue::Boolean(true)
+    );
+}
+
+fn options(server: &MockServer, path: &std::path::Path) -> posthog_rs::ClientOptions {
+    options_builder(&server.base_url())
+        .flag_definitions_path(path)
+        .cohort_membership_provider(KnownMembers)
+        .build()
+        .unwrap()
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn clients_resolve_provided_cohorts_without_remote_flags() {
+    let server = MockServer::start();
+    let mock = flags_mock(&server, no_flags());
+    let path = definitions_path("cohort-membership");
+    std::fs::write(&path, definitions().to_string()).unwrap();
+    let client = posthog_rs::client(options(&server, &path)).await;
+    let _ = std::fs::remove_file(&path);
+
+    let flags = client
+        .evaluate_flags("member", posthog_rs::EvaluateFlagsOptions::default())
+        .await
+        .unwrap();
+
+    assert!(flags.is_enabled("static-cohort-flag"));
+    assert!(flags.is_enabled("behavioral-cohort-flag"));
+    assert_eq!(mock.hits(), 0);
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn clients_resolve_provided_cohorts_without_remote_flags() {
+    let server = MockServer::start();
+    let mock = flags_mock(&server, no_flags());
+    let path = definitions_path("cohort-membership");
+    std::fs::write(&path, definitions().to_string()).unwrap();
+    let client = posthog_rs::client(options(&server, &path));
+    let _ = std::fs::remove_file(&path);
+
+    let flags = client
+        .evaluate_flags("member", posthog_rs::EvaluateFlagsOptions::default())
+        .unwrap();
+
+    assert!(flags.is_enabled("static-cohort-flag"));
+    assert!(flags.is_enabled("behavioral-cohort-flag"));
+    assert_eq!(mock.hits(), 0);
+}
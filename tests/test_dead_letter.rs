@@ -0,0 +1,90 @@
+//! A `DeadLetterSink` receives the events the background worker gives up on.
+//! These runs use the built-in `NdjsonDeadLetterSink` against an endpoint that
+//! rejects every capture, so they hold for both the v0 and v1 pipelines.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{options_builder, temp_path};
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, Event, NdjsonDeadLetterSink};
+use serde_json::Value;
+
+fn dead_letter_path() -> PathBuf {
+    temp_path("dead-letters.ndjson")
+}
+
+fn options(server: &MockServer, path: &Path) -> ClientOptions {
+    options_builder(&server.base_url())
+        .dead_letter_sink(NdjsonDeadLetterSink::open(path).unwrap())
+        .build()
+        .unwrap()
+}
+
+fn reject_captures(server: &MockServer) {
+    server.mock(|when, then| {
+        when.method(POST);
+        then.status(413).body("payload too large");
+    });
+}
+
+fn signed_up() -> Event {
+    let mut event = Event::new("signed_up", "user-1");
+    event.insert_prop("plan", "pro").unwrap();
+    event
+}
+
+fn read_dead_letters(path: &Path) -> Vec<Value> {
+    let contents = std::fs::read_to_string(path).unwrap();
+    let _ = std::fs::remove_file(path);
+    contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn assert_dead_lettered(lines: &[Value]) {
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["reason"], "rejected");
+    assert_eq!(lines[0]["status"], 413);
+    assert_eq!(lines[0]["event"]["event"], "signed_up");
+    assert_eq!(lines[0]["event"]["distinct_id"], "user-1");
+    assert_eq!(lines[0]["event"]["properties"]["plan"], "pro");
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejected_events_are_written_to_the_ndjson_sink() {
+        let server = MockServer::start();
+        reject_captures(&server);
+        let path = dead_letter_path();
+        let client = posthog_rs::client(options(&server, &path)).await;
+
+        client.capture(signed_up());
+        client.flush().await;
+
+        assert_dead_lettered(&read_dead_letters(&path));
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn rejected_events_are_written_to_the_ndjson_sink() {
+        let server = MockServer::start();
+        reject_captures(&server);
+        let path = dead_letter_path();
+        let client = posthog_rs::client(options(&server, &path));
+
+        client.capture(signed_up());
+        client.flush();
+
+        assert_dead_lettered(&read_dead_letters(&path));
+    }
+}
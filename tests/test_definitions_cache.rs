@@ -0,0 +1,194 @@
+//! Flag definitions persisted with `DefinitionsCacheOptions`: written after
+//! each fetch, loaded on startup before the first poll, and ignored once
+//! older than the configured max age.
+
+mod common;
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use common::{definitions_path, only_local, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{DefinitionsCacheOptions, FlagCache, FlagPoller, LocalEvaluationConfig};
+use serde_json::{json, Value};
+
+fn definitions(key: &str) -> Value {
+    json!({
+        "flags": [{
+            "key": key,
+            "active": true,
+            "filters": {
+                "groups": [{"properties": [], "rollout_percentage": 100.0}]
+            }
+        }],
+        "group_type_mapping": {},
+        "cohorts": {}
+    })
+}
+
+fn cache_path() -> PathBuf {
+    definitions_path("definitions-cache")
+}
+
+fn persist(path: &Path, etag: &str, definitions: Value) {
+    let contents = json!({ "etag": etag, "definitions": definitions });
+    std::fs::write(path, contents.to_string()).unwrap();
+}
+
+fn config(server: &MockServer) -> LocalEvaluationConfig {
+    LocalEvaluationConfig {
+        personal_api_key: "test_personal_key".to_string(),
+        project_api_key: "test_project_key".to_string(),
+        api_host: server.base_url(),
+        poll_interval: Duration::from_secs(60),
+        request_timeout: Duration::from_secs(5),
+    }
+}
+
+fn unreachable_definitions(server: &MockServer) {
+    server.mock(|when, then| {
+        when.method(GET).path("/flags/definitions/");
+        then.status(503);
+    });
+}
+
+#[test]
+fn poller_persists_fetched_definitions_with_their_etag() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/flags/definitions/");
+        then.status(200)
+            .header("ETag", "\"v1\"")
+            .json_body(definitions("fresh-flag"));
+    });
+    let path = cache_path();
+    let _ = std::fs::remove_file(&path);
+
+    let mut poller = FlagPoller::new(config(&server), FlagCache::new())
+        .with_definitions_cache(DefinitionsCacheOptions::new(&path));
+    poller.start();
+    poller.stop();
+
+    let persisted: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(persisted["etag"], "\"v1\"");
+    assert_eq!(persisted["definitions"]["flags"][0]["key"], "fresh-flag");
+}
+
+#[test]
+fn poller_starts_from_persisted_definitions_when_posthog_is_unreachable() {
+    let server = MockServer::start();
+    unreachable_definitions(&server);
+    let path = cache_path();
+    persist(&path, "\"v1\"", definitions("persisted-flag"));
+
+    let cache = FlagCache::new();
+    let mut poller = FlagPoller::new(config(&server), cache.clone())
+        .with_definitions_cache(DefinitionsCacheOptions::new(&path));
+    poller.start();
+    poller.stop();
+    let _ = std::fs::remove_file(&path);
+
+    assert!(cache.get_flag("persisted-flag").is_some());
+}
+
+#[test]
+fn persisted_etag_makes_the_first_request_conditional() {
+    let server = MockServer::start();
+    let not_modified = server.mock(|when, then| {
+        when.method(GET)
+            .path("/flags/definitions/")
+            .header("if-none-match", "\"v1\"");
+        then.status(304);
+    });
+    let path = cache_path();
+    persist(&path, "\"v1\"", definitions("persisted-flag"));
+    let day_ago = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(day_ago)
+        .unwrap();
+
+    let cache = FlagCache::new();
+    let mut poller = FlagPoller::new(config(&server), cache.clone())
+        .with_definitions_cache(DefinitionsCacheOptions::new(&path));
+    poller.start();
+    poller.stop();
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(not_modified.hits(), 1);
+    assert!(cache.get_flag("persisted-flag").is_some());
+    assert!(modified > day_ago, "a 304 should refresh the file's age");
+}
+
+#[test]
+fn stale_persisted_definitions_are_ignored() {
+    let server = MockServer::start();
+    unreachable_definitions(&server);
+    let path = cache_path();
+    persist(&path, "\"v1\"", definitions("stale-flag"));
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+        .unwrap();
+
+    let cache = FlagCache::new();
+    let mut poller = FlagPoller::new(config(&server), cache.clone()).with_definitions_cache(
+        DefinitionsCacheOptions::new(&path).max_age(Duration::from_secs(60 * 60)),
+    );
+    poller.start();
+    poller.stop();
+    let _ = std::fs::remove_file(&path);
+
+    assert!(cache.get_flag("stale-flag").is_none());
+}
+
+mod client {
+    use super::*;
+    use posthog_rs::ClientOptions;
+
+    pub(super) fn options(server: &MockServer, path: &Path) -> ClientOptions {
+        options_builder(&server.base_url())
+            .secret_key("phx_test")
+            .enable_local_evaluation(true)
+            .flag_definitions_cache(DefinitionsCacheOptions::new(path))
+            .build()
+            .unwrap()
+    }
+
+    #[cfg(feature = "async-client")]
+    #[tokio::test]
+    async fn evaluates_persisted_definitions_while_posthog_is_unreachable() {
+        let server = MockServer::start();
+        unreachable_definitions(&server);
+        let path = cache_path();
+        persist(&path, "\"v1\"", definitions("persisted-flag"));
+
+        let client = posthog_rs::client(options(&server, &path)).await;
+        let flags = client.evaluate_flags("user-1", only_local()).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(flags.is_enabled("persisted-flag"));
+    }
+
+    #[cfg(not(feature = "async-client"))]
+    #[test]
+    fn evaluates_persisted_definitions_while_posthog_is_unreachable() {
+        let server = MockServer::start();
+        unreachable_definitions(&server);
+        let path = cache_path();
+        persist(&path, "\"v1\"", definitions("persisted-flag"));
+
+        let client = posthog_rs::client(options(&server, &path));
+        let flags = client.evaluate_flags("user-1", only_local()).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(flags.is_enabled("persisted-flag"));
+    }
+}
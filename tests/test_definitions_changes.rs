@@ -0,0 +1,127 @@
+//! Subscribers to flag definition changes (`FlagCache::on_change`,
+//! `Client::on_flag_definitions_change`) receive a `FlagDefinitionsDiff` for
+//! every update that changes something.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::definitions_path;
+use posthog_rs::{FlagCache, FlagDefinitionsDiff};
+use serde_json::{json, Value};
+
+fn definitions(kill_switch_active: bool, rollout: f64) -> Value {
+    json!({
+        "flags": [
+            {
+                "key": "payments-kill-switch",
+                "active": kill_switch_active,
+                "filters": { "groups": [{ "properties": [], "rollout_percentage": 100.0 }] }
+            },
+            {
+                "key": "new-checkout",
+                "active": true,
+                "filters": { "groups": [{ "properties": [], "rollout_percentage": rollout }] }
+            }
+        ],
+        "cohorts": {}
+    })
+}
+
+type Listener = Box<dyn Fn(&FlagDefinitionsDiff) + Send + Sync>;
+
+/// Register a listener with `subscribe` that keeps every diff it receives.
+fn recorded(subscribe: impl FnOnce(Listener)) -> Arc<Mutex<Vec<FlagDefinitionsDiff>>> {
+    let diffs = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&diffs);
+    subscribe(Box::new(move |diff| {
+        sink.lock().unwrap().push(diff.clone())
+    }));
+    diffs
+}
+
+#[test]
+fn cache_listeners_receive_a_diff_per_changing_update() {
+    let cache = FlagCache::new();
+    let diffs = recorded(|listener| cache.on_change(listener));
+
+    let load = |value: Value| {
+        cache
+            .load_from_reader(value.to_string().as_bytes())
+            .unwrap()
+    };
+    load(definitions(true, 10.0));
+    load(definitions(true, 10.0));
+    load(definitions(false, 50.0));
+
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.len(), 2, "an unchanged update is not reported");
+    assert_eq!(diffs[0].added, ["new-checkout", "payments-kill-switch"]);
+    assert_eq!(diffs[1].deactivated, ["payments-kill-switch"]);
+    assert_eq!(diffs[1].rollout_changed, ["new-checkout"]);
+    assert!(diffs[1].added.is_empty());
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn client_listeners_see_changes_to_watched_definitions() {
+    let path = definitions_path("definitions-changes");
+    std::fs::write(&path, definitions(true, 10.0).to_string()).unwrap();
+    let client = posthog_rs::client(
+        posthog_rs::ClientOptionsBuilder::default()
+            .api_key("phc_test".to_string())
+            .flag_definitions_path(&path)
+            .watch_flag_definitions(true)
+            .poll_interval_seconds(1u64)
+            .build()
+            .unwrap(),
+    )
+    .await;
+    let diffs = recorded(|listener| client.on_flag_definitions_change(listener));
+
+    std::fs::write(&path, definitions(false, 10.0).to_string()).unwrap();
+    for _ in 0..40 {
+        if !diffs.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let _ = std::fs::remove_file(&path);
+
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].deactivated, ["payments-kill-switch"]);
+    assert!(diffs[0].affects_flag("payments-kill-switch"));
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn client_listeners_see_changes_to_watched_definitions() {
+    let path = definitions_path("definitions-changes");
+    std::fs::write(&path, definitions(true, 10.0).to_string()).unwrap();
+    let client = posthog_rs::client(
+        posthog_rs::ClientOptionsBuilder::default()
+            .api_key("phc_test".to_string())
+            .flag_definitions_path(&path)
+            .watch_flag_definitions(true)
+            .poll_interval_seconds(1u64)
+            .build()
+            .unwrap(),
+    );
+    let diffs = recorded(|listener| client.on_flag_definitions_change(listener));
+
+    std::fs::write(&path, definitions(false, 10.0).to_string()).unwrap();
+    for _ in 0..40 {
+        if !diffs.lock().unwrap().is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let _ = std::fs::remove_file(&path);
+
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].deactivated, ["payments-kill-switch"]);
+    assert!(diffs[0].affects_flag("payments-kill-switch"));
+}
@@ -0,0 +1,148 @@
+//! Local evaluation bootstrapped from a static `/flags/definitions` file:
+//! `FlagCache::load_from_file`, and clients configured with
+//! `flag_definitions_path` that never ask PostHog for flags.
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use common::{definitions_path, options_builder, pro_user};
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, Error, FlagCache};
+use serde_json::json;
+
+fn definitions(key: &str) -> String {
+    json!({
+        "flags": [{
+            "key": key,
+            "active": true,
+            "filters": {
+                "groups": [{
+                    "properties": [{ "key": "plan", "value": "pro", "type": "person" }],
+                    "rollout_percentage": 100.0
+                }]
+            }
+        }]
+    })
+    .to_string()
+}
+
+fn options(server: &MockServer, path: &Path, watch: bool) -> ClientOptions {
+    options_builder(&server.base_url())
+        .flag_definitions_path(path)
+        .watch_flag_definitions(watch)
+        .poll_interval_seconds(1u64)
+        .build()
+        .unwrap()
+}
+
+fn remote_flags(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST).path("/flags/");
+        then.status(500);
+    })
+}
+
+#[test]
+fn flag_cache_loads_definitions_from_a_file() {
+    let path = definitions_path("definitions-cache");
+    std::fs::write(&path, definitions("pro-dashboard")).unwrap();
+    let cache = FlagCache::new();
+
+    cache.load_from_file(&path).unwrap();
+    std::fs::write(&path, "{ not json").unwrap();
+    let malformed = cache.load_from_file(&path);
+    let _ = std::fs::remove_file(&path);
+    let missing = cache.load_from_file(&path);
+
+    assert!(matches!(malformed, Err(Error::Serialization(_))));
+    assert!(matches!(missing, Err(Error::Io(_))));
+    assert!(cache.get_flag("pro-dashboard").is_some());
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn evaluates_flags_from_the_file_without_remote_requests() {
+        let server = MockServer::start();
+        let flags_mock = remote_flags(&server);
+        let path = definitions_path("definitions-client");
+        std::fs::write(&path, definitions("pro-dashboard")).unwrap();
+
+        let client = posthog_rs::client(options(&server, &path, false)).await;
+        let flags = client.evaluate_flags("user-1", pro_user()).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert!(!flags.is_enabled("unknown-flag"));
+        assert_eq!(flags_mock.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn reloads_a_watched_file_when_it_changes() {
+        let server = MockServer::start();
+        let path = definitions_path("definitions-watched");
+        std::fs::write(&path, definitions("first-flag")).unwrap();
+        let client = posthog_rs::client(options(&server, &path, true)).await;
+
+        std::fs::write(&path, definitions("second-flag-renamed")).unwrap();
+        let mut reloaded = false;
+        for _ in 0..40 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let flags = client.evaluate_flags("user-1", pro_user()).await.unwrap();
+            if flags.is_enabled("second-flag-renamed") {
+                reloaded = true;
+                break;
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+
+        assert!(reloaded, "the changed file should have been reloaded");
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn evaluates_flags_from_the_file_without_remote_requests() {
+        let server = MockServer::start();
+        let flags_mock = remote_flags(&server);
+        let path = definitions_path("definitions-client");
+        std::fs::write(&path, definitions("pro-dashboard")).unwrap();
+
+        let client = posthog_rs::client(options(&server, &path, false));
+        let flags = client.evaluate_flags("user-1", pro_user()).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert!(!flags.is_enabled("unknown-flag"));
+        assert_eq!(flags_mock.hits(), 0);
+    }
+
+    #[test]
+    fn reloads_a_watched_file_when_it_changes() {
+        let server = MockServer::start();
+        let path = definitions_path("definitions-watched");
+        std::fs::write(&path, definitions("first-flag")).unwrap();
+        let client = posthog_rs::client(options(&server, &path, true));
+
+        std::fs::write(&path, definitions("second-flag-renamed")).unwrap();
+        let mut reloaded = false;
+        for _ in 0..40 {
+            std::thread::sleep(Duration::from_millis(100));
+            let flags = client.evaluate_flags("user-1", pro_user()).unwrap();
+            if flags.is_enabled("second-flag-renamed") {
+                reloaded = true;
+                break;
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+
+        assert!(reloaded, "the changed file should have been reloaded");
+    }
+}
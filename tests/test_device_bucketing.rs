@@ -0,0 +1,159 @@
+//! `EvaluateFlagsOptions::device_id` is sent to `/flags`, buckets locally
+//! evaluated flags whose bucketing identifier is the device, and is recorded
+//! as `$device_id` on `$feature_flag_called`.
+
+mod common;
+
+use common::{definitions_path, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::EvaluateFlagsOptions;
+use serde_json::json;
+
+/// Where the background worker ships analytics captures.
+#[cfg(feature = "capture-v1")]
+const CAPTURE_PATH: &str = "/i/v1/analytics/events";
+#[cfg(not(feature = "capture-v1"))]
+const CAPTURE_PATH: &str = "/batch/";
+
+/// Bucketed on the distinct ID, `anon-1` is in the rollout and `user-2` is
+/// not. Both are on `device-1`, which is.
+fn definitions() -> serde_json::Value {
+    json!({
+        "flags": [{
+            "key": "device-flag",
+            "active": true,
+            "bucketing_identifier": "device_id",
+            "filters": { "groups": [{ "properties": [], "rollout_percentage": 50 }] }
+        }],
+        "group_type_mapping": {}
+    })
+}
+
+fn on_device(device_id: &str) -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        device_id: Some(device_id.to_string()),
+        ..Default::default()
+    }
+}
+
+fn options(server: &MockServer, path: Option<&std::path::Path>) -> posthog_rs::ClientOptions {
+    let mut builder = options_builder(&server.base_url());
+    if let Some(path) = path {
+        builder.flag_definitions_path(path);
+    }
+    builder.build().unwrap()
+}
+
+fn exposures_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .path(CAPTURE_PATH)
+            .body_contains("$feature_flag_called")
+            .body_contains("\"$device_id\":\"device-1\"");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn flags_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .path("/flags/")
+            .json_body_partial(json!({ "device_id": "device-1" }).to_string());
+        then.status(200).json_body(json!({
+            "flags": { "remote-flag": { "key": "remote-flag", "enabled": true, "variant": null } }
+        }));
+    })
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn device_bucketed_flags_keep_their_value_across_login() {
+        let server = MockServer::start();
+        let exposures = exposures_mock(&server);
+        let path = definitions_path("device-bucketing");
+        std::fs::write(&path, definitions().to_string()).unwrap();
+        let client = posthog_rs::client(options(&server, Some(&path))).await;
+        let _ = std::fs::remove_file(&path);
+
+        for distinct_id in ["anon-1", "user-2"] {
+            let flags = client
+                .evaluate_flags(distinct_id, on_device("device-1"))
+                .await
+                .unwrap();
+            assert!(flags.is_enabled("device-flag"), "{}", distinct_id);
+            client.flush().await;
+        }
+        let flags = client
+            .evaluate_flags("user-2", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+        assert!(!flags.is_enabled("device-flag"));
+        client.flush().await;
+
+        assert_eq!(exposures.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn device_id_is_sent_to_flags() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server);
+        let client = posthog_rs::client(options(&server, None)).await;
+
+        let flags = client
+            .evaluate_flags("user-2", on_device("device-1"))
+            .await
+            .unwrap();
+
+        assert!(flags.is_enabled("remote-flag"));
+        assert_eq!(mock.hits(), 1);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking_client {
+    use super::*;
+
+    #[test]
+    fn device_bucketed_flags_keep_their_value_across_login() {
+        let server = MockServer::start();
+        let exposures = exposures_mock(&server);
+        let path = definitions_path("device-bucketing");
+        std::fs::write(&path, definitions().to_string()).unwrap();
+        let client = posthog_rs::client(options(&server, Some(&path)));
+        let _ = std::fs::remove_file(&path);
+
+        for distinct_id in ["anon-1", "user-2"] {
+            let flags = client
+                .evaluate_flags(distinct_id, on_device("device-1"))
+                .unwrap();
+            assert!(flags.is_enabled("device-flag"), "{}", distinct_id);
+            client.flush();
+        }
+        let flags = client
+            .evaluate_flags("user-2", EvaluateFlagsOptions::default())
+            .unwrap();
+        assert!(!flags.is_enabled("device-flag"));
+        client.flush();
+
+        assert_eq!(exposures.hits(), 2);
+    }
+
+    #[test]
+    fn device_id_is_sent_to_flags() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server);
+        let client = posthog_rs::client(options(&server, None));
+
+        let flags = client
+            .evaluate_flags("user-2", on_device("device-1"))
+            .unwrap();
+
+        assert!(flags.is_enabled("remote-flag"));
+        assert_eq!(mock.hits(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+//! `Client::get_experiment_variant` reads an experiment flag, reports holdouts
+//! and records one deduplicated exposure per user and variant, whether the flag
+//! comes from remote `/flags` or from local flag definitions.
+
+mod common;
+
+use common::{definitions_path, flags_mock, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::EvaluateFlagsOptions;
+use serde_json::{json, Value};
+
+/// Where the background worker ships analytics captures.
+#[cfg(feature = "capture-v1")]
+const CAPTURE_PATH: &str = "/i/v1/analytics/events";
+#[cfg(not(feature = "capture-v1"))]
+const CAPTURE_PATH: &str = "/batch/";
+
+fn capture_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .path(CAPTURE_PATH)
+            .body_contains("$feature_flag_called");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn flag(key: &str, variant: &str, reason: &str, payload: Value) -> Value {
+    json!({
+        "key": key,
+        "enabled": true,
+        "variant": variant,
+        "reason": { "code": reason, "description": null, "condition_index": 0 },
+        "metadata": { "id": 1, "version": 1, "description": null, "payload": payload }
+    })
+}
+
+fn experiment_flags() -> Value {
+    json!({
+        "flags": {
+            "checkout": flag(
+                "checkout",
+                "test",
+                "condition_match",
+                json!({ "headline": "Buy now" })
+            ),
+            "pricing": flag("pricing", "holdout-4", "holdout_condition_value", Value::Null)
+        }
+    })
+}
+
+fn options(server: &MockServer) -> posthog_rs::ClientOptions {
+    options_builder(&server.base_url()).build().unwrap()
+}
+
+/// The same experiments as [`experiment_flags`], as local definitions. Payloads
+/// are JSON-encoded strings, as PostHog serves them.
+fn experiment_definitions() -> Value {
+    json!({
+        "flags": [
+            {
+                "key": "checkout",
+                "active": true,
+                "filters": {
+                    "groups": [{ "properties": [], "rollout_percentage": 100 }],
+                    "multivariate": { "variants": [
+                        { "key": "test", "rollout_percentage": 100 }
+                    ] },
+                    "payloads": { "test": "{\"headline\":\"Buy now\"}" }
+                }
+            },
+            {
+                "key": "pricing",
+                "active": true,
+                "filters": {
+                    "groups": [{ "properties": [], "rollout_percentage": 100 }],
+                    "holdout_groups": [{
+                        "properties": [],
+                        "rollout_percentage": 100,
+                        "variant": "holdout-4"
+                    }]
+                }
+            }
+        ]
+    })
+}
+
+fn local_options(server: &MockServer, path: &std::path::Path) -> posthog_rs::ClientOptions {
+    options_builder(&server.base_url())
+        .flag_definitions_path(path)
+        .build()
+        .unwrap()
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn experiment_assignments_record_one_exposure() {
+    let server = MockServer::start();
+    let flags = flags_mock(&server, experiment_flags());
+    let exposures = capture_mock(&server);
+    let client = posthog_rs::client(options(&server)).await;
+
+    for _ in 0..2 {
+        let assignment = client
+            .get_experiment_variant("checkout", "user", EvaluateFlagsOptions::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.arm(), Some("test"));
+        assert_eq!(assignment.payload, Some(json!({ "headline": "Buy now" })));
+    }
+    client.flush().await;
+    let held_out = client
+        .get_experiment_variant("pricing", "user", EvaluateFlagsOptions::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(held_out.in_holdout);
+    assert_eq!(held_out.arm(), None);
+    client.flush().await;
+
+    assert_eq!(flags.hits(), 3);
+    assert_eq!(exposures.hits(), 2);
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn experiment_assignments_record_one_exposure() {
+    let server = MockServer::start();
+    let flags = flags_mock(&server, experiment_flags());
+    let exposures = capture_mock(&server);
+    let client = posthog_rs::client(options(&server));
+
+    for _ in 0..2 {
+        let assignment = client
+            .get_experiment_variant("checkout", "user", EvaluateFlagsOptions::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.arm(), Some("test"));
+        assert_eq!(assignment.payload, Some(json!({ "headline": "Buy now" })));
+    }
+    client.flush();
+    let held_out = client
+        .get_experiment_variant("pricing", "user", EvaluateFlagsOptions::default())
+        .unwrap()
+        .unwrap();
+    assert!(held_out.in_holdout);
+    assert_eq!(held_out.arm(), None);
+    client.flush();
+
+    assert_eq!(flags.hits(), 3);
+    assert_eq!(exposures.hits(), 2);
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn locally_evaluated_assignments_carry_payloads_and_holdouts() {
+    let server = MockServer::start();
+    let flags = flags_mock(&server, experiment_flags());
+    let exposures = capture_mock(&server);
+    let path = definitions_path("experiments");
+    std::fs::write(&path, experiment_definitions().to_string()).unwrap();
+    let client = posthog_rs::client(local_options(&server, &path)).await;
+    let _ = std::fs::remove_file(&path);
+
+    let assignment = client
+        .get_experiment_variant("checkout", "user", EvaluateFlagsOptions::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(assignment.arm(), Some("test"));
+    assert_eq!(assignment.payload, Some(json!({ "headline": "Buy now" })));
+    client.flush().await;
+    let held_out = client
+        .get_experiment_variant("pricing", "user", EvaluateFlagsOptions::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(held_out.in_holdout);
+    assert_eq!(held_out.arm(), None);
+    client.flush().await;
+
+    assert_eq!(flags.hits(), 0);
+    assert_eq!(exposures.hits(), 2);
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn locally_evaluated_assignments_carry_payloads_and_holdouts() {
+    let server = MockServer::start();
+    let flags = flags_mock(&server, experiment_flags());
+    let exposures = capture_mock(&server);
+    let path = definitions_path("experiments");
+    std::fs::write(&path, experiment_definitions().to_string()).unwrap();
+    let client = posthog_rs::client(local_options(&server, &path));
+    let _ = std::fs::remove_file(&path);
+
+    let assignment = client
+        .get_experiment_variant("checkout", "user", EvaluateFlagsOptions::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(assignment.arm(), Some("test"));
+    assert_eq!(assignment.payload, Some(json!({ "headline": "Buy now" })));
+    client.flush();
+    let held_out = client
+        .get_experiment_variant("pricing", "user", EvaluateFlagsOptions::default())
+        .unwrap()
+        .unwrap();
+    assert!(held_out.in_holdout);
+    assert_eq!(held_out.arm(), None);
+    client.flush();
+
+    assert_eq!(flags.hits(), 0);
+    assert_eq!(exposures.hits(), 2);
+}
@@ -0,0 +1,349 @@
+//! Flag values forced with `FlagOverrides` and `Client::override_flag`: they
+//! win over remote `/flags`, are reported with their own reason, carry only
+//! forced payloads, and mark the `$feature_flag_called` events they produce.
+
+mod common;
+
+use common::{flags_mock, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, ClientOptionsBuilder, EvaluateFlagsOptions, FlagOverrides};
+use serde_json::{json, Value};
+
+fn options(server: &MockServer) -> ClientOptions {
+    options_with(
+        server,
+        FlagOverrides::new().flag("new-checkout", "variant-b"),
+    )
+}
+
+fn options_with(server: &MockServer, overrides: FlagOverrides) -> ClientOptions {
+    options_builder(&server.base_url())
+        .recording(true)
+        .flag_overrides(overrides)
+        .build()
+        .unwrap()
+}
+
+fn forced_payload() -> FlagOverrides {
+    FlagOverrides::new()
+        .flag("new-checkout", "variant-b")
+        .payload("new-checkout", json!({ "steps": 2 }))
+}
+
+fn remote_flags() -> Value {
+    json!({
+        "featureFlags": { "new-checkout": "control", "beta-dashboard": true },
+        "featureFlagPayloads": {
+            "new-checkout": { "steps": 3 },
+            "beta-dashboard": { "theme": "dark" }
+        }
+    })
+}
+
+fn only(keys: &[&str]) -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        flag_keys: Some(keys.iter().map(|k| k.to_string()).collect()),
+        ..Default::default()
+    }
+}
+
+fn flag_called(events: &[Value], distinct_id: &str, key: &str) -> Value {
+    events
+        .iter()
+        .find(|e| {
+            e["event"] == "$feature_flag_called"
+                && e["distinct_id"] == distinct_id
+                && e["properties"]["$feature_flag"] == key
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            panic!(
+                "no $feature_flag_called event for {} / {}",
+                distinct_id, key
+            )
+        })
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+    use posthog_rs::FlagValue;
+
+    #[tokio::test]
+    async fn overrides_win_over_remote_flags_and_mark_their_events() {
+        let server = MockServer::start();
+        flags_mock(&server, remote_flags());
+        let client = posthog_rs::client(options(&server)).await;
+        client.override_flag_for("dev-1", "beta-dashboard", false);
+
+        let dev = client
+            .evaluate_flags("dev-1", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+        let user = client
+            .evaluate_flags("user-2", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            dev.get_flag("new-checkout"),
+            Some(FlagValue::String("variant-b".into()))
+        );
+        assert!(!dev.is_enabled("beta-dashboard"));
+        assert!(user.is_enabled("beta-dashboard"));
+        assert_eq!(
+            dev.get_flag_reason("new-checkout"),
+            Some("Overridden locally")
+        );
+        assert_ne!(
+            user.get_flag_reason("beta-dashboard"),
+            Some("Overridden locally")
+        );
+
+        let events = client.captured_events().await;
+        let overridden = flag_called(&events, "dev-1", "new-checkout");
+        assert_eq!(
+            overridden["properties"]["$feature_flag_response"],
+            "variant-b"
+        );
+        assert_eq!(overridden["properties"]["$feature_flag_overridden"], true);
+        assert_eq!(
+            overridden["properties"]["$feature_flag_reason"],
+            "Overridden locally"
+        );
+        let remote = flag_called(&events, "user-2", "beta-dashboard");
+        assert!(remote["properties"]
+            .get("$feature_flag_overridden")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn overridden_flags_skip_posthog_until_cleared() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, remote_flags());
+        let client = posthog_rs::client(options(&server)).await;
+
+        let forced = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .await
+            .unwrap();
+        #[allow(deprecated)]
+        let legacy = client
+            .get_feature_flag("new-checkout", "user-1", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(mock.hits(), 0);
+
+        client.clear_flag_overrides();
+        let evaluated = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            forced.get_flag("new-checkout"),
+            Some(FlagValue::String("variant-b".into()))
+        );
+        assert_eq!(legacy, Some(FlagValue::String("variant-b".into())));
+        assert_eq!(
+            evaluated.get_flag("new-checkout"),
+            Some(FlagValue::String("control".into()))
+        );
+        assert_eq!(mock.hits(), 1);
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn overridden_flags_only_carry_forced_payloads() {
+        let server = MockServer::start();
+        flags_mock(&server, remote_flags());
+
+        let client = posthog_rs::client(options(&server)).await;
+        let payload = client
+            .get_feature_flag_payload("new-checkout", "user-1")
+            .await
+            .unwrap();
+        let (_, payloads) = client
+            .get_feature_flags("user-1", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(payload, None);
+        assert_eq!(payloads.get("new-checkout"), None);
+        assert_eq!(payloads["beta-dashboard"], json!({ "theme": "dark" }));
+
+        let client = posthog_rs::client(options_with(&server, forced_payload())).await;
+        let payload = client
+            .get_feature_flag_payload("new-checkout", "user-1")
+            .await
+            .unwrap();
+        let (_, payloads) = client
+            .get_feature_flags("user-1", None, None, None)
+            .await
+            .unwrap();
+        let flags = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .await
+            .unwrap();
+        assert_eq!(payload, Some(json!({ "steps": 2 })));
+        assert_eq!(payloads["new-checkout"], json!({ "steps": 2 }));
+        assert_eq!(
+            flags.get_flag_payload("new-checkout"),
+            Some(json!({ "steps": 2 }))
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_clients_still_apply_overrides() {
+        let client = posthog_rs::client(
+            ClientOptionsBuilder::default()
+                .api_key(String::new())
+                .flag_overrides(FlagOverrides::new().flag("new-checkout", "variant-b"))
+                .build()
+                .unwrap(),
+        )
+        .await;
+
+        let flags = client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(flags.keys(), ["new-checkout"]);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+    use posthog_rs::FlagValue;
+
+    #[test]
+    fn overrides_win_over_remote_flags_and_mark_their_events() {
+        let server = MockServer::start();
+        flags_mock(&server, remote_flags());
+        let client = posthog_rs::client(options(&server));
+        client.override_flag_for("dev-1", "beta-dashboard", false);
+
+        let dev = client
+            .evaluate_flags("dev-1", EvaluateFlagsOptions::default())
+            .unwrap();
+        let user = client
+            .evaluate_flags("user-2", EvaluateFlagsOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            dev.get_flag("new-checkout"),
+            Some(FlagValue::String("variant-b".into()))
+        );
+        assert!(!dev.is_enabled("beta-dashboard"));
+        assert!(user.is_enabled("beta-dashboard"));
+        assert_eq!(
+            dev.get_flag_reason("new-checkout"),
+            Some("Overridden locally")
+        );
+        assert_ne!(
+            user.get_flag_reason("beta-dashboard"),
+            Some("Overridden locally")
+        );
+
+        let events = client.captured_events();
+        let overridden = flag_called(&events, "dev-1", "new-checkout");
+        assert_eq!(
+            overridden["properties"]["$feature_flag_response"],
+            "variant-b"
+        );
+        assert_eq!(overridden["properties"]["$feature_flag_overridden"], true);
+        assert_eq!(
+            overridden["properties"]["$feature_flag_reason"],
+            "Overridden locally"
+        );
+        let remote = flag_called(&events, "user-2", "beta-dashboard");
+        assert!(remote["properties"]
+            .get("$feature_flag_overridden")
+            .is_none());
+    }
+
+    #[test]
+    fn overridden_flags_skip_posthog_until_cleared() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, remote_flags());
+        let client = posthog_rs::client(options(&server));
+
+        let forced = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .unwrap();
+        #[allow(deprecated)]
+        let legacy = client
+            .get_feature_flag("new-checkout", "user-1", None, None, None)
+            .unwrap();
+        assert_eq!(mock.hits(), 0);
+
+        client.clear_flag_overrides();
+        let evaluated = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .unwrap();
+
+        assert_eq!(
+            forced.get_flag("new-checkout"),
+            Some(FlagValue::String("variant-b".into()))
+        );
+        assert_eq!(legacy, Some(FlagValue::String("variant-b".into())));
+        assert_eq!(
+            evaluated.get_flag("new-checkout"),
+            Some(FlagValue::String("control".into()))
+        );
+        assert_eq!(mock.hits(), 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn overridden_flags_only_carry_forced_payloads() {
+        let server = MockServer::start();
+        flags_mock(&server, remote_flags());
+
+        let client = posthog_rs::client(options(&server));
+        let payload = client
+            .get_feature_flag_payload("new-checkout", "user-1")
+            .unwrap();
+        let (_, payloads) = client
+            .get_feature_flags("user-1", None, None, None)
+            .unwrap();
+        assert_eq!(payload, None);
+        assert_eq!(payloads.get("new-checkout"), None);
+        assert_eq!(payloads["beta-dashboard"], json!({ "theme": "dark" }));
+
+        let client = posthog_rs::client(options_with(&server, forced_payload()));
+        let payload = client
+            .get_feature_flag_payload("new-checkout", "user-1")
+            .unwrap();
+        let (_, payloads) = client
+            .get_feature_flags("user-1", None, None, None)
+            .unwrap();
+        let flags = client
+            .evaluate_flags("user-1", only(&["new-checkout"]))
+            .unwrap();
+        assert_eq!(payload, Some(json!({ "steps": 2 })));
+        assert_eq!(payloads["new-checkout"], json!({ "steps": 2 }));
+        assert_eq!(
+            flags.get_flag_payload("new-checkout"),
+            Some(json!({ "steps": 2 }))
+        );
+    }
+
+    #[test]
+    fn disabled_clients_still_apply_overrides() {
+        let client = posthog_rs::client(
+            ClientOptionsBuilder::default()
+                .api_key(String::new())
+                .flag_overrides(FlagOverrides::new().flag("new-checkout", "variant-b"))
+                .build()
+                .unwrap(),
+        );
+
+        let flags = client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .unwrap();
+
+        assert_eq!(flags.keys(), ["new-checkout"]);
+    }
+}
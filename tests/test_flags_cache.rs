@@ -0,0 +1,195 @@
+//! A client configured with `FlagsCacheOptions` reuses remote `/flags`
+//! responses within their TTL and serves stale ones when `/flags` fails.
+
+mod common;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use common::{flags_mock, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{EvaluateFlagsOptions, FlagValue, FlagsCacheOptions, FlagsCacheStatus};
+use serde_json::{json, Value};
+
+fn options(server: &MockServer, ttl: Option<Duration>) -> posthog_rs::ClientOptions {
+    let mut builder = options_builder(&server.base_url());
+    builder.feature_flags_request_max_retries(0u32);
+    if let Some(ttl) = ttl {
+        builder.flags_cache(FlagsCacheOptions::new(ttl));
+    }
+    builder.build().unwrap()
+}
+
+fn alpha_flags() -> Value {
+    json!({
+        "flags": { "alpha": { "key": "alpha", "enabled": true, "variant": null } }
+    })
+}
+
+fn failing_flags_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST).path("/flags/");
+        then.status(503);
+    })
+}
+
+fn with_plan(plan: &str) -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        person_properties: Some(HashMap::from([("plan".to_string(), json!(plan))])),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn responses_are_reused_within_their_ttl() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, Some(Duration::from_secs(60)))).await;
+
+        let first = client
+            .evaluate_flags("user", with_plan("pro"))
+            .await
+            .unwrap();
+        let second = client
+            .evaluate_flags("user", with_plan("pro"))
+            .await
+            .unwrap();
+        assert_eq!(first.flags_cache_status(), Some(FlagsCacheStatus::Miss));
+        assert_eq!(second.flags_cache_status(), Some(FlagsCacheStatus::Hit));
+        assert!(second.is_enabled("alpha"));
+        assert_eq!(mock.hits(), 1);
+
+        let other = client
+            .evaluate_flags("user", with_plan("free"))
+            .await
+            .unwrap();
+        assert_eq!(other.flags_cache_status(), Some(FlagsCacheStatus::Miss));
+        assert_eq!(mock.hits(), 2);
+
+        for _ in 0..2 {
+            let (flags, _) = client
+                .get_feature_flags("user", None, None, None)
+                .await
+                .unwrap();
+            assert_eq!(flags["alpha"], FlagValue::Boolean(true));
+        }
+        assert_eq!(mock.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn stale_responses_are_served_when_flags_fails() {
+        let server = MockServer::start();
+        let mut mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, Some(Duration::ZERO))).await;
+        client
+            .evaluate_flags("user", with_plan("pro"))
+            .await
+            .unwrap();
+        client
+            .get_feature_flags("user", None, None, None)
+            .await
+            .unwrap();
+        mock.delete();
+        let failing = failing_flags_mock(&server);
+
+        let stale = client
+            .evaluate_flags("user", with_plan("pro"))
+            .await
+            .unwrap();
+        assert_eq!(stale.flags_cache_status(), Some(FlagsCacheStatus::Stale));
+        assert!(stale.is_enabled("alpha"));
+        let (flags, _) = client
+            .get_feature_flags("user", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(flags["alpha"], FlagValue::Boolean(true));
+        assert!(client
+            .evaluate_flags("someone-else", with_plan("pro"))
+            .await
+            .is_err());
+        assert_eq!(failing.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn uncached_clients_report_no_cache_status() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, None)).await;
+
+        for _ in 0..2 {
+            let flags = client
+                .evaluate_flags("user", with_plan("pro"))
+                .await
+                .unwrap();
+            assert_eq!(flags.flags_cache_status(), None);
+        }
+        assert_eq!(mock.hits(), 2);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking_client {
+    use super::*;
+
+    #[test]
+    fn responses_are_reused_within_their_ttl() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, Some(Duration::from_secs(60))));
+
+        let first = client.evaluate_flags("user", with_plan("pro")).unwrap();
+        let second = client.evaluate_flags("user", with_plan("pro")).unwrap();
+        assert_eq!(first.flags_cache_status(), Some(FlagsCacheStatus::Miss));
+        assert_eq!(second.flags_cache_status(), Some(FlagsCacheStatus::Hit));
+        assert!(second.is_enabled("alpha"));
+        assert_eq!(mock.hits(), 1);
+
+        let other = client.evaluate_flags("user", with_plan("free")).unwrap();
+        assert_eq!(other.flags_cache_status(), Some(FlagsCacheStatus::Miss));
+        assert_eq!(mock.hits(), 2);
+
+        for _ in 0..2 {
+            let (flags, _) = client.get_feature_flags("user", None, None, None).unwrap();
+            assert_eq!(flags["alpha"], FlagValue::Boolean(true));
+        }
+        assert_eq!(mock.hits(), 3);
+    }
+
+    #[test]
+    fn stale_responses_are_served_when_flags_fails() {
+        let server = MockServer::start();
+        let mut mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, Some(Duration::ZERO)));
+        client.evaluate_flags("user", with_plan("pro")).unwrap();
+        client.get_feature_flags("user", None, None, None).unwrap();
+        mock.delete();
+        let failing = failing_flags_mock(&server);
+
+        let stale = client.evaluate_flags("user", with_plan("pro")).unwrap();
+        assert_eq!(stale.flags_cache_status(), Some(FlagsCacheStatus::Stale));
+        assert!(stale.is_enabled("alpha"));
+        let (flags, _) = client.get_feature_flags("user", None, None, None).unwrap();
+        assert_eq!(flags["alpha"], FlagValue::Boolean(true));
+        assert!(client
+            .evaluate_flags("someone-else", with_plan("pro"))
+            .is_err());
+        assert_eq!(failing.hits(), 3);
+    }
+
+    #[test]
+    fn uncached_clients_report_no_cache_status() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, alpha_flags());
+        let client = posthog_rs::client(options(&server, None));
+
+        for _ in 0..2 {
+            let flags = client.evaluate_flags("user", with_plan("pro")).unwrap();
+            assert_eq!(flags.flags_cache_status(), None);
+        }
+        assert_eq!(mock.hits(), 2);
+    }
+}
@@ -0,0 +1,102 @@
+//! `group_identify` and the `Group` builder send `$groupidentify` events through
+//! both the background transport and immediate capture. Assertions are
+//! wire-format-agnostic: they hold for both the v0 `/batch/` and v1
+//! `/i/v1/analytics/events` bodies.
+
+use std::collections::HashMap;
+
+use httpmock::prelude::*;
+use posthog_rs::{Event, Group};
+use serde_json::json;
+
+fn group_identify_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"$groupidentify""#)
+            .body_contains(r#""distinct_id":"$company_acme""#)
+            .body_contains(r#""$group_type":"company""#)
+            .body_contains(r#""$group_key":"acme""#)
+            .body_contains(r#""$group_set":{"name":"Acme Inc."}"#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn acme_properties() -> HashMap<String, serde_json::Value> {
+    HashMap::from([("name".to_string(), json!("Acme Inc."))])
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    async fn create_test_client(base_url: String) -> posthog_rs::Client {
+        let options: posthog_rs::ClientOptions = ("test_api_key", base_url.as_str()).into();
+        posthog_rs::client(options).await
+    }
+
+    #[tokio::test]
+    async fn group_identify_sends_group_set() {
+        let server = MockServer::start();
+        let mock = group_identify_mock(&server);
+
+        let client = create_test_client(server.base_url()).await;
+        client.group_identify("company", "acme", acme_properties());
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn group_builder_sends_with_capture_immediate() {
+        let server = MockServer::start();
+        let mock = group_identify_mock(&server);
+
+        let client = create_test_client(server.base_url()).await;
+        let group = Group::new("company", "acme")
+            .set("name", "Acme Inc.")
+            .unwrap();
+        let summary = client.capture_immediate(Event::from(group)).await.unwrap();
+
+        assert_eq!(summary.submitted(), 1);
+        mock.assert_hits(1);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    fn create_test_client(base_url: String) -> posthog_rs::Client {
+        let options: posthog_rs::ClientOptions = ("test_api_key", base_url.as_str()).into();
+        posthog_rs::client(options)
+    }
+
+    #[test]
+    fn group_identify_sends_group_set() {
+        let server = MockServer::start();
+        let mock = group_identify_mock(&server);
+
+        let client = create_test_client(server.base_url());
+        client.group_identify("company", "acme", acme_properties());
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn group_builder_sends_with_capture_immediate() {
+        let server = MockServer::start();
+        let mock = group_identify_mock(&server);
+
+        let client = create_test_client(server.base_url());
+        let group = Group::new("company", "acme")
+            .set("name", "Acme Inc.")
+            .unwrap();
+        let summary = client.capture_immediate(Event::from(group)).unwrap();
+
+        assert_eq!(summary.submitted(), 1);
+        mock.assert_hits(1);
+    }
+}
@@ -0,0 +1,233 @@
+//! A custom `HttpTransport` carries every request the client makes: background
+//! capture, remote `/flags`, and the local-evaluation definitions poller. The
+//! in-memory fake here stands in for a corporate HTTP stack; no sockets are
+//! opened. Capture assertions hold for both the v0 `/batch/` and v1
+//! `/i/v1/analytics/events` bodies.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{only_local, options_builder};
+use posthog_rs::{
+    ClientOptions, Event, FlagValue, HttpMethod, HttpRequest, HttpResponse, HttpTransport,
+    TransportError, TransportErrorKind,
+};
+use serde_json::json;
+
+/// Answers from canned bodies and records every request it sees.
+#[derive(Clone, Default)]
+struct FakeTransport {
+    requests: Arc<Mutex<Vec<HttpRequest>>>,
+    refuse_flags: bool,
+}
+
+impl FakeTransport {
+    fn requests_to(&self, path: &str) -> Vec<HttpRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.url.contains(path))
+            .cloned()
+            .collect()
+    }
+}
+
+impl HttpTransport for FakeTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let url = request.url.clone();
+        self.requests.lock().unwrap().push(request);
+        let body = if url.contains("/flags/definitions/") {
+            json!({
+                "flags": [{
+                    "key": "local-flag",
+                    "active": true,
+                    "filters": {
+                        "groups": [{ "properties": [], "rollout_percentage": 100.0 }]
+                    }
+                }],
+                "group_type_mapping": {},
+                "cohorts": {}
+            })
+        } else if url.contains("/flags/") {
+            if self.refuse_flags {
+                return Err(TransportError::new(
+                    TransportErrorKind::Connect,
+                    "connection refused",
+                ));
+            }
+            json!({ "featureFlags": { "remote-flag": "variant-a" }, "featureFlagPayloads": {} })
+        } else {
+            json!({ "results": {} })
+        };
+        Ok(
+            HttpResponse::new(200, body.to_string())
+                .with_header("Content-Type", "application/json"),
+        )
+    }
+}
+
+fn options(transport: &FakeTransport) -> ClientOptions {
+    options_builder("http://posthog.invalid")
+        .http_transport(transport.clone())
+        .build()
+        .unwrap()
+}
+
+fn local_evaluation_options(transport: &FakeTransport) -> ClientOptions {
+    options_builder("http://posthog.invalid")
+        .secret_key("phx_test".to_string())
+        .enable_local_evaluation(true)
+        .http_transport(transport.clone())
+        .build()
+        .unwrap()
+}
+
+fn assert_captured(transport: &FakeTransport) {
+    let captures: Vec<HttpRequest> = transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|r| !r.url.contains("/flags"))
+        .cloned()
+        .collect();
+    assert_eq!(captures.len(), 1);
+    let request = &captures[0];
+    assert_eq!(request.method, HttpMethod::Post);
+    assert!(request.url.starts_with("http://posthog.invalid/"));
+    assert_eq!(request.header("Content-Type"), Some("application/json"));
+    assert!(String::from_utf8_lossy(&request.body).contains(r#""event":"order_placed""#));
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn capture_goes_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(options(&transport)).await;
+
+        client.capture(Event::new("order_placed", "user-1"));
+        client.flush().await;
+
+        assert_captured(&transport);
+    }
+
+    #[tokio::test]
+    async fn remote_flags_go_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(options(&transport)).await;
+
+        let (flags, _) = client
+            .get_feature_flags("user-1", None, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            flags.get("remote-flag"),
+            Some(&FlagValue::String("variant-a".to_string()))
+        );
+        let requests = transport.requests_to("/flags/");
+        assert_eq!(requests.len(), 1);
+        assert!(String::from_utf8_lossy(&requests[0].body).contains(r#""distinct_id":"user-1""#));
+    }
+
+    #[tokio::test]
+    async fn connect_errors_are_not_retried() {
+        let transport = FakeTransport {
+            refuse_flags: true,
+            ..Default::default()
+        };
+        let client = posthog_rs::client(options(&transport)).await;
+
+        let result = client.get_feature_flags("user-1", None, None, None).await;
+
+        assert!(matches!(result, Err(posthog_rs::Error::Connection(_))));
+        assert_eq!(transport.requests_to("/flags/").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn definitions_poller_goes_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(local_evaluation_options(&transport)).await;
+
+        let flags = client.evaluate_flags("user-1", only_local()).await.unwrap();
+
+        assert!(flags.is_enabled("local-flag"));
+        let definitions = transport.requests_to("/flags/definitions/");
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].method, HttpMethod::Get);
+        assert_eq!(
+            definitions[0].header("authorization"),
+            Some("Bearer phx_test")
+        );
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn capture_goes_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(options(&transport));
+
+        client.capture(Event::new("order_placed", "user-1"));
+        client.flush();
+
+        assert_captured(&transport);
+    }
+
+    #[test]
+    fn remote_flags_go_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(options(&transport));
+
+        let (flags, _) = client
+            .get_feature_flags("user-1", None, None, None)
+            .unwrap();
+
+        assert_eq!(
+            flags.get("remote-flag"),
+            Some(&FlagValue::String("variant-a".to_string()))
+        );
+        let requests = transport.requests_to("/flags/");
+        assert_eq!(requests.len(), 1);
+        assert!(String::from_utf8_lossy(&requests[0].body).contains(r#""distinct_id":"user-1""#));
+    }
+
+    #[test]
+    fn connect_errors_are_not_retried() {
+        let transport = FakeTransport {
+            refuse_flags: true,
+            ..Default::default()
+        };
+        let client = posthog_rs::client(options(&transport));
+
+        let result = client.get_feature_flags("user-1", None, None, None);
+
+        assert!(matches!(result, Err(posthog_rs::Error::Connection(_))));
+        assert_eq!(transport.requests_to("/flags/").len(), 1);
+    }
+
+    #[test]
+    fn definitions_poller_goes_through_the_transport() {
+        let transport = FakeTransport::default();
+        let client = posthog_rs::client(local_evaluation_options(&transport));
+
+        let flags = client.evaluate_flags("user-1", only_local()).unwrap();
+
+        assert!(flags.is_enabled("local-flag"));
+        let definitions = transport.requests_to("/flags/definitions/");
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].method, HttpMethod::Get);
+        assert_eq!(
+            definitions[0].header("authorization"),
+            Some("Bearer phx_test")
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Person-update APIs (`identify`, `alias`, `set_person_properties*`,
+//! `unset_person_properties`) enqueue reserved events through the background
+//! transport. Assertions are wire-format-agnostic: they hold for both the v0
+//! `/batch/` and v1 `/i/v1/analytics/events` bodies.
+
+use std::collections::HashMap;
+
+use httpmock::prelude::*;
+use posthog_rs::IdentifyOptions;
+use serde_json::json;
+
+fn identify_options() -> IdentifyOptions {
+    IdentifyOptions::new()
+        .set("email", "jane@example.com")
+        .unwrap()
+        .set_once("first_plan", "free")
+        .unwrap()
+        .anon_distinct_id("anon-1")
+}
+
+fn identify_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"$identify""#)
+            .body_contains(r#""distinct_id":"user-1""#)
+            .body_contains(r#""$set":{"email":"jane@example.com"}"#)
+            .body_contains(r#""$set_once":{"first_plan":"free"}"#)
+            .body_contains(r#""$anon_distinct_id":"anon-1""#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn person_update_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"$create_alias""#)
+            .body_contains(r#""alias":"legacy-42""#)
+            .body_contains(r#""$set":{"plan":"pro"}"#)
+            .body_contains(r#""$set_once":{"plan":"pro"}"#)
+            .body_contains(r#""$unset":["plan"]"#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn plan() -> HashMap<String, serde_json::Value> {
+    HashMap::from([("plan".to_string(), json!("pro"))])
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    async fn create_test_client(base_url: String) -> posthog_rs::Client {
+        let options: posthog_rs::ClientOptions = ("test_api_key", base_url.as_str()).into();
+        posthog_rs::client(options).await
+    }
+
+    #[tokio::test]
+    async fn identify_sends_identify_event_with_merge() {
+        let server = MockServer::start();
+        let mock = identify_mock(&server);
+
+        let client = create_test_client(server.base_url()).await;
+        client.identify("user-1", identify_options()).unwrap();
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn alias_and_person_property_updates_share_one_batch() {
+        let server = MockServer::start();
+        let mock = person_update_mock(&server);
+
+        let client = create_test_client(server.base_url()).await;
+        client.alias("user-1", "legacy-42").unwrap();
+        client.set_person_properties("user-1", plan()).unwrap();
+        client.set_person_properties_once("user-1", plan()).unwrap();
+        client
+            .unset_person_properties("user-1", vec!["plan".to_string()])
+            .unwrap();
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    fn create_test_client(base_url: String) -> posthog_rs::Client {
+        let options: posthog_rs::ClientOptions = ("test_api_key", base_url.as_str()).into();
+        posthog_rs::client(options)
+    }
+
+    #[test]
+    fn identify_sends_identify_event_with_merge() {
+        let server = MockServer::start();
+        let mock = identify_mock(&server);
+
+        let client = create_test_client(server.base_url());
+        client.identify("user-1", identify_options()).unwrap();
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn alias_and_person_property_updates_share_one_batch() {
+        let server = MockServer::start();
+        let mock = person_update_mock(&server);
+
+        let client = create_test_client(server.base_url());
+        client.alias("user-1", "legacy-42").unwrap();
+        client.set_person_properties("user-1", plan()).unwrap();
+        client.set_person_properties_once("user-1", plan()).unwrap();
+        client
+            .unset_person_properties("user-1", vec!["plan".to_string()])
+            .unwrap();
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+}
+
+#[test]
+fn global_person_updates_require_initialized_client_unless_disabled() {
+    assert!(matches!(
+        posthog_rs::identify("user-1", IdentifyOptions::new()),
+        Err(posthog_rs::Error::NotInitialized)
+    ));
+
+    // Like `capture`, the person-update helpers no-op once the global client
+    // is disabled.
+    posthog_rs::disable_global();
+    posthog_rs::identify("user-1", IdentifyOptions::new()).unwrap();
+    posthog_rs::alias("user-1", "legacy-42").unwrap();
+    posthog_rs::set_person_properties("user-1", HashMap::new()).unwrap();
+    posthog_rs::set_person_properties_once("user-1", HashMap::new()).unwrap();
+    posthog_rs::unset_person_properties("user-1", vec!["plan".to_string()]).unwrap();
+}
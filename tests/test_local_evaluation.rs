@@ -7,12 +7,14 @@ mod common;
 
 use common::default_user_agent;
 use httpmock::prelude::*;
-#[cfg(feature = "async-client")]
-use posthog_rs::{AsyncFlagPoller, ClientOptionsBuilder};
 use posthog_rs::{
-    FeatureFlag, FeatureFlagCondition, FeatureFlagFilters, FlagCache, FlagPoller, FlagValue,
-    LocalEvaluationConfig, LocalEvaluationResponse, LocalEvaluator, Property,
+    match_feature_flag_with_context, ConditionKind, ConditionOutcome, EvaluationContext,
+    FeatureFlag, FeatureFlagCondition, FeatureFlagFilters, FlagCache, FlagExplanation, FlagPoller,
+    FlagReason, FlagValue, LocalEvaluationConfig, LocalEvaluationResponse, LocalEvaluator,
+    MultivariateFilter, MultivariateVariant, Property, PropertyOutcome,
 };
+#[cfg(feature = "async-client")]
+use posthog_rs::{AsyncFlagPoller, ClientOptionsBuilder};
 use reqwest::header::USER_AGENT;
 use serde_json::json;
 use std::collections::HashMap;
@@ -29,6 +31,7 @@ fn test_local_evaluation_basic() {
         key: "test-flag".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![],
@@ -40,6 +43,8 @@ fn test_local_evaluation_basic() {
             payloads: HashMap::new(),
             aggregation_group_type_index: None,
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     };
 
@@ -76,6 +81,7 @@ fn test_local_evaluation_with_properties() {
         key: "premium-feature".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![Property {
@@ -92,6 +98,8 @@ fn test_local_evaluation_with_properties() {
             payloads: HashMap::new(),
             aggregation_group_type_index: None,
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     };
 
@@ -392,24 +400,30 @@ fn test_cache_operations() {
             key: "flag1".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![],
                 multivariate: None,
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         },
         FeatureFlag {
             key: "flag2".to_string(),
             active: true,
             has_experiment: None,
+            bucketing_identifier: None,
             filters: FeatureFlagFilters {
                 groups: vec![],
                 multivariate: None,
                 payloads: HashMap::new(),
                 aggregation_group_type_index: None,
                 early_exit: false,
+                super_groups: None,
+                holdout_groups: None,
             },
         },
     ];
@@ -900,6 +914,7 @@ fn mixed_flag() -> FeatureFlag {
         key: "mixed-flag".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![
                 // Group condition: company plan == enterprise
@@ -931,6 +946,8 @@ fn mixed_flag() -> FeatureFlag {
             payloads: HashMap::new(),
             aggregation_group_type_index: None, // null = mixed
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     }
 }
@@ -940,6 +957,7 @@ fn only_group_flag() -> FeatureFlag {
         key: "only-group-flag".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![Property {
@@ -957,6 +975,8 @@ fn only_group_flag() -> FeatureFlag {
             // Pure group flag at the flag level
             aggregation_group_type_index: Some(0),
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     }
 }
@@ -1073,6 +1093,7 @@ fn test_mixed_flag_only_group_condition_no_groups_returns_false() {
         key: "mixed-only-group".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![Property {
@@ -1089,6 +1110,8 @@ fn test_mixed_flag_only_group_condition_no_groups_returns_false() {
             payloads: HashMap::new(),
             aggregation_group_type_index: None,
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     };
     let evaluator = LocalEvaluator::new(cache_with(flag));
@@ -1114,6 +1137,7 @@ fn test_group_condition_uses_group_key_for_bucketing() {
         key: "rollout-flag".to_string(),
         active: true,
         has_experiment: None,
+        bucketing_identifier: None,
         filters: FeatureFlagFilters {
             groups: vec![FeatureFlagCondition {
                 properties: vec![],
@@ -1125,6 +1149,8 @@ fn test_group_condition_uses_group_key_for_bucketing() {
             payloads: HashMap::new(),
             aggregation_group_type_index: Some(0),
             early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
         },
     };
     let evaluator = LocalEvaluator::new(cache_with(flag));
@@ -1160,3 +1186,437 @@ fn test_group_condition_uses_group_key_for_bucketing() {
         .unwrap();
     assert_eq!(result, Some(FlagValue::Boolean(false)));
 }
+
+// ---- Evaluation reasons ----
+
+fn reasons_flag(active: bool) -> FeatureFlag {
+    let condition = |key: &str, value: &str, rollout: f64| FeatureFlagCondition {
+        properties: vec![Property {
+            key: key.to_string(),
+            value: json!(value),
+            operator: "exact".to_string(),
+            property_type: Some("person".to_string()),
+        }],
+        rollout_percentage: Some(rollout),
+        variant: None,
+        aggregation_group_type_index: None,
+    };
+    FeatureFlag {
+        key: "reasons-flag".to_string(),
+        active,
+        has_experiment: None,
+        bucketing_identifier: None,
+        filters: FeatureFlagFilters {
+            groups: vec![
+                condition("email", "test@example.com", 100.0),
+                condition("plan", "pro", 0.0),
+            ],
+            multivariate: None,
+            payloads: HashMap::new(),
+            aggregation_group_type_index: None,
+            early_exit: false,
+            super_groups: None,
+            holdout_groups: None,
+        },
+    }
+}
+
+fn evaluate_reason(flag: FeatureFlag, email: &str, plan: &str) -> (bool, FlagReason) {
+    let evaluator = LocalEvaluator::new(cache_with(flag));
+    let mut properties = HashMap::new();
+    properties.insert("email".to_string(), json!(email));
+    properties.insert("plan".to_string(), json!(plan));
+    let detail = evaluator
+        .evaluate_flag_detailed(
+            "reasons-flag",
+            "user-123",
+            &properties,
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap()
+        .expect("flag is defined");
+    assert_eq!(detail.key, "reasons-flag");
+    (
+        detail.enabled,
+        detail.reason.expect("local results carry a reason"),
+    )
+}
+
+#[test]
+fn test_local_evaluation_reports_the_matching_condition() {
+    let (enabled, reason) = evaluate_reason(reasons_flag(true), "test@example.com", "free");
+
+    assert!(enabled);
+    assert_eq!(reason.code, "condition_match");
+    assert_eq!(reason.condition_index, Some(0));
+    assert_eq!(
+        reason.description.as_deref(),
+        Some("Matched condition set 1")
+    );
+}
+
+#[test]
+fn test_local_evaluation_reports_out_of_rollout_bound() {
+    let (enabled, reason) = evaluate_reason(reasons_flag(true), "other@example.com", "pro");
+
+    assert!(!enabled);
+    assert_eq!(reason.code, "out_of_rollout_bound");
+    assert_eq!(reason.condition_index, Some(1));
+}
+
+#[test]
+fn test_local_evaluation_reports_no_condition_match_and_disabled_flags() {
+    let (enabled, reason) = evaluate_reason(reasons_flag(true), "other@example.com", "free");
+    assert!(!enabled);
+    assert_eq!(reason.code, "no_condition_match");
+    assert_eq!(reason.condition_index, None);
+
+    let (enabled, reason) = evaluate_reason(reasons_flag(false), "test@example.com", "free");
+    assert!(!enabled);
+    assert_eq!(reason.code, "flag_disabled");
+}
+
+#[test]
+fn test_detailed_and_plain_local_evaluation_agree() {
+    let evaluator = LocalEvaluator::new(cache_with(reasons_flag(true)));
+    let mut properties = HashMap::new();
+    properties.insert("email".to_string(), json!("test@example.com"));
+
+    let detailed = evaluator.evaluate_all_flags_detailed(
+        "user-123",
+        &properties,
+        &HashMap::new(),
+        &HashMap::new(),
+    );
+    let plain = evaluator.evaluate_flag(
+        "reasons-flag",
+        "user-123",
+        &properties,
+        &HashMap::new(),
+        &HashMap::new(),
+    );
+
+    let detail = detailed["reasons-flag"].as_ref().unwrap();
+    assert_eq!(Some(detail.value()), plain.unwrap());
+    assert!(evaluator
+        .evaluate_flag_detailed(
+            "missing",
+            "user-123",
+            &properties,
+            &HashMap::new(),
+            &HashMap::new()
+        )
+        .unwrap()
+        .is_none());
+}
+
+// ---- Definition snapshots ----
+
+fn definitions(flag_key: &str) -> LocalEvaluationResponse {
+    serde_json::from_value(json!({
+        "flags": [{
+            "key": flag_key,
+            "active": true,
+            "filters": {
+                "groups": [{
+                    "properties": [
+                        { "key": "email", "value": "@example\\.com$", "operator": "regex", "type": "person" },
+                        { "key": "id", "value": "7", "operator": "in", "type": "cohort" }
+                    ],
+                    "rollout_percentage": 100.0
+                }]
+            }
+        }],
+        "cohorts": {
+            "7": {
+                "id": "7",
+                "name": "Swiss",
+                "properties": { "type": "AND", "values": [
+                    { "type": "property", "key": "country", "value": "CH", "operator": "exact" }
+                ] }
+            }
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_snapshot_keeps_its_definitions_across_updates() {
+    let cache = FlagCache::new();
+    cache.update(definitions("first-flag"));
+    let before = cache.snapshot();
+
+    cache.update(definitions("second-flag"));
+
+    assert!(before.flag("first-flag").is_some());
+    assert!(before.flag("second-flag").is_none());
+    assert!(before.cohort("7").is_some());
+    let after = cache.snapshot();
+    assert!(after.flag("first-flag").is_none());
+    assert!(after.flag("second-flag").is_some());
+}
+
+#[test]
+fn test_snapshot_evaluation_agrees_with_match_feature_flag_with_context() {
+    let cache = FlagCache::new();
+    cache.update(definitions("swiss-example"));
+    let evaluator = LocalEvaluator::new(cache.clone());
+    let flag = cache.get_flag("swiss-example").unwrap();
+    let (cohorts, flags) = (cache.get_cohort_definitions(), cache.get_flags_map());
+    let (groups, group_properties, group_type_mapping) =
+        (HashMap::new(), HashMap::new(), HashMap::new());
+    let ctx = EvaluationContext {
+        cohorts: &cohorts,
+        flags: &flags,
+        distinct_id: "user-1",
+        device_id: None,
+        groups: &groups,
+        group_properties: &group_properties,
+        group_type_mapping: &group_type_mapping,
+        cohort_membership: None,
+    };
+
+    for (email, country) in [
+        ("jane@example.com", "ch"),
+        ("jane@example.com", "de"),
+        ("jane@example.org", "ch"),
+    ] {
+        let mut properties = HashMap::new();
+        properties.insert("email".to_string(), json!(email));
+        properties.insert("country".to_string(), json!(country));
+
+        let compiled = evaluator
+            .evaluate_flag(
+                "swiss-example",
+                "user-1",
+                &properties,
+                &groups,
+                &group_properties,
+            )
+            .unwrap();
+        let free = match_feature_flag_with_context(&flag, &properties, &ctx).unwrap();
+
+        assert_eq!(compiled, Some(free), "{} in {}", email, country);
+        assert_eq!(
+            compiled,
+            Some(FlagValue::Boolean(
+                email.ends_with(".com") && country == "ch"
+            ))
+        );
+    }
+}
+
+// ---- Explanations ----
+
+fn explain(
+    evaluator: &LocalEvaluator,
+    key: &str,
+    properties: &[(&str, serde_json::Value)],
+) -> FlagExplanation {
+    let properties = properties
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+    evaluator
+        .explain(
+            key,
+            "user-123",
+            &properties,
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .expect("flag is defined")
+}
+
+#[test]
+fn test_explain_traces_properties_and_rollout() {
+    let evaluator = LocalEvaluator::new(cache_with(reasons_flag(true)));
+
+    let explanation = explain(
+        &evaluator,
+        "reasons-flag",
+        &[
+            ("email", json!("other@example.com")),
+            ("plan", json!("pro")),
+        ],
+    );
+
+    assert_eq!(explanation.value, Some(FlagValue::Boolean(false)));
+    assert_eq!(
+        explanation.reason.as_ref().map(|r| r.code.as_str()),
+        Some("out_of_rollout_bound")
+    );
+    let [email, plan] = explanation.conditions.as_slice() else {
+        panic!("expected two conditions, got {:?}", explanation.conditions);
+    };
+    assert_eq!(email.outcome, ConditionOutcome::PropertiesNotMatched);
+    assert_eq!(email.bucketing_id.as_deref(), Some("user-123"));
+    assert_eq!(
+        email.properties[0].property.value,
+        json!("test@example.com")
+    );
+    assert_eq!(email.properties[0].actual, Some(json!("other@example.com")));
+    assert_eq!(email.properties[0].outcome, PropertyOutcome::NotMatched);
+    assert!(email.rollout.is_none());
+
+    assert_eq!(plan.outcome, ConditionOutcome::OutOfRolloutBound);
+    assert_eq!(plan.properties[0].outcome, PropertyOutcome::Matched);
+    let rollout = plan.rollout.expect("the rollout was checked");
+    assert_eq!(rollout.percentage, 0.0);
+    assert!(rollout.hash > 0.0 && !rollout.included);
+}
+
+#[test]
+fn test_explain_reports_inconclusive_properties() {
+    let evaluator = LocalEvaluator::new(cache_with(reasons_flag(true)));
+
+    let explanation = explain(
+        &evaluator,
+        "reasons-flag",
+        &[("email", json!("other@example.com"))],
+    );
+
+    assert_eq!(explanation.value, None);
+    assert_eq!(explanation.reason, None);
+    let plan = &explanation.conditions[1];
+    assert!(matches!(
+        &plan.properties[0].outcome,
+        PropertyOutcome::Inconclusive(message) if message.contains("'plan'")
+    ));
+    assert_eq!(plan.properties[0].actual, None);
+    assert!(matches!(plan.outcome, ConditionOutcome::Inconclusive(_)));
+}
+
+#[test]
+fn test_explain_reports_the_variant_bucket() {
+    let mut flag = reasons_flag(true);
+    flag.filters.groups = vec![FeatureFlagCondition {
+        properties: vec![],
+        rollout_percentage: Some(100.0),
+        variant: None,
+        aggregation_group_type_index: None,
+    }];
+    flag.filters.multivariate = Some(MultivariateFilter {
+        variants: vec![
+            MultivariateVariant {
+                key: "control".to_string(),
+                rollout_percentage: 50.0,
+            },
+            MultivariateVariant {
+                key: "test".to_string(),
+                rollout_percentage: 50.0,
+            },
+        ],
+    });
+    let evaluator = LocalEvaluator::new(cache_with(flag));
+
+    let explanation = explain(&evaluator, "reasons-flag", &[]);
+    let evaluated = evaluator
+        .evaluate_flag(
+            "reasons-flag",
+            "user-123",
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .unwrap();
+
+    assert_eq!(explanation.value, evaluated);
+    assert_eq!(explanation.conditions[0].outcome, ConditionOutcome::Matched);
+    let variant = explanation.variant.expect("a variant was picked");
+    let hash = variant.hash.expect("the variant was bucketed");
+    let expected = if hash < 0.5 { "control" } else { "test" };
+    assert_eq!(variant.variant.as_deref(), Some(expected));
+    assert!(!variant.overridden);
+}
+
+#[test]
+fn test_explain_reports_skipped_group_conditions_and_unknown_flags() {
+    let evaluator = LocalEvaluator::new(cache_with(only_group_flag()));
+
+    let explanation = explain(&evaluator, "only-group-flag", &[]);
+
+    assert_eq!(explanation.value, Some(FlagValue::Boolean(false)));
+    let condition = &explanation.conditions[0];
+    assert_eq!(condition.group_type.as_deref(), Some("company"));
+    assert_eq!(condition.bucketing_id, None);
+    assert!(matches!(condition.outcome, ConditionOutcome::Skipped(_)));
+    assert!(evaluator
+        .explain(
+            "missing-flag",
+            "user-123",
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new()
+        )
+        .is_none());
+}
+
+fn early_access_experiment() -> FeatureFlag {
+    serde_json::from_value(json!({
+        "key": "early-access-experiment",
+        "active": true,
+        "filters": {
+            "groups": [{ "properties": [], "rollout_percentage": 100 }],
+            "super_groups": [{
+                "properties": [{
+                    "key": "$feature_enrollment/early-access-experiment",
+                    "type": "person",
+                    "value": ["true"],
+                    "operator": "exact"
+                }],
+                "rollout_percentage": 100
+            }],
+            "holdout_groups": [{
+                "properties": [],
+                "rollout_percentage": 100,
+                "variant": "holdout-7"
+            }]
+        }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_explain_reports_super_conditions_and_holdouts() {
+    let cache = cache_with(early_access_experiment());
+    let evaluator = LocalEvaluator::new(cache.clone());
+
+    let held_out = explain(&evaluator, "early-access-experiment", &[]);
+    assert_eq!(held_out.value, Some(FlagValue::from("holdout-7")));
+    assert_eq!(
+        held_out.reason.as_ref().map(|r| r.code.as_str()),
+        Some("holdout_condition_value")
+    );
+    let [enrollment, holdout] = held_out.conditions.as_slice() else {
+        panic!("expected two conditions, got {:?}", held_out.conditions);
+    };
+    assert_eq!(enrollment.kind, ConditionKind::Super);
+    assert!(matches!(enrollment.outcome, ConditionOutcome::Skipped(_)));
+    assert_eq!(holdout.kind, ConditionKind::Holdout);
+    assert_eq!(holdout.outcome, ConditionOutcome::Matched);
+    assert!(holdout.rollout.is_some_and(|rollout| rollout.included));
+
+    let enrolled = explain(
+        &evaluator,
+        "early-access-experiment",
+        &[("$feature_enrollment/early-access-experiment", json!(true))],
+    );
+    assert_eq!(enrolled.value, Some(FlagValue::Boolean(true)));
+    assert_eq!(
+        enrolled.reason,
+        Some(FlagReason {
+            code: "super_condition_value".to_string(),
+            condition_index: Some(0),
+            description: Some("Super condition value".to_string()),
+        })
+    );
+    assert_eq!(enrolled.conditions.len(), 1);
+    assert_eq!(enrolled.conditions[0].outcome, ConditionOutcome::Matched);
+
+    let keys = cache.property_keys("early-access-experiment").unwrap();
+    assert!(keys
+        .person
+        .contains("$feature_enrollment/early-access-experiment"));
+}
@@ -0,0 +1,121 @@
+//! With the `metrics` feature the client's delivery counters are forwarded to
+//! the installed `metrics` recorder. The recorder is process-global, so this
+//! file holds a single test per client flavor. Captures go to a rejecting
+//! endpoint so the assertions hold for both the v0 and v1 pipelines.
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use common::options_builder;
+use httpmock::prelude::*;
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+use posthog_rs::{ClientOptions, Event};
+
+/// Counters by metric name, summed across labels.
+#[derive(Default)]
+struct CountingRecorder {
+    counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+}
+
+impl CountingRecorder {
+    fn get(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0, |counter| counter.load(Ordering::Relaxed))
+    }
+}
+
+impl Recorder for CountingRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        let counter = Arc::clone(
+            self.counters
+                .lock()
+                .unwrap()
+                .entry(key.name().to_string())
+                .or_default(),
+        );
+        Counter::from_arc(counter)
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::noop()
+    }
+}
+
+fn recorder() -> &'static CountingRecorder {
+    static RECORDER: OnceLock<&'static CountingRecorder> = OnceLock::new();
+    RECORDER.get_or_init(|| {
+        let recorder: &'static CountingRecorder = Box::leak(Box::default());
+        metrics::set_global_recorder(recorder).unwrap();
+        recorder
+    })
+}
+
+/// Rejects every capture and drops `discarded` events in `before_send`.
+fn options(server: &MockServer) -> ClientOptions {
+    server.mock(|when, then| {
+        when.method(POST);
+        then.status(400);
+    });
+    options_builder(&server.base_url())
+        .flush_interval_ms(600_000u64)
+        .before_send(|event| (event.event_name() != "discarded").then_some(event))
+        .build()
+        .unwrap()
+}
+
+fn assert_counted(recorder: &CountingRecorder) {
+    assert_eq!(recorder.get("posthog.events.enqueued"), 3);
+    assert_eq!(recorder.get("posthog.events.dropped_before_send"), 1);
+    assert_eq!(recorder.get("posthog.events.rejected"), 2);
+    assert_eq!(recorder.get("posthog.events.sent"), 0);
+    assert!(recorder.get("posthog.capture.bytes_sent") > 0);
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn capture_drop_and_flush_move_the_counters() {
+    let recorder = recorder();
+    let server = MockServer::start();
+    let client = posthog_rs::client(options(&server)).await;
+
+    client.capture(Event::new("signed_up", "user-1"));
+    client.capture(Event::new("discarded", "user-1"));
+    client.capture(Event::new("checkout_started", "user-1"));
+    assert_eq!(recorder.get("posthog.events.enqueued"), 3);
+    assert_eq!(recorder.get("posthog.events.rejected"), 0);
+
+    client.flush().await;
+    assert_counted(recorder);
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn capture_drop_and_flush_move_the_counters() {
+    let recorder = recorder();
+    let server = MockServer::start();
+    let client = posthog_rs::client(options(&server));
+
+    client.capture(Event::new("signed_up", "user-1"));
+    client.capture(Event::new("discarded", "user-1"));
+    client.capture(Event::new("checkout_started", "user-1"));
+    assert_eq!(recorder.get("posthog.events.enqueued"), 3);
+    assert_eq!(recorder.get("posthog.events.rejected"), 0);
+
+    client.flush();
+    assert_counted(recorder);
+}
@@ -9,6 +9,7 @@
 #![cfg(feature = "async-client")]
 
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::net::TcpListener;
 use std::sync::{
@@ -189,10 +190,24 @@ fn start_recording_server(gate: bool) -> RecordingServer {
 async fn captured_flag_called_properties(
     gate: bool,
     flag_key: &str,
+) -> serde_json::Map<String, Value> {
+    captured_flag_called_properties_with(gate, flag_key, HashMap::new()).await
+}
+
+/// Like [`captured_flag_called_properties`], with client super properties.
+async fn captured_flag_called_properties_with(
+    gate: bool,
+    flag_key: &str,
+    super_properties: HashMap<String, Value>,
 ) -> serde_json::Map<String, Value> {
     let server = start_recording_server(gate);
 
-    let options: posthog_rs::ClientOptions = ("phc_test", server.base_url.as_str()).into();
+    let options = posthog_rs::ClientOptionsBuilder::default()
+        .api_key("phc_test".to_string())
+        .host(server.base_url.as_str())
+        .super_properties(super_properties)
+        .build()
+        .unwrap();
     let client = posthog_rs::client(options).await;
     let snapshot = client
         .evaluate_flags("user-1", EvaluateFlagsOptions::default())
@@ -271,3 +286,23 @@ async fn remote_gate_off_keeps_full_event() {
     );
     assert_eq!(props.get("$feature/plain"), Some(&json!(true)));
 }
+
+fn service_super_property() -> HashMap<String, Value> {
+    HashMap::from([("service".to_string(), json!("billing"))])
+}
+
+#[tokio::test]
+async fn super_properties_reach_full_flag_called_event() {
+    let props =
+        captured_flag_called_properties_with(false, "plain", service_super_property()).await;
+    assert_eq!(props.get("service"), Some(&json!("billing")));
+    assert_eq!(props.get("$feature_flag"), Some(&json!("plain")));
+}
+
+#[tokio::test]
+async fn super_properties_are_trimmed_from_minimal_flag_called_event() {
+    let props = captured_flag_called_properties_with(true, "plain", service_super_property()).await;
+    // The allowlist still applies: the super property is dropped.
+    assert!(!props.contains_key("service"));
+    assert_eq!(props.get("$feature_flag"), Some(&json!("plain")));
+}
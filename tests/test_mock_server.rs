@@ -0,0 +1,219 @@
+#![cfg(feature = "mock-server")]
+
+//! End-to-end runs against the in-process `MockPostHog` server: capture in the
+//! build's wire format (v0 `/batch/` or v1 `/i/v1/analytics/events`, compressed
+//! or not), remote and local flag evaluation from programmed definitions, and
+//! retries driven by injected failures.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{options_builder, pro_user};
+use posthog_rs::{
+    ClientOptions, EvaluateFlagsOptions, Event, FlagValue, LocalEvaluationResponse, MockPostHog,
+    MockResponse, MockRoute,
+};
+use serde_json::json;
+
+fn definitions() -> LocalEvaluationResponse {
+    serde_json::from_value(json!({
+        "flags": [
+            {
+                "key": "pro-dashboard",
+                "active": true,
+                "filters": {
+                    "groups": [{
+                        "properties": [{ "key": "plan", "value": "pro", "type": "person" }],
+                        "rollout_percentage": 100.0
+                    }],
+                    "payloads": { "true": { "columns": 3 } }
+                }
+            },
+            {
+                "key": "checkout",
+                "active": true,
+                "filters": {
+                    "groups": [{ "properties": [], "rollout_percentage": 100.0, "variant": "one-page" }],
+                    "multivariate": { "variants": [
+                        { "key": "one-page", "rollout_percentage": 50.0 },
+                        { "key": "classic", "rollout_percentage": 50.0 }
+                    ] }
+                }
+            }
+        ]
+    }))
+    .unwrap()
+}
+
+fn options(server: &MockPostHog) -> ClientOptions {
+    options_builder(&server.base_url())
+        .retry_initial_backoff_ms(1u64)
+        .retry_max_backoff_ms(2000u64)
+        .build()
+        .unwrap()
+}
+
+fn local_evaluation_options(server: &MockPostHog) -> ClientOptions {
+    options_builder(&server.base_url())
+        .secret_key("phx_test".to_string())
+        .enable_local_evaluation(true)
+        .build()
+        .unwrap()
+}
+
+fn local_pro_user() -> EvaluateFlagsOptions {
+    EvaluateFlagsOptions {
+        only_evaluate_locally: true,
+        ..pro_user()
+    }
+}
+
+fn assert_received(server: &MockPostHog, names: &[&str]) {
+    let events = server.events();
+    let received: Vec<&str> = events.iter().filter_map(|e| e["event"].as_str()).collect();
+    assert_eq!(received, names);
+    assert!(events.iter().all(|e| e["distinct_id"] == "user-1"));
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn receives_captured_events() {
+        let server = MockPostHog::start();
+        let client = posthog_rs::client(options(&server)).await;
+
+        client.capture(Event::new("signed_up", "user-1"));
+        client.capture(Event::new("upgraded", "user-1"));
+        client.flush().await;
+
+        assert_received(&server, &["signed_up", "upgraded"]);
+    }
+
+    #[tokio::test]
+    async fn honors_injected_retry_after() {
+        let server = MockPostHog::start();
+        server.respond_next(
+            MockRoute::Capture,
+            MockResponse::status(503).with_retry_after(1),
+        );
+        let client = posthog_rs::client(options(&server)).await;
+
+        let started = Instant::now();
+        client
+            .capture_immediate(Event::new("signed_up", "user-1"))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(900));
+        assert_eq!(server.hits(MockRoute::Capture), 2);
+        assert_received(&server, &["signed_up"]);
+    }
+
+    #[tokio::test]
+    async fn evaluates_remote_flags_from_definitions() {
+        let server = MockPostHog::start();
+        server.set_flag_definitions(definitions());
+        let client = posthog_rs::client(options(&server)).await;
+
+        let flags = client.evaluate_flags("user-1", pro_user()).await.unwrap();
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert_eq!(
+            flags.get_flag_payload("pro-dashboard"),
+            Some(json!({ "columns": 3 }))
+        );
+        assert_eq!(
+            flags.get_flag("checkout"),
+            Some(FlagValue::String("one-page".to_string()))
+        );
+        assert_eq!(server.hits(MockRoute::Flags), 1);
+    }
+
+    #[tokio::test]
+    async fn serves_definitions_to_local_evaluation() {
+        let server = MockPostHog::start();
+        server.set_flag_definitions(definitions());
+        let client = posthog_rs::client(local_evaluation_options(&server)).await;
+
+        let flags = client
+            .evaluate_flags("user-1", local_pro_user())
+            .await
+            .unwrap();
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert_eq!(server.hits(MockRoute::FlagDefinitions), 1);
+        assert_eq!(server.hits(MockRoute::Flags), 0);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn receives_captured_events() {
+        let server = MockPostHog::start();
+        let client = posthog_rs::client(options(&server));
+
+        client.capture(Event::new("signed_up", "user-1"));
+        client.capture(Event::new("upgraded", "user-1"));
+        client.flush();
+
+        assert_received(&server, &["signed_up", "upgraded"]);
+    }
+
+    #[test]
+    fn honors_injected_retry_after() {
+        let server = MockPostHog::start();
+        server.respond_next(
+            MockRoute::Capture,
+            MockResponse::status(503).with_retry_after(1),
+        );
+        let client = posthog_rs::client(options(&server));
+
+        let started = Instant::now();
+        client
+            .capture_immediate(Event::new("signed_up", "user-1"))
+            .unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(900));
+        assert_eq!(server.hits(MockRoute::Capture), 2);
+        assert_received(&server, &["signed_up"]);
+    }
+
+    #[test]
+    fn evaluates_remote_flags_from_definitions() {
+        let server = MockPostHog::start();
+        server.set_flag_definitions(definitions());
+        let client = posthog_rs::client(options(&server));
+
+        let flags = client.evaluate_flags("user-1", pro_user()).unwrap();
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert_eq!(
+            flags.get_flag_payload("pro-dashboard"),
+            Some(json!({ "columns": 3 }))
+        );
+        assert_eq!(
+            flags.get_flag("checkout"),
+            Some(FlagValue::String("one-page".to_string()))
+        );
+        assert_eq!(server.hits(MockRoute::Flags), 1);
+    }
+
+    #[test]
+    fn serves_definitions_to_local_evaluation() {
+        let server = MockPostHog::start();
+        server.set_flag_definitions(definitions());
+        let client = posthog_rs::client(local_evaluation_options(&server));
+
+        let flags = client.evaluate_flags("user-1", local_pro_user()).unwrap();
+
+        assert!(flags.is_enabled("pro-dashboard"));
+        assert_eq!(server.hits(MockRoute::FlagDefinitions), 1);
+        assert_eq!(server.hits(MockRoute::Flags), 0);
+    }
+}
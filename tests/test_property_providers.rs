@@ -0,0 +1,381 @@
+//! Properties a flag filters on but that were not passed in are loaded from a
+//! `PersonPropertyProvider` or `GroupPropertyProvider` before local
+//! evaluation gives up and falls back to remote `/flags`.
+
+mod common;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use common::{definitions_path, flags_mock, no_flags, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{
+    FlagCache, FlagPropertyKeys, FlagValue, GroupPropertyProvider, LocalEvaluator,
+    PersonPropertyProvider,
+};
+use serde_json::{json, Value};
+
+/// Knows the plan and country of `enterprise-user` and `free-user`, and the
+/// size of the `acme` company. Records the keys it was asked for.
+#[derive(Clone, Default)]
+struct UserStore {
+    requests: Arc<Mutex<Vec<Vec<String>>>>,
+}
+
+impl UserStore {
+    fn requests(&self) -> Vec<Vec<String>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl PersonPropertyProvider for UserStore {
+    fn person_properties(&self, distinct_id: &str, keys: &[String]) -> HashMap<String, Value> {
+        self.requests.lock().unwrap().push(keys.to_vec());
+        let known = match distinct_id {
+            "enterprise-user" => json!({ "plan": "enterprise", "country": "NZ", "email": "a@b.c" }),
+            "free-user" => json!({ "plan": "free", "country": "US" }),
+            _ => return HashMap::new(),
+        };
+        serde_json::from_value(known).unwrap()
+    }
+}
+
+impl GroupPropertyProvider for UserStore {
+    fn group_properties(
+        &self,
+        group_type: &str,
+        group_key: &str,
+        keys: &[String],
+    ) -> HashMap<String, Value> {
+        self.requests.lock().unwrap().push(keys.to_vec());
+        match (group_type, group_key) {
+            ("company", "acme") => HashMap::from([("employees".to_string(), json!(500))]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+fn definitions() -> Value {
+    json!({
+        "flags": [
+            {
+                "key": "plan-flag",
+                "active": true,
+                "filters": { "groups": [{
+                    "properties": [{ "key": "plan", "type": "person", "value": "enterprise", "operator": "exact" }],
+                    "rollout_percentage": 100.0
+                }] }
+            },
+            {
+                "key": "cohort-flag",
+                "active": true,
+                "filters": { "groups": [{
+                    "properties": [{ "key": "id", "type": "cohort", "value": 3, "operator": "in" }],
+                    "rollout_percentage": 100.0
+                }] }
+            },
+            {
+                "key": "company-flag",
+                "active": true,
+                "filters": {
+                    "aggregation_group_type_index": 0,
+                    "groups": [{
+                        "properties": [{ "key": "employees", "type": "group", "group_type_index": 0, "value": 100, "operator": "gt" }],
+                        "rollout_percentage": 100.0
+                    }]
+                }
+            }
+        ],
+        "cohorts": {
+            "3": {
+                "id": "3",
+                "name": "New Zealand",
+                "properties": { "type": "AND", "values": [
+                    { "key": "country", "type": "person", "value": "NZ", "operator": "exact" }
+                ] }
+            }
+        },
+        "group_type_mapping": { "0": "company" }
+    })
+}
+
+fn cache() -> FlagCache {
+    let cache = FlagCache::new();
+    cache
+        .load_from_reader(definitions().to_string().as_bytes())
+        .unwrap();
+    cache
+}
+
+fn evaluate(
+    evaluator: &LocalEvaluator,
+    key: &str,
+    distinct_id: &str,
+    person_properties: Value,
+) -> Option<FlagValue> {
+    evaluator
+        .evaluate_flag(
+            key,
+            distinct_id,
+            &serde_json::from_value(person_properties).unwrap(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+        .ok()
+        .flatten()
+}
+
+#[test]
+fn flags_list_the_property_keys_they_need() {
+    let cache = cache();
+    let person = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+
+    let keys = cache.property_keys("plan-flag").unwrap();
+    assert_eq!(keys.person, person(&["plan"]));
+    assert!(keys.groups.is_empty());
+
+    let keys = cache.property_keys("cohort-flag").unwrap();
+    assert_eq!(keys.person, person(&["country"]));
+
+    let keys = cache.property_keys("company-flag").unwrap();
+    assert!(keys.person.is_empty());
+    assert_eq!(
+        keys.groups,
+        BTreeMap::from([("company".to_string(), person(&["employees"]))])
+    );
+    assert!(!keys.is_empty());
+
+    assert_eq!(cache.property_keys("missing-flag"), None);
+    assert_eq!(
+        cache.snapshot().property_keys("plan-flag"),
+        cache.property_keys("plan-flag")
+    );
+    assert!(FlagPropertyKeys::default().is_empty());
+}
+
+#[test]
+fn missing_person_properties_are_loaded_from_the_provider() {
+    let store = UserStore::default();
+    let plain = LocalEvaluator::new(cache());
+    let provided = LocalEvaluator::new(cache()).with_person_property_provider(store.clone());
+
+    assert_eq!(
+        evaluate(&plain, "plan-flag", "enterprise-user", json!({})),
+        None
+    );
+    assert_eq!(
+        evaluate(&provided, "plan-flag", "enterprise-user", json!({})),
+        Some(FlagValue::Boolean(true))
+    );
+    assert_eq!(
+        evaluate(&provided, "plan-flag", "free-user", json!({})),
+        Some(FlagValue::Boolean(false))
+    );
+    assert_eq!(
+        evaluate(&provided, "cohort-flag", "enterprise-user", json!({})),
+        Some(FlagValue::Boolean(true))
+    );
+    assert_eq!(
+        evaluate(&provided, "plan-flag", "stranger", json!({})),
+        None
+    );
+    assert_eq!(
+        store.requests(),
+        [vec!["plan"], vec!["plan"], vec!["country"], vec!["plan"]]
+    );
+
+    // Passed-in properties win and are not loaded again.
+    assert_eq!(
+        evaluate(
+            &provided,
+            "plan-flag",
+            "enterprise-user",
+            json!({ "plan": "free" })
+        ),
+        Some(FlagValue::Boolean(false))
+    );
+    assert_eq!(store.requests().len(), 4);
+}
+
+#[test]
+fn evaluating_all_flags_loads_missing_properties_once() {
+    let store = UserStore::default();
+    let evaluator = LocalEvaluator::new(cache()).with_person_property_provider(store.clone());
+
+    let flags = evaluator.evaluate_all_flags(
+        "enterprise-user",
+        &HashMap::new(),
+        &HashMap::new(),
+        &HashMap::new(),
+    );
+
+    assert_eq!(
+        flags["plan-flag"].as_ref().ok(),
+        Some(&FlagValue::Boolean(true))
+    );
+    assert_eq!(
+        flags["cohort-flag"].as_ref().ok(),
+        Some(&FlagValue::Boolean(true))
+    );
+    assert_eq!(
+        flags["company-flag"].as_ref().ok(),
+        Some(&FlagValue::Boolean(false))
+    );
+    assert_eq!(store.requests(), [vec!["country", "plan"]]);
+}
+
+#[test]
+fn missing_group_properties_are_loaded_for_passed_in_groups() {
+    let store = UserStore::default();
+    let evaluator = LocalEvaluator::new(cache()).with_group_property_provider(store.clone());
+    let evaluate = |group_key: &str| {
+        evaluator
+            .evaluate_flag(
+                "company-flag",
+                "someone",
+                &HashMap::new(),
+                &HashMap::from([("company".to_string(), group_key.to_string())]),
+                &HashMap::new(),
+            )
+            .ok()
+            .flatten()
+    };
+
+    assert_eq!(evaluate("acme"), Some(FlagValue::Boolean(true)));
+    assert_eq!(evaluate("unknown"), None);
+    assert_eq!(store.requests(), [vec!["employees"], vec!["employees"]]);
+
+    let explanation = evaluator
+        .explain(
+            "company-flag",
+            "someone",
+            &HashMap::new(),
+            &HashMap::from([("company".to_string(), "acme".to_string())]),
+            &HashMap::new(),
+        )
+        .unwrap();
+    assert_eq!(explanation.value, Some(FlagValue::Boolean(true)));
+    assert_eq!(
+        explanation.conditions[0].properties[0].actual,
+        Some(json!(500))
+    );
+}
+
+#[test]
+fn panicking_providers_leave_flags_inconclusive() {
+    struct Broken;
+
+    impl PersonPropertyProvider for Broken {
+        fn person_properties(&self, _: &str, _: &[String]) -> HashMap<String, Value> {
+            panic!("user store unavailable")
+        }
+    }
+
+    let evaluator = LocalEvaluator::new(cache()).with_person_property_provider(Broken);
+    assert_eq!(
+        evaluate(&evaluator, "plan-flag", "enterprise-user", json!({})),
+        None
+    );
+}
+
+fn options(server: &MockServer, path: &std::path::Path) -> posthog_rs::ClientOptions {
+    options_builder(&server.base_url())
+        .flag_definitions_path(path)
+        .person_property_provider(UserStore::default())
+        .group_property_provider(UserStore::default())
+        .build()
+        .unwrap()
+}
+
+fn evaluate_flags_options() -> posthog_rs::EvaluateFlagsOptions {
+    posthog_rs::EvaluateFlagsOptions {
+        groups: Some(HashMap::from([("company".to_string(), "acme".to_string())])),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn clients_load_missing_properties_without_remote_flags() {
+    let server = MockServer::start();
+    let mock = flags_mock(&server, no_flags());
+    let path = definitions_path("property-providers");
+    std::fs::write(&path, definitions().to_string()).unwrap();
+    let client = posthog_rs::client(options(&server, &path)).await;
+    let _ = std::fs::remove_file(&path);
+
+    let flags = client
+        .evaluate_flags("enterprise-user", evaluate_flags_options())
+        .await
+        .unwrap();
+
+    assert!(flags.is_enabled("plan-flag"));
+    assert!(flags.is_enabled("cohort-flag"));
+    assert!(flags.is_enabled("company-flag"));
+    assert_eq!(mock.hits(), 0);
+}
+
+#[cfg(feature = "async-client")]
+#[tokio::test]
+async fn async_clients_call_providers_off_the_runtime_thread() {
+    #[derive(Clone, Default)]
+    struct Threads(Arc<Mutex<Vec<std::thread::ThreadId>>>);
+
+    impl PersonPropertyProvider for Threads {
+        fn person_properties(&self, _: &str, _: &[String]) -> HashMap<String, Value> {
+            self.0.lock().unwrap().push(std::thread::current().id());
+            HashMap::from([("plan".to_string(), json!("enterprise"))])
+        }
+    }
+
+    let server = MockServer::start();
+    flags_mock(&server, no_flags());
+    let path = definitions_path("property-providers");
+    std::fs::write(&path, definitions().to_string()).unwrap();
+    let threads = Threads::default();
+    let client = posthog_rs::client(
+        options_builder(&server.base_url())
+            .flag_definitions_path(&path)
+            .person_property_provider(threads.clone())
+            .build()
+            .unwrap(),
+    )
+    .await;
+    let _ = std::fs::remove_file(&path);
+
+    let flags = client
+        .evaluate_flags("someone", posthog_rs::EvaluateFlagsOptions::default())
+        .await
+        .unwrap();
+    #[allow(deprecated)]
+    let plan_flag = client
+        .get_feature_flag("plan-flag", "someone", None, None, None)
+        .await
+        .unwrap();
+
+    assert!(flags.is_enabled("plan-flag"));
+    assert_eq!(plan_flag, Some(FlagValue::Boolean(true)));
+    let threads = threads.0.lock().unwrap();
+    assert_eq!(threads.len(), 2);
+    assert!(threads.iter().all(|id| *id != std::thread::current().id()));
+}
+
+#[cfg(not(feature = "async-client"))]
+#[test]
+fn clients_load_missing_properties_without_remote_flags() {
+    let server = MockServer::start();
+    let mock = flags_mock(&server, no_flags());
+    let path = definitions_path("property-providers");
+    std::fs::write(&path, definitions().to_string()).unwrap();
+    let client = posthog_rs::client(options(&server, &path));
+    let _ = std::fs::remove_file(&path);
+
+    let flags = client
+        .evaluate_flags("enterprise-user", evaluate_flags_options())
+        .unwrap();
+
+    assert!(flags.is_enabled("plan-flag"));
+    assert!(flags.is_enabled("cohort-flag"));
+    assert!(flags.is_enabled("company-flag"));
+    assert_eq!(mock.hits(), 0);
+}
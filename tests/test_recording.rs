@@ -0,0 +1,208 @@
+//! A recording client (`Client::recording`, `ClientOptionsBuilder::recording`)
+//! runs the full capture pipeline and keeps the final wire events in memory
+//! instead of sending them. Assertions read fields shared by the v0 `/batch/`
+//! and v1 `/i/v1/analytics/events` event shapes.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{flags_mock, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, ClientOptionsBuilder, EvaluateFlagsOptions, Event};
+use serde_json::{json, Value};
+
+/// Recording options with a super property and a `before_send` hook, so the
+/// recorded event shows both ran.
+fn pipeline_options() -> ClientOptions {
+    ClientOptionsBuilder::default()
+        .api_key("phc_test".to_string())
+        .recording(true)
+        .super_properties(HashMap::from([("service".to_string(), json!("billing"))]))
+        .before_send(|mut event| {
+            event.insert_prop("scrubbed", true).unwrap();
+            Some(event)
+        })
+        .build()
+        .unwrap()
+}
+
+/// Recording options whose remote `/flags` requests reach `server`.
+fn flags_options(server: &MockServer) -> ClientOptions {
+    options_builder(&server.base_url())
+        .recording(true)
+        .build()
+        .unwrap()
+}
+
+fn checkout_flags() -> Value {
+    json!({
+        "featureFlags": { "checkout-v2": true },
+        "featureFlagPayloads": {}
+    })
+}
+
+fn assert_pipeline_ran(event: &Value) {
+    assert_eq!(event["distinct_id"], "user-1");
+    assert_eq!(event["properties"]["plan"], "pro");
+    assert_eq!(event["properties"]["service"], "billing");
+    assert_eq!(event["properties"]["scrubbed"], true);
+    assert!(event["properties"]["$os"].is_string());
+}
+
+fn signed_up() -> Event {
+    let mut event = Event::new("signed_up", "user-1");
+    event.insert_prop("plan", "pro").unwrap();
+    event
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+    use posthog_rs::Client;
+
+    #[tokio::test]
+    async fn records_captured_events_after_the_pipeline() {
+        let client = posthog_rs::client(pipeline_options()).await;
+
+        client.capture(signed_up());
+        client.capture(Event::new("checkout_started", "user-1"));
+
+        let event = client.assert_captured("signed_up").await;
+        assert_pipeline_ran(&event);
+        assert_eq!(client.captured_events().await.len(), 2);
+
+        client.clear_captured().await;
+        assert!(client.captured_events().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_immediate_capture() {
+        let client = Client::recording().await;
+
+        client.capture_immediate(signed_up()).await.unwrap();
+
+        assert_eq!(client.captured_events().await.len(), 1);
+        client.assert_captured("signed_up").await;
+    }
+
+    #[tokio::test]
+    async fn recording_client_answers_flag_requests_offline() {
+        let client = Client::recording().await;
+
+        let flags = client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+        assert!(!flags.is_enabled("checkout-v2"));
+        let called = client.assert_captured("$feature_flag_called").await;
+        assert_eq!(called["properties"]["$feature_flag_error"], "flag_missing");
+    }
+
+    #[tokio::test]
+    async fn flag_called_events_are_deduplicated() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, checkout_flags());
+        let client = posthog_rs::client(flags_options(&server)).await;
+
+        for _ in 0..2 {
+            let flags = client
+                .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+                .await
+                .unwrap();
+            assert!(flags.is_enabled("checkout-v2"));
+        }
+
+        mock.assert_hits(2);
+        let called: Vec<_> = client
+            .captured_events()
+            .await
+            .into_iter()
+            .filter(|e| e["event"] == "$feature_flag_called")
+            .collect();
+        assert_eq!(called.len(), 1);
+        assert_eq!(called[0]["properties"]["$feature_flag"], "checkout-v2");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected a captured `signed_up` event; captured: [\"other\"]")]
+    async fn assert_captured_lists_recorded_events_when_missing() {
+        let client = Client::recording().await;
+        client.capture(Event::new("other", "user-1"));
+        client.assert_captured("signed_up").await;
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+    use posthog_rs::Client;
+
+    #[test]
+    fn records_captured_events_after_the_pipeline() {
+        let client = posthog_rs::client(pipeline_options());
+
+        client.capture(signed_up());
+        client.capture(Event::new("checkout_started", "user-1"));
+
+        let event = client.assert_captured("signed_up");
+        assert_pipeline_ran(&event);
+        assert_eq!(client.captured_events().len(), 2);
+
+        client.clear_captured();
+        assert!(client.captured_events().is_empty());
+    }
+
+    #[test]
+    fn records_immediate_capture() {
+        let client = Client::recording();
+
+        client.capture_immediate(signed_up()).unwrap();
+
+        assert_eq!(client.captured_events().len(), 1);
+        client.assert_captured("signed_up");
+    }
+
+    #[test]
+    fn recording_client_answers_flag_requests_offline() {
+        let client = Client::recording();
+
+        let flags = client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .unwrap();
+        assert!(!flags.is_enabled("checkout-v2"));
+        let called = client.assert_captured("$feature_flag_called");
+        assert_eq!(called["properties"]["$feature_flag_error"], "flag_missing");
+    }
+
+    #[test]
+    fn flag_called_events_are_deduplicated() {
+        let server = MockServer::start();
+        let mock = flags_mock(&server, checkout_flags());
+        let client = posthog_rs::client(flags_options(&server));
+
+        for _ in 0..2 {
+            let flags = client
+                .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+                .unwrap();
+            assert!(flags.is_enabled("checkout-v2"));
+        }
+
+        mock.assert_hits(2);
+        let called: Vec<_> = client
+            .captured_events()
+            .into_iter()
+            .filter(|e| e["event"] == "$feature_flag_called")
+            .collect();
+        assert_eq!(called.len(), 1);
+        assert_eq!(called[0]["properties"]["$feature_flag"], "checkout-v2");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a captured `signed_up` event; captured: [\"other\"]")]
+    fn assert_captured_lists_recorded_events_when_missing() {
+        let client = Client::recording();
+        client.capture(Event::new("other", "user-1"));
+        client.assert_captured("signed_up");
+    }
+}
@@ -0,0 +1,196 @@
+//! Acceptance coverage for the on-disk event spool: events a client could not
+//! deliver are replayed by the next client opened on the same directory, or by
+//! the same client once PostHog is reachable again, and events PostHog
+//! rejected are not. Runs on the async client; assertions are
+//! wire-format-agnostic (they hold for both the v0 `/batch/` and v1
+//! `/i/v1/analytics/events` bodies).
+#![cfg(feature = "async-client")]
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::options_builder;
+use httpmock::prelude::*;
+use posthog_rs::{Client, ClientOptionsBuilder, Event, NdjsonDeadLetterSink, SpoolOptions};
+use serde_json::json;
+
+fn spool_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("posthog-spool-test-{}", uuid::Uuid::now_v7()))
+}
+
+/// Options spooling to `dir` that give up on a capture after one attempt.
+fn spooling(host: &str, dir: &Path) -> ClientOptionsBuilder {
+    let mut builder = options_builder(host);
+    builder
+        .flush_interval_ms(600_000u64)
+        .max_capture_attempts(1u32)
+        .shutdown_timeout_ms(1_000u64)
+        .spool(SpoolOptions::new(dir));
+    builder
+}
+
+async fn spooled_client(host: String, dir: &Path) -> Client {
+    posthog_rs::client(spooling(&host, dir).build().unwrap()).await
+}
+
+/// Total bytes left in the spool directory.
+fn spooled_bytes(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum()
+}
+
+fn status_mock(server: &MockServer, status: u16) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST);
+        then.status(status)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+#[tokio::test]
+async fn undelivered_events_are_replayed_by_the_next_client() {
+    let dir = spool_dir();
+
+    // PostHog is down: the only attempt fails with a transient 503.
+    let outage = MockServer::start();
+    let failed = status_mock(&outage, 503);
+    let client = spooled_client(outage.base_url(), &dir).await;
+    client.capture(Event::new("order_placed", "user-1"));
+    client.capture(Event::new("order_shipped", "user-1"));
+    client.shutdown().await;
+    failed.assert_hits(1);
+    assert!(
+        spooled_bytes(&dir) > 0,
+        "undelivered events were not spooled"
+    );
+
+    // The next run replays both events on startup.
+    let recovered = MockServer::start();
+    let replayed = recovered.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"order_placed""#)
+            .body_contains(r#""event":"order_shipped""#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    });
+    let client = spooled_client(recovered.base_url(), &dir).await;
+    client.flush().await;
+    replayed.assert_hits(1);
+    client.shutdown().await;
+    assert_eq!(spooled_bytes(&dir), 0, "delivered events stayed spooled");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn rejected_events_are_not_replayed() {
+    let dir = spool_dir();
+
+    let server = MockServer::start();
+    let rejected = status_mock(&server, 400);
+    let client = spooled_client(server.base_url(), &dir).await;
+    client.capture(Event::new("malformed", "user-1"));
+    client.shutdown().await;
+    rejected.assert_hits(1);
+    assert_eq!(spooled_bytes(&dir), 0);
+
+    // A fresh client has nothing to replay.
+    let client = spooled_client(server.base_url(), &dir).await;
+    client.flush().await;
+    client.shutdown().await;
+    rejected.assert_hits(1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn parked_events_are_resent_after_the_next_delivery() {
+    let dir = spool_dir();
+
+    let server = MockServer::start();
+    let mut outage = status_mock(&server, 503);
+    let client = spooled_client(server.base_url(), &dir).await;
+    client.capture(Event::new("order_placed", "user-1"));
+    client.flush().await;
+    outage.assert_hits(1);
+    outage.delete();
+
+    let resent = server.mock(|when, then| {
+        when.method(POST).body_contains(r#""event":"order_placed""#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    });
+    let delivered = status_mock(&server, 200);
+    client.capture(Event::new("order_shipped", "user-1"));
+    client.flush().await;
+    // The parked event goes out right after the delivery that re-armed it.
+    client.flush().await;
+    delivered.assert_hits(1);
+    resent.assert_hits(1);
+    assert_eq!(spooled_bytes(&dir), 0, "resent events stayed spooled");
+
+    client.shutdown().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn spooled_events_are_not_also_dead_lettered() {
+    let dir = spool_dir();
+    let dead_letters = dir.with_extension("ndjson");
+
+    let server = MockServer::start();
+    let outage = status_mock(&server, 503);
+    let client = posthog_rs::client(
+        spooling(&server.base_url(), &dir)
+            .dead_letter_sink(NdjsonDeadLetterSink::open(&dead_letters).unwrap())
+            .build()
+            .unwrap(),
+    )
+    .await;
+    // Out of retries, then abandoned at shutdown: kept in the spool both times.
+    client.capture(Event::new("order_placed", "user-1"));
+    client.flush().await;
+    client.capture(Event::new("order_shipped", "user-1"));
+    client.shutdown().await;
+    outage.assert_hits(2);
+
+    assert!(
+        spooled_bytes(&dir) > 0,
+        "undelivered events were not spooled"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&dead_letters).unwrap(),
+        "",
+        "spooled events were also dead-lettered"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_file(dead_letters).unwrap();
+}
+
+#[tokio::test]
+async fn a_second_client_on_the_same_directory_runs_without_a_spool() {
+    let dir = spool_dir();
+
+    let server = MockServer::start();
+    let outage = status_mock(&server, 503);
+    let first = spooled_client(server.base_url(), &dir).await;
+    let second = spooled_client(server.base_url(), &dir).await;
+    second.capture(Event::new("unspooled", "user-1"));
+    second.shutdown().await;
+    outage.assert_hits(1);
+    assert_eq!(
+        spooled_bytes(&dir),
+        0,
+        "the locked directory was written to"
+    );
+
+    first.shutdown().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
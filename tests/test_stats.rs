@@ -0,0 +1,152 @@
+//! `Client::stats()` counts what the client's background worker, remote
+//! `/flags` requests and definitions poller did. Capture assertions use a
+//! rejecting endpoint so they hold for both the v0 and v1 pipelines.
+
+mod common;
+
+use common::{flags_mock, only_local, options_builder};
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, EvaluateFlagsOptions, Event};
+use serde_json::{json, Value};
+
+fn options(server: &MockServer) -> ClientOptions {
+    options_builder(&server.base_url()).build().unwrap()
+}
+
+fn local_evaluation_options(server: &MockServer) -> ClientOptions {
+    options_builder(&server.base_url())
+        .secret_key("phx_test".to_string())
+        .enable_local_evaluation(true)
+        .build()
+        .unwrap()
+}
+
+fn reject_captures(server: &MockServer) {
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/batch");
+        then.status(400);
+    });
+    server.mock(|when, then| {
+        when.method(POST).path_contains("/i/v1/");
+        then.status(400);
+    });
+}
+
+fn beta_flags() -> Value {
+    json!({
+        "featureFlags": { "beta": true },
+        "featureFlagPayloads": {}
+    })
+}
+
+fn definitions_mock(server: &MockServer) {
+    server.mock(|when, then| {
+        when.method(GET).path("/flags/definitions/");
+        then.status(200).json_body(json!({ "flags": [] }));
+    });
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_rejected_capture() {
+        let server = MockServer::start();
+        reject_captures(&server);
+        let client = posthog_rs::client(options(&server)).await;
+
+        client.capture(Event::new("signed_up", "user-1"));
+        client.flush().await;
+
+        let stats = client.stats();
+        assert_eq!(stats.events_enqueued, 1);
+        assert_eq!(stats.events_rejected, 1);
+        assert_eq!(stats.events_sent, 0);
+        assert!(stats.bytes_sent > 0);
+    }
+
+    #[tokio::test]
+    async fn times_remote_flag_requests() {
+        let server = MockServer::start();
+        flags_mock(&server, beta_flags());
+        let client = posthog_rs::client(options(&server)).await;
+
+        client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .await
+            .unwrap();
+
+        let flags = client.stats().flag_requests;
+        assert_eq!(flags.requests, 1);
+        assert_eq!(flags.failures, 0);
+        assert_eq!(flags.last_latency, flags.max_latency);
+        assert!(flags.last_latency.is_some());
+    }
+
+    #[tokio::test]
+    async fn stamps_definitions_polls() {
+        let server = MockServer::start();
+        definitions_mock(&server);
+        let client = posthog_rs::client(local_evaluation_options(&server)).await;
+
+        client.evaluate_flags("user-1", only_local()).await.unwrap();
+
+        let stats = client.stats();
+        assert!(stats.last_poll_success.is_some());
+        assert!(stats.last_poll_failure.is_none());
+        assert_eq!(stats.flag_requests.requests, 0);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn counts_rejected_capture() {
+        let server = MockServer::start();
+        reject_captures(&server);
+        let client = posthog_rs::client(options(&server));
+
+        client.capture(Event::new("signed_up", "user-1"));
+        client.flush();
+
+        let stats = client.stats();
+        assert_eq!(stats.events_enqueued, 1);
+        assert_eq!(stats.events_rejected, 1);
+        assert_eq!(stats.events_sent, 0);
+        assert!(stats.bytes_sent > 0);
+    }
+
+    #[test]
+    fn times_remote_flag_requests() {
+        let server = MockServer::start();
+        flags_mock(&server, beta_flags());
+        let client = posthog_rs::client(options(&server));
+
+        client
+            .evaluate_flags("user-1", EvaluateFlagsOptions::default())
+            .unwrap();
+
+        let flags = client.stats().flag_requests;
+        assert_eq!(flags.requests, 1);
+        assert_eq!(flags.failures, 0);
+        assert_eq!(flags.last_latency, flags.max_latency);
+        assert!(flags.last_latency.is_some());
+    }
+
+    #[test]
+    fn stamps_definitions_polls() {
+        let server = MockServer::start();
+        definitions_mock(&server);
+        let client = posthog_rs::client(local_evaluation_options(&server));
+
+        client.evaluate_flags("user-1", only_local()).unwrap();
+
+        let stats = client.stats();
+        assert!(stats.last_poll_success.is_some());
+        assert!(stats.last_poll_failure.is_none());
+        assert_eq!(stats.flag_requests.requests, 0);
+    }
+}
@@ -0,0 +1,229 @@
+//! Client super properties (`ClientOptionsBuilder::super_properties`,
+//! `Client::register`/`unregister`) are attached to every captured event with
+//! caller-wins semantics, on both the background transport and immediate
+//! capture. Assertions are wire-format-agnostic: they hold for both the v0
+//! `/batch/` and v1 `/i/v1/analytics/events` bodies.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::options_builder;
+use httpmock::prelude::*;
+use posthog_rs::{ClientOptions, Event};
+use serde_json::json;
+
+fn options(base_url: &str) -> ClientOptions {
+    options_builder(base_url)
+        .super_properties(HashMap::from([
+            ("service".to_string(), json!("billing")),
+            ("region".to_string(), json!("eu")),
+        ]))
+        .build()
+        .unwrap()
+}
+
+fn event_with_region() -> Event {
+    let mut event = Event::new("invoice_paid", "user-1");
+    event.insert_prop("region", "us").unwrap();
+    event
+}
+
+/// Matches a request carrying the builder super properties, with the event's
+/// own `region` winning over the super property.
+fn caller_wins_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"invoice_paid""#)
+            .body_contains(r#""service":"billing""#)
+            .body_contains(r#""region":"us""#);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+/// Matches a request carrying the runtime-registered `tier` but not the
+/// unregistered `service`.
+fn registered_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"invoice_paid""#)
+            .body_contains(r#""tier":"pro""#)
+            .body_contains(r#""region":"eu""#)
+            .matches(|req| {
+                req.body
+                    .as_ref()
+                    .is_some_and(|body| !String::from_utf8_lossy(body).contains("\"service\""))
+            });
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+/// Matches a request carrying the builder super properties but not the `tier`
+/// registered after the event was captured.
+fn captured_before_register_mock(server: &MockServer) -> httpmock::Mock<'_> {
+    server.mock(|when, then| {
+        when.method(POST)
+            .body_contains(r#""event":"invoice_paid""#)
+            .body_contains(r#""service":"billing""#)
+            .matches(|req| {
+                req.body
+                    .as_ref()
+                    .is_some_and(|body| !String::from_utf8_lossy(body).contains("\"tier\""))
+            });
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({ "results": {} }));
+    })
+}
+
+fn tier() -> HashMap<String, serde_json::Value> {
+    HashMap::from([("tier".to_string(), json!("pro"))])
+}
+
+#[cfg(feature = "async-client")]
+mod async_client {
+    use super::*;
+
+    #[tokio::test]
+    async fn super_properties_are_attached_and_caller_wins() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url())).await;
+        client.capture(event_with_region());
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn register_and_unregister_apply_at_runtime() {
+        let server = MockServer::start();
+        let mock = registered_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url())).await;
+        client.register(tier());
+        client.unregister("service");
+        client.capture(Event::new("invoice_paid", "user-1"));
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn queued_events_keep_super_properties_from_capture_time() {
+        let server = MockServer::start();
+        let mock = captured_before_register_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url())).await;
+        client.capture(Event::new("invoice_paid", "user-1"));
+        client.register(tier());
+        client.unregister("service");
+        client.flush().await;
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn super_properties_reach_capture_immediate() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url())).await;
+        client.capture_immediate(event_with_region()).await.unwrap();
+
+        mock.assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn register_does_not_leak_into_clients_built_from_cloned_options() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let options = options(&server.base_url());
+        let first = posthog_rs::client(options.clone()).await;
+        let second = posthog_rs::client(options).await;
+        first.register(HashMap::from([("region".to_string(), json!("apac"))]));
+        first.unregister("service");
+        second.capture(event_with_region());
+        second.flush().await;
+
+        mock.assert_hits(1);
+    }
+}
+
+#[cfg(not(feature = "async-client"))]
+mod blocking {
+    use super::*;
+
+    #[test]
+    fn super_properties_are_attached_and_caller_wins() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url()));
+        client.capture(event_with_region());
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn register_and_unregister_apply_at_runtime() {
+        let server = MockServer::start();
+        let mock = registered_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url()));
+        client.register(tier());
+        client.unregister("service");
+        client.capture(Event::new("invoice_paid", "user-1"));
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn queued_events_keep_super_properties_from_capture_time() {
+        let server = MockServer::start();
+        let mock = captured_before_register_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url()));
+        client.capture(Event::new("invoice_paid", "user-1"));
+        client.register(tier());
+        client.unregister("service");
+        client.flush();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn super_properties_reach_capture_immediate() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let client = posthog_rs::client(options(&server.base_url()));
+        client.capture_immediate(event_with_region()).unwrap();
+
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn register_does_not_leak_into_clients_built_from_cloned_options() {
+        let server = MockServer::start();
+        let mock = caller_wins_mock(&server);
+
+        let options = options(&server.base_url());
+        let first = posthog_rs::client(options.clone());
+        let second = posthog_rs::client(options);
+        first.register(HashMap::from([("region".to_string(), json!("apac"))]));
+        first.unregister("service");
+        second.capture(event_with_region());
+        second.flush();
+
+        mock.assert_hits(1);
+    }
+}
@@ -0,0 +1,76 @@
+//! Import progress, persisted after every delivered batch.
+//!
+//! The checkpoint maps each input file (as named on the command line) to the
+//! number of its records that are known to be persisted. A rerun skips that
+//! many records and carries on from the first batch that was not confirmed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    #[serde(skip)]
+    path: PathBuf,
+    files: BTreeMap<String, u64>,
+}
+
+impl Checkpoint {
+    /// Read the checkpoint at `path`, or start an empty one if there is none.
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let mut checkpoint = match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Checkpoint::default(),
+            Err(e) => return Err(e),
+        };
+        checkpoint.path = path.to_path_buf();
+        Ok(checkpoint)
+    }
+
+    /// Records of `file` already imported.
+    pub(crate) fn records_done(&self, file: &str) -> u64 {
+        self.files.get(file).copied().unwrap_or(0)
+    }
+
+    /// Record that the first `records` records of `file` are imported, and
+    /// persist the checkpoint. The file is replaced atomically, so an
+    /// interruption leaves either the old or the new progress.
+    pub(crate) fn advance(&mut self, file: &str, records: u64) -> io::Result<()> {
+        self.files.insert(file.to_string(), records);
+        let contents = serde_json::to_vec_pretty(self)?;
+        let mut staging = self.path.clone().into_os_string();
+        staging.push(".tmp");
+        fs::write(&staging, contents)?;
+        fs::rename(&staging, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_survives_a_reload() {
+        let path = std::env::temp_dir().join(format!(
+            "posthog-import-checkpoint-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let mut checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!(checkpoint.records_done("events.ndjson"), 0);
+        checkpoint.advance("events.ndjson", 500).unwrap();
+        checkpoint.advance("events.ndjson", 1000).unwrap();
+        checkpoint.advance("users.csv", 20).unwrap();
+
+        let reloaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(reloaded.records_done("events.ndjson"), 1000);
+        assert_eq!(reloaded.records_done("users.csv"), 20);
+
+        let _ = fs::remove_file(&path);
+    }
+}
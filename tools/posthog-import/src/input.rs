@@ -0,0 +1,406 @@
+//! Reading event files and mapping their records to [`Event`]s.
+//!
+//! Both formats are read as a sequence of records — one per NDJSON line or
+//! CSV row — so a checkpoint can count how many it has consumed. Record `n`
+//! of a file is always the `n`th line or row, including ones that are blank
+//! or fail to map, which keeps the count stable across runs.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use clap::{Args, ValueEnum};
+use posthog_rs::Event;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Namespace for the UUIDs derived from records that carry none.
+const RECORD_NAMESPACE: Uuid = Uuid::from_u128(0x6d1c_2f0a_8b4e_4c55_9a3f_0e7b_51c2_d8a4);
+
+/// One input record: field name to value. CSV cells are strings.
+pub(crate) struct Record {
+    pub(crate) fields: Map<String, Value>,
+    /// Whether the event was captured as a historical migration, when the
+    /// record says so: set on lines written by `NdjsonDeadLetterSink`.
+    pub(crate) historical_migration: Option<bool>,
+}
+
+/// Input file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    /// `.csv` files are CSV, everything else NDJSON.
+    Auto,
+    /// One JSON object per line.
+    Ndjson,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+impl Format {
+    fn resolve(self, path: &Path) -> Format {
+        match self {
+            Format::Auto => match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+                _ => Format::Ndjson,
+            },
+            format => format,
+        }
+    }
+}
+
+/// Why a record could not be read.
+#[derive(Debug)]
+pub(crate) enum RecordError {
+    /// The file could not be read; the import stops.
+    Io(io::Error),
+    /// The record is malformed; it is skipped.
+    Invalid(String),
+}
+
+pub(crate) type Records = Box<dyn Iterator<Item = Result<Option<Record>, RecordError>>>;
+
+/// Open `path` and iterate over its records. Blank NDJSON lines yield
+/// `Ok(None)` so they still count towards the record index.
+pub(crate) fn open(path: &Path, format: Format) -> io::Result<Records> {
+    let file = File::open(path)?;
+    Ok(match format.resolve(path) {
+        Format::Csv => csv_records(file),
+        _ => ndjson_records(file),
+    })
+}
+
+fn ndjson_records(file: File) -> Records {
+    Box::new(BufReader::new(file).lines().map(|line| {
+        let line = line.map_err(RecordError::Io)?;
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        match serde_json::from_str(&line) {
+            Ok(Value::Object(record)) => Ok(Some(unwrap_dead_letter(record))),
+            Ok(_) => Err(RecordError::Invalid("not a JSON object".to_string())),
+            Err(e) => Err(RecordError::Invalid(format!("invalid JSON: {e}"))),
+        }
+    }))
+}
+
+/// Lines written by `NdjsonDeadLetterSink` nest the event under `"event"`,
+/// next to the reason and the historical-migration flag; re-drive the event
+/// itself, the way it was captured.
+fn unwrap_dead_letter(mut fields: Map<String, Value>) -> Record {
+    if fields.contains_key("reason") {
+        if let Some(Value::Object(event)) = fields.remove("event") {
+            return Record {
+                fields: event,
+                historical_migration: Some(
+                    fields.get("historical_migration") == Some(&Value::Bool(true)),
+                ),
+            };
+        }
+    }
+    Record {
+        fields,
+        historical_migration: None,
+    }
+}
+
+fn csv_records(file: File) -> Records {
+    let mut reader = csv::Reader::from_reader(file);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return Box::new(std::iter::once(Err(csv_error(e)))),
+    };
+    Box::new(reader.into_records().map(move |row| {
+        let row = row.map_err(csv_error)?;
+        Ok(Some(Record {
+            fields: headers
+                .iter()
+                .zip(row.iter())
+                .filter(|(_, cell)| !cell.is_empty())
+                .map(|(header, cell)| (header.to_string(), Value::String(cell.to_string())))
+                .collect(),
+            historical_migration: None,
+        }))
+    }))
+}
+
+fn csv_error(error: csv::Error) -> RecordError {
+    if error.is_io_error() {
+        match error.into_kind() {
+            csv::ErrorKind::Io(e) => RecordError::Io(e),
+            _ => unreachable!("is_io_error checked the kind"),
+        }
+    } else {
+        RecordError::Invalid(error.to_string())
+    }
+}
+
+/// Which record fields become the event's name, distinct ID, timestamp and
+/// UUID. Every other field becomes a property, and a `properties` object (or,
+/// in CSV, a JSON-object cell) is merged in as properties too. A `groups`
+/// object of group type to group key sets the event's groups.
+#[derive(Debug, Clone, Args)]
+pub(crate) struct FieldMap {
+    /// Field holding the event name.
+    #[arg(long, default_value = "event", value_name = "FIELD")]
+    pub(crate) event_field: String,
+    /// Event name for records without an event name field.
+    #[arg(long, value_name = "NAME")]
+    pub(crate) event_name: Option<String>,
+    /// Field holding the distinct ID.
+    #[arg(long, default_value = "distinct_id", value_name = "FIELD")]
+    pub(crate) distinct_id_field: String,
+    /// Field holding the timestamp: RFC 3339, `YYYY-MM-DD HH:MM:SS` in UTC,
+    /// or Unix seconds or milliseconds.
+    #[arg(long, default_value = "timestamp", value_name = "FIELD")]
+    pub(crate) timestamp_field: String,
+    /// Field holding the event UUID. Records without one get a UUID derived
+    /// from their file, position and contents, so re-sending them is
+    /// deduplicated.
+    #[arg(long, default_value = "uuid", value_name = "FIELD")]
+    pub(crate) uuid_field: String,
+}
+
+impl FieldMap {
+    /// Map record number `index` of `file` to an event.
+    pub(crate) fn to_event(
+        &self,
+        mut record: Map<String, Value>,
+        file: &str,
+        index: u64,
+    ) -> Result<Event, String> {
+        let derived_uuid = Uuid::new_v5(
+            &RECORD_NAMESPACE,
+            format!("{file}:{index}:{}", Value::Object(record.clone())).as_bytes(),
+        );
+
+        let name = take_string(&mut record, &self.event_field)
+            .or_else(|| self.event_name.clone())
+            .ok_or_else(|| format!("missing event name field `{}`", self.event_field))?;
+        let distinct_id = take_string(&mut record, &self.distinct_id_field)
+            .ok_or_else(|| format!("missing distinct ID field `{}`", self.distinct_id_field))?;
+        let mut event = Event::new(name, distinct_id);
+
+        match record.remove(&self.timestamp_field) {
+            None | Some(Value::Null) => {}
+            Some(value) => event
+                .set_timestamp(parse_timestamp(&value)?)
+                .map_err(|e| e.to_string())?,
+        }
+        event.set_uuid(match record.remove(&self.uuid_field) {
+            None | Some(Value::Null) => derived_uuid,
+            Some(Value::String(uuid)) => {
+                Uuid::parse_str(&uuid).map_err(|e| format!("invalid UUID `{uuid}`: {e}"))?
+            }
+            Some(other) => return Err(format!("invalid UUID `{other}`")),
+        });
+
+        if let Some(groups) = record.remove("groups") {
+            for (group_type, group_key) in json_object(groups, "groups")? {
+                match group_key {
+                    Value::String(group_key) => event.add_group(&group_type, &group_key),
+                    other => return Err(format!("invalid group key `{other}`")),
+                }
+            }
+        }
+        if let Some(properties) = record.remove("properties") {
+            let properties = json_object(properties, "properties")?;
+            record = properties.into_iter().chain(record).collect();
+        }
+        for (key, value) in record {
+            event.insert_prop(key, value).map_err(|e| e.to_string())?;
+        }
+        Ok(event)
+    }
+}
+
+/// A JSON object field, which CSV carries as a JSON-encoded cell.
+fn json_object(value: Value, field: &str) -> Result<Map<String, Value>, String> {
+    match value {
+        Value::Object(object) => Ok(object),
+        Value::String(json) => match serde_json::from_str(&json) {
+            Ok(Value::Object(object)) => Ok(object),
+            _ => Err(format!("`{field}` is not a JSON object")),
+        },
+        _ => Err(format!("`{field}` is not a JSON object")),
+    }
+}
+
+fn take_string(record: &mut Map<String, Value>, field: &str) -> Option<String> {
+    match record.remove(field)? {
+        Value::String(s) if !s.is_empty() => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_timestamp(value: &Value) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("invalid timestamp `{value}`");
+    let text = match value {
+        Value::String(text) => text.trim(),
+        Value::Number(n) => return n.as_f64().and_then(from_unix).ok_or_else(invalid),
+        _ => return Err(invalid()),
+    };
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(text, format) {
+            return Ok(timestamp.and_utc());
+        }
+    }
+    text.parse().ok().and_then(from_unix).ok_or_else(invalid)
+}
+
+/// Unix seconds, or milliseconds for values too large to be seconds.
+fn from_unix(value: f64) -> Option<DateTime<Utc>> {
+    let millis = if value.abs() < 1e11 {
+        value * 1000.0
+    } else {
+        value
+    };
+    Utc.timestamp_millis_opt(millis as i64).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields() -> FieldMap {
+        FieldMap {
+            event_field: "event".to_string(),
+            event_name: None,
+            distinct_id_field: "distinct_id".to_string(),
+            timestamp_field: "timestamp".to_string(),
+            uuid_field: "uuid".to_string(),
+        }
+    }
+
+    fn record(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(record) => record,
+            _ => unreachable!(),
+        }
+    }
+
+    fn event_json(event: &Event) -> Value {
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn maps_fields_and_merges_properties() {
+        let fields = FieldMap {
+            distinct_id_field: "user".to_string(),
+            ..fields()
+        };
+        let event = fields
+            .to_event(
+                record(json!({
+                    "event": "signed_up",
+                    "user": "user-1",
+                    "timestamp": "2024-03-01 12:30:00",
+                    "uuid": "0190b3c4-5d6e-7f80-9a1b-2c3d4e5f6a7b",
+                    "properties": { "plan": "free", "source": "ads" },
+                    "plan": "pro",
+                })),
+                "events.ndjson",
+                0,
+            )
+            .unwrap();
+
+        let json = event_json(&event);
+        assert_eq!(event.event_name(), "signed_up");
+        assert_eq!(event.distinct_id(), "user-1");
+        assert_eq!(json["timestamp"], "2024-03-01T12:30:00");
+        assert_eq!(json["uuid"], "0190b3c4-5d6e-7f80-9a1b-2c3d4e5f6a7b");
+        assert_eq!(event.properties()["plan"], "pro");
+        assert_eq!(event.properties()["source"], "ads");
+        assert!(!event.properties().contains_key("user"));
+    }
+
+    #[test]
+    fn derived_uuids_are_stable_per_record() {
+        let fields = FieldMap {
+            event_name: Some("pageview".to_string()),
+            ..fields()
+        };
+        let row = record(json!({ "distinct_id": "user-1", "path": "/" }));
+
+        let first = event_json(&fields.to_event(row.clone(), "a.csv", 7).unwrap());
+        let again = event_json(&fields.to_event(row.clone(), "a.csv", 7).unwrap());
+        let elsewhere = event_json(&fields.to_event(row.clone(), "a.csv", 8).unwrap());
+        let other_file = event_json(&fields.to_event(row, "b.csv", 7).unwrap());
+
+        assert_eq!(first["event"], "pageview");
+        assert_eq!(first["uuid"], again["uuid"]);
+        assert_ne!(first["uuid"], elsewhere["uuid"]);
+        assert_ne!(first["uuid"], other_file["uuid"]);
+    }
+
+    #[test]
+    fn rejects_records_without_a_distinct_id() {
+        let error = fields()
+            .to_event(record(json!({ "event": "signed_up" })), "events.ndjson", 0)
+            .unwrap_err();
+        assert!(error.contains("distinct_id"), "{error}");
+    }
+
+    #[test]
+    fn parses_timestamp_formats() {
+        let expected = Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap();
+        for value in [
+            json!("2024-03-01T13:30:00+01:00"),
+            json!("2024-03-01 12:30:00"),
+            json!("2024-03-01T12:30:00.000"),
+            json!(1709296200),
+            json!(1709296200000u64),
+            json!("1709296200"),
+        ] {
+            assert_eq!(parse_timestamp(&value), Ok(expected), "{value}");
+        }
+        assert!(parse_timestamp(&json!("yesterday")).is_err());
+    }
+
+    #[test]
+    fn maps_groups_to_event_groups() {
+        let event = fields()
+            .to_event(
+                record(json!({
+                    "event": "invoice_paid",
+                    "distinct_id": "user-1",
+                    "groups": { "company": "acme" },
+                })),
+                "events.ndjson",
+                0,
+            )
+            .unwrap();
+
+        assert_eq!(event_json(&event)["groups"], json!({ "company": "acme" }));
+        assert!(!event.properties().contains_key("groups"));
+    }
+
+    #[test]
+    fn unwraps_dead_letter_lines() {
+        let line = record(json!({
+            "reason": "rejected",
+            "status": 413,
+            "historical_migration": true,
+            "event": { "event": "signed_up", "distinct_id": "user-1" },
+        }));
+        let unwrapped = unwrap_dead_letter(line);
+        assert_eq!(
+            Value::Object(unwrapped.fields),
+            json!({ "event": "signed_up", "distinct_id": "user-1" })
+        );
+        assert_eq!(unwrapped.historical_migration, Some(true));
+
+        let live = unwrap_dead_letter(record(json!({
+            "reason": "shutdown",
+            "event": { "event": "signed_up", "distinct_id": "user-1" },
+        })));
+        assert_eq!(live.historical_migration, Some(false));
+        let plain = unwrap_dead_letter(record(json!({ "event": "signed_up" })));
+        assert_eq!(plain.historical_migration, None);
+    }
+}
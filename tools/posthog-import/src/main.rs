@@ -0,0 +1,210 @@
+//! `posthog-import`: send NDJSON or CSV event files to PostHog.
+//!
+//! Records are mapped to events (see [`input::FieldMap`]) and sent in batches
+//! with `capture_batch_immediate`, as historical-migration batches unless
+//! `--live` is given. After each batch the returned `CaptureSummary` is
+//! checked and, once every event in it is persisted, the checkpoint file is
+//! advanced past it. Rerunning the same command after an interruption skips
+//! everything the checkpoint covers and resumes at the first unconfirmed
+//! batch; its events keep their UUIDs, so anything that did land is
+//! deduplicated.
+//!
+//! NDJSON lines written by `NdjsonDeadLetterSink` are accepted as-is, so a
+//! dead-letter file can be re-driven with this tool. Those events keep the
+//! historical-migration flag recorded on their line, whatever `--live` says.
+
+mod checkpoint;
+mod input;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use posthog_rs::{Client, ClientOptionsBuilder, Error, Event};
+
+use crate::checkpoint::Checkpoint;
+use crate::input::{FieldMap, Format, RecordError};
+
+/// Import NDJSON or CSV event files into PostHog.
+#[derive(Debug, Parser)]
+#[command(name = "posthog-import", version)]
+struct Args {
+    /// Event files to import, in order.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Project API key.
+    #[arg(long, env = "POSTHOG_API_KEY", hide_env_values = true)]
+    api_key: String,
+    /// PostHog host, such as `https://eu.i.posthog.com`.
+    #[arg(long, env = "POSTHOG_HOST")]
+    host: Option<String>,
+    /// Input format.
+    #[arg(long, value_enum, default_value_t = Format::Auto)]
+    format: Format,
+    /// Where progress is recorded, and resumed from.
+    #[arg(long, default_value = "posthog-import.checkpoint.json")]
+    checkpoint: PathBuf,
+    /// Events per request.
+    #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+    /// Attempts at a batch that failed transiently or whose events are not all
+    /// persisted before giving up.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: u32,
+    /// Send as regular live events instead of a historical migration.
+    /// Dead-letter lines keep the flag they were captured with.
+    #[arg(long)]
+    live: bool,
+    #[command(flatten)]
+    fields: FieldMap,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    imported: u64,
+    skipped: u64,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let checkpoint = args.checkpoint.clone();
+    match run(args).await {
+        Ok(totals) => {
+            eprintln!(
+                "posthog-import: imported {} event(s), skipped {} invalid record(s)",
+                totals.imported, totals.skipped
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!(
+                "posthog-import: {e}\nprogress is saved in {}; rerun the same command to resume",
+                checkpoint.display()
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<Totals, String> {
+    let mut options = ClientOptionsBuilder::default();
+    options.api_key(args.api_key.clone());
+    if let Some(host) = &args.host {
+        options.host(host.clone());
+    }
+    let options = options.build().map_err(|e| e.to_string())?;
+    let client = posthog_rs::client(options).await;
+
+    let mut checkpoint = Checkpoint::load(&args.checkpoint)
+        .map_err(|e| format!("failed to read {}: {e}", args.checkpoint.display()))?;
+    let mut totals = Totals::default();
+
+    for path in &args.files {
+        let file = path.display().to_string();
+        let done = checkpoint.records_done(&file);
+        let records = input::open(path, args.format).map_err(|e| format!("{file}: {e}"))?;
+
+        let mut batch = Vec::new();
+        let mut historical = !args.live;
+        let mut consumed = done;
+        let mut confirmed = done;
+        for (index, record) in (0u64..).zip(records).skip(done as usize) {
+            consumed = index + 1;
+            let mapped = match record {
+                Ok(None) => continue,
+                Ok(Some(record)) => {
+                    // A batch is sent with one flag; start a new one when it changes.
+                    let record_historical = record.historical_migration.unwrap_or(!args.live);
+                    if record_historical != historical && !batch.is_empty() {
+                        let sent = batch.len() as u64;
+                        send(&client, &args, &mut batch, historical).await?;
+                        totals.imported += sent;
+                        checkpoint_to(&mut checkpoint, &args, &file, index)?;
+                        confirmed = index;
+                    }
+                    historical = record_historical;
+                    args.fields.to_event(record.fields, &file, index)
+                }
+                Err(RecordError::Invalid(e)) => Err(e),
+                Err(RecordError::Io(e)) => return Err(format!("{file}: {e}")),
+            };
+            match mapped {
+                Ok(event) => batch.push(event),
+                Err(e) => {
+                    eprintln!("posthog-import: {file}: skipping record {consumed}: {e}");
+                    totals.skipped += 1;
+                }
+            }
+            if batch.len() as u64 == args.batch_size {
+                send(&client, &args, &mut batch, historical).await?;
+                totals.imported += args.batch_size;
+                checkpoint_to(&mut checkpoint, &args, &file, consumed)?;
+                confirmed = consumed;
+            }
+        }
+        if !batch.is_empty() {
+            let sent = batch.len() as u64;
+            send(&client, &args, &mut batch, historical).await?;
+            totals.imported += sent;
+        }
+        if consumed > confirmed {
+            checkpoint_to(&mut checkpoint, &args, &file, consumed)?;
+        }
+    }
+    Ok(totals)
+}
+
+/// Send `batch` until the summary confirms every event persisted, then clear
+/// it. A batch left partly unpersisted, or failed by a transport error, rate
+/// limiting or a server error, is re-sent whole; events that did land are
+/// deduplicated by UUID.
+async fn send(
+    client: &Client,
+    args: &Args,
+    batch: &mut Vec<Event>,
+    historical_migration: bool,
+) -> Result<(), String> {
+    for attempt in 1..=args.max_attempts {
+        let summary = match client
+            .capture_batch_immediate(batch.clone(), historical_migration)
+            .await
+        {
+            Ok(summary) => summary,
+            Err(e @ (Error::Connection(_) | Error::RateLimit | Error::ServerError { .. })) => {
+                eprintln!(
+                    "posthog-import: capture failed: {e} (attempt {attempt} of {})",
+                    args.max_attempts
+                );
+                continue;
+            }
+            Err(e) => return Err(format!("capture failed: {e}")),
+        };
+        // `all_persisted` is vacuously true for an empty summary.
+        if summary.submitted() == batch.len() && summary.all_persisted() {
+            batch.clear();
+            return Ok(());
+        }
+        eprintln!(
+            "posthog-import: {} of {} event(s) not persisted (attempt {attempt} of {})",
+            batch.len() - summary.submitted() + summary.not_persisted(),
+            batch.len(),
+            args.max_attempts
+        );
+    }
+    Err(format!(
+        "a batch was still not fully persisted after {} attempt(s)",
+        args.max_attempts
+    ))
+}
+
+fn checkpoint_to(
+    checkpoint: &mut Checkpoint,
+    args: &Args,
+    file: &str,
+    records: u64,
+) -> Result<(), String> {
+    checkpoint
+        .advance(file, records)
+        .map_err(|e| format!("failed to write {}: {e}", args.checkpoint.display()))
+}
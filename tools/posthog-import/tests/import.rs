@@ -0,0 +1,174 @@
+//! Runs of the `posthog-import` binary against the in-process `MockPostHog`
+//! server, checking what lands and what the checkpoint records.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use posthog_rs::{MockPostHog, MockResponse, MockRoute};
+use serde_json::Value;
+
+struct Workdir(PathBuf);
+
+impl Workdir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("posthog-import-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Workdir(dir)
+    }
+
+    fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn checkpoint(&self) -> PathBuf {
+        self.0.join("checkpoint.json")
+    }
+}
+
+impl Drop for Workdir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn import(server: &MockPostHog, dir: &Workdir, file: &Path, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_posthog-import"))
+        .env("POSTHOG_API_KEY", "phc_test")
+        .arg("--host")
+        .arg(server.base_url())
+        .arg("--checkpoint")
+        .arg(dir.checkpoint())
+        .args(["--batch-size", "2"])
+        .args(extra)
+        .arg(file)
+        .output()
+        .unwrap()
+}
+
+fn received(server: &MockPostHog) -> Vec<String> {
+    server
+        .events()
+        .iter()
+        .map(|event| event["event"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn records_done(dir: &Workdir, file: &Path) -> Option<u64> {
+    let checkpoint: Value = serde_json::from_slice(&fs::read(dir.checkpoint()).ok()?).unwrap();
+    checkpoint["files"][file.display().to_string()].as_u64()
+}
+
+#[test]
+fn imports_ndjson_and_resumes_past_the_checkpoint() {
+    let server = MockPostHog::start();
+    let dir = Workdir::new("ndjson");
+    let lines = [
+        r#"{"event":"signed_up","distinct_id":"user-1","timestamp":"2024-03-01T12:00:00Z","plan":"pro"}"#,
+        "",
+        r#"{"reason":"rejected","status":413,"event":{"event":"upgraded","distinct_id":"user-1","properties":{"plan":"team"}}}"#,
+        r#"{"event":"invited","distinct_id":"user-1"}"#,
+    ];
+    let file = dir.file("events.ndjson", &(lines.join("\n") + "\n"));
+
+    let output = import(&server, &dir, &file, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(received(&server), ["signed_up", "upgraded", "invited"]);
+    assert_eq!(server.events()[0]["properties"]["plan"], "pro");
+    assert_eq!(server.events()[1]["properties"]["plan"], "team");
+    assert_eq!(records_done(&dir, &file), Some(4));
+
+    server.clear_events();
+    fs::write(
+        &file,
+        lines.join("\n") + "\n" + r#"{"event":"churned","distinct_id":"user-1"}"# + "\n",
+    )
+    .unwrap();
+    let output = import(&server, &dir, &file, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(received(&server), ["churned"]);
+    assert_eq!(records_done(&dir, &file), Some(5));
+}
+
+#[test]
+fn failed_batches_are_not_checkpointed_and_rerun_in_full() {
+    let server = MockPostHog::start();
+    let dir = Workdir::new("csv");
+    let file = dir.file(
+        "events.csv",
+        "user,time,plan\nuser-1,2024-03-01 12:00:00,pro\nuser-2,1709294400,\nuser-3,,team\n",
+    );
+    let args = [
+        "--event-name",
+        "signed_up",
+        "--distinct-id-field",
+        "user",
+        "--timestamp-field",
+        "time",
+    ];
+
+    server.respond_next(MockRoute::Capture, MockResponse::status(400));
+    let output = import(&server, &dir, &file, &args);
+    assert!(!output.status.success());
+    assert_eq!(records_done(&dir, &file), None);
+
+    let output = import(&server, &dir, &file, &args);
+    assert!(output.status.success(), "{output:?}");
+    let events = server.events();
+    let users: Vec<&str> = events
+        .iter()
+        .map(|event| event["distinct_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(users, ["user-1", "user-2", "user-3"]);
+    assert_eq!(events[0]["properties"]["plan"], "pro");
+    assert!(events[1]["properties"].get("plan").is_none());
+    assert_eq!(records_done(&dir, &file), Some(3));
+}
+
+#[test]
+fn dead_letter_lines_keep_their_groups_and_historical_flag() {
+    let server = MockPostHog::start();
+    let dir = Workdir::new("dead-letter");
+    let lines = [
+        r#"{"reason":"rejected","status":413,"historical_migration":true,"event":{"event":"invoice_paid","distinct_id":"user-1","groups":{"company":"acme"}}}"#,
+        r#"{"reason":"shutdown","event":{"event":"invoice_sent","distinct_id":"user-1"}}"#,
+        r#"{"event":"invoice_viewed","distinct_id":"user-1"}"#,
+    ];
+    let file = dir.file("dead-letters.ndjson", &(lines.join("\n") + "\n"));
+
+    let output = import(&server, &dir, &file, &[]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        received(&server),
+        ["invoice_paid", "invoice_sent", "invoice_viewed"]
+    );
+    let events = server.events();
+    assert_eq!(events[0]["properties"]["$groups"]["company"], "acme");
+    assert!(events[0]["properties"].get("groups").is_none());
+    // The live dead-letter line is sent apart from its historical neighbours.
+    assert_eq!(server.hits(MockRoute::Capture), 3);
+    assert_eq!(records_done(&dir, &file), Some(3));
+}
+
+#[test]
+fn transient_failures_are_retried() {
+    let server = MockPostHog::start();
+    let dir = Workdir::new("transient");
+    let file = dir.file(
+        "events.ndjson",
+        r#"{"event":"signed_up","distinct_id":"user-1"}"#,
+    );
+    // Enough 503s to outlast the client's own retries on the first attempt.
+    for _ in 0..3 {
+        server.respond_next(MockRoute::Capture, MockResponse::status(503));
+    }
+
+    let output = import(&server, &dir, &file, &["--max-attempts", "5"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(received(&server), ["signed_up"]);
+    assert_eq!(records_done(&dir, &file), Some(1));
+}